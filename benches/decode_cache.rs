@@ -0,0 +1,54 @@
+//! Compares stepping a tight arithmetic loop with and without the decode
+//! cache. Run with `cargo bench --bench decode_cache`.
+
+use std::time::{Duration, Instant};
+
+use chip8_emulator::{interpreter::Chip8Interpreter, memory::CosmacRAM};
+
+const STEPS: usize = 5_000_000;
+
+fn run(decode_cache: bool) -> Duration {
+    let program: Vec<u8> = [
+        0x6001u16, // V0 = 1
+        0x8104,    // V1 += V0
+        0x8214,    // V2 += V1
+        0x8325,    // V3 -= V2
+        0x7401,    // V4 += 1
+        0x1202,    // loop
+    ]
+    .into_iter()
+    .flat_map(u16::to_be_bytes)
+    .collect();
+
+    let mut ram = CosmacRAM::new();
+    ram.load_chip8_program(&program).unwrap();
+    let mut chip8 = Chip8Interpreter::with_seed(0);
+    if decode_cache {
+        chip8.enable_decode_cache();
+    }
+    chip8.reset(&mut ram);
+
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        chip8.step(&mut ram).unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let uncached = run(false);
+    let cached = run(true);
+    let nanos_per_step = |elapsed: Duration| elapsed.as_nanos() as f64 / STEPS as f64;
+    println!(
+        "without decode cache: {:>6.1} ns/step",
+        nanos_per_step(uncached)
+    );
+    println!(
+        "with decode cache:    {:>6.1} ns/step",
+        nanos_per_step(cached)
+    );
+    println!(
+        "speedup:              {:>6.2}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
@@ -0,0 +1,70 @@
+//! Compares stepping a loop of memory accesses through `I` on the RAM itself
+//! with stepping it through a bus that wraps the RAM and passes every access
+//! on. Run with `cargo bench --bench memory_bus`.
+
+use std::time::{Duration, Instant};
+
+use chip8_emulator::{bus::MemoryBus, interpreter::Chip8Interpreter, memory::CosmacRAM};
+
+const STEPS: usize = 5_000_000;
+
+// A bus that intercepts nothing, to measure the cost of going through one.
+struct PassThroughBus(CosmacRAM);
+
+impl MemoryBus for PassThroughBus {
+    fn read(&self, address: usize) -> u8 {
+        self.0.read(address)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        self.0.write(address, value)
+    }
+
+    fn ram(&self) -> &CosmacRAM {
+        &self.0
+    }
+
+    fn ram_mut(&mut self) -> &mut CosmacRAM {
+        &mut self.0
+    }
+}
+
+fn run<B: MemoryBus>(bus: &mut B) -> Duration {
+    let program: Vec<u8> = [
+        0xA300u16, // I = 0x300
+        0xF365,    // V0..V3 = M[I]
+        0x7001,    // V0 += 1
+        0xF355,    // M[I] = V0..V3
+        0xA310,    // I = 0x310
+        0xF033,    // M[I] = BCD of V0
+        0x1200,    // loop
+    ]
+    .into_iter()
+    .flat_map(u16::to_be_bytes)
+    .collect();
+
+    bus.ram_mut().load_chip8_program(&program).unwrap();
+    let mut chip8 = Chip8Interpreter::with_seed(0);
+    chip8.reset(bus.ram_mut());
+
+    let start = Instant::now();
+    for _ in 0..STEPS {
+        chip8.step(bus).unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let ram = run(&mut CosmacRAM::new());
+    let wrapped = run(&mut PassThroughBus(CosmacRAM::new()));
+    let nanos_per_step = |elapsed: Duration| elapsed.as_nanos() as f64 / STEPS as f64;
+    println!("RAM as the bus:     {:>6.1} ns/step", nanos_per_step(ram));
+    println!(
+        "pass-through bus:   {:>6.1} ns/step",
+        nanos_per_step(wrapped)
+    );
+    println!(
+        "slowdown:           {:>6.2}x",
+        wrapped.as_secs_f64() / ram.as_secs_f64()
+    );
+}
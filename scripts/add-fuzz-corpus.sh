@@ -0,0 +1,128 @@
+#!/usr/bin/env bash
+# Minimize a crashing input and add it to the fuzz regression corpus, pinning
+# the outcome it runs to.
+#
+# Usage: scripts/add-fuzz-corpus.sh <file> [name]
+#
+# An input script at <file> with a .keys extension is used while minimizing,
+# and added with the case.
+set -euo pipefail
+
+if [[ $# -lt 1 || $# -gt 2 ]]; then
+    echo "usage: $0 <file> [name]" >&2
+    exit 1
+fi
+
+input="$1"
+keys="${input%.*}.keys"
+root="$(cd "$(dirname "$0")/.." && pwd)"
+corpus_dir="$root/tests/fuzz_corpus"
+name="${2:-$(basename "${input%.*}")}"
+name="$(echo "$name" | tr -c 'A-Za-z0-9_\n' '_')"
+dest="$corpus_dir/$name.ch8"
+
+if [[ -e "$dest" ]]; then
+    echo "$dest already exists, pick another name" >&2
+    exit 1
+fi
+
+# The corpus test prints the outcome of a single case when asked, which is
+# what the input is minimized against.
+runner="$(cd "$root" && cargo test --quiet --test fuzz_corpus --no-run --message-format=json |
+    python3 -c '
+import json, sys
+
+for line in sys.stdin:
+    message = json.loads(line)
+    if message.get("reason") == "compiler-artifact" and message["target"]["name"] == "fuzz_corpus":
+        print(message["executable"])
+')"
+
+work="$(mktemp -d)"
+trap 'rm -rf "$work"' EXIT
+python3 - "$input" "$keys" "$runner" "$work" <<'PY'
+import os, re, shutil, subprocess, sys
+
+input_path, keys_path, runner, work = sys.argv[1:]
+case = os.path.join(work, "case.ch8")
+if os.path.exists(keys_path):
+    shutil.copy(keys_path, os.path.join(work, "case.keys"))
+
+
+def outcome(words):
+    with open(case, "wb") as f:
+        f.write(b"".join(words))
+    result = subprocess.run(
+        [runner, "--ignored", "--exact", "print_case_outcome", "--nocapture"],
+        env={**os.environ, "FUZZ_CORPUS_CASE": case},
+        stdout=subprocess.PIPE,
+        stderr=subprocess.DEVNULL,
+        text=True,
+    )
+    # the outcome is printed after the test's name, on the same line
+    printed = re.search(r"outcome: (.+)", result.stdout)
+    if printed:
+        return printed[1]
+    sys.exit(f"no outcome from the corpus test:\n{result.stdout}")
+
+
+def strip_zero_padding(words):
+    # trailing zero instructions are padding left over from the fuzzer's
+    # fixed size inputs
+    while words and words[-1] == b"\0\0":
+        words = words[:-1]
+    return words
+
+
+data = open(input_path, "rb").read()
+words = strip_zero_padding([data[i : i + 2] for i in range(0, len(data), 2)])
+if not words:
+    sys.exit(f"{input_path} is empty after stripping zero padding")
+
+expected = outcome(words)
+if expected == "stop InstructionLimit":
+    sys.exit(f"{input_path} runs into the instruction limit, so has nothing to pin")
+print(f"minimizing {len(data)} bytes, keeping the outcome `{expected}`")
+
+# Drop each instruction word in turn, then zero each one left, keeping every
+# change after which the outcome still reproduces, until none can be made.
+changed = True
+while changed:
+    changed = False
+    for i in reversed(range(len(words))):
+        candidate = words[:i] + words[i + 1 :]
+        if candidate and outcome(candidate) == expected:
+            words = candidate
+            changed = True
+    for i in range(len(words)):
+        if words[i].strip(b"\0"):
+            candidate = words[:i] + [b"\0" * len(words[i])] + words[i + 1 :]
+            if outcome(candidate) == expected:
+                words = candidate
+                changed = True
+
+with open(case, "wb") as f:
+    f.write(b"".join(words))
+with open(os.path.join(work, "case.expect"), "w") as f:
+    f.write(expected + "\n")
+print(f"minimized to {len(b''.join(words))} bytes")
+PY
+
+for existing in "$corpus_dir"/*.ch8; do
+    if cmp -s "$work/case.ch8" "$existing"; then
+        echo "program already in corpus as $existing" >&2
+        exit 1
+    fi
+done
+
+cp "$work/case.ch8" "$dest"
+if [[ -e "$work/case.keys" ]]; then
+    cp "$work/case.keys" "${dest%.ch8}.keys"
+fi
+outcome="$(cat "$work/case.expect")"
+if [[ "$outcome" == panic ]]; then
+    echo "added $dest, which still panics: fix the crash, then pin the outcome it runs to in ${dest%.ch8}.expect"
+else
+    cp "$work/case.expect" "${dest%.ch8}.expect"
+    echo "added $dest, running to \`$outcome\`"
+fi
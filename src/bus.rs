@@ -0,0 +1,204 @@
+//! Memory-mapping extra state into the address space seen by CHIP-8
+//! programs.
+//!
+//! The interpreter reads and writes memory through `I` (FX33, FX55, FX65,
+//! DXYN and F002) by way of a [`MemoryBus`]. [`CosmacRAM`] is the bus on its
+//! own, and a wrapper around it can intercept particular addresses, e.g. to
+//! expose a frame counter to the program.
+//!
+//! Only those accesses go through the bus. Instruction fetches, the V
+//! registers, `I`, the stack and the rest of the interpreter work area, and
+//! the display buffer all go straight to the RAM the bus wraps, so a bus
+//! can't map code, or the interpreter's own state, to a peripheral. The
+//! decode cache, tracing and step hooks read instructions and registers from
+//! the RAM, and would otherwise disagree with what was run.
+//!
+//! Stepping through a wrapper that intercepts nothing costs a little more than
+//! stepping the RAM itself, see `cargo bench --bench memory_bus`.
+//!
+//! # Example
+//! ```
+//! # use chip8_emulator::{bus::MemoryBus, interpreter::Chip8Interpreter, memory::CosmacRAM};
+//! // A bus where address 0x0E00 always reads as 0x42
+//! struct MagicBus(CosmacRAM);
+//!
+//! impl MemoryBus for MagicBus {
+//!     fn read(&self, address: usize) -> u8 {
+//!         match address {
+//!             0x0E00 => 0x42,
+//!             _ => self.0.read(address),
+//!         }
+//!     }
+//!
+//!     fn write(&mut self, address: usize, value: u8) {
+//!         self.0.write(address, value)
+//!     }
+//!
+//!     fn ram(&self) -> &CosmacRAM {
+//!         &self.0
+//!     }
+//!
+//!     fn ram_mut(&mut self) -> &mut CosmacRAM {
+//!         &mut self.0
+//!     }
+//! }
+//!
+//! let mut bus = MagicBus(CosmacRAM::new());
+//! // I = 0x0E00, then load V0 from it
+//! bus.0.load_chip8_program(&[0xAE, 0x00, 0xF0, 0x65]).unwrap();
+//! let mut chip8 = Chip8Interpreter::with_seed(0);
+//! chip8.reset(bus.ram_mut());
+//! chip8.step(&mut bus).unwrap();
+//! chip8.step(&mut bus).unwrap();
+//! assert_eq!(bus.ram().get_v_registers()[0], 0x42);
+//! ```
+//!
+//! [`CosmacRAM`]: crate::memory::CosmacRAM
+
+use crate::memory::CosmacRAM;
+
+/// The memory that CHIP-8 instructions read and write through `I`, and
+/// nothing else, see the [module](self) docs.
+///
+/// The interpreter checks that an access is inside RAM, and not write
+/// protected, before it goes through the bus.
+pub trait MemoryBus {
+    /// The byte a CHIP-8 instruction reads at `address`.
+    fn read(&self, address: usize) -> u8;
+
+    /// Write a byte from a CHIP-8 instruction to `address`.
+    fn write(&mut self, address: usize, value: u8);
+
+    /// The RAM behind the bus, holding the interpreter's own state.
+    fn ram(&self) -> &CosmacRAM;
+
+    /// The RAM behind the bus, mutably.
+    fn ram_mut(&mut self) -> &mut CosmacRAM;
+}
+
+impl MemoryBus for CosmacRAM {
+    #[inline]
+    fn read(&self, address: usize) -> u8 {
+        self.bytes()[address]
+    }
+
+    #[inline]
+    fn write(&mut self, address: usize, value: u8) {
+        self.load_bytes_unprotected(&[value], address)
+            .expect("Bus writes should be inside RAM.");
+    }
+
+    #[inline]
+    fn ram(&self) -> &CosmacRAM {
+        self
+    }
+
+    #[inline]
+    fn ram_mut(&mut self) -> &mut CosmacRAM {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryBus;
+    use crate::{
+        interpreter::Chip8Interpreter, memory::CosmacRAM, quirks::Quirks, rng::MockChip8Rng,
+    };
+
+    const COUNTER_ADDRESS: usize = 0x0FFE;
+
+    // A bus where `COUNTER_ADDRESS` reads as a counter, e.g. of frames, and
+    // swallows writes, keeping the last one.
+    struct CounterBus {
+        ram: CosmacRAM,
+        counter: u8,
+        last_write: Option<u8>,
+    }
+
+    // An interpreter that lets I point past the program area, at the top of
+    // memory where the counter is, as with the FX1E overflow quirk.
+    fn new_chip8() -> Chip8Interpreter<MockChip8Rng> {
+        let quirks = Quirks {
+            i_overflow_sets_vf: true,
+            ..Quirks::cosmac_vip()
+        };
+        Chip8Interpreter::with_quirks(MockChip8Rng::new(), quirks)
+    }
+
+    impl MemoryBus for CounterBus {
+        fn read(&self, address: usize) -> u8 {
+            match address {
+                COUNTER_ADDRESS => self.counter,
+                _ => self.ram.read(address),
+            }
+        }
+
+        fn write(&mut self, address: usize, value: u8) {
+            match address {
+                COUNTER_ADDRESS => self.last_write = Some(value),
+                _ => self.ram.write(address, value),
+            }
+        }
+
+        fn ram(&self) -> &CosmacRAM {
+            &self.ram
+        }
+
+        fn ram_mut(&mut self) -> &mut CosmacRAM {
+            &mut self.ram
+        }
+    }
+
+    #[test]
+    fn program_reads_memory_mapped_counter() {
+        let mut bus = CounterBus {
+            ram: CosmacRAM::new(),
+            counter: 0,
+            last_write: None,
+        };
+        bus.ram
+            .load_chip8_program(&chip8_program_into_bytes!(
+                0xAFFE  // I = 0x0FFE
+                0xF065  // V0 = counter
+                0x1200
+            ))
+            .unwrap();
+        let mut chip8 = new_chip8();
+        chip8.reset(bus.ram_mut());
+
+        for counter in [7, 8, 200] {
+            bus.counter = counter;
+            chip8.step_n(&mut bus, 3).unwrap();
+            assert_eq!(bus.ram.get_v_registers()[0], counter);
+        }
+        assert_eq!(
+            bus.ram.bytes()[COUNTER_ADDRESS],
+            0,
+            "The counter isn't stored in RAM"
+        );
+    }
+
+    #[test]
+    fn program_writes_memory_mapped_address() {
+        let mut bus = CounterBus {
+            ram: CosmacRAM::new(),
+            counter: 0,
+            last_write: None,
+        };
+        bus.ram
+            .load_chip8_program(&chip8_program_into_bytes!(
+                0x6042  // V0 = 0x42
+                0x6199  // V1 = 0x99
+                0xAFFD  // I = 0x0FFD
+                0xF155  // M[0x0FFD] = V0, M[0x0FFE] = V1
+            ))
+            .unwrap();
+        let mut chip8 = new_chip8();
+        chip8.reset(bus.ram_mut());
+
+        chip8.step_n(&mut bus, 4).unwrap();
+        assert_eq!(bus.last_write, Some(0x99));
+        assert_eq!(bus.ram.bytes()[0x0FFD..], [0x42, 0x00, 0x00]);
+    }
+}
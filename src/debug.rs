@@ -1,46 +1,90 @@
+// Checks for CHIP-8 programs doing what the COSMAC VIP interpreter can't,
+// made in every build to return an error rather than corrupting memory or
+// running into the interpreter's own memory.
+
 use crate::{
-    interpreter::STACK_POINTER_ADDRESS,
-    memory::{
-        CosmacRAM, MEMORY_START_ADDRESS, PROGRAM_LAST_ADDRESS, PROGRAM_START_ADDRESS,
-        STACK_START_ADDRESS,
-    },
+    interpreter::STACK_POINTER_OFFSET,
+    memory::{CosmacRAM, MEMORY_START_ADDRESS},
+    Error, Result,
 };
 
-pub fn panic_if_pc_address_not_in_chip8_program_range(address: u16) {
-    if !(PROGRAM_START_ADDRESS..=PROGRAM_LAST_ADDRESS).contains(&(address as usize)) {
-        panic!(
-            "Attempt to set program counter to address {address:#X} which is outside of \
-            CHIP-8 program address range."
-        );
+// The most subroutine calls the COSMAC VIP interpreter can nest.
+const MAX_CHIP8_STACK_DEPTH: usize = 12;
+
+pub fn check_pc_address_in_chip8_program_range(
+    ram: &CosmacRAM,
+    address: u16,
+    program_start_address: usize,
+) -> Result<()> {
+    if !(program_start_address..=ram.program_last_address()).contains(&(address as usize)) {
+        return Err(Error::ProgramCounterOutOfRange(address));
     }
+    Ok(())
 }
 
-pub fn panic_if_i_address_out_of_bounds(address: u16) {
+pub fn check_i_address_in_bounds(
+    ram: &CosmacRAM,
+    address: u16,
+    i_overflow_sets_vf: bool,
+) -> Result<()> {
     // `I` register needs to be able to access character glyphs, which lie before
-    // PROGRAM_START_ADDRESS.
-    if !(MEMORY_START_ADDRESS..=PROGRAM_LAST_ADDRESS).contains(&(address as usize)) {
-        panic!(
-            "Attempt to set I address to {address:#X} which is outside of \
-            normal operating range."
-        );
+    // PROGRAM_START_ADDRESS. With the FX1E overflow quirk it can also be left
+    // anywhere up to the top of memory, before wrapping past 0x0FFF.
+    let last_address = match i_overflow_sets_vf {
+        true => ram.memory_size() - 1,
+        false => ram.program_last_address(),
+    };
+    if !(MEMORY_START_ADDRESS..=last_address).contains(&(address as usize)) {
+        return Err(Error::IAddressOutOfRange(address));
     }
+    Ok(())
 }
 
-pub fn panic_if_chip8_stack_empty_on_subroutine_return(ram: &CosmacRAM) {
-    let sp = ram.get_u16_at(STACK_POINTER_ADDRESS);
-    if sp == STACK_START_ADDRESS as u16 {
-        panic!(
-            "Cannot return when not in a subroutine. \
-            CHIP-8 subroutine stack is empty!"
-        );
+pub fn check_chip8_stack_not_empty_on_subroutine_return(ram: &CosmacRAM) -> Result<()> {
+    if ram.chip8_stack_depth() == 0 {
+        return Err(Error::Chip8StackUnderflow);
     }
+    Ok(())
 }
 
-pub fn panic_if_chip8_stack_full(ram: &CosmacRAM) {
-    if ram.get_u16_at(STACK_POINTER_ADDRESS) == STACK_START_ADDRESS as u16 + 12 * 2 {
-        panic!(
-            "CHIP-8 stack overflow! \
-            COSMAC VIP only allows 12 levels of subroutine nesting."
+pub fn check_chip8_stack_not_full(ram: &CosmacRAM) -> Result<()> {
+    // a stack pointer written over by the program may be outside of the stack
+    // altogether, leaving nowhere to push to
+    let stack_pointer = ram.get_work_area_u16(STACK_POINTER_OFFSET) as usize;
+    let stack_range = ram.stack_start_address()..ram.interpreter_work_area_start_address() - 1;
+    if !stack_range.contains(&stack_pointer) || ram.chip8_stack_depth() == MAX_CHIP8_STACK_DEPTH {
+        return Err(Error::Chip8StackOverflow);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_i_address_in_bounds;
+    use crate::{memory::CosmacRAM, Error};
+
+    #[test]
+    fn i_address_in_program_range_without_overflow_quirk() {
+        let ram = CosmacRAM::new();
+        let last_address = ram.program_last_address() as u16;
+        assert_eq!(check_i_address_in_bounds(&ram, last_address, false), Ok(()));
+        assert_eq!(
+            check_i_address_in_bounds(&ram, last_address + 1, false),
+            Err(Error::IAddressOutOfRange(last_address + 1))
+        );
+        assert_eq!(
+            check_i_address_in_bounds(&ram, 0x0FFF, false),
+            Err(Error::IAddressOutOfRange(0x0FFF))
+        );
+    }
+
+    #[test]
+    fn i_address_up_to_top_of_memory_with_overflow_quirk() {
+        let ram = CosmacRAM::new();
+        assert_eq!(check_i_address_in_bounds(&ram, 0x0FFF, true), Ok(()));
+        assert_eq!(
+            check_i_address_in_bounds(&ram, 0x1000, true),
+            Err(Error::IAddressOutOfRange(0x1000))
         );
     }
 }
@@ -0,0 +1,176 @@
+//! Disassembly of CHIP-8 instructions into human readable mnemonics.
+//!
+//! The mnemonics follow the widely used syntax of Cowgod's CHIP-8 technical
+//! reference, extended with the SCHIP and XO-CHIP instructions. Words that are
+//! not a known instruction disassemble to a `DW` (define word) directive.
+
+/// Disassemble a single CHIP-8 instruction.
+pub fn disassemble(instruction: u16) -> String {
+    let x = (instruction & 0x0F00) >> 8;
+    let y = (instruction & 0x00F0) >> 4;
+    let n = instruction & 0x000F;
+    let kk = instruction & 0x00FF;
+    let mmm = instruction & 0x0FFF;
+
+    match instruction {
+        0x00E0 => "CLS".to_string(),
+        0x00EE => "RET".to_string(),
+        op if op & 0xFFF0 == 0x00C0 => format!("SCD {n}"),
+        0x00FB => "SCR".to_string(),
+        0x00FC => "SCL".to_string(),
+        0x00FD => "EXIT".to_string(),
+        0x00FE => "LOW".to_string(),
+        0x00FF => "HIGH".to_string(),
+        op if op & 0xF000 == 0x0000 => format!("SYS 0x{mmm:0>3X}"),
+        op if op & 0xF000 == 0x1000 => format!("JP 0x{mmm:0>3X}"),
+        op if op & 0xF000 == 0x2000 => format!("CALL 0x{mmm:0>3X}"),
+        op if op & 0xF000 == 0x3000 => format!("SE V{x:X}, 0x{kk:0>2X}"),
+        op if op & 0xF000 == 0x4000 => format!("SNE V{x:X}, 0x{kk:0>2X}"),
+        op if op & 0xF00F == 0x5000 => format!("SE V{x:X}, V{y:X}"),
+        0x7000 => "NOP".to_string(),
+        op if op & 0xF000 == 0x6000 => format!("LD V{x:X}, 0x{kk:0>2X}"),
+        op if op & 0xF000 == 0x7000 => format!("ADD V{x:X}, 0x{kk:0>2X}"),
+        op if op & 0xF00F == 0x8000 => format!("LD V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8001 => format!("OR V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8002 => format!("AND V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8003 => format!("XOR V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8004 => format!("ADD V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8005 => format!("SUB V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8006 => format!("SHR V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x8007 => format!("SUBN V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x800E => format!("SHL V{x:X}, V{y:X}"),
+        op if op & 0xF00F == 0x9000 => format!("SNE V{x:X}, V{y:X}"),
+        op if op & 0xF000 == 0xA000 => format!("LD I, 0x{mmm:0>3X}"),
+        op if op & 0xF000 == 0xB000 => format!("JP V0, 0x{mmm:0>3X}"),
+        op if op & 0xF000 == 0xC000 => format!("RND V{x:X}, 0x{kk:0>2X}"),
+        op if op & 0xF000 == 0xD000 => format!("DRW V{x:X}, V{y:X}, {n}"),
+        op if op & 0xF0FF == 0xE09E => format!("SKP V{x:X}"),
+        op if op & 0xF0FF == 0xE0A1 => format!("SKNP V{x:X}"),
+        op if op & 0xF0FF == 0xF001 => format!("PLANE {x}"),
+        0xF002 => "AUDIO".to_string(),
+        op if op & 0xF0FF == 0xF007 => format!("LD V{x:X}, DT"),
+        op if op & 0xF0FF == 0xF00A => format!("LD V{x:X}, K"),
+        op if op & 0xF0FF == 0xF015 => format!("LD DT, V{x:X}"),
+        op if op & 0xF0FF == 0xF018 => format!("LD ST, V{x:X}"),
+        op if op & 0xF0FF == 0xF01E => format!("ADD I, V{x:X}"),
+        op if op & 0xF0FF == 0xF029 => format!("LD F, V{x:X}"),
+        op if op & 0xF0FF == 0xF030 => format!("LD HF, V{x:X}"),
+        op if op & 0xF0FF == 0xF033 => format!("LD B, V{x:X}"),
+        op if op & 0xF0FF == 0xF03A => format!("PITCH V{x:X}"),
+        op if op & 0xF0FF == 0xF055 => format!("LD [I], V{x:X}"),
+        op if op & 0xF0FF == 0xF065 => format!("LD V{x:X}, [I]"),
+        op if op & 0xF0FF == 0xF075 => format!("LD R, V{x:X}"),
+        op if op & 0xF0FF == 0xF085 => format!("LD V{x:X}, R"),
+        op => format!("DW 0x{op:0>4X}"),
+    }
+}
+
+/// The family of opcodes an instruction belongs to, e.g. `"8XY4"` for
+/// `0x8124`. Words that are not a known instruction belong to the `"????"`
+/// family.
+pub fn opcode_family(instruction: u16) -> &'static str {
+    match instruction {
+        0x00E0 => "00E0",
+        0x00EE => "00EE",
+        op if op & 0xFFF0 == 0x00C0 => "00CN",
+        0x00FB => "00FB",
+        0x00FC => "00FC",
+        0x00FD => "00FD",
+        0x00FE => "00FE",
+        0x00FF => "00FF",
+        op if op & 0xF000 == 0x0000 => "0MMM",
+        op if op & 0xF000 == 0x1000 => "1MMM",
+        op if op & 0xF000 == 0x2000 => "2MMM",
+        op if op & 0xF000 == 0x3000 => "3XKK",
+        op if op & 0xF000 == 0x4000 => "4XKK",
+        op if op & 0xF00F == 0x5000 => "5XY0",
+        op if op & 0xF000 == 0x6000 => "6XKK",
+        op if op & 0xF000 == 0x7000 => "7XKK",
+        op if op & 0xF00F == 0x8000 => "8XY0",
+        op if op & 0xF00F == 0x8001 => "8XY1",
+        op if op & 0xF00F == 0x8002 => "8XY2",
+        op if op & 0xF00F == 0x8003 => "8XY3",
+        op if op & 0xF00F == 0x8004 => "8XY4",
+        op if op & 0xF00F == 0x8005 => "8XY5",
+        op if op & 0xF00F == 0x8006 => "8XY6",
+        op if op & 0xF00F == 0x8007 => "8XY7",
+        op if op & 0xF00F == 0x800E => "8XYE",
+        op if op & 0xF00F == 0x9000 => "9XY0",
+        op if op & 0xF000 == 0xA000 => "AMMM",
+        op if op & 0xF000 == 0xB000 => "BMMM",
+        op if op & 0xF000 == 0xC000 => "CXKK",
+        op if op & 0xF000 == 0xD000 => "DXYN",
+        op if op & 0xF0FF == 0xE09E => "EX9E",
+        op if op & 0xF0FF == 0xE0A1 => "EXA1",
+        0xF002 => "F002",
+        op if op & 0xF0FF == 0xF001 => "FN01",
+        op if op & 0xF0FF == 0xF007 => "FX07",
+        op if op & 0xF0FF == 0xF00A => "FX0A",
+        op if op & 0xF0FF == 0xF015 => "FX15",
+        op if op & 0xF0FF == 0xF018 => "FX18",
+        op if op & 0xF0FF == 0xF01E => "FX1E",
+        op if op & 0xF0FF == 0xF029 => "FX29",
+        op if op & 0xF0FF == 0xF030 => "FX30",
+        op if op & 0xF0FF == 0xF033 => "FX33",
+        op if op & 0xF0FF == 0xF03A => "FX3A",
+        op if op & 0xF0FF == 0xF055 => "FX55",
+        op if op & 0xF0FF == 0xF065 => "FX65",
+        op if op & 0xF0FF == 0xF075 => "FX75",
+        op if op & 0xF0FF == 0xF085 => "FX85",
+        _ => "????",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, opcode_family};
+
+    #[test]
+    fn disassemble_instructions() {
+        let expected = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x00C4, "SCD 4"),
+            (0x00FD, "EXIT"),
+            (0x0123, "SYS 0x123"),
+            (0x1208, "JP 0x208"),
+            (0x2ABC, "CALL 0xABC"),
+            (0x3744, "SE V7, 0x44"),
+            (0x5AB0, "SE VA, VB"),
+            (0x7000, "NOP"),
+            (0x7F01, "ADD VF, 0x01"),
+            (0x8124, "ADD V1, V2"),
+            (0x812E, "SHL V1, V2"),
+            (0xA05C, "LD I, 0x05C"),
+            (0xB300, "JP V0, 0x300"),
+            (0xD125, "DRW V1, V2, 5"),
+            (0xE3A1, "SKNP V3"),
+            (0xF201, "PLANE 2"),
+            (0xF002, "AUDIO"),
+            (0xF40A, "LD V4, K"),
+            (0xF555, "LD [I], V5"),
+            (0xF665, "LD V6, [I]"),
+        ];
+        for (instruction, mnemonic) in expected {
+            assert_eq!(disassemble(instruction), mnemonic);
+        }
+    }
+
+    #[test]
+    fn disassemble_unknown_instruction() {
+        assert_eq!(disassemble(0x5001), "DW 0x5001");
+        assert_eq!(disassemble(0xE000), "DW 0xE000");
+        assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+    }
+
+    #[test]
+    fn opcode_families() {
+        assert_eq!(opcode_family(0x1208), "1MMM");
+        assert_eq!(opcode_family(0x7000), "7XKK");
+        assert_eq!(opcode_family(0x812E), "8XYE");
+        assert_eq!(opcode_family(0xD125), "DXYN");
+        assert_eq!(opcode_family(0xF002), "F002");
+        assert_eq!(opcode_family(0xF201), "FN01");
+        assert_eq!(opcode_family(0x5001), "????");
+    }
+}
@@ -0,0 +1,133 @@
+//! Filters applied to the display as it is drawn, between the CHIP-8 display
+//! buffer and the colors put on the screen.
+
+use crate::memory::DisplayView;
+
+/// Phosphor decay, to hide the flicker of sprites that are erased and drawn
+/// again with XOR.
+///
+/// Each pixel has an intensity that is full while the pixel is on, and
+/// fades out evenly over `decay_frames` frames once it is turned off, like
+/// the glow of the phosphor on a CRT. A sprite that is only off for a frame
+/// at a time barely dims.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhosphorFilter {
+    decay_frames: u8,
+    width: usize,
+    // the number of frames until each pixel is dark, `decay_frames` while
+    // the pixel is on
+    frames_left: Vec<u8>,
+}
+
+impl PhosphorFilter {
+    /// A filter where pixels take `decay_frames` frames to fade out.
+    ///
+    /// # Panics
+    /// Panics if `decay_frames` is 0.
+    pub fn new(decay_frames: u8) -> Self {
+        assert!(decay_frames > 0, "Pixels should fade for at least a frame");
+        Self {
+            decay_frames,
+            width: 0,
+            frames_left: Vec::new(),
+        }
+    }
+
+    /// Move on to the next frame, showing `display`. Pixels that are on are
+    /// at full intensity, and the rest fade a step. All pixels start dark
+    /// again when the size of the display changes.
+    ///
+    /// Returns whether the intensity of any pixel changed, so that the
+    /// display should be drawn again even if `display` itself hasn't changed.
+    pub fn update(&mut self, display: DisplayView) -> bool {
+        let num_pixels = display.width() * display.height();
+        if self.width != display.width() || self.frames_left.len() != num_pixels {
+            self.width = display.width();
+            self.frames_left = vec![0; num_pixels];
+        }
+        let mut changed = false;
+        for (frames_left, (_, _, on)) in self.frames_left.iter_mut().zip(display.pixels()) {
+            let next = match on {
+                true => self.decay_frames,
+                false => frames_left.saturating_sub(1),
+            };
+            changed |= next != *frames_left;
+            *frames_left = next;
+        }
+        changed
+    }
+
+    /// The intensity of the pixel at (x, y), from 0 (dark) to 255 (fully on).
+    /// Pixels outside of the last display shown are dark.
+    pub fn intensity(&self, x: usize, y: usize) -> u8 {
+        if x >= self.width {
+            return 0;
+        }
+        self.frames_left
+            .get(y * self.width + x)
+            .map_or(0, |&frames_left| {
+                (frames_left as u32 * 255 / self.decay_frames as u32) as u8
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PhosphorFilter;
+    use crate::memory::{DisplayView, LORES_DISPLAY_SIZE};
+
+    // A lores display with only the top left pixel possibly on.
+    fn display(top_left_on: bool) -> Vec<u8> {
+        let mut plane = vec![0; LORES_DISPLAY_SIZE.plane_size()];
+        plane[0] = (top_left_on as u8) << 7;
+        plane
+    }
+
+    #[test]
+    fn pixel_fades_out_evenly() {
+        let mut filter = PhosphorFilter::new(4);
+        let mut intensities = Vec::new();
+        for on in [true, false, false, false, false, false] {
+            filter.update(DisplayView::new(&display(on), LORES_DISPLAY_SIZE));
+            intensities.push(filter.intensity(0, 0));
+        }
+        assert_eq!(intensities, [255, 191, 127, 63, 0, 0]);
+        assert_eq!(filter.intensity(1, 0), 0);
+    }
+
+    #[test]
+    fn pixel_toggling_every_frame_barely_dims() {
+        let mut filter = PhosphorFilter::new(4);
+        let mut intensities = Vec::new();
+        for frame in 0..6 {
+            filter.update(DisplayView::new(
+                &display(frame % 2 == 0),
+                LORES_DISPLAY_SIZE,
+            ));
+            intensities.push(filter.intensity(0, 0));
+        }
+        assert_eq!(intensities, [255, 191, 255, 191, 255, 191]);
+
+        // with no decay the pixel flickers between on and off
+        let mut filter = PhosphorFilter::new(1);
+        let mut intensities = Vec::new();
+        for frame in 0..4 {
+            filter.update(DisplayView::new(
+                &display(frame % 2 == 0),
+                LORES_DISPLAY_SIZE,
+            ));
+            intensities.push(filter.intensity(0, 0));
+        }
+        assert_eq!(intensities, [255, 0, 255, 0]);
+    }
+
+    #[test]
+    fn changes_until_dark() {
+        let mut filter = PhosphorFilter::new(2);
+        let changes: Vec<_> = [true, true, false, false, false]
+            .into_iter()
+            .map(|on| filter.update(DisplayView::new(&display(on), LORES_DISPLAY_SIZE)))
+            .collect();
+        assert_eq!(changes, [true, false, true, true, false]);
+    }
+}
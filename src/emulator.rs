@@ -1,167 +1,3911 @@
 use std::{
+    fs, io,
+    path::{Path, PathBuf},
     thread::sleep,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
-use pixels::PixelsBuilder;
+use pixels::{Pixels, PixelsBuilder};
 use winit::{
-    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
-    event_loop::EventLoop,
+    event::{ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoopBuilder,
+    platform::run_return::EventLoopExtRunReturn,
     window::WindowBuilder,
 };
 
 use crate::{
-    interpreter::Chip8Interpreter,
-    memory::CosmacRAM,
-    peripherals::{Beeper, Tone},
-    Result,
+    display_filter::PhosphorFilter,
+    emulator_thread::{EmulatorInput, EmulatorOutput, EmulatorThread},
+    gif::GifRecorder,
+    hash,
+    interpreter::{
+        Chip8Interpreter, Chip8State, Chip8StateOwned, StepOutcome, TimerMode, NUM_RPL_FLAGS,
+    },
+    keymap::KeyMap,
+    memory::{CosmacRAM, DisplaySize, DisplayView, NUM_DISPLAY_PLANES},
+    overlay::{
+        debug_lines, draw_debug_panel, draw_rates, RateMeter, DEBUG_PANEL_HEIGHT, DEBUG_PANEL_WIDTH,
+    },
+    palette::{next_palette, palette_cycle, Palette},
+    peripherals::{Beeper, SampledTone, Tone, ToneState, Volume},
+    quirks::{Chip8Variant, Quirks},
+    recording::{Recording, ReplayRng},
+    rng::{Chip8Rng, LfsrRng, RngAlgorithm},
+    rom::Rom,
+    rom_config::RomConfig,
+    save_state::SaveState,
+    scheduler::Scheduler,
+    screenshot::{capture_file_name, scale_rgba, screenshot_png},
+    speed::{Speed, TurboMode},
+    Error, Result,
 };
 
-type Chip8 = Chip8Interpreter<fastrand::Rng>;
+type Chip8 = Chip8Interpreter<EmulatorRng>;
 
 const INSTRUCTIONS_FREQ_HZ: u64 = 700; // number of CHIP-8 instructions performed per second
-const INSTRUCTION_DURATION: Duration = Duration::from_micros(1_000_000 / INSTRUCTIONS_FREQ_HZ);
+const TIMER_TICK_DURATION: Duration = Duration::from_micros(16_667); // 60Hz
 const DISPLAY_SCALE_FACTOR: u32 = 16;
 const TONE_FREQ_HZ: u32 = 440;
+const WINDOW_TITLE: &str = "CHIP-8 Emulator";
+// F1 to F4 load the save state in slots 1 to 4, and save it with Shift held
+const SAVE_SLOT_KEYS: [VirtualKeyCode; 4] = [
+    VirtualKeyCode::F1,
+    VirtualKeyCode::F2,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+];
+// the longest clip recorded, in 60Hz ticks, to bound the memory it takes
+const MAX_CLIP_TICKS: usize = 60 * 60;
+// the furthest the emulation falls behind real time before the time it
+// would take to catch up on is dropped, e.g. after a stall in a debugger
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+// how far back the frame and instruction rates shown are averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+// holding the frame advance key advances a frame, then after a delay keeps
+// advancing them at a steady rate
+const FRAME_ADVANCE_REPEAT_DELAY: Duration = Duration::from_millis(400);
+const FRAME_ADVANCE_REPEAT_INTERVAL: Duration = Duration::from_millis(100);
+// how long the volume is shown in the title after it's changed
+const VOLUME_SHOWN_FOR: Duration = Duration::from_secs(2);
+// how long a notice, e.g. where a screenshot was saved, is shown in the title
+const NOTICE_SHOWN_FOR: Duration = Duration::from_secs(2);
 
-pub fn run(chip8_program: &[u8]) -> Result<()> {
-    // Initialise CHIP-8 RAM/"CPU"
-    let mut ram = CosmacRAM::new();
-    ram.load_chip8_program(chip8_program)?;
-    let mut chip8 = Chip8::new(fastrand::Rng::new());
-    chip8.reset(&mut ram);
+/// How [`run_with_config`] runs a rom. The defaults are those of [`run`].
+///
+/// # Example
+/// ```no_run
+/// # use chip8_emulator::{emulator::{self, EmulatorConfig}, rom::Rom};
+/// let rom = Rom::from_file("game.ch8").unwrap();
+/// let config = EmulatorConfig::new()
+///     .instructions_per_second(1200)
+///     .scale(8)
+///     .tone_hz(220)
+///     .palette([0x33, 0xFF, 0x33, 0xFF], [0x00, 0x00, 0x00, 0xFF]);
+/// emulator::run_with_config(&rom, config).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmulatorConfig {
+    variant: Chip8Variant,
+    quirks: Option<Quirks>,
+    rpl_flags_path: Option<PathBuf>,
+    save_states_path: Option<PathBuf>,
+    autosave_path: Option<PathBuf>,
+    resume: bool,
+    record_inputs_path: Option<PathBuf>,
+    play_inputs: Option<Recording>,
+    stop_after_playback: bool,
+    seed: Option<u64>,
+    rng: RngAlgorithm,
+    instructions_per_second: u64,
+    scale: u32,
+    tone_hz: u32,
+    volume: Volume,
+    palette: Palette,
+    key_map: KeyMap,
+    pause_keys: Vec<VirtualKeyCode>,
+    reset_keys: Vec<VirtualKeyCode>,
+    palette_keys: Vec<VirtualKeyCode>,
+    overlay_keys: Vec<VirtualKeyCode>,
+    debug_keys: Vec<VirtualKeyCode>,
+    screenshot_keys: Vec<VirtualKeyCode>,
+    clip_keys: Vec<VirtualKeyCode>,
+    frame_advance_keys: Vec<VirtualKeyCode>,
+    turbo_keys: Vec<VirtualKeyCode>,
+    slower_keys: Vec<VirtualKeyCode>,
+    faster_keys: Vec<VirtualKeyCode>,
+    turbo_mode: TurboMode,
+    pause_on_focus_loss: bool,
+    mute_keys: Vec<VirtualKeyCode>,
+    volume_down_keys: Vec<VirtualKeyCode>,
+    volume_up_keys: Vec<VirtualKeyCode>,
+    screenshot_dir: PathBuf,
+    screenshot_scale: Option<u32>,
+    phosphor_decay_frames: Option<u8>,
+    rom_title: Option<String>,
+}
 
-    // Set up devices (screen, keyboard and audio)
-    env_logger::init();
-    let event_loop = EventLoop::new();
-
-    let window = {
-        let size = winit::dpi::LogicalSize::new(64, 32);
-        let scaled_size = winit::dpi::LogicalSize::new(
-            size.width * DISPLAY_SCALE_FACTOR,
-            size.height * DISPLAY_SCALE_FACTOR,
-        );
-        WindowBuilder::new()
-            .with_title("CHIP-8 Emulator")
-            .with_inner_size(scaled_size)
-            .with_min_inner_size(size)
-            .build(&event_loop)
-            .unwrap()
-    };
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            variant: Chip8Variant::default(),
+            quirks: None,
+            rpl_flags_path: None,
+            save_states_path: None,
+            autosave_path: None,
+            resume: false,
+            record_inputs_path: None,
+            play_inputs: None,
+            stop_after_playback: false,
+            seed: None,
+            rng: RngAlgorithm::default(),
+            instructions_per_second: INSTRUCTIONS_FREQ_HZ,
+            scale: DISPLAY_SCALE_FACTOR,
+            tone_hz: TONE_FREQ_HZ,
+            volume: Volume::FULL,
+            palette: Palette::default(),
+            key_map: KeyMap::default(),
+            pause_keys: vec![VirtualKeyCode::P, VirtualKeyCode::Space],
+            reset_keys: vec![VirtualKeyCode::Back],
+            palette_keys: vec![VirtualKeyCode::F10],
+            overlay_keys: vec![VirtualKeyCode::F11],
+            debug_keys: vec![VirtualKeyCode::Tab],
+            screenshot_keys: vec![VirtualKeyCode::F12],
+            clip_keys: vec![VirtualKeyCode::F9],
+            frame_advance_keys: vec![VirtualKeyCode::Period],
+            // Tab, the usual turbo key, shows the debug panel
+            turbo_keys: vec![VirtualKeyCode::Grave],
+            slower_keys: vec![VirtualKeyCode::Minus],
+            faster_keys: vec![VirtualKeyCode::Equals],
+            turbo_mode: TurboMode::default(),
+            pause_on_focus_loss: true,
+            mute_keys: vec![VirtualKeyCode::M],
+            volume_down_keys: vec![VirtualKeyCode::LBracket],
+            volume_up_keys: vec![VirtualKeyCode::RBracket],
+            screenshot_dir: PathBuf::from("."),
+            screenshot_scale: None,
+            phosphor_decay_frames: None,
+            rom_title: None,
+        }
+    }
+}
 
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture =
-            pixels::SurfaceTexture::new(window_size.width, window_size.height, &window);
-        let mut pixels = PixelsBuilder::new(64, 32, surface_texture)
-            .enable_vsync(true)
-            .build()
-            .unwrap();
+impl EmulatorConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        // initialise frame buffer
-        pixels
-            .frame_mut()
-            .copy_from_slice(&rgba_pixels_from_cosmac_display_buffer(&ram));
+    /// Interpret the rom as `variant` would.
+    pub fn variant(mut self, variant: Chip8Variant) -> Self {
+        self.variant = variant;
+        self
+    }
 
-        pixels
-    };
+    /// Name the rom `title` in the window title, e.g. the title a rom
+    /// database gives it, rather than by its file name.
+    pub fn rom_title(mut self, title: impl Into<String>) -> Self {
+        self.rom_title = Some(title.into());
+        self
+    }
 
-    let beeper = Beeper::new(TONE_FREQ_HZ);
+    /// Change the settings set in `rom_config`. A variant other than the one
+    /// configured drops the quirks set for it, before the quirk overrides are
+    /// applied to those of the variant. The key map file is left to the
+    /// caller to read, see [`key_map`](Self::key_map).
+    ///
+    /// # Panics
+    /// Panics if the instruction rate set is 0.
+    pub fn rom_config(mut self, rom_config: &RomConfig) -> Self {
+        if let Some(variant) = rom_config
+            .variant
+            .filter(|&variant| variant != self.variant)
+        {
+            self.variant = variant;
+            self.quirks = None;
+        }
+        if !rom_config.quirks.is_empty() {
+            let quirks = self.quirks.unwrap_or(self.variant.quirks());
+            self = self.quirks(rom_config.quirks.apply(quirks));
+        }
+        if let Some(instructions_per_second) = rom_config.instructions_per_second {
+            self = self.instructions_per_second(instructions_per_second);
+        }
+        if let Some(palette) = rom_config.palette {
+            self = self.palette_preset(palette);
+        }
+        if let Some(tone_hz) = rom_config.tone_hz {
+            self = self.tone_hz(tone_hz);
+        }
+        self
+    }
 
-    // run the main event loop
-    event_loop.run(move |event, _, control_flow| {
-        control_flow.set_poll();
+    /// Use `quirks` instead of those of the variant.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
 
-        match event {
-            Event::MainEventsCleared => {
-                let is_draw_instruction = Chip8::is_on_draw_instruction(&ram);
+    /// Load the SCHIP RPL user flags from this file at startup and write them
+    /// back to it when the window is closed.
+    pub fn rpl_flags_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rpl_flags_path = Some(path.into());
+        self
+    }
 
-                let start = Instant::now();
-                chip8.step(&mut ram);
-                sleep(start + INSTRUCTION_DURATION - Instant::now());
+    /// Save states in slots next to the rom file at `rom_path`, see
+    /// [`save_slot_path`]. Shift+F1 to Shift+F4 save to slots 1 to 4, and F1
+    /// to F4 load them. Without this the slot keys do nothing.
+    pub fn save_states_path(mut self, rom_path: impl Into<PathBuf>) -> Self {
+        self.save_states_path = Some(rom_path.into());
+        self
+    }
 
-                // update tone
-                let tone_should_be_sounding = Chip8::is_tone_sounding(&ram);
-                if tone_should_be_sounding && !beeper.is_tone_on() {
-                    beeper.start_tone();
-                } else if !tone_should_be_sounding && beeper.is_tone_on() {
-                    beeper.stop_tone();
-                }
+    /// Write a save state to this file when the window is closed.
+    pub fn autosave_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.autosave_path = Some(path.into());
+        self
+    }
+
+    /// Whether to carry on from the save state in the [autosave
+    /// file](Self::autosave_path) at startup, rather than starting the rom
+    /// afresh. A state saved with a different rom is ignored with a warning.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Record the hex key presses, timer ticks and random numbers given to the
+    /// rom, and write them to this file when the window is closed or the rom
+    /// exits, to be [played back](Self::play_inputs) later.
+    ///
+    /// The recording starts with the rom, so the rom starts afresh without
+    /// its RPL user flags, whatever [`resume`](Self::resume) says, and the
+    /// reset and save slot load keys do nothing while recording.
+    pub fn record_inputs(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record_inputs_path = Some(path.into());
+        self
+    }
+
+    /// Play back inputs [recorded](Self::record_inputs) with the same rom,
+    /// reproducing the recorded run exactly. The hex keys on the keyboard are
+    /// ignored until the recording ends, after which they work as usual
+    /// unless [`stop_after_playback`](Self::stop_after_playback) is set.
+    ///
+    /// As with recording, the rom starts afresh without its RPL user flags,
+    /// and the reset and save slot load keys do nothing during playback.
+    pub fn play_inputs(mut self, recording: Recording) -> Self {
+        self.play_inputs = Some(recording);
+        self
+    }
+
+    /// Whether to close the window when the [played back](Self::play_inputs)
+    /// recording ends, rather than carrying on with live input.
+    pub fn stop_after_playback(mut self, stop: bool) -> Self {
+        self.stop_after_playback = stop;
+        self
+    }
+
+    /// Seed the random number generator used by CXKK, so that runs can be
+    /// reproduced.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// The random number generator used by CXKK, `fastrand`'s by default.
+    pub fn rng(mut self, rng: RngAlgorithm) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// The number of CHIP-8 instructions run each second. They're run in
+    /// bursts, one each 60Hz frame, with the timers ticking and the display
+    /// presented once a frame whatever the rate.
+    ///
+    /// # Panics
+    /// Panics if `instructions_per_second` is 0.
+    pub fn instructions_per_second(mut self, instructions_per_second: u64) -> Self {
+        assert!(
+            instructions_per_second > 0,
+            "At least one instruction should run each second"
+        );
+        self.instructions_per_second = instructions_per_second;
+        self
+    }
+
+    /// The number of window pixels, across and down, for each CHIP-8 pixel.
+    ///
+    /// # Panics
+    /// Panics if `scale` is 0.
+    pub fn scale(mut self, scale: u32) -> Self {
+        assert!(scale > 0, "The display scale should be at least 1");
+        self.scale = scale;
+        self
+    }
+
+    /// The pitch of the tone played while the tone timer is running, for
+    /// variants without XO-CHIP audio patterns.
+    pub fn tone_hz(mut self, tone_hz: u32) -> Self {
+        self.tone_hz = tone_hz;
+        self
+    }
+
+    /// The volume of the tone, in percent, full by default. The volume keys
+    /// change it from there for the rest of the run.
+    ///
+    /// # Panics
+    /// Panics if `percent` is more than 100.
+    pub fn volume(mut self, percent: u8) -> Self {
+        self.volume = Volume::new(percent);
+        self
+    }
+
+    /// The RGBA colors of lit and unlit pixels, see [`Palette::new`].
+    pub fn palette(self, on: [u8; 4], off: [u8; 4]) -> Self {
+        self.palette_preset(Palette::new(on, off))
+    }
+
+    /// The colors to draw the display in, e.g. [`Palette::AMBER`]. Black on
+    /// white by default.
+    pub fn palette_preset(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Fade pixels out over `decay_frames` 60Hz frames once they are turned
+    /// off, to hide flicker, see [`PhosphorFilter`]. Off by default.
+    ///
+    /// # Panics
+    /// Panics if `decay_frames` is 0.
+    pub fn phosphor_decay(mut self, decay_frames: u8) -> Self {
+        assert!(decay_frames > 0, "Pixels should fade for at least a frame");
+        self.phosphor_decay_frames = Some(decay_frames);
+        self
+    }
+
+    /// The host keys that press each of the hex keys.
+    pub fn key_map(mut self, key_map: KeyMap) -> Self {
+        self.key_map = key_map;
+        self
+    }
+
+    /// The keys that pause and resume the emulation, P and Space by default.
+    pub fn pause_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.pause_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that restart the rom from the beginning, Backspace by default.
+    pub fn reset_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.reset_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that switch to the next built-in palette, F10 by default.
+    pub fn palette_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.palette_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that show and hide the frames and instructions per second
+    /// counter, F11 by default. The counter starts hidden.
+    pub fn overlay_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.overlay_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that show and hide a panel next to the display with the
+    /// registers, the instruction being run and the call stack, Tab by
+    /// default. The panel starts hidden.
+    pub fn debug_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.debug_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that save a screenshot of the display as a PNG, F12 by
+    /// default. See [`EmulatorConfig::screenshot_dir`].
+    pub fn screenshot_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.screenshot_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that start and stop recording a clip of the display, saved
+    /// as an animated GIF, F9 by default. Clips are at most a minute long.
+    /// See [`EmulatorConfig::screenshot_dir`].
+    pub fn clip_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.clip_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that run a single 60Hz frame of the rom while paused, Period
+    /// by default. Holding one down keeps advancing frames, ten a second.
+    pub fn frame_advance_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.frame_advance_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that run the emulation faster while held, see
+    /// [`EmulatorConfig::turbo_mode`]. Backquote by default, as Tab shows
+    /// the [debug panel](Self::debug_keys).
+    pub fn turbo_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.turbo_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that halve the instructions run each second for the rest of
+    /// the run, Minus by default. The timers keep ticking at 60Hz.
+    pub fn slower_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.slower_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that double the instructions run each second for the rest of
+    /// the run, Equals by default. The timers keep ticking at 60Hz.
+    pub fn faster_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.faster_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// What the [turbo keys](Self::turbo_keys) run faster: everything,
+    /// timers included, by default, or only the instructions.
+    pub fn turbo_mode(mut self, turbo_mode: TurboMode) -> Self {
+        self.turbo_mode = turbo_mode;
+        self
+    }
+
+    /// Whether to pause while the window doesn't have focus, resuming once it
+    /// gets it back unless paused with the [pause keys](Self::pause_keys)
+    /// too. On by default.
+    pub fn pause_on_focus_loss(mut self, pause: bool) -> Self {
+        self.pause_on_focus_loss = pause;
+        self
+    }
+
+    /// The keys that mute and unmute the tone, M by default.
+    pub fn mute_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.mute_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that turn the volume of the tone down by 10%, `[` by
+    /// default.
+    pub fn volume_down_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.volume_down_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// The keys that turn the volume of the tone up by 10%, `]` by default.
+    pub fn volume_up_keys(mut self, keys: impl IntoIterator<Item = VirtualKeyCode>) -> Self {
+        self.volume_up_keys = keys.into_iter().collect();
+        self
+    }
+
+    /// Save screenshots and clips in this directory, as
+    /// `<rom>-YYYYMMDD-HHMMSS.png` and `.gif`. The working directory by
+    /// default.
+    pub fn screenshot_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.screenshot_dir = dir.into();
+        self
+    }
+
+    /// Draw each CHIP-8 pixel in screenshots and clips as a `scale` by `scale`
+    /// square, the [window scale](Self::scale) by default.
+    ///
+    /// # Panics
+    /// Panics if `scale` is 0.
+    pub fn screenshot_scale(mut self, scale: u32) -> Self {
+        assert!(
+            scale > 0,
+            "Screenshots should be at least a pixel per pixel"
+        );
+        self.screenshot_scale = Some(scale);
+        self
+    }
+
+    // The action bound to `key`, if any.
+    fn hotkey(&self, key: VirtualKeyCode) -> Option<Hotkey> {
+        [
+            (&self.pause_keys, Hotkey::Pause),
+            (&self.reset_keys, Hotkey::Reset),
+            (&self.palette_keys, Hotkey::Palette),
+            (&self.overlay_keys, Hotkey::Overlay),
+            (&self.debug_keys, Hotkey::Debug),
+            (&self.screenshot_keys, Hotkey::Screenshot),
+            (&self.clip_keys, Hotkey::Clip),
+            (&self.frame_advance_keys, Hotkey::FrameAdvance),
+            (&self.turbo_keys, Hotkey::Turbo),
+            (&self.slower_keys, Hotkey::Slower),
+            (&self.faster_keys, Hotkey::Faster),
+            (&self.mute_keys, Hotkey::Mute),
+            (&self.volume_down_keys, Hotkey::VolumeDown),
+            (&self.volume_up_keys, Hotkey::VolumeUp),
+        ]
+        .into_iter()
+        .find(|(keys, _)| keys.contains(&key))
+        .map(|(_, hotkey)| hotkey)
+    }
+}
+
+/// Run a CHIP-8 rom in a new window, interpreting it as `variant` would.
+/// If `quirks` are given, they are used instead of those of `variant`.
+///
+/// If `rpl_flags_path` is given, the SCHIP RPL user flags are loaded from that
+/// file at startup and written back to it when the window is closed.
+///
+/// If `seed` is given, the random number generator used by CXKK is seeded
+/// with it, so that runs can be reproduced.
+///
+/// Everything else is left as in [`EmulatorConfig::default`], see
+/// [`run_with_config`] to change it.
+pub fn run(
+    rom: &Rom,
+    variant: Chip8Variant,
+    quirks: Option<Quirks>,
+    rpl_flags_path: Option<&Path>,
+    seed: Option<u64>,
+) -> Result<()> {
+    let mut config = EmulatorConfig::new().variant(variant);
+    config.quirks = quirks;
+    config.rpl_flags_path = rpl_flags_path.map(Path::to_path_buf);
+    config.seed = seed;
+    run_with_config(rom, config)
+}
+
+/// Run a CHIP-8 rom in a new window, as set up by `config`, until the window
+/// is closed or the rom exits.
+///
+/// # Errors
+/// Returns an error if the rom can't be loaded, if the window or audio can't
+/// be set up, if an instruction can't be run, or if the recorded inputs can't
+/// be saved.
+pub fn run_with_config(rom: &Rom, config: EmulatorConfig) -> Result<()> {
+    let mut emulator = Emulator::new(config);
+    emulator.load_rom(rom)?;
+    emulator.run()
+}
+
+/// An [`EmulatorCore`] with everything the emulator does around it: the
+/// hotkeys, the tone, save states, captures and overlays.
+/// It draws into a frame buffer rather than a window, so that it can be run
+/// without one, e.g. in tests, and [`Emulator::run`] shows it in a window.
+///
+/// # Example
+/// ```no_run
+/// # use chip8_emulator::{emulator::{Emulator, EmulatorConfig}, rom::Rom};
+/// let mut emulator = Emulator::new(EmulatorConfig::new());
+/// emulator.load_rom(&Rom::from_file("game.ch8").unwrap()).unwrap();
+/// emulator.run().unwrap();
+/// ```
+pub struct Emulator<T: SampledTone = ToneState> {
+    config: EmulatorConfig,
+    core: EmulatorCore,
+    tone: T,
+    rom_crc32: u32,
+    status: WindowStatus,
+    palettes: Vec<(&'static str, Palette)>,
+    palette_index: usize,
+    initial_palette_index: usize,
+    // the RPL user flags file, unless recorded inputs are being used
+    rpl_flags_path: Option<PathBuf>,
+    // the RPL user flags as last read from or written to the file, which
+    // is only written again once they change
+    rpl_flags_in_file: [u8; NUM_RPL_FLAGS],
+    pause: PauseState,
+    // how far the core is behind real time
+    scheduler: Scheduler,
+    speed: Speed,
+    // the hotkeys held down, so that their repeated presses are ignored
+    held_hotkeys: Vec<Hotkey>,
+    modifiers: ModifiersState,
+    // the save slot keys held down, a bit for each slot
+    held_slot_keys: u8,
+    // the hex keys held down on the host keyboard, which the CHIP-8 doesn't
+    // see changing while paused
+    held_keys: u16,
+    clip: Option<GifRecorder>,
+    // when the frame advance key was pressed while paused, if it's still
+    // held, and the frames advanced since
+    frame_advance_held_since: Option<Instant>,
+    frames_advanced: u32,
+    // until when the volume is shown in the title, after it was changed
+    volume_shown_until: Option<Instant>,
+    // until when the notice is shown in the title
+    notice_shown_until: Option<Instant>,
+    show_overlay: bool,
+    show_debug: bool,
+    // frames drawn since starting, averaged over the last second for the
+    // overlay along with the instructions run
+    frames_drawn: u64,
+    fps_meter: RateMeter,
+    ips_meter: RateMeter,
+    redraw_requested: bool,
+}
+
+/// What to do after [`Emulator::tick`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickOutcome {
+    /// Nothing needs doing until this time, unless an event comes in.
+    WaitUntil(Instant),
+    /// Nothing needs doing until an event comes in, e.g. while paused.
+    Wait,
+    /// The rom exited, or the inputs played back ran out, so the emulator
+    /// should stop.
+    Exit,
+}
+
+// The actions bound to keys by the config, other than the hex keys and the
+// save slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hotkey {
+    Pause,
+    Reset,
+    Palette,
+    Overlay,
+    Debug,
+    Screenshot,
+    Clip,
+    FrameAdvance,
+    Turbo,
+    Slower,
+    Faster,
+    Mute,
+    VolumeDown,
+    VolumeUp,
+}
+
+impl Emulator {
+    /// An emulator set up by `config`, keeping track of its tone for the
+    /// window to play. Nothing runs until a rom is [loaded](Self::load_rom).
+    pub fn new(config: EmulatorConfig) -> Self {
+        Self::with_tone(config, ToneState::default())
+    }
+
+    /// Run the emulator in a new window until the window is closed or the
+    /// rom exits. The emulator runs on a thread of its own, and the window
+    /// draws the frames and plays the tone it sends back.
+    ///
+    /// # Errors
+    /// Returns an error if the window or audio can't be set up, if the
+    /// window can't be drawn to, or if an instruction can't be run.
+    pub fn run(self) -> Result<()> {
+        let _ = env_logger::try_init();
+        let tone = Beeper::new(self.config.tone_hz)?;
+        let mut event_loop = EventLoopBuilder::<EmulatorOutput>::with_user_event().build();
 
-                // update display (waits for VBLANK)
-                if is_draw_instruction {
-                    window.request_redraw();
+        let mut frame_size = self.frame_size();
+        let window = {
+            let size = winit::dpi::LogicalSize::new(frame_size.0 as u32, frame_size.1 as u32);
+            let scaled_size = winit::dpi::LogicalSize::new(
+                size.width * self.config.scale,
+                size.height * self.config.scale,
+            );
+            WindowBuilder::new()
+                .with_title(self.title())
+                .with_inner_size(scaled_size)
+                .with_min_inner_size(size)
+                .build(&event_loop)
+                .map_err(|e| Error::Window(e.to_string()))?
+        };
+        let mut pixels = {
+            let window_size = window.inner_size();
+            let surface_texture =
+                pixels::SurfaceTexture::new(window_size.width, window_size.height, &window);
+            PixelsBuilder::new(frame_size.0 as u32, frame_size.1 as u32, surface_texture)
+                .enable_vsync(true)
+                .build()
+                .map_err(|e| Error::Window(e.to_string()))?
+        };
+
+        let mut thread = Some(EmulatorThread::spawn(self, event_loop.create_proxy()));
+        let mut result = Ok(());
+        // the last frame sent, drawn again whenever the window needs it
+        let mut frame = None;
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_wait();
+            let Some(emulator) = &thread else {
+                control_flow.set_exit();
+                return;
+            };
+
+            match event {
+                Event::UserEvent(output) => match output {
+                    EmulatorOutput::Frame {
+                        pixels,
+                        width,
+                        height,
+                    } => {
+                        frame = Some((pixels, (width, height)));
+                        window.request_redraw();
+                    }
+                    EmulatorOutput::Tone(true) => tone.start_tone(),
+                    EmulatorOutput::Tone(false) => tone.stop_tone(),
+                    EmulatorOutput::TonePattern(pattern, playback_rate_hz) => {
+                        tone.set_pattern(&pattern, playback_rate_hz)
+                    }
+                    EmulatorOutput::Volume(volume) => tone.set_volume(volume),
+                    EmulatorOutput::Title(title) => window.set_title(&title),
+                    EmulatorOutput::Stopped => {
+                        result = thread.take().map_or(Ok(()), EmulatorThread::join);
+                        control_flow.set_exit();
+                    }
+                },
+                Event::RedrawRequested(_) => {
+                    if let Some((frame, size)) = &frame {
+                        if let Err(e) = present(&mut pixels, &mut frame_size, frame, *size) {
+                            result = Err(e);
+                            control_flow.set_exit();
+                        }
+                    }
                 }
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::CloseRequested => {
+                        result = thread.take().map_or(Ok(()), EmulatorThread::close);
+                        control_flow.set_exit();
+                    }
+                    WindowEvent::ModifiersChanged(state) => {
+                        emulator.send(EmulatorInput::Modifiers(state))
+                    }
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        emulator.send(EmulatorInput::Key(input))
+                    }
+                    WindowEvent::Focused(focused) => emulator.send(EmulatorInput::Focused(focused)),
+                    _ => (),
+                },
+                _ => (),
             }
-            Event::RedrawRequested(_) => {
-                pixels
-                    .frame_mut()
-                    .copy_from_slice(&rgba_pixels_from_cosmac_display_buffer(&ram));
-
-                // vsync is enabled in render call, but need to simulate it for case
-                // when window is minimised, as graphics library doesn't wait for VBLANKs
-                // when the window is not on the screen.
-                let target_render_time = Instant::now() + Duration::from_micros(16_667);
-                pixels.render().unwrap();
-                let now = Instant::now();
-                if now < target_render_time {
-                    sleep(target_render_time - now);
+        });
+        // the window stopped by itself, e.g. as it couldn't be drawn to
+        if let Some(thread) = thread {
+            result = result.and(thread.close());
+        }
+        tone.stop_tone();
+        result
+    }
+}
+
+impl<T: SampledTone> Emulator<T> {
+    /// An emulator set up by `config`, playing its tone through `tone`.
+    pub fn with_tone(config: EmulatorConfig, tone: T) -> Self {
+        let (palettes, palette_index) = palette_cycle(config.palette);
+        tone.set_volume(config.volume.output());
+        Self {
+            core: EmulatorCore::new(config.clone()),
+            tone,
+            rom_crc32: 0,
+            status: WindowStatus::default(),
+            palettes,
+            palette_index,
+            initial_palette_index: palette_index,
+            rpl_flags_path: None,
+            rpl_flags_in_file: [0; NUM_RPL_FLAGS],
+            pause: PauseState::default(),
+            scheduler: Scheduler::new(MAX_CATCH_UP),
+            speed: Speed::new(config.instructions_per_second, config.turbo_mode),
+            held_hotkeys: Vec::new(),
+            modifiers: ModifiersState::empty(),
+            held_slot_keys: 0,
+            held_keys: 0,
+            clip: None,
+            frame_advance_held_since: None,
+            frames_advanced: 0,
+            volume_shown_until: None,
+            notice_shown_until: None,
+            show_overlay: false,
+            show_debug: false,
+            frames_drawn: 0,
+            fps_meter: RateMeter::new(RATE_WINDOW),
+            ips_meter: RateMeter::new(RATE_WINDOW),
+            redraw_requested: true,
+            config,
+        }
+    }
+
+    /// Load `rom` and start it from the beginning, replacing whatever was
+    /// running. If the config says so, its inputs are played back or
+    /// recorded from here, or it carries on from its autosave.
+    ///
+    /// # Errors
+    /// Returns an error if the rom doesn't fit in RAM.
+    pub fn load_rom(&mut self, rom: &Rom) -> Result<()> {
+        self.core.load_rom(rom)?;
+
+        // recorded inputs only reproduce a run from the same starting state,
+        // so the rom starts afresh, and its RPL user flags are left alone
+        let mut inputs = InputStatus::Live;
+        let mut rpl_flags_path = self.config.rpl_flags_path.clone();
+        if self.config.play_inputs.is_some() {
+            inputs = InputStatus::PlayingBack;
+            rpl_flags_path = None;
+        } else if self.config.record_inputs_path.is_some() {
+            inputs = InputStatus::Recording;
+            rpl_flags_path = None;
+        }
+        if let Some(path) = &rpl_flags_path {
+            load_rpl_flags(&mut self.core.chip8, path);
+        }
+        self.rpl_flags_in_file = *self.core.chip8.rpl_flags();
+        if let (true, Some(path), InputStatus::Live) =
+            (self.config.resume, &self.config.autosave_path, inputs)
+        {
+            match resume_decision(read_autosave(path), rom.crc32()) {
+                Resume::FreshStart => (),
+                Resume::FromAutosave(state) => {
+                    if let Err(e) = self.core.load_state(&state) {
+                        eprintln!("{}: {}, starting afresh", path.display(), e)
+                    }
+                }
+                Resume::FreshStartWithWarning(warning) => {
+                    eprintln!("{}: {}, starting afresh", path.display(), warning)
                 }
             }
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    control_flow.set_exit();
+        }
+
+        self.rom_crc32 = rom.crc32();
+        self.rpl_flags_path = rpl_flags_path;
+        // still paused if the window doesn't have focus
+        self.pause.by_key = false;
+        self.status = WindowStatus {
+            rom_name: rom.name().map(str::to_string),
+            rom_title: self.config.rom_title.clone(),
+            inputs,
+            palette_name: self.status.palette_name,
+            speed: self.speed.label(),
+            muted: self.config.volume.is_muted(),
+            paused: self.pause.is_paused(),
+            ..WindowStatus::default()
+        };
+        self.tone.stop_tone();
+        self.scheduler.restart();
+        self.redraw_requested = true;
+        Ok(())
+    }
+
+    /// Run the emulator up to `now`: the instructions and 60Hz ticks of the
+    /// timers due since it was last run, unless paused. The first run after
+    /// loading the rom, or resuming, starts the clock. While paused, frames
+    /// are advanced instead if the frame advance key is held.
+    ///
+    /// # Errors
+    /// Returns an error if an instruction can't be run.
+    pub fn tick(&mut self, now: Instant) -> Result<TickOutcome> {
+        if self.volume_shown_until.is_some_and(|until| now >= until) {
+            self.volume_shown_until = None;
+            self.status.volume_percent = None;
+        }
+        if self.notice_shown_until.is_some_and(|until| now >= until) {
+            self.notice_shown_until = None;
+            self.status.notice = None;
+        }
+        if self.status.paused {
+            // wake up to take the volume or notice out of the title
+            let shown_until = self
+                .volume_shown_until
+                .into_iter()
+                .chain(self.notice_shown_until)
+                .min();
+            return Ok(match (self.advance_frames(now)?, shown_until) {
+                (TickOutcome::Wait, Some(until)) => TickOutcome::WaitUntil(until),
+                (outcome, _) => outcome,
+            });
+        }
+
+        // turbo can pass emulated time faster than real time
+        let time_scale = self.speed.time_scale();
+        let behind = self.scheduler.catch_up(now) * time_scale;
+        let events = self.core.advance(behind, &KeyState::from(self.held_keys))?;
+        if let Some(outcome) = self.handle_frame_events(&events) {
+            return Ok(outcome);
+        }
+        if events.ticks > 0 {
+            self.ips_meter.record(now, self.core.instructions_run());
+        }
+
+        // update display (waits for VBLANK), every frame while the counter or
+        // debug panel is showing to keep it current
+        if events.display_changed || (events.ticks > 0 && (self.show_overlay || self.show_debug)) {
+            self.redraw_requested = true;
+        }
+        // wait while ahead of real time
+        let until_next_frame = self.core.time_until_next_frame() / time_scale;
+        Ok(TickOutcome::WaitUntil(
+            self.scheduler
+                .next_step_at(until_next_frame)
+                .unwrap_or(now + until_next_frame),
+        ))
+    }
+
+    /// Handle a key being pressed or released on the host keyboard, whether
+    /// it's a hex key or a hotkey.
+    ///
+    /// # Errors
+    /// Returns an error if the rom can't be restarted.
+    pub fn handle_key_event(&mut self, input: KeyboardInput) -> Result<()> {
+        let pressed = input.state == ElementState::Pressed;
+        if let Some(hotkey) = input
+            .virtual_keycode
+            .and_then(|key| self.config.hotkey(key))
+        {
+            // a held key repeats its presses, which shouldn't act again
+            let repeated = self.held_hotkeys.contains(&hotkey);
+            self.held_hotkeys.retain(|&held| held != hotkey);
+            if pressed {
+                self.held_hotkeys.push(hotkey);
+            }
+            match (hotkey, pressed) {
+                (Hotkey::FrameAdvance, false) => self.frame_advance_held_since = None,
+                (Hotkey::Turbo, false) => self.change_speed(|speed| speed.set_turbo(false)),
+                (_, true) if !repeated => self.press_hotkey(hotkey)?,
+                _ => (),
+            }
+        } else if let Some(slot) = input.virtual_keycode.and_then(save_slot) {
+            let slot_bit = 1 << (slot - 1);
+            if pressed && self.held_slot_keys & slot_bit == 0 {
+                self.use_save_slot(slot);
+            }
+            match pressed {
+                true => self.held_slot_keys |= slot_bit,
+                false => self.held_slot_keys &= !slot_bit,
+            }
+        } else if let Some(key) = self
+            .config
+            .key_map
+            .hex_key(input.scancode, input.virtual_keycode)
+        {
+            // the CHIP-8 is handed the keys held down when it next runs
+            if let Some(keys) = hex_key_transition(self.held_keys, key, pressed) {
+                self.held_keys = keys;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the window gaining or losing focus. The keys held down are let
+    /// go as it loses focus, as they would be released in another window,
+    /// and the emulation pauses if the config says so.
+    pub fn set_focused(&mut self, focused: bool) {
+        if !focused {
+            if self.held_hotkeys.contains(&Hotkey::Turbo) {
+                self.change_speed(|speed| speed.set_turbo(false));
+            }
+            self.held_hotkeys.clear();
+            self.held_slot_keys = 0;
+            self.held_keys = 0;
+            self.modifiers = ModifiersState::empty();
+            self.frame_advance_held_since = None;
+        }
+        let pause_on_focus_loss = self.config.pause_on_focus_loss;
+        self.change_pause(|pause| pause.focus_changed(focused, pause_on_focus_loss));
+    }
+
+    /// Track the modifier keys held down, e.g. Shift to save to a slot.
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    /// The width and height of the frame drawn by [`Emulator::render_into`],
+    /// which changes with the display resolution and the debug panel.
+    pub fn frame_size(&self) -> (usize, usize) {
+        let display_size = self.core.display_size();
+        match self.show_debug {
+            true => debug_frame_size(display_size),
+            false => (display_size.width, display_size.height),
+        }
+    }
+
+    /// Draw the display, and the overlays shown, into `frame`, RGBA pixels
+    /// in rows of [`Emulator::frame_size`].
+    ///
+    /// # Panics
+    /// Panics if `frame` isn't the size of [`Emulator::frame_size`].
+    pub fn render_into(&mut self, frame: &mut [u8]) {
+        let palette = *self.palette();
+        if self.show_debug {
+            let pixels = self.core.framebuffer_rgba(&palette).to_vec();
+            frame.copy_from_slice(&rgba_pixels_with_debug_panel(
+                &pixels,
+                self.core.display_size(),
+                &self.core.state(),
+                &palette,
+            ));
+        } else {
+            frame.copy_from_slice(self.core.framebuffer_rgba(&palette));
+        }
+
+        self.frames_drawn += 1;
+        self.fps_meter.record(Instant::now(), self.frames_drawn);
+        if self.show_overlay {
+            draw_rates(
+                frame,
+                self.frame_size().0,
+                (self.fps_meter.rate(), self.ips_meter.rate()),
+                self.core.redraws_skipped(),
+                self.palette().on,
+                self.palette().off,
+            );
+        }
+    }
+
+    /// Whether the frame has changed since it was last drawn, clearing the
+    /// request.
+    pub fn take_redraw_request(&mut self) -> bool {
+        std::mem::take(&mut self.redraw_requested)
+    }
+
+    /// The window title, showing the rom and what the emulator is doing.
+    pub fn title(&self) -> String {
+        self.status.title()
+    }
+
+    /// The state of the CHIP-8 interpreter.
+    pub fn state(&self) -> Chip8State<'_> {
+        self.core.state()
+    }
+
+    /// The number of instructions run since the emulator was set up.
+    pub fn instructions_run(&self) -> u64 {
+        self.core.instructions_run()
+    }
+
+    /// Save what should outlive the run, as when the window is closed: the
+    /// RPL user flags, recorded inputs, the clip being recorded and the
+    /// autosave.
+    ///
+    /// # Errors
+    /// Returns an error, naming the file, if the recorded inputs couldn't be
+    /// saved, as without them the run can't be played back.
+    pub fn close(&mut self) -> Result<()> {
+        self.save_on_exit();
+        if let Some(recorder) = self.clip.take() {
+            self.save_clip(&recorder);
+        }
+        if let Some(path) = &self.config.autosave_path {
+            let state = self.core.save_state().with_rom_crc32(self.rom_crc32);
+            if let Err(e) = fs::write(path, state.to_bytes()) {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        }
+        match (
+            self.core.chip8.stop_recording(),
+            &self.config.record_inputs_path,
+        ) {
+            (Some(recording), Some(path)) => save_inputs(&recording, path),
+            _ => Ok(()),
+        }
+    }
+
+    /// The tone the emulator starts and stops.
+    pub fn tone(&self) -> &T {
+        &self.tone
+    }
+
+    fn palette(&self) -> &Palette {
+        &self.palettes[self.palette_index].1
+    }
+
+    // Keep up with what the core did over a tick or a frame advance, or say
+    // why the emulator should stop.
+    fn handle_frame_events(&mut self, events: &FrameEvents) -> Option<TickOutcome> {
+        if events.exited {
+            self.save_on_exit();
+            return Some(TickOutcome::Exit);
+        }
+        if events.playback_finished {
+            if self.config.stop_after_playback {
+                return Some(TickOutcome::Exit);
+            }
+            self.status.inputs = InputStatus::Live;
+            self.status.playback_finished = true;
+        }
+        self.status.halted = events.halted;
+        for _ in 0..events.ticks {
+            self.capture_clip_frame();
+        }
+        if !self.status.paused {
+            update_tone(&self.core.chip8, &self.core.ram, &self.tone);
+        }
+        None
+    }
+
+    // Capture the display presented at a 60Hz tick for the clip being
+    // recorded.
+    fn capture_clip_frame(&mut self) {
+        let Some(recorder) = &mut self.clip else {
+            return;
+        };
+        let [first_plane, second_plane] = [0, 1].map(|plane| {
+            DisplayView::new(
+                self.core
+                    .chip8
+                    .display_plane_for_render(&self.core.ram, plane),
+                self.core.display_size(),
+            )
+        });
+        recorder.push_frame(first_plane, second_plane);
+        if recorder.is_full() {
+            let recorder = self.clip.take().unwrap();
+            self.save_clip(&recorder);
+            self.status.recording = false;
+        }
+    }
+
+    // Advance a frame for each one due while the frame advance key is held.
+    // The timers are frozen and the last frame is redrawn as needed while
+    // paused, so otherwise there's nothing to do until the next event.
+    fn advance_frames(&mut self, now: Instant) -> Result<TickOutcome> {
+        let Some(held_since) = self.frame_advance_held_since else {
+            return Ok(TickOutcome::Wait);
+        };
+        while now.saturating_duration_since(held_since) >= frame_advance_time(self.frames_advanced)
+        {
+            self.frames_advanced += 1;
+            let events = self.core.advance_frame()?;
+            if let Some(outcome) = self.handle_frame_events(&events) {
+                return Ok(outcome);
+            }
+            self.redraw_requested = true;
+        }
+        Ok(TickOutcome::WaitUntil(
+            held_since + frame_advance_time(self.frames_advanced),
+        ))
+    }
+
+    fn press_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+        match hotkey {
+            Hotkey::Pause => self.change_pause(PauseState::toggle),
+            // the recorded inputs must carry on from the start of the rom
+            Hotkey::Reset if self.status.inputs != InputStatus::Live => (),
+            Hotkey::Reset => {
+                self.core.restart()?;
+                self.tone.stop_tone();
+                self.held_keys = 0;
+                self.status.halted = false;
+                self.status.playback_finished = false;
+                self.scheduler.restart();
+                self.redraw_requested = true;
+            }
+            Hotkey::Palette => {
+                self.palette_index = next_palette(&self.palettes, self.palette_index);
+                self.status.palette_name = (self.palette_index != self.initial_palette_index)
+                    .then_some(self.palettes[self.palette_index].0);
+                self.redraw_requested = true;
+            }
+            Hotkey::Overlay => {
+                self.show_overlay = !self.show_overlay;
+                self.redraw_requested = true;
+            }
+            Hotkey::Debug => {
+                self.show_debug = !self.show_debug;
+                self.redraw_requested = true;
+            }
+            Hotkey::Screenshot => {
+                let path = self.capture_path("png");
+                let png = screenshot_png(
+                    &self.core.chip8,
+                    &self.core.ram,
+                    self.palette(),
+                    self.capture_scale(),
+                );
+                self.show_saved(&path, fs::write(&path, png));
+            }
+            Hotkey::Clip => {
+                match self.clip.take() {
+                    Some(recorder) => self.save_clip(&recorder),
+                    None => self.clip = Some(GifRecorder::new(MAX_CLIP_TICKS)),
                 }
-                WindowEvent::KeyboardInput { input, .. } => {
-                    if input.state == ElementState::Released {
-                        Chip8::set_current_key_press(&mut ram, None);
-                    } else if let Some(key_code) = input.virtual_keycode {
-                        Chip8::set_current_key_press(
-                            &mut ram,
-                            match key_code {
-                                VirtualKeyCode::Key1 => Some(0x1),
-                                VirtualKeyCode::Key2 => Some(0x2),
-                                VirtualKeyCode::Key3 => Some(0x3),
-                                VirtualKeyCode::Q => Some(0x4),
-                                VirtualKeyCode::W => Some(0x5),
-                                VirtualKeyCode::E => Some(0x6),
-                                VirtualKeyCode::A => Some(0x7),
-                                VirtualKeyCode::S => Some(0x8),
-                                VirtualKeyCode::D => Some(0x9),
-                                VirtualKeyCode::X => Some(0x0),
-                                VirtualKeyCode::Z => Some(0xA),
-                                VirtualKeyCode::C => Some(0xB),
-                                VirtualKeyCode::Key4 => Some(0xC),
-                                VirtualKeyCode::R => Some(0xD),
-                                VirtualKeyCode::F => Some(0xE),
-                                VirtualKeyCode::V => Some(0xF),
-                                _ => None,
-                            },
-                        );
-                    }
+                self.status.recording = self.clip.is_some();
+            }
+            Hotkey::FrameAdvance => {
+                if self.status.paused {
+                    self.frame_advance_held_since = Some(Instant::now());
+                    self.frames_advanced = 0;
                 }
-                _ => (),
-            },
-            _ => (),
+            }
+            Hotkey::Turbo => self.change_speed(|speed| speed.set_turbo(true)),
+            Hotkey::Slower => self.change_speed(Speed::halve),
+            Hotkey::Faster => self.change_speed(Speed::double),
+            Hotkey::Mute => self.change_volume(Volume::toggle_mute),
+            Hotkey::VolumeDown => self.change_volume(Volume::down),
+            Hotkey::VolumeUp => self.change_volume(Volume::up),
         }
-    });
+        Ok(())
+    }
+
+    // Change the volume of the tone for the rest of the run, showing it in
+    // the title for a while, or that it's muted for as long as it is.
+    fn change_volume(&mut self, change: impl FnOnce(&mut Volume)) {
+        change(&mut self.config.volume);
+        self.tone.set_volume(self.config.volume.output());
+        self.status.muted = self.config.volume.is_muted();
+        self.status.volume_percent = Some(self.config.volume.percent());
+        self.volume_shown_until = Some(Instant::now() + VOLUME_SHOWN_FOR);
+    }
+
+    // Show in the title for a while that `path` was saved, or why it wasn't.
+    fn show_saved(&mut self, path: &Path, result: io::Result<()>) {
+        let file_name = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy();
+        self.status.notice = Some(match result {
+            Ok(()) => format!("saved {file_name}"),
+            Err(e) => format!("couldn't save {file_name}: {e}"),
+        });
+        self.notice_shown_until = Some(Instant::now() + NOTICE_SHOWN_FOR);
+    }
+
+    // Pause or resume as `change` says, stopping the tone and the clock while
+    // paused.
+    fn change_pause(&mut self, change: impl FnOnce(&mut PauseState)) {
+        let was_paused = self.pause.is_paused();
+        change(&mut self.pause);
+        self.status.paused = self.pause.is_paused();
+        if was_paused == self.status.paused {
+            return;
+        }
+        self.frame_advance_held_since = None;
+        if self.status.paused {
+            self.tone.stop_tone();
+        } else {
+            // don't catch up on the time passed while paused, or count it
+            // in the rates
+            self.scheduler.restart();
+            self.fps_meter.clear();
+            self.ips_meter.clear();
+        }
+    }
+
+    // Change the speed the emulation runs at from the next tick, and show it
+    // in the title.
+    fn change_speed(&mut self, change: impl FnOnce(&mut Speed)) {
+        change(&mut self.speed);
+        self.core
+            .set_instructions_per_second(self.speed.instructions_per_second());
+        self.status.speed = self.speed.label();
+    }
+
+    // Save to `slot` with Shift held, or load from it.
+    fn use_save_slot(&mut self, slot: u8) {
+        let Some(rom_path) = &self.config.save_states_path else {
+            return;
+        };
+        let path = save_slot_path(rom_path, slot);
+        if self.modifiers.shift() {
+            if let Err(e) = save_to_slot(&self.core, &path) {
+                eprintln!("{}: {}", path.display(), e);
+            }
+        } else if self.status.inputs != InputStatus::Live {
+            // a loaded state can't be reproduced from the inputs
+        } else if let Err(e) = load_from_slot(&mut self.core, &path) {
+            eprintln!("{}: {}", path.display(), e);
+        } else {
+            if !self.status.paused {
+                update_tone(&self.core.chip8, &self.core.ram, &self.tone);
+            }
+            self.status.halted = false;
+            self.scheduler.restart();
+            self.redraw_requested = true;
+        }
+    }
+
+    // Save the RPL user flags, if they changed, when the window is closed or
+    // the rom exits.
+    fn save_on_exit(&mut self) {
+        let rpl_flags = *self.core.chip8.rpl_flags();
+        if let (Some(path), true) = (&self.rpl_flags_path, rpl_flags != self.rpl_flags_in_file) {
+            save_rpl_flags(&self.core.chip8, path);
+            self.rpl_flags_in_file = rpl_flags;
+        }
+    }
+
+    // Encode a clip in the current palette and save it next to the
+    // screenshots, showing where it went in the title.
+    fn save_clip(&mut self, recorder: &GifRecorder) {
+        let path = self.capture_path("gif");
+        let gif = recorder.encode(self.palette(), self.capture_scale());
+        self.show_saved(&path, fs::write(&path, gif));
+    }
+
+    // Where to save a screenshot or clip taken now, with `extension`.
+    fn capture_path(&self, extension: &str) -> PathBuf {
+        let rom_name = self.status.rom_name.as_deref().unwrap_or("screenshot");
+        self.config
+            .screenshot_dir
+            .join(capture_file_name(rom_name, SystemTime::now(), extension))
+    }
+
+    fn capture_scale(&self) -> usize {
+        self.config.screenshot_scale.unwrap_or(self.config.scale) as usize
+    }
 }
 
-fn rgba_pixels_from_cosmac_display_buffer(ram: &CosmacRAM) -> Vec<u8> {
-    ram.display_buffer()
-        .iter()
-        .flat_map(|pixel_byte| {
-            let mut color_pixels = [[0xFFu8, 0xFF, 0xFF, 0xFF]; 8]; // default to 8 white pixels
-            for (i, rgb_pixel) in color_pixels.iter_mut().enumerate() {
-                if pixel_byte & (1 << (7 - i)) != 0 {
-                    // make pixel black
-                    rgb_pixel[0] = 0x00; // R
-                    rgb_pixel[1] = 0x00; // G
-                    rgb_pixel[2] = 0x00; // B
+// Draw `frame`, RGBA pixels of `size`, into `pixels` and show it, resizing
+// `pixels` first if the frame size changed from `frame_size`.
+fn present(
+    pixels: &mut Pixels,
+    frame_size: &mut (usize, usize),
+    frame: &[u8],
+    size: (usize, usize),
+) -> Result<()> {
+    // SCHIP programs can switch between lores and hires at any time
+    if size != *frame_size {
+        *frame_size = size;
+        pixels
+            .resize_buffer(size.0 as u32, size.1 as u32)
+            .map_err(|e| Error::Window(e.to_string()))?;
+    }
+    pixels.frame_mut().copy_from_slice(frame);
+
+    // vsync is enabled in render call, but need to simulate it for case
+    // when window is minimised, as graphics library doesn't wait for VBLANKs
+    // when the window is not on the screen.
+    let target_render_time = Instant::now() + Duration::from_micros(16_667);
+    pixels.render().map_err(|e| Error::Window(e.to_string()))?;
+    let now = Instant::now();
+    if now < target_render_time {
+        sleep(target_render_time - now);
+    }
+    Ok(())
+}
+
+/// A CHIP-8 machine run by the clock, without a window or an audio device,
+/// for embedding the emulator in another program. The host calls
+/// [`EmulatorCore::advance`] with the time passed and the hex keys held
+/// down, and draws [`EmulatorCore::framebuffer_rgba`] and starts or stops a
+/// tone as the [`FrameEvents`] say. [`Emulator`] runs one in a window.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use chip8_emulator::{emulator::{EmulatorConfig, EmulatorCore, KeyState}, palette::Palette, rom::Rom};
+/// let mut core = EmulatorCore::new(EmulatorConfig::new());
+/// core.load_rom(&Rom::new(vec![0x12, 0x00]).unwrap()).unwrap();
+/// let mut keys = KeyState::new();
+/// keys.set(0x5, true);
+/// let events = core.advance(Duration::from_millis(16), &keys).unwrap();
+/// if events.display_changed {
+///     let frame = core.framebuffer_rgba(&Palette::default());
+///     # assert_eq!(frame.len(), 4 * 64 * 32);
+/// }
+/// ```
+pub struct EmulatorCore {
+    config: EmulatorConfig,
+    chip8: Chip8,
+    ram: CosmacRAM,
+    // the rom loaded, to restart it from
+    program: Vec<u8>,
+    phosphor: Option<PhosphorFilter>,
+    // the time left until the next 60Hz frame is due
+    until_frame: Duration,
+    // the part of an instruction carried over to the next frame, in 60ths,
+    // so that rates that aren't a multiple of 60 are kept to
+    instruction_sixtieths: u64,
+    // the hex keys last handed to the CHIP-8, one bit per key, or `None` if
+    // it should be handed all of them
+    keys_seen: Option<u16>,
+    halted: bool,
+    playing_back: bool,
+    // something was drawn since the display was last presented at a tick
+    display_changed: bool,
+    // the display as last presented, to tell whether drawing changed it
+    presented: DisplaySnapshot,
+    redraws_skipped: u64,
+    instructions_run: u64,
+    framebuffer: Vec<u8>,
+    // the display and palette that `framebuffer` shows, so that only the
+    // rows changed since are converted again
+    rendered: Option<(DisplaySnapshot, Palette)>,
+}
+
+/// What happened over an [`EmulatorCore::advance`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameEvents {
+    /// The display presented at a 60Hz tick changed, or faded, so it should
+    /// be drawn again.
+    pub display_changed: bool,
+    /// Whether the tone should be sounding now.
+    pub tone_on: bool,
+    /// The number of 60Hz ticks of the timers.
+    pub ticks: u32,
+    /// The program is stuck in a jump or call to itself, so only the timers
+    /// run.
+    pub halted: bool,
+    /// The program exited with 00FD.
+    pub exited: bool,
+    /// The inputs played back ran out, and the keys given are used from
+    /// here.
+    pub playback_finished: bool,
+}
+
+/// The hex keys held down, as given to [`EmulatorCore::advance`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyState(u16);
+
+impl KeyState {
+    /// No keys held down.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold hex `key` down, or let it go.
+    pub fn set(&mut self, key: u8, pressed: bool) {
+        match pressed {
+            true => self.0 |= 1 << (key & 0x0F),
+            false => self.0 &= !(1 << (key & 0x0F)),
+        }
+    }
+
+    /// Whether hex `key` is held down.
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.0 & (1 << (key & 0x0F)) != 0
+    }
+}
+
+/// The keys held down, one bit per key, from bit 0 for key 0.
+impl From<u16> for KeyState {
+    fn from(keys: u16) -> Self {
+        Self(keys)
+    }
+}
+
+impl EmulatorCore {
+    /// A machine set up by `config`. Nothing runs until a rom is
+    /// [loaded](Self::load_rom).
+    pub fn new(config: EmulatorConfig) -> Self {
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = new_chip8(&config, &mut ram);
+        chip8.enable_double_buffering(&ram);
+        Self {
+            presented: DisplaySnapshot::of(&chip8, &ram),
+            chip8,
+            ram,
+            program: Vec::new(),
+            phosphor: config.phosphor_decay_frames.map(PhosphorFilter::new),
+            until_frame: Duration::ZERO,
+            instruction_sixtieths: 0,
+            keys_seen: Some(0),
+            halted: false,
+            playing_back: false,
+            display_changed: false,
+            redraws_skipped: 0,
+            instructions_run: 0,
+            framebuffer: Vec::new(),
+            rendered: None,
+            config,
+        }
+    }
+
+    /// Load `rom` and start it from the beginning, replacing whatever was
+    /// running. If the config says so, its inputs are played back or
+    /// recorded from here.
+    ///
+    /// # Errors
+    /// Returns an error if the rom doesn't fit in RAM.
+    pub fn load_rom(&mut self, rom: &Rom) -> Result<()> {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program_at(rom.bytes(), self.config.variant.program_start_address())?;
+        let mut chip8 = new_chip8(&self.config, &mut ram);
+        if let Some(recording) = &self.config.play_inputs {
+            chip8.start_replay(recording);
+        } else if self.config.record_inputs_path.is_some() {
+            chip8.start_recording();
+        }
+
+        // render from a presented copy of the display, updated at 60Hz, so
+        // that sprites being erased and redrawn don't flicker
+        chip8.enable_double_buffering(&ram);
+        self.playing_back = chip8.is_replaying();
+        self.chip8 = chip8;
+        self.ram = ram;
+        self.program = rom.bytes().to_vec();
+        self.keys_seen = Some(0);
+        self.start_clock();
+        Ok(())
+    }
+
+    /// Start the rom again from the beginning, as if it had just been
+    /// loaded.
+    ///
+    /// # Errors
+    /// Returns an error if the rom no longer fits in RAM.
+    pub fn restart(&mut self) -> Result<()> {
+        restart(
+            &mut self.chip8,
+            &mut self.ram,
+            &self.program,
+            self.config.variant.program_start_address(),
+        )?;
+        self.keys_seen = Some(0);
+        self.start_clock();
+        Ok(())
+    }
+
+    /// Carry on from `state`, with the keys given to the next
+    /// [`EmulatorCore::advance`] rather than those held when it was saved.
+    /// Leaves the machine as it was if the state can't be loaded.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<()> {
+        self.chip8.load_state(&mut self.ram, state)?;
+        self.keys_seen = None;
+        self.start_clock();
+        Ok(())
+    }
+
+    /// The state of the machine, to [load](Self::load_state) again later.
+    pub fn save_state(&self) -> SaveState {
+        self.chip8.save_state(&self.ram)
+    }
+
+    /// Run the machine for `elapsed` time with `keys` held down: a 60Hz
+    /// frame for each one that falls due, each running the instructions of a
+    /// frame at the configured rate, then ticking the timers once and
+    /// presenting the display, as at the vertical blank. Time left over
+    /// counts towards the next frame, and the first frame is due straight
+    /// away. It stops early if the program exits, or the inputs played back
+    /// run out.
+    ///
+    /// # Errors
+    /// Returns an error if an instruction can't be run.
+    pub fn advance(&mut self, elapsed: Duration, keys: &KeyState) -> Result<FrameEvents> {
+        self.hand_over_keys(keys);
+        let mut events = FrameEvents::default();
+        let mut remaining = elapsed;
+        while remaining >= self.until_frame {
+            remaining -= self.until_frame;
+            self.until_frame = TIMER_TICK_DURATION;
+            self.run_frame(&mut events)?;
+            if events.exited || self.end_of_playback(&mut events) {
+                remaining = Duration::ZERO;
+                break;
+            }
+        }
+        self.until_frame -= remaining;
+        events.halted = self.halted;
+        events.tone_on = Chip8::is_tone_sounding(&self.ram);
+        Ok(events)
+    }
+
+    /// Run a single 60Hz frame straight away, whatever the time. The keys
+    /// held down are left as they were.
+    ///
+    /// # Errors
+    /// Returns an error if an instruction can't be run.
+    pub fn advance_frame(&mut self) -> Result<FrameEvents> {
+        let mut events = FrameEvents::default();
+        self.run_frame(&mut events)?;
+        if !events.exited {
+            self.end_of_playback(&mut events);
+        }
+        events.halted = self.halted;
+        events.tone_on = Chip8::is_tone_sounding(&self.ram);
+        Ok(events)
+    }
+
+    /// Run `instructions_per_second` from the next frame on, rather than the
+    /// rate configured.
+    ///
+    /// # Panics
+    /// Panics if `instructions_per_second` is 0.
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u64) {
+        self.config = self
+            .config
+            .clone()
+            .instructions_per_second(instructions_per_second);
+    }
+
+    /// How long until the next 60Hz frame is due, however long that is.
+    pub fn time_until_next_frame(&self) -> Duration {
+        self.until_frame
+    }
+
+    /// The display, as RGBA pixels in `palette`, in rows of
+    /// [`EmulatorCore::display_size`].
+    pub fn framebuffer_rgba(&mut self, palette: &Palette) -> &[u8] {
+        let display = DisplaySnapshot::of(&self.chip8, &self.ram);
+        match &self.rendered {
+            // fading pixels can change on any row
+            Some((rendered, rendered_palette))
+                if self.phosphor.is_none()
+                    && rendered_palette == palette
+                    && rendered.size == display.size =>
+            {
+                let row_size = 4 * display.size.width;
+                for y in changed_rows(rendered, &display) {
+                    display.write_rgba_row(
+                        y,
+                        palette,
+                        &mut self.framebuffer[y * row_size..(y + 1) * row_size],
+                    );
                 }
             }
-            color_pixels
-        })
-        .flatten()
-        .collect()
+            _ => {
+                self.framebuffer = rgba_pixels_from_cosmac_display_buffer(
+                    &self.chip8,
+                    &self.ram,
+                    palette,
+                    self.phosphor.as_ref(),
+                );
+            }
+        }
+        self.rendered = Some((display, *palette));
+        &self.framebuffer
+    }
+
+    /// The size of the display presented, which SCHIP programs can switch
+    /// between lores and hires at any time.
+    pub fn display_size(&self) -> DisplaySize {
+        self.chip8.display_size_for_render(&self.ram)
+    }
+
+    /// The CRC-32 of the display size and all display planes, to compare
+    /// displays without storing them.
+    pub fn display_hash(&self) -> u32 {
+        display_hash(&self.ram)
+    }
+
+    /// The state of the CHIP-8 interpreter.
+    pub fn state(&self) -> Chip8State<'_> {
+        Chip8::state(&self.ram)
+    }
+
+    /// The number of instructions run since the machine was set up.
+    pub fn instructions_run(&self) -> u64 {
+        self.instructions_run
+    }
+
+    /// The number of 60Hz frames in which the program drew, but left the
+    /// display as it was, e.g. erasing a sprite and drawing it again in the
+    /// same place, so no redraw was needed.
+    pub fn redraws_skipped(&self) -> u64 {
+        self.redraws_skipped
+    }
+
+    // Start counting time afresh, as when the program starts or its state
+    // is replaced, with the first frame due straight away.
+    fn start_clock(&mut self) {
+        self.until_frame = Duration::ZERO;
+        self.instruction_sixtieths = 0;
+        self.halted = false;
+        self.display_changed = false;
+        self.presented = DisplaySnapshot::of(&self.chip8, &self.ram);
+    }
+
+    // Run a 60Hz frame: the instructions due over it, unless halted, then a
+    // tick of the timers.
+    fn run_frame(&mut self, events: &mut FrameEvents) -> Result<()> {
+        let instructions = instructions_due(
+            &mut self.instruction_sixtieths,
+            self.config.instructions_per_second,
+        );
+        let instructions = match self.halted {
+            true => 0,
+            false => instructions,
+        };
+        let frame = step_frame(&mut self.chip8, &mut self.ram, instructions)?;
+        self.instructions_run += frame.instructions;
+        if frame.exited {
+            events.exited = true;
+            return Ok(());
+        }
+        self.halted |= frame.halted;
+        self.display_changed |= frame.display_changed;
+        self.after_tick(events);
+        Ok(())
+    }
+
+    // Hand the CHIP-8 the keys that changed since the last advance, or all
+    // of them after its keys were replaced.
+    fn hand_over_keys(&mut self, keys: &KeyState) {
+        match self.keys_seen {
+            None => sync_hex_keys(&mut self.chip8, &mut self.ram, keys.0),
+            Some(keys_seen) => {
+                for key in (0..16).filter(|key| (keys_seen ^ keys.0) & (1 << key) != 0) {
+                    self.chip8
+                        .handle_key(&mut self.ram, key, keys.is_pressed(key));
+                }
+            }
+        }
+        self.keys_seen = Some(keys.0);
+    }
+
+    // Fade the display, and present any change to it, after a tick of the
+    // timers.
+    fn after_tick(&mut self, events: &mut FrameEvents) {
+        events.ticks += 1;
+        let mut display_changed = false;
+        if std::mem::take(&mut self.display_changed) {
+            let presented = DisplaySnapshot::of(&self.chip8, &self.ram);
+            display_changed = !changed_rows(&self.presented, &presented).is_empty();
+            match display_changed {
+                true => self.presented = presented,
+                false => self.redraws_skipped += 1,
+            }
+        }
+        if let Some(phosphor) = &mut self.phosphor {
+            // fading pixels change without the display changing
+            display_changed |= phosphor.update(DisplayView::new(
+                self.chip8.display_buffer_for_render(&self.ram),
+                self.chip8.display_size_for_render(&self.ram),
+            ));
+        }
+        events.display_changed |= display_changed;
+    }
+
+    // Whether the inputs played back just ran out, in which case the keys
+    // held down are handed over in full.
+    fn end_of_playback(&mut self, events: &mut FrameEvents) -> bool {
+        if !self.playing_back || self.chip8.is_replaying() {
+            return false;
+        }
+        self.playing_back = false;
+        self.keys_seen = None;
+        events.playback_finished = true;
+        true
+    }
+}
+
+// A reset interpreter for a rom loaded into `ram`, as set up by `config`.
+fn new_chip8(config: &EmulatorConfig, ram: &mut CosmacRAM) -> Chip8 {
+    let seeded_rng = SeededRng::new(config.rng, config.seed);
+    let rng = match &config.play_inputs {
+        Some(recording) => EmulatorRng::replaying(recording, seeded_rng),
+        None => EmulatorRng::Seeded(seeded_rng),
+    };
+    let mut chip8 = Chip8::with_variant(rng, config.variant);
+    if let Some(quirks) = config.quirks {
+        chip8.set_quirks(quirks);
+    }
+    chip8.set_timer_mode(ram, TimerMode::Ticked);
+    chip8.reset(ram);
+    chip8
+}
+
+// What the window title shows about the running emulator. The title is set
+// again when this changes, not every frame.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct WindowStatus {
+    rom_name: Option<String>,
+    // shown instead of the name, when the rom was recognised
+    rom_title: Option<String>,
+    halted: bool,
+    paused: bool,
+    recording: bool,
+    inputs: InputStatus,
+    // the inputs played back ran out, until the next reset
+    playback_finished: bool,
+    // only named once changed from the one configured
+    palette_name: Option<&'static str>,
+    // only shown once changed from the one configured
+    speed: Option<String>,
+    muted: bool,
+    // only shown for a while after it's changed
+    volume_percent: Option<u8>,
+    // e.g. where a screenshot was saved, shown for a while
+    notice: Option<String>,
+}
+
+// What paused the emulation: the pause key, the window losing focus, or
+// both. Regaining focus only resumes what losing it paused, while the pause
+// key resumes whatever paused it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PauseState {
+    by_key: bool,
+    by_focus: bool,
+}
+
+impl PauseState {
+    fn is_paused(&self) -> bool {
+        self.by_key || self.by_focus
+    }
+
+    // Pause, or resume, as the pause key is pressed.
+    fn toggle(&mut self) {
+        *self = Self {
+            by_key: !self.is_paused(),
+            by_focus: false,
+        };
+    }
+
+    // Pause as the window loses focus, if `pause_on_focus_loss`, and resume
+    // as it gets it back.
+    fn focus_changed(&mut self, focused: bool, pause_on_focus_loss: bool) {
+        self.by_focus = !focused && pause_on_focus_loss;
+    }
+}
+
+// Where the hex key presses, timer ticks and random numbers come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum InputStatus {
+    #[default]
+    Live,
+    // live, and recorded to a file
+    Recording,
+    // from a recording, until it ends
+    PlayingBack,
+}
+
+impl WindowStatus {
+    fn title(&self) -> String {
+        let mut title = match self.rom_title.as_ref().or(self.rom_name.as_ref()) {
+            Some(name) => format!("{name} - {WINDOW_TITLE}"),
+            None => WINDOW_TITLE.to_string(),
+        };
+        if self.halted {
+            title.push_str(" (HALTED)");
+        }
+        if self.paused {
+            title.push_str(" (paused)");
+        }
+        if self.recording {
+            title.push_str(" (REC)");
+        }
+        match self.inputs {
+            InputStatus::Live => (),
+            InputStatus::Recording => title.push_str(" (recording inputs)"),
+            InputStatus::PlayingBack => title.push_str(" (playing back)"),
+        }
+        if self.playback_finished {
+            title.push_str(" (playback finished)");
+        }
+        if let Some(name) = self.palette_name {
+            title.push_str(&format!(" ({name})"));
+        }
+        if let Some(speed) = &self.speed {
+            title.push_str(&format!(" ({speed})"));
+        }
+        match (self.muted, self.volume_percent) {
+            (true, _) => title.push_str(" (muted)"),
+            (false, Some(percent)) => title.push_str(&format!(" (volume {percent}%)")),
+            (false, None) => (),
+        }
+        if let Some(notice) = &self.notice {
+            title.push_str(&format!(" ({notice})"));
+        }
+        title
+    }
+}
+
+// The hex keys held down after `key` is pressed or released, or `None` if
+// that doesn't change which keys are held, e.g. for a press repeated while
+// the key is held, or the release of a key pressed before a reset.
+fn hex_key_transition(held_keys: u16, key: u8, pressed: bool) -> Option<u16> {
+    let keys = if pressed {
+        held_keys | 1 << key
+    } else {
+        held_keys & !(1 << key)
+    };
+    (keys != held_keys).then_some(keys)
+}
+
+// Bring the hex keys seen by the CHIP-8 in line with those held down on the
+// host keyboard, one bit per key, after key events were held back. Keys that
+// were pressed and released in the meantime are never seen.
+fn sync_hex_keys<T: Chip8Rng>(
+    chip8: &mut Chip8Interpreter<T>,
+    ram: &mut CosmacRAM,
+    held_keys: u16,
+) {
+    for key in 0..16 {
+        chip8.handle_key(ram, key, held_keys & (1 << key) != 0);
+    }
+}
+
+// What running some instructions did, as far as the emulator cares.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct FrameOutcome {
+    instructions: u64,
+    display_changed: bool,
+    halted: bool,
+    exited: bool,
+}
+
+impl FrameOutcome {
+    fn add_step(&mut self, outcome: StepOutcome) {
+        match outcome {
+            StepOutcome::DrewToDisplay | StepOutcome::ClearedDisplay => self.display_changed = true,
+            StepOutcome::Halted => self.halted = true,
+            StepOutcome::Exited => self.exited = true,
+            _ => (),
+        }
+    }
+}
+
+// The number of instructions due over the next 60Hz frame at
+// `instructions_per_second`. The sixtieths of an instruction left over are
+// carried in `instruction_sixtieths` to the next frame, so that every second
+// runs `instructions_per_second` in all.
+fn instructions_due(instruction_sixtieths: &mut u64, instructions_per_second: u64) -> u64 {
+    let sixtieths = *instruction_sixtieths + instructions_per_second;
+    *instruction_sixtieths = sixtieths % 60;
+    sixtieths / 60
+}
+
+// Run a frame's worth of `instructions`, then count the timers down by a
+// tick, as the emulator does over a 60Hz frame. The frame ends early if the
+// program halts or exits, and the timers are left alone if it exits.
+fn step_frame<T: Chip8Rng>(
+    chip8: &mut Chip8Interpreter<T>,
+    ram: &mut CosmacRAM,
+    instructions: u64,
+) -> Result<FrameOutcome> {
+    let mut frame = FrameOutcome::default();
+    for _ in 0..instructions {
+        frame.add_step(chip8.step(ram)?);
+        frame.instructions += 1;
+        if frame.halted || frame.exited {
+            break;
+        }
+    }
+    if !frame.exited {
+        chip8.tick_60hz(ram);
+    }
+    Ok(frame)
+}
+
+// How long the frame advance key is held before advancing the frame after
+// `frames_advanced` frames, the first straight away.
+fn frame_advance_time(frames_advanced: u32) -> Duration {
+    match frames_advanced {
+        0 => Duration::ZERO,
+        n => FRAME_ADVANCE_REPEAT_DELAY + FRAME_ADVANCE_REPEAT_INTERVAL * (n - 1),
+    }
+}
+
+// Write the recorded inputs to `path`, with the path in the error if they
+// couldn't be.
+fn save_inputs(recording: &Recording, path: &Path) -> Result<()> {
+    fs::write(path, recording.to_bytes()).map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("{}: {}", path.display(), e),
+        ))
+    })
+}
+
+// The generator picked by an `RngAlgorithm`.
+enum SeededRng {
+    Fastrand(fastrand::Rng),
+    Lfsr(LfsrRng),
+}
+
+impl SeededRng {
+    // Seed the generator with `seed`, or at random if not given.
+    fn new(algorithm: RngAlgorithm, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+        match algorithm {
+            RngAlgorithm::Fastrand => Self::Fastrand(fastrand::Rng::with_seed(seed)),
+            RngAlgorithm::Lfsr => Self::Lfsr(LfsrRng::new(seed as u16)),
+        }
+    }
+}
+
+impl Chip8Rng for SeededRng {
+    fn random_u8(&self) -> u8 {
+        match self {
+            Self::Fastrand(rng) => rng.random_u8(),
+            Self::Lfsr(rng) => rng.random_u8(),
+        }
+    }
+}
+
+// The random number generator used by CXKK. When playing back inputs, the
+// recorded random numbers are used, then those of the fallback once they
+// run out, so that the rom can carry on live after the recording ends.
+enum EmulatorRng {
+    Seeded(SeededRng),
+    Replaying {
+        replay: ReplayRng,
+        fallback: SeededRng,
+    },
+}
+
+impl EmulatorRng {
+    fn replaying(recording: &Recording, fallback: SeededRng) -> Self {
+        Self::Replaying {
+            replay: recording.replay_rng(),
+            fallback,
+        }
+    }
+}
+
+impl Chip8Rng for EmulatorRng {
+    fn random_u8(&self) -> u8 {
+        match self {
+            Self::Seeded(rng) => rng.random_u8(),
+            Self::Replaying { replay, fallback } if replay.is_finished() => fallback.random_u8(),
+            Self::Replaying { replay, .. } => replay.random_u8(),
+        }
+    }
+}
+
+/// The file that the save state in `slot` is kept in for the rom at
+/// `rom_path`, e.g. `pong.slot1.c8state` for `pong.ch8`.
+pub fn save_slot_path(rom_path: &Path, slot: u8) -> PathBuf {
+    rom_path.with_extension(format!("slot{slot}.c8state"))
+}
+
+// The save slot loaded or saved with `key`, from 1.
+fn save_slot(key: VirtualKeyCode) -> Option<u8> {
+    SAVE_SLOT_KEYS
+        .iter()
+        .position(|&slot_key| slot_key == key)
+        .map(|index| index as u8 + 1)
+}
+
+fn save_to_slot(core: &EmulatorCore, path: &Path) -> Result<()> {
+    fs::write(path, core.save_state().to_bytes())?;
+    Ok(())
+}
+
+// Leaves the emulator as it was if the slot can't be loaded.
+fn load_from_slot(core: &mut EmulatorCore, path: &Path) -> Result<()> {
+    let state = SaveState::from_bytes(&fs::read(path)?)?;
+    core.load_state(&state)
+}
+
+// What to do at startup when resuming from an autosave.
+#[derive(Debug, PartialEq, Eq)]
+enum Resume {
+    FreshStart,
+    FromAutosave(SaveState),
+    // the autosave can't be used, for the reason given
+    FreshStartWithWarning(String),
+}
+
+// The autosave in `path`, or `None` if there isn't one.
+fn read_autosave(path: &Path) -> Option<Result<SaveState>> {
+    match fs::read(path) {
+        Ok(bytes) => Some(SaveState::from_bytes(&bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => Some(Err(e.into())),
+    }
+}
+
+// Whether to resume from `autosave`, read by `read_autosave`, when running
+// the rom with `rom_crc32`. Only a state saved with the same rom is resumed.
+fn resume_decision(autosave: Option<Result<SaveState>>, rom_crc32: u32) -> Resume {
+    match autosave {
+        None => Resume::FreshStart,
+        Some(Err(e)) => Resume::FreshStartWithWarning(e.to_string()),
+        Some(Ok(state)) => match state.rom_crc32() {
+            Some(crc32) if crc32 == rom_crc32 => Resume::FromAutosave(state),
+            Some(_) => Resume::FreshStartWithWarning("saved with a different rom".to_string()),
+            None => Resume::FreshStartWithWarning("not saved with a known rom".to_string()),
+        },
+    }
+}
+
+// Start `program` again from the beginning, as if it had just been loaded.
+// Everything from the start of the program up to the interpreter's own state
+// is cleared first, in case the program wrote past its end, and the reset
+// clears the rest along with the display, timers and hex keys.
+fn restart<T: Chip8Rng>(
+    chip8: &mut Chip8Interpreter<T>,
+    ram: &mut CosmacRAM,
+    program: &[u8],
+    program_start_address: usize,
+) -> Result<()> {
+    ram.zero_out_range(program_start_address..ram.stack_start_address())?;
+    ram.load_chip8_program_at(program, program_start_address)?;
+    chip8.reset(ram);
+    Ok(())
+}
+
+/// Options for [`run_headless`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessOptions {
+    /// Stop after running this many instructions.
+    pub max_instructions: usize,
+    /// Stop once the run has taken this long in real time, if given.
+    pub max_duration: Option<Duration>,
+    /// The seed for the random number generator used by CXKK.
+    pub seed: u64,
+    pub rng: RngAlgorithm,
+    pub variant: Chip8Variant,
+    /// Keys to press and release during the run. Each change is made just
+    /// before the instruction with its step index is run.
+    pub key_events: Vec<ScriptedKey>,
+    /// Record the inputs to the run, returned in [`HeadlessReport::recording`].
+    pub record_inputs: bool,
+    /// Play back these recorded inputs from the start of the run. The key
+    /// events and the seed are only used once the recording ends.
+    pub play_inputs: Option<Recording>,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            max_instructions: INSTRUCTIONS_FREQ_HZ as usize * 60,
+            max_duration: None,
+            seed: 0,
+            rng: RngAlgorithm::default(),
+            variant: Chip8Variant::default(),
+            key_events: Vec::new(),
+            record_inputs: false,
+            play_inputs: None,
+        }
+    }
+}
+
+/// A hex key being pressed or released partway through a headless run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedKey {
+    /// The number of instructions run before the key changes.
+    pub step: usize,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Why [`run_headless`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessStopReason {
+    /// [`HeadlessOptions::max_instructions`] instructions ran.
+    InstructionLimit,
+    /// [`HeadlessOptions::max_duration`] passed.
+    TimeLimit,
+    /// The program exited with 00FD.
+    Exited,
+    /// The program is stuck in a jump or call to itself.
+    Halted,
+    /// FX0A is waiting for a key and no more key events are scripted.
+    WaitingForKey,
+}
+
+/// The result of [`run_headless`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessReport {
+    /// The state of the interpreter when the run stopped.
+    pub state: Chip8StateOwned,
+    /// The CRC-32 of the display size and all display planes, to compare the
+    /// final display of runs without storing it.
+    pub display_hash: u32,
+    /// The number of instructions run.
+    pub instructions: usize,
+    pub stop_reason: HeadlessStopReason,
+    /// The inputs to the run, if [`HeadlessOptions::record_inputs`] was set.
+    pub recording: Option<Recording>,
+}
+
+/// Run a CHIP-8 program without a window or audio, e.g. for tests and
+/// tooling. Timers are ticked after each 60Hz frame's worth of instructions
+/// at `INSTRUCTIONS_FREQ_HZ`, paced as in the emulator rather than by the
+/// clock, so that runs with the same options and key events are
+/// reproducible.
+///
+/// # Errors
+/// Returns an error if the program can't be loaded, or if an instruction
+/// can't be run.
+pub fn run_headless(program: &[u8], options: HeadlessOptions) -> Result<HeadlessReport> {
+    let mut ram = CosmacRAM::new();
+    ram.load_chip8_program_at(program, options.variant.program_start_address())?;
+    let seeded_rng = SeededRng::new(options.rng, Some(options.seed));
+    let rng = match &options.play_inputs {
+        Some(recording) => EmulatorRng::replaying(recording, seeded_rng),
+        None => EmulatorRng::Seeded(seeded_rng),
+    };
+    let mut chip8 = Chip8::with_variant(rng, options.variant);
+    chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+    chip8.reset(&mut ram);
+    if let Some(recording) = &options.play_inputs {
+        chip8.start_replay(recording);
+    }
+    if options.record_inputs {
+        chip8.start_recording();
+    }
+
+    let mut key_events = options.key_events;
+    key_events.sort_by_key(|event| event.step);
+    let mut key_events = key_events.into_iter().peekable();
+
+    let start = Instant::now();
+    let mut instruction_sixtieths = 0;
+    let mut frame_instructions_left =
+        instructions_due(&mut instruction_sixtieths, INSTRUCTIONS_FREQ_HZ);
+    let mut instructions = 0;
+    let stop_reason = loop {
+        if instructions >= options.max_instructions {
+            break HeadlessStopReason::InstructionLimit;
+        }
+        if options
+            .max_duration
+            .is_some_and(|max_duration| start.elapsed() >= max_duration)
+        {
+            break HeadlessStopReason::TimeLimit;
+        }
+        while let Some(event) = key_events.next_if(|event| event.step <= instructions) {
+            chip8.handle_key(&mut ram, event.key, event.pressed);
+        }
+
+        let outcome = chip8.step(&mut ram)?;
+        instructions += 1;
+        frame_instructions_left -= 1;
+        while frame_instructions_left == 0 {
+            chip8.tick_60hz(&mut ram);
+            frame_instructions_left =
+                instructions_due(&mut instruction_sixtieths, INSTRUCTIONS_FREQ_HZ);
+        }
+        match outcome {
+            StepOutcome::Exited => break HeadlessStopReason::Exited,
+            StepOutcome::Halted => break HeadlessStopReason::Halted,
+            StepOutcome::WaitingForKey if key_events.peek().is_none() && !chip8.is_replaying() => {
+                break HeadlessStopReason::WaitingForKey;
+            }
+            _ => {}
+        }
+    };
+
+    Ok(HeadlessReport {
+        state: Chip8::state(&ram).into(),
+        display_hash: display_hash(&ram),
+        instructions,
+        stop_reason,
+        recording: chip8.stop_recording(),
+    })
+}
+
+fn display_hash(ram: &CosmacRAM) -> u32 {
+    let size = ram.display_size();
+    let mut bytes = vec![size.width as u8, size.height as u8];
+    for plane in 0..NUM_DISPLAY_PLANES {
+        bytes.extend(ram.display_plane(plane));
+    }
+    hash::crc32(&bytes)
+}
+
+// Start or stop the tone to match the CHIP-8 tone timer, playing the XO-CHIP
+// audio pattern if the program has loaded one.
+fn update_tone<T: Chip8Rng>(chip8: &Chip8Interpreter<T>, ram: &CosmacRAM, tone: &impl SampledTone) {
+    if let Some(pattern) = chip8.audio_pattern() {
+        tone.set_pattern(pattern, chip8.audio_playback_rate_hz());
+    }
+
+    let tone_should_be_sounding = Chip8Interpreter::<T>::is_tone_sounding(ram);
+    if tone_should_be_sounding && !tone.is_tone_on() {
+        tone.start_tone();
+    } else if !tone_should_be_sounding && tone.is_tone_on() {
+        tone.stop_tone();
+    }
+}
+
+fn load_rpl_flags(chip8: &mut Chip8, path: &Path) {
+    match fs::read(path) {
+        Ok(bytes) => match <[u8; NUM_RPL_FLAGS]>::try_from(bytes.as_slice()) {
+            Ok(flags) => chip8.set_rpl_flags(&flags),
+            Err(_) => eprintln!("{}: ignoring malformed RPL user flags", path.display()),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => eprintln!("{}: {}", path.display(), e),
+    }
+}
+
+fn save_rpl_flags(chip8: &Chip8, path: &Path) {
+    if let Err(e) = fs::write(path, chip8.rpl_flags()) {
+        eprintln!("{}: {}", path.display(), e);
+    }
+}
+
+// A copy of the display planes that frontends render, and their size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DisplaySnapshot {
+    size: DisplaySize,
+    planes: [Vec<u8>; NUM_DISPLAY_PLANES],
+}
+
+impl DisplaySnapshot {
+    fn of<T: Chip8Rng>(chip8: &Chip8Interpreter<T>, ram: &CosmacRAM) -> Self {
+        Self {
+            size: chip8.display_size_for_render(ram),
+            planes: [0, 1].map(|plane| chip8.display_plane_for_render(ram, plane).to_vec()),
+        }
+    }
+
+    // Write row `y` of the display, as RGBA pixels in `palette`, into `row`.
+    fn write_rgba_row(&self, y: usize, palette: &Palette, row: &mut [u8]) {
+        let row_size = self.size.row_size();
+        let [first_plane, second_plane] = [0, 1].map(|plane| &self.planes[plane][y * row_size..]);
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let is_on = |plane: &[u8]| plane[x / 8] & (0x80 >> (x % 8)) != 0;
+            pixel.copy_from_slice(&palette.color(is_on(first_plane), is_on(second_plane)));
+        }
+    }
+}
+
+// The rows that differ, on any plane, between the displays `before` and
+// `after`, or all of them if the display changed size.
+fn changed_rows(before: &DisplaySnapshot, after: &DisplaySnapshot) -> Vec<usize> {
+    if before.size != after.size {
+        return (0..after.size.height).collect();
+    }
+    let row_size = after.size.row_size();
+    (0..after.size.height)
+        .filter(|y| {
+            let rows = y * row_size..(y + 1) * row_size;
+            before
+                .planes
+                .iter()
+                .zip(&after.planes)
+                .any(|(before, after)| before[rows.clone()] != after[rows.clone()])
+        })
+        .collect()
+}
+
+// Map each pixel's combination of display planes to its RGBA color in
+// `palette`, fading out pixels on the first plane with `phosphor` if given.
+// The pixels are in rows of `chip8.display_size_for_render(ram).width`.
+pub(crate) fn rgba_pixels_from_cosmac_display_buffer<T: Chip8Rng>(
+    chip8: &Chip8Interpreter<T>,
+    ram: &CosmacRAM,
+    palette: &Palette,
+    phosphor: Option<&PhosphorFilter>,
+) -> Vec<u8> {
+    let [first_plane, second_plane] = [0, 1].map(|plane| {
+        DisplayView::new(
+            chip8.display_plane_for_render(ram, plane),
+            chip8.display_size_for_render(ram),
+        )
+    });
+    first_plane
+        .pixels()
+        .zip(second_plane.pixels())
+        .flat_map(
+            |((x, y, first_plane_on), (_, _, second_plane_on))| match phosphor {
+                Some(phosphor) if !second_plane_on => {
+                    palette.faded(phosphor.intensity(x as usize, y as usize))
+                }
+                _ => palette.color(first_plane_on, second_plane_on),
+            },
+        )
+        .collect()
+}
+
+// The size of the frame drawn for a display of `display_size` with the
+// debug panel next to it. The display is scaled up, by a whole number, to be
+// at least as tall as the panel.
+fn debug_frame_size(display_size: DisplaySize) -> (usize, usize) {
+    let scale = DEBUG_PANEL_HEIGHT.div_ceil(display_size.height).max(1);
+    (
+        display_size.width * scale + DEBUG_PANEL_WIDTH,
+        display_size.height * scale,
+    )
+}
+
+// Draw the debug panel showing `state` next to the display, given as RGBA
+// `display_pixels` of `display_size`, in `palette`. The frame is
+// `debug_frame_size(display_size)`.
+fn rgba_pixels_with_debug_panel(
+    display_pixels: &[u8],
+    display_size: DisplaySize,
+    state: &Chip8State,
+    palette: &Palette,
+) -> Vec<u8> {
+    let (frame_width, frame_height) = debug_frame_size(display_size);
+    let scale = frame_height / display_size.height;
+    let display_width = display_size.width * scale;
+    let scaled = scale_rgba(display_pixels, display_size.width, scale);
+
+    let mut frame = Vec::with_capacity(4 * frame_width * frame_height);
+    for row in scaled.chunks(4 * display_width) {
+        frame.extend(row);
+        frame.extend(palette.off.repeat(DEBUG_PANEL_WIDTH));
+    }
+    draw_debug_panel(
+        &mut frame,
+        frame_width,
+        display_width,
+        &debug_lines(state),
+        palette.on,
+        palette.off,
+    );
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{
+        display_filter::PhosphorFilter,
+        interpreter::{
+            Chip8Interpreter, Chip8StateOwned, TimerMode, KEY_EVENT_COUNT_OFFSET,
+            KEY_EVENT_QUEUE_OFFSET,
+        },
+        keymap::{HostKey, KeyMap},
+        memory::{CosmacRAM, DisplaySize},
+        overlay::{debug_lines, draw_debug_panel, DEBUG_PANEL_WIDTH},
+        palette::Palette,
+        peripherals::Tone,
+        quirks::Chip8Variant,
+        recording::{RecordedInput, Recording},
+        rng::{Chip8Rng, LfsrRng, MockChip8Rng, RngAlgorithm},
+        rom::Rom,
+        run_loop_harness::{key_input, RecordingTone},
+        speed::TurboMode,
+        test_utils::{new_chip8_with_program, TempFile},
+        Error,
+    };
+
+    use std::{
+        fs,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use winit::event::VirtualKeyCode;
+
+    use super::{
+        changed_rows, debug_frame_size, frame_advance_time, hex_key_transition, instructions_due,
+        load_from_slot, read_autosave, restart, resume_decision,
+        rgba_pixels_from_cosmac_display_buffer, rgba_pixels_with_debug_panel, run_headless,
+        save_slot, save_slot_path, save_to_slot, step_frame, sync_hex_keys, update_tone,
+        DisplaySnapshot, Emulator, EmulatorConfig, EmulatorCore, EmulatorRng, FrameOutcome,
+        HeadlessOptions, HeadlessStopReason, InputStatus, KeyState, PauseState, Resume,
+        ScriptedKey, SeededRng, TickOutcome, WindowStatus, FRAME_ADVANCE_REPEAT_DELAY,
+        FRAME_ADVANCE_REPEAT_INTERVAL, NOTICE_SHOWN_FOR, TIMER_TICK_DURATION, VOLUME_SHOWN_FOR,
+    };
+
+    fn emulator_with_program(program: &[u8], config: EmulatorConfig) -> Emulator<RecordingTone> {
+        let mut emulator = Emulator::with_tone(config, RecordingTone::default());
+        emulator
+            .load_rom(&Rom::new(program.to_vec()).unwrap())
+            .unwrap();
+        emulator
+    }
+
+    fn core_with_program(program: &[u8], config: EmulatorConfig) -> EmulatorCore {
+        let mut core = EmulatorCore::new(config);
+        core.load_rom(&Rom::new(program.to_vec()).unwrap()).unwrap();
+        core
+    }
+
+    #[test]
+    fn audio_pattern_and_pitch_are_handed_to_tone() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA300
+            0xF002
+            0x6070
+            0xF03A
+            0x6005
+            0xF018
+            NOOP
+        ));
+        let pattern = [
+            0xF0, 0x0F, 0xAA, 0x55, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF,
+        ];
+        ram.load_bytes(&pattern, 0x0300)
+            .expect("Pattern should fit into RAM.");
+        let tone = RecordingTone::default();
+
+        update_tone(&chip8, &ram, &tone);
+        assert_eq!(tone.pattern(), None, "No pattern loaded yet");
+        assert!(!tone.is_tone_on());
+
+        for _ in 0..6 {
+            chip8.step(&mut ram).unwrap();
+        }
+        update_tone(&chip8, &ram, &tone);
+        assert_eq!(tone.pattern(), Some((pattern, 8000.0)));
+        assert!(tone.is_tone_on());
+    }
+
+    #[test]
+    fn pattern_stops_when_tone_timer_expires() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA300
+            0xF002
+            0x6005
+            0xF018
+            NOOP
+        ));
+        let tone = RecordingTone::default();
+
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+        update_tone(&chip8, &ram, &tone);
+        assert!(tone.is_tone_on());
+
+        mock_instant::MockClock::advance(Duration::from_millis(100));
+        chip8.step(&mut ram).unwrap();
+        update_tone(&chip8, &ram, &tone);
+        assert!(!tone.is_tone_on());
+        assert_eq!(tone.pattern(), Some(([0; 16], 4000.0)));
+    }
+
+    #[test]
+    fn emulator_ticks_run_instructions_and_timers() {
+        let start = Instant::now();
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x6005  // V0 = 5
+                0xF015  // delay timer = V0
+                0x7101  // V1 += 1
+                0x1204  // loop
+            ),
+            EmulatorConfig::new().instructions_per_second(1000),
+        );
+        // the first frame runs straight away, and starts the clock: 16
+        // instructions, then a tick of the timers
+        assert_eq!(
+            emulator.tick(start).unwrap(),
+            TickOutcome::WaitUntil(start + TIMER_TICK_DURATION)
+        );
+        assert_eq!(emulator.state().v_registers[1], 7);
+        assert_eq!(emulator.state().timer, 4);
+
+        // two more frames are due by then
+        emulator
+            .tick(start + 2 * TIMER_TICK_DURATION + TIMER_TICK_DURATION / 2)
+            .unwrap();
+        assert_eq!(emulator.state().timer, 2);
+    }
+
+    #[test]
+    fn emulator_catches_up_only_so_far_after_a_stall() {
+        let start = Instant::now();
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7101  // V1 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new().instructions_per_second(1000),
+        );
+        emulator.tick(start).unwrap();
+        emulator.tick(start + Duration::from_secs(10)).unwrap();
+        // the instructions of the frames in the first 250ms, two at a time
+        assert_eq!(emulator.state().v_registers[1], 125);
+    }
+
+    #[test]
+    fn volume_hotkeys_set_tone_volume() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x1200  // loop
+            ),
+            EmulatorConfig::new().volume(50),
+        );
+        assert_eq!(emulator.tone().volume(), 0.5);
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::RBracket, true))
+            .unwrap();
+        assert_eq!(emulator.tone().volume(), 0.6);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (volume 60%)");
+
+        // muted for as long as it's muted, at the same level once unmuted
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::M, true))
+            .unwrap();
+        assert_eq!(emulator.tone().volume(), 0.0);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (muted)");
+        emulator.tick(Instant::now() + VOLUME_SHOWN_FOR).unwrap();
+        assert!(emulator.title().ends_with(" (muted)"));
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::M, false))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::M, true))
+            .unwrap();
+        assert_eq!(emulator.tone().volume(), 0.6);
+
+        // the level is only shown for a while
+        assert!(emulator.title().ends_with(" (volume 60%)"));
+        emulator.tick(Instant::now() + VOLUME_SHOWN_FOR).unwrap();
+        assert!(!emulator.title().contains("volume"));
+    }
+
+    #[test]
+    fn screenshot_hotkey_shows_where_it_was_saved() {
+        let config = EmulatorConfig::new().screenshot_dir(std::env::temp_dir());
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(NOOP), config);
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::F12, true))
+            .unwrap();
+        let title = emulator.title();
+        let file_name = title
+            .strip_prefix("CHIP-8 Emulator (paused) (saved ")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap_or_else(|| panic!("unexpected title {title:?}"));
+        let saved = TempFile {
+            path: std::env::temp_dir().join(file_name),
+        };
+        assert!(fs::read(&saved.path).unwrap().starts_with(b"\x89PNG"));
+
+        // only shown for a while, waking up while paused to take it out
+        assert!(matches!(
+            emulator.tick(Instant::now()).unwrap(),
+            TickOutcome::WaitUntil(_)
+        ));
+        emulator.tick(Instant::now() + NOTICE_SHOWN_FOR).unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (paused)");
+    }
+
+    #[test]
+    fn clip_hotkey_shows_where_the_clip_was_saved() {
+        let config = EmulatorConfig::new().screenshot_dir(std::env::temp_dir());
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(NOOP), config);
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::F9, true))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (REC)");
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::F9, false))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::F9, true))
+            .unwrap();
+        let title = emulator.title();
+        let file_name = title
+            .strip_prefix("CHIP-8 Emulator (saved ")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap_or_else(|| panic!("unexpected title {title:?}"));
+        let saved = TempFile {
+            path: std::env::temp_dir().join(file_name),
+        };
+        assert!(fs::read(&saved.path).unwrap().starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn screenshot_hotkey_shows_why_it_wasnt_saved() {
+        let missing_dir = TempFile::new("no-such-screenshot-dir");
+        let config = EmulatorConfig::new().screenshot_dir(&missing_dir.path);
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(NOOP), config);
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::F12, true))
+            .unwrap();
+        assert!(emulator
+            .title()
+            .starts_with("CHIP-8 Emulator (couldn't save screenshot-"));
+    }
+
+    #[test]
+    fn speed_hotkeys_change_speed_and_title() {
+        let start = Instant::now();
+        let program = chip8_program_into_bytes!(
+            0x60FF  // V0 = 255
+            0xF015  // delay timer = V0
+            0x7101  // V1 += 1
+            0x1204  // loop
+        );
+        // 10 instructions a frame
+        let config = EmulatorConfig::new().instructions_per_second(600);
+        let mut emulator = emulator_with_program(&program, config.clone());
+        emulator.tick(start).unwrap();
+        assert_eq!(emulator.state().timer, 254);
+
+        // turbo runs 5 frames in the time of one, and waits a fifth as long
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Grave, true))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (turbo 5x)");
+        assert_eq!(
+            emulator.tick(start + TIMER_TICK_DURATION).unwrap(),
+            TickOutcome::WaitUntil(start + TIMER_TICK_DURATION + TIMER_TICK_DURATION / 5)
+        );
+        assert_eq!(emulator.state().timer, 249);
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Grave, false))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator");
+
+        // doubling the rate runs twice the instructions in a frame, with the
+        // timers ticking as before
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Equals, true))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (1200 IPS)");
+        let v1 = emulator.state().v_registers[1];
+        emulator.tick(start + 2 * TIMER_TICK_DURATION).unwrap();
+        assert_eq!(emulator.state().v_registers[1], v1 + 10);
+        assert_eq!(emulator.state().timer, 248);
+
+        // turbo can leave the timers alone, running only the instructions
+        // faster
+        let mut emulator =
+            emulator_with_program(&program, config.turbo_mode(TurboMode::InstructionsOnly));
+        emulator.tick(start).unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Grave, true))
+            .unwrap();
+        let v1 = emulator.state().v_registers[1];
+        emulator.tick(start + TIMER_TICK_DURATION).unwrap();
+        assert_eq!(emulator.state().v_registers[1], v1 + 25);
+        assert_eq!(emulator.state().timer, 253);
+    }
+
+    #[test]
+    fn emulator_stops_when_rom_exits() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x00FD  // exit
+            ),
+            EmulatorConfig::new().variant(Chip8Variant::SuperChip1_1),
+        );
+        assert_eq!(emulator.tick(Instant::now()).unwrap(), TickOutcome::Exit);
+    }
+
+    #[test]
+    fn pause_hotkey_toggles_once_a_press() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7101  // V1 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        assert_eq!(emulator.tick(Instant::now()).unwrap(), TickOutcome::Wait);
+        assert_eq!(emulator.state().v_registers[1], 0);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (paused)");
+
+        // the OS repeats the press while the key is held
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (paused)");
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, false))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Space, true))
+            .unwrap();
+        assert_eq!(emulator.title(), "CHIP-8 Emulator");
+        // resuming runs the first frame, of 11 instructions, at once
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().v_registers[1], 6);
+    }
+
+    #[test]
+    fn hex_keys_held_back_while_paused() {
+        let key_map =
+            KeyMap::from_map(&HashMap::from([(0x5, HostKey::Virtual(VirtualKeyCode::W))])).unwrap();
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x1200  // loop
+            ),
+            EmulatorConfig::new().key_map(key_map),
+        );
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::W, true))
+            .unwrap();
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 0);
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, false))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 1 << 5);
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::W, false))
+            .unwrap();
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 0);
+    }
+
+    #[test]
+    fn focus_loss_pauses_and_lets_go_of_keys() {
+        let key_map =
+            KeyMap::from_map(&HashMap::from([(0x5, HostKey::Virtual(VirtualKeyCode::W))])).unwrap();
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7101  // V1 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new().key_map(key_map.clone()),
+        );
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::W, true))
+            .unwrap();
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 1 << 5);
+
+        // the key's release goes to another window
+        emulator.set_focused(false);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (paused)");
+        let v1 = emulator.state().v_registers[1];
+        assert_eq!(emulator.tick(Instant::now()).unwrap(), TickOutcome::Wait);
+        assert_eq!(emulator.state().v_registers[1], v1);
+
+        emulator.set_focused(true);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator");
+        let resumed = Instant::now();
+        emulator.tick(resumed).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 0);
+        emulator.tick(resumed + TIMER_TICK_DURATION).unwrap();
+        assert!(emulator.state().v_registers[1] > v1);
+
+        // paused with the pause key, regaining focus doesn't resume
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        emulator.set_focused(false);
+        emulator.set_focused(true);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (paused)");
+
+        // keys are let go whether or not it pauses
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7101  // V1 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new()
+                .key_map(key_map)
+                .pause_on_focus_loss(false),
+        );
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::W, true))
+            .unwrap();
+        emulator.tick(Instant::now()).unwrap();
+        emulator.set_focused(false);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator");
+        emulator.tick(Instant::now()).unwrap();
+        assert_eq!(emulator.state().hex_keys_pressed, 0);
+    }
+
+    #[test]
+    fn pause_by_key_outlasts_focus() {
+        let mut pause = PauseState::default();
+        pause.focus_changed(false, true);
+        assert!(pause.is_paused());
+        pause.focus_changed(true, true);
+        assert!(!pause.is_paused());
+
+        pause.toggle();
+        pause.focus_changed(false, true);
+        pause.focus_changed(true, true);
+        assert!(pause.is_paused());
+        pause.toggle();
+        assert!(!pause.is_paused());
+
+        // the pause key resumes while paused by focus, too
+        pause.focus_changed(false, true);
+        pause.toggle();
+        assert!(!pause.is_paused());
+
+        pause.focus_changed(false, false);
+        assert!(!pause.is_paused());
+    }
+
+    #[test]
+    fn frame_advance_runs_a_frame_while_paused() {
+        let mut emulator = emulator_with_program(
+            &[0x71, 0x01].repeat(30), // V1 += 1
+            EmulatorConfig::new(),
+        );
+        // does nothing unless paused
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Period, true))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Period, false))
+            .unwrap();
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::P, true))
+            .unwrap();
+        assert_eq!(emulator.tick(Instant::now()).unwrap(), TickOutcome::Wait);
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Period, true))
+            .unwrap();
+        let outcome = emulator.tick(Instant::now()).unwrap();
+        assert!(matches!(outcome, TickOutcome::WaitUntil(_)), "{outcome:?}");
+        assert_eq!(emulator.state().v_registers[1], 11);
+        assert!(emulator.take_redraw_request());
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Period, false))
+            .unwrap();
+        assert_eq!(emulator.tick(Instant::now()).unwrap(), TickOutcome::Wait);
+        assert_eq!(emulator.state().v_registers[1], 11);
+    }
+
+    #[test]
+    fn debug_hotkey_widens_the_frame() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x1200  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+        let mut frame = vec![0; 4 * 64 * 32];
+        assert_eq!(emulator.frame_size(), (64, 32));
+        assert!(emulator.take_redraw_request());
+        emulator.render_into(&mut frame);
+        assert!(!emulator.take_redraw_request());
+        assert!(frame.chunks(4).all(|pixel| pixel == Palette::default().off));
+
+        emulator
+            .handle_key_event(key_input(VirtualKeyCode::Tab, true))
+            .unwrap();
+        assert!(emulator.take_redraw_request());
+        let (width, height) = debug_frame_size(DisplaySize {
+            width: 64,
+            height: 32,
+        });
+        assert_eq!(emulator.frame_size(), (width, height));
+        let mut frame = vec![0; 4 * width * height];
+        emulator.render_into(&mut frame);
+        assert!(frame[4 * 128..4 * 129] == Palette::default().on);
+    }
+
+    #[test]
+    fn core_ticks_timers_at_60hz_whatever_the_instruction_rate() {
+        for instructions_per_second in [100, 5000] {
+            let mut core = core_with_program(
+                &chip8_program_into_bytes!(
+                    0x60FF  // V0 = 255
+                    0xF015  // timer = V0
+                    0x7101  // V1 += 1
+                    0x1204  // loop
+                ),
+                EmulatorConfig::new().instructions_per_second(instructions_per_second),
+            );
+            let keys = KeyState::new();
+            // the first frame is due straight away
+            let events = core.advance(Duration::ZERO, &keys).unwrap();
+            assert_eq!(events.ticks, 1);
+            assert_eq!(core.time_until_next_frame(), TIMER_TICK_DURATION);
+
+            let events = core.advance(60 * TIMER_TICK_DURATION, &keys).unwrap();
+            assert_eq!(events.ticks, 60);
+            let timer = core.state().timer;
+            let instructions_run = core.instructions_run();
+            let events = core.advance(60 * TIMER_TICK_DURATION, &keys).unwrap();
+            assert_eq!(events.ticks, 60);
+            assert_eq!(core.state().timer, timer - 60);
+            assert_eq!(
+                core.instructions_run() - instructions_run,
+                instructions_per_second
+            );
+        }
+    }
+
+    #[test]
+    fn core_display_is_the_same_however_time_is_given() {
+        let program = chip8_program_into_bytes!(
+            0x6005  // V0 = 5
+            0xE09E  // skip the next instruction if key V0 is pressed
+            0x1202  // wait for it
+            0xF029  // I = font sprite for V0
+            0xD015  // draw it at (V0, V1)
+            0x120A  // halt
+        );
+        let mut keys = KeyState::new();
+        keys.set(0x5, true);
+
+        let mut at_once = core_with_program(&program, EmulatorConfig::new());
+        let blank = at_once.display_hash();
+        let events = at_once
+            .advance(Duration::from_millis(50), &KeyState::new())
+            .unwrap();
+        assert!(!events.display_changed);
+        assert_eq!(at_once.display_hash(), blank);
+        let events = at_once.advance(Duration::from_millis(50), &keys).unwrap();
+        assert!(events.display_changed);
+        assert!(events.halted);
+        assert_ne!(at_once.display_hash(), blank);
+
+        let mut in_steps = core_with_program(&program, EmulatorConfig::new());
+        for step in 0..100 {
+            let step_keys = match step < 50 {
+                true => KeyState::new(),
+                false => keys,
+            };
+            in_steps
+                .advance(Duration::from_millis(1), &step_keys)
+                .unwrap();
+        }
+        assert_eq!(in_steps.display_hash(), at_once.display_hash());
+        assert_eq!(
+            Chip8StateOwned::from(in_steps.state()),
+            Chip8StateOwned::from(at_once.state())
+        );
+
+        // the digit 5 is drawn with 14 pixels
+        let palette = Palette::default();
+        let frame = at_once.framebuffer_rgba(&palette);
+        assert_eq!(frame.len(), 4 * 64 * 32);
+        assert_eq!(
+            frame.chunks(4).filter(|&pixel| pixel == palette.on).count(),
+            14
+        );
+    }
+
+    #[test]
+    fn core_skips_redraws_when_drawing_leaves_the_display_alone() {
+        let mut core = core_with_program(
+            &chip8_program_into_bytes!(
+                0x00E0  // clear the display, which is already clear
+                0x1200  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+        let keys = KeyState::new();
+        let events = core.advance(Duration::ZERO, &keys).unwrap();
+        assert!(!events.display_changed);
+        let events = core.advance(9 * TIMER_TICK_DURATION, &keys).unwrap();
+        assert_eq!(events.ticks, 9);
+        assert!(!events.display_changed);
+        assert_eq!(core.redraws_skipped(), 10);
+    }
+
+    #[test]
+    fn core_framebuffer_converts_changed_rows() {
+        let mut core = core_with_program(
+            &chip8_program_into_bytes!(
+                0x6005  // V0 = 5
+                0xF029  // I = font sprite for V0
+                0xD015  // draw it at (V0, V1)
+                0x710A  // V1 += 10
+                0xD015  // draw it at (V0, V1)
+                0x00E0  // clear the display
+                0x120C  // halt
+            ),
+            // an instruction a frame
+            EmulatorConfig::new().instructions_per_second(60),
+        );
+        let palette = Palette::default();
+        let mut frames_changed = 0;
+        for _ in 0..10 {
+            let events = core.advance(TIMER_TICK_DURATION, &KeyState::new()).unwrap();
+            frames_changed += events.display_changed as u32;
+            let expected =
+                rgba_pixels_from_cosmac_display_buffer(&core.chip8, &core.ram, &palette, None);
+            assert_eq!(core.framebuffer_rgba(&palette), expected);
+        }
+        assert_eq!(frames_changed, 3);
+        assert_eq!(core.redraws_skipped(), 0);
+
+        // a new palette converts every row
+        let palette = Palette::PAPER_WHITE;
+        assert!(core
+            .framebuffer_rgba(&palette)
+            .chunks(4)
+            .all(|pixel| pixel == palette.off));
+    }
+
+    #[test]
+    fn changed_rows_between_displays() {
+        let lores = DisplaySize {
+            width: 64,
+            height: 32,
+        };
+        let blank = DisplaySnapshot {
+            size: lores,
+            planes: [vec![0; lores.plane_size()], vec![0; lores.plane_size()]],
+        };
+        assert!(changed_rows(&blank, &blank).is_empty());
+
+        let mut drawn = blank.clone();
+        drawn.planes[0][3 * lores.row_size() + 7] = 0x01;
+        drawn.planes[1][10 * lores.row_size()] = 0x80;
+        assert_eq!(changed_rows(&blank, &drawn), [3, 10]);
+        assert_eq!(changed_rows(&drawn, &blank), [3, 10]);
+
+        // every row changes with the resolution, even if all are blank
+        let hires = DisplaySize {
+            width: 128,
+            height: 64,
+        };
+        let hires_blank = DisplaySnapshot {
+            size: hires,
+            planes: [vec![0; hires.plane_size()], vec![0; hires.plane_size()]],
+        };
+        assert_eq!(changed_rows(&blank, &hires_blank).len(), 64);
+    }
+
+    #[test]
+    fn headless_display_hash_is_stable() {
+        let program = chip8_program_into_bytes!(
+            0x6005  // V0 = 5
+            0x610A  // V1 = 10
+            0xC20F  // V2 = random digit
+            0xF229  // I = font sprite for V2
+            0xD015  // draw at (V0, V1)
+            0x6308  // V3 = 8
+            0xF329  // I = font sprite for V3
+            0xD105  // draw at (V1, V0)
+            0x1210  // halt
+        );
+
+        let report = run_headless(&program, HeadlessOptions::default()).unwrap();
+        assert_eq!(report.stop_reason, HeadlessStopReason::Halted);
+        // the second draw waits for the next jiffy, like the COSMAC VIP,
+        // retried to the end of the 11 instruction frame
+        assert_eq!(report.instructions, 13);
+        assert_eq!(report.state.program_counter, 0x0210);
+        assert_ne!(
+            report.display_hash,
+            run_headless(&[0x12, 0x00], HeadlessOptions::default())
+                .unwrap()
+                .display_hash
+        );
+
+        for _ in 0..3 {
+            assert_eq!(
+                run_headless(&program, HeadlessOptions::default()).unwrap(),
+                report
+            );
+        }
+    }
+
+    #[test]
+    fn headless_run_with_lfsr_rng() {
+        let program = chip8_program_into_bytes!(
+            0xC0FF  // V0 = random byte
+            0xC1FF  // V1 = random byte
+            0x1204  // halt
+        );
+        let options = HeadlessOptions {
+            seed: 0x1_ACE1,
+            rng: RngAlgorithm::Lfsr,
+            ..HeadlessOptions::default()
+        };
+
+        let report = run_headless(&program, options).unwrap();
+        let lfsr = LfsrRng::new(0xACE1);
+        assert_eq!(
+            report.state.v_registers[..2],
+            [lfsr.random_u8(), lfsr.random_u8()]
+        );
+    }
+
+    #[test]
+    fn headless_frames_carry_over_part_instructions() {
+        let program = chip8_program_into_bytes!(
+            0x6006  // V0 = 6
+            0xF015  // delay timer = V0
+            0xF107  // V1 = delay timer
+            0x3100  // skip if V1 == 0
+            0x1204  // loop
+            0x120A  // halt
+        );
+
+        let report = run_headless(&program, HeadlessOptions::default()).unwrap();
+        assert_eq!(report.stop_reason, HeadlessStopReason::Halted);
+        // 6 frames of 11, 12, 12, 11, 12 and 12 instructions, then the jump
+        // back, the read of the expired timer, the skip and the halt
+        assert_eq!(report.instructions, 70 + 4);
+    }
+
+    #[test]
+    fn headless_instruction_and_time_limits() {
+        let program = chip8_program_into_bytes!(
+            0x7001  // V0 += 1
+            0x1200
+        );
+        let options = HeadlessOptions {
+            max_instructions: 100,
+            ..Default::default()
+        };
+        let report = run_headless(&program, options).unwrap();
+        assert_eq!(report.stop_reason, HeadlessStopReason::InstructionLimit);
+        assert_eq!(report.instructions, 100);
+        assert_eq!(report.state.v_registers[0], 50);
+
+        let options = HeadlessOptions {
+            max_instructions: usize::MAX,
+            max_duration: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let report = run_headless(&program, options).unwrap();
+        assert_eq!(report.stop_reason, HeadlessStopReason::TimeLimit);
+        assert_eq!(report.instructions, 0);
+    }
+
+    #[test]
+    fn headless_scripted_keys() {
+        let program = chip8_program_into_bytes!(
+            0xF30A  // V3 = next key
+            0xF329  // I = font sprite for V3
+            0xD005  // draw at (V0, V0)
+            0xF30A  // V3 = next key
+        );
+        let options = HeadlessOptions {
+            key_events: vec![
+                ScriptedKey {
+                    step: 11,
+                    key: 0xA,
+                    pressed: false,
+                },
+                ScriptedKey {
+                    step: 10,
+                    key: 0xA,
+                    pressed: true,
+                },
+            ],
+            ..Default::default()
+        };
+        let report = run_headless(&program, options).unwrap();
+        assert_eq!(report.stop_reason, HeadlessStopReason::WaitingForKey);
+        assert_eq!(report.state.v_registers[3], 0xA);
+        assert_eq!(report.state.program_counter, 0x0206);
+    }
+
+    #[test]
+    fn headless_playback_reproduces_recorded_run() {
+        let program = chip8_program_into_bytes!(
+            0xF00A  // V0 = next key
+            0xC10F  // V1 = random digit
+            0xF129  // I = font sprite for V1
+            0xD225  // draw at (V2, V2)
+            0x7206  // V2 += 6
+            0x6303  // V3 = 3
+            0xF315  // delay timer = V3
+            0xF307  // V3 = delay timer
+            0x3300  // skip if V3 == 0
+            0x120E  // loop until the delay timer runs out
+            0x1200
+        );
+        let key_events = [(10, 0x5), (200, 0x7), (450, 0xC)]
+            .into_iter()
+            .flat_map(|(step, key)| {
+                [(step, true), (step + 20, false)].map(|(step, pressed)| ScriptedKey {
+                    step,
+                    key,
+                    pressed,
+                })
+            })
+            .collect();
+        let options = HeadlessOptions {
+            seed: 1,
+            key_events,
+            record_inputs: true,
+            ..Default::default()
+        };
+        let recorded = run_headless(&program, options).unwrap();
+        assert_eq!(recorded.stop_reason, HeadlessStopReason::WaitingForKey);
+
+        // play back from a file, with another seed and no keys of its own
+        let file = TempFile::with_bytes("playback.c8rec", &recorded.recording.unwrap().to_bytes());
+        let recording = Recording::from_bytes(&fs::read(&file.path).unwrap()).unwrap();
+        let options = HeadlessOptions {
+            seed: 2,
+            max_instructions: recorded.instructions,
+            play_inputs: Some(recording),
+            ..Default::default()
+        };
+        let played = run_headless(&program, options).unwrap();
+        assert_eq!(played.instructions, recorded.instructions);
+        assert_eq!(played.state, recorded.state);
+        assert_eq!(played.display_hash, recorded.display_hash);
+
+        let unplayed = run_headless(
+            &program,
+            HeadlessOptions {
+                seed: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_ne!(unplayed.display_hash, recorded.display_hash);
+    }
+
+    #[test]
+    fn title_shows_when_playback_finishes() {
+        let program = chip8_program_into_bytes!(
+            0xC0FF  // V0 = random byte
+            0x7101  // V1 += 1
+            0x1202  // loop
+        );
+        let recorded = run_headless(
+            &program,
+            HeadlessOptions {
+                max_instructions: 5,
+                record_inputs: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let recording = recorded.recording.unwrap();
+
+        let mut emulator = emulator_with_program(
+            &program,
+            EmulatorConfig::new().play_inputs(recording.clone()),
+        );
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (playing back)");
+        let outcome = emulator
+            .tick(Instant::now() + TIMER_TICK_DURATION * 2)
+            .unwrap();
+        assert_ne!(outcome, TickOutcome::Exit);
+        assert_eq!(emulator.title(), "CHIP-8 Emulator (playback finished)");
+
+        let mut emulator = emulator_with_program(
+            &program,
+            EmulatorConfig::new()
+                .play_inputs(recording)
+                .stop_after_playback(true),
+        );
+        let outcome = emulator
+            .tick(Instant::now() + TIMER_TICK_DURATION * 2)
+            .unwrap();
+        assert_eq!(outcome, TickOutcome::Exit);
+    }
+
+    #[test]
+    fn step_frame_runs_a_frame_then_ticks() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6005  // V0 = 5
+            0xF015  // delay timer = V0
+            0x7101  // V1 += 1
+            0x1204  // loop
+        ));
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+
+        let frame = step_frame(&mut chip8, &mut ram, 11).unwrap();
+        assert_eq!(
+            frame,
+            FrameOutcome {
+                instructions: 11,
+                ..FrameOutcome::default()
+            }
+        );
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.v_registers[1], 5);
+        assert_eq!(state.timer, 4);
+
+        let frame = step_frame(&mut chip8, &mut ram, 10).unwrap();
+        assert_eq!(
+            frame,
+            FrameOutcome {
+                instructions: 10,
+                ..FrameOutcome::default()
+            }
+        );
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.v_registers[1], 10);
+        assert_eq!(state.timer, 3);
+    }
+
+    #[test]
+    fn step_frame_stops_at_halt() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x00E0  // clear the display
+            0x1202  // halt
+            0x7101  // V1 += 1, never run
+        ));
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        let frame = step_frame(&mut chip8, &mut ram, 11).unwrap();
+        assert_eq!(
+            frame,
+            FrameOutcome {
+                instructions: 2,
+                display_changed: true,
+                halted: true,
+                exited: false,
+            }
+        );
+    }
+
+    #[test]
+    fn frames_per_second_of_instructions() {
+        for (instructions_per_second, expected_frames) in [
+            (700, vec![11, 12, 12, 11, 12, 12]),
+            (600, vec![10, 10, 10]),
+            (30, vec![0, 1, 0, 1]),
+        ] {
+            let mut instruction_sixtieths = 0;
+            let frames: Vec<u64> = expected_frames
+                .iter()
+                .map(|_| instructions_due(&mut instruction_sixtieths, instructions_per_second))
+                .collect();
+            assert_eq!(
+                frames, expected_frames,
+                "{instructions_per_second} per second"
+            );
+        }
+    }
+
+    #[test]
+    fn frame_advance_repeats_after_delay() {
+        assert_eq!(frame_advance_time(0), Duration::ZERO);
+        assert_eq!(frame_advance_time(1), FRAME_ADVANCE_REPEAT_DELAY);
+        assert_eq!(
+            frame_advance_time(3),
+            FRAME_ADVANCE_REPEAT_DELAY + 2 * FRAME_ADVANCE_REPEAT_INTERVAL
+        );
+    }
+
+    #[test]
+    fn replayed_random_numbers_fall_back_when_used_up() {
+        let mut recording = Recording::new();
+        recording.push(RecordedInput::Random(0x42));
+        recording.end_step();
+        let rng =
+            EmulatorRng::replaying(&recording, SeededRng::new(RngAlgorithm::Fastrand, Some(3)));
+        assert_eq!(rng.random_u8(), 0x42);
+        let fallback = fastrand::Rng::with_seed(3);
+        assert_eq!(rng.random_u8(), fallback.random_u8());
+    }
+
+    #[test]
+    fn config_builder() {
+        let fg = [0x33, 0xFF, 0x33, 0xFF];
+        let bg = [0x00, 0x00, 0x00, 0xFF];
+        let config = EmulatorConfig::new()
+            .instructions_per_second(1200)
+            .scale(8)
+            .tone_hz(220)
+            .palette(fg, bg)
+            .seed(7)
+            .rng(RngAlgorithm::Lfsr)
+            .rom_title("Pong (1 player)");
+        assert_eq!(config.instructions_per_second, 1200);
+        assert_eq!(config.scale, 8);
+        assert_eq!(config.tone_hz, 220);
+        assert_eq!(config.palette, Palette::new(fg, bg));
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.rng, RngAlgorithm::Lfsr);
+        assert_eq!(config.rom_title.as_deref(), Some("Pong (1 player)"));
+        assert_eq!(config.quirks, None);
+
+        let config = EmulatorConfig::new()
+            .record_inputs("run.c8rec")
+            .play_inputs(Recording::new())
+            .stop_after_playback(true);
+        assert_eq!(config.record_inputs_path, Some("run.c8rec".into()));
+        assert_eq!(config.play_inputs, Some(Recording::new()));
+        assert!(config.stop_after_playback);
+    }
+
+    #[test]
+    fn save_slot_paths_next_to_rom() {
+        assert_eq!(
+            save_slot_path(Path::new("roms/pong.ch8"), 1),
+            Path::new("roms/pong.slot1.c8state")
+        );
+        assert_eq!(
+            save_slot_path(Path::new("pong"), 4),
+            Path::new("pong.slot4.c8state")
+        );
+    }
+
+    #[test]
+    fn save_slot_keys() {
+        let slots: Vec<_> = [
+            VirtualKeyCode::F1,
+            VirtualKeyCode::F2,
+            VirtualKeyCode::F3,
+            VirtualKeyCode::F4,
+            VirtualKeyCode::F5,
+            VirtualKeyCode::Key1,
+        ]
+        .into_iter()
+        .map(save_slot)
+        .collect();
+        assert_eq!(slots, [Some(1), Some(2), Some(3), Some(4), None, None]);
+    }
+
+    #[test]
+    fn save_and_load_slot() {
+        let mut core = core_with_program(
+            &chip8_program_into_bytes!(
+                0x7001  // V0 += 1
+                0x6A05  // VA = 5
+                0xFA18  // tone timer = VA
+                0x1200
+            ),
+            EmulatorConfig::new().instructions_per_second(1000),
+        );
+        let mut keys = KeyState::new();
+        keys.set(0x7, true);
+        core.advance(Duration::from_millis(2), &keys).unwrap();
+
+        let file = TempFile::new("save_and_load_slot.slot1.c8state");
+        save_to_slot(&core, &file.path).unwrap();
+        let saved_ram = core.ram.bytes().to_vec();
+        core.advance(TIMER_TICK_DURATION, &keys).unwrap();
+        assert_ne!(core.ram.bytes(), saved_ram);
+
+        load_from_slot(&mut core, &file.path).unwrap();
+        assert_eq!(core.ram.bytes(), saved_ram);
+        assert_eq!(core.state().hex_keys_pressed, 1 << 0x7);
+        assert!(core.advance(Duration::ZERO, &keys).unwrap().tone_on);
+    }
+
+    #[test]
+    fn loading_bad_slot_leaves_state_alone() {
+        let mut core = core_with_program(
+            &chip8_program_into_bytes!(0x7001 0x1200),
+            EmulatorConfig::new(),
+        );
+        core.advance(Duration::ZERO, &KeyState::new()).unwrap();
+        let ram_before = core.ram.bytes().to_vec();
+
+        let missing = TempFile::new("missing.slot2.c8state");
+        assert!(matches!(
+            load_from_slot(&mut core, &missing.path),
+            Err(Error::Io(_))
+        ));
+        let corrupt = TempFile::with_bytes("corrupt.slot3.c8state", b"C8SS");
+        assert_eq!(
+            load_from_slot(&mut core, &corrupt.path),
+            Err(Error::InvalidSaveState)
+        );
+        assert_eq!(core.ram.bytes(), ram_before);
+    }
+
+    #[test]
+    fn resume_only_with_same_rom() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x7001 0x1200))
+            .unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        chip8.reset(&mut ram);
+        chip8.step(&mut ram).unwrap();
+        let state = chip8.save_state(&ram);
+
+        assert_eq!(resume_decision(None, 0x1234), Resume::FreshStart);
+        assert_eq!(
+            resume_decision(Some(Ok(state.clone().with_rom_crc32(0x1234))), 0x1234),
+            Resume::FromAutosave(state.clone().with_rom_crc32(0x1234))
+        );
+        assert!(matches!(
+            resume_decision(Some(Ok(state.clone().with_rom_crc32(0x5678))), 0x1234),
+            Resume::FreshStartWithWarning(_)
+        ));
+        assert!(matches!(
+            resume_decision(Some(Ok(state)), 0x1234),
+            Resume::FreshStartWithWarning(_)
+        ));
+        assert!(matches!(
+            resume_decision(Some(Err(Error::InvalidSaveState)), 0x1234),
+            Resume::FreshStartWithWarning(_)
+        ));
+    }
+
+    #[test]
+    fn rpl_flags_written_only_once_changed() {
+        let start = Instant::now();
+        let file = TempFile::new("rpl_flags_written_only_once_changed.rpl");
+        let config = EmulatorConfig::new()
+            .variant(Chip8Variant::SuperChip1_1)
+            .rpl_flags_path(&file.path);
+
+        // a rom that leaves the flags alone doesn't create the file
+        let mut emulator =
+            emulator_with_program(&chip8_program_into_bytes!(0x1200), config.clone());
+        emulator.tick(start).unwrap();
+        emulator.close().unwrap();
+        assert!(!file.path.exists());
+
+        // saving them with FX75 writes them, once
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x6011  // V0 = 0x11
+                0xF075  // save V0 to the flags
+                0x1204
+            ),
+            config.clone(),
+        );
+        emulator.tick(start).unwrap();
+        emulator.close().unwrap();
+        assert_eq!(fs::read(&file.path).unwrap(), [0x11, 0, 0, 0, 0, 0, 0, 0]);
+        fs::remove_file(&file.path).unwrap();
+        emulator.close().unwrap();
+        assert!(!file.path.exists());
+
+        // flags loaded from the file and left alone aren't written back
+        fs::write(&file.path, [0x22; 8]).unwrap();
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(0x1200), config);
+        fs::remove_file(&file.path).unwrap();
+        emulator.tick(start).unwrap();
+        emulator.close().unwrap();
+        assert!(!file.path.exists());
+    }
+
+    #[test]
+    fn recorded_inputs_saved_on_close() {
+        let file = TempFile::new("recorded_inputs_saved_on_close.c8rec");
+        let config = EmulatorConfig::new().record_inputs(&file.path);
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(0x1200), config);
+        emulator.tick(Instant::now()).unwrap();
+        emulator.close().unwrap();
+        Recording::from_bytes(&fs::read(&file.path).unwrap()).unwrap();
+
+        // the caller is told where they couldn't be saved
+        let missing_dir = TempFile::new("no-such-recording-dir");
+        let path = missing_dir.path.join("run.c8rec");
+        let config = EmulatorConfig::new().record_inputs(&path);
+        let mut emulator = emulator_with_program(&chip8_program_into_bytes!(0x1200), config);
+        emulator.tick(Instant::now()).unwrap();
+        let error = emulator.close().unwrap_err();
+        assert!(matches!(error, Error::Io(_)));
+        assert!(error.to_string().contains(&path.display().to_string()));
+    }
+
+    #[test]
+    fn read_autosave_file() {
+        let missing = TempFile::new("missing.autosave.c8state");
+        assert_eq!(read_autosave(&missing.path), None);
+        let corrupt = TempFile::with_bytes("corrupt.autosave.c8state", b"C8SS");
+        assert_eq!(
+            read_autosave(&corrupt.path),
+            Some(Err(Error::InvalidSaveState))
+        );
+    }
+
+    #[test]
+    fn default_config_matches_fixed_settings() {
+        let config = EmulatorConfig::default();
+        assert_eq!(config.instructions_per_second, 700);
+        assert_eq!(config.scale, 16);
+        assert_eq!(config.tone_hz, 440);
+        assert_eq!(config.palette, Palette::PAPER_WHITE);
+        assert_eq!(config.rpl_flags_path, None);
+        assert_eq!(config.seed, None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_instructions_per_second() {
+        EmulatorConfig::new().instructions_per_second(0);
+    }
+
+    #[test]
+    fn window_title_shows_status() {
+        let mut status = WindowStatus::default();
+        assert_eq!(status.title(), "CHIP-8 Emulator");
+        status.rom_name = Some("pong".to_string());
+        assert_eq!(status.title(), "pong - CHIP-8 Emulator");
+        status.paused = true;
+        assert_eq!(status.title(), "pong - CHIP-8 Emulator (paused)");
+        status.halted = true;
+        assert_eq!(status.title(), "pong - CHIP-8 Emulator (HALTED) (paused)");
+        status.paused = false;
+        status.palette_name = Some("amber");
+        assert_eq!(status.title(), "pong - CHIP-8 Emulator (HALTED) (amber)");
+        status.recording = true;
+        assert_eq!(
+            status.title(),
+            "pong - CHIP-8 Emulator (HALTED) (REC) (amber)"
+        );
+        status.halted = false;
+        status.recording = false;
+        status.inputs = InputStatus::Recording;
+        assert_eq!(
+            status.title(),
+            "pong - CHIP-8 Emulator (recording inputs) (amber)"
+        );
+        status.inputs = InputStatus::PlayingBack;
+        assert_eq!(
+            status.title(),
+            "pong - CHIP-8 Emulator (playing back) (amber)"
+        );
+        status.inputs = InputStatus::Live;
+        status.playback_finished = true;
+        assert_eq!(
+            status.title(),
+            "pong - CHIP-8 Emulator (playback finished) (amber)"
+        );
+        status.playback_finished = false;
+        status.notice = Some("saved pong-20230415-093005.png".to_string());
+        assert_eq!(
+            status.title(),
+            "pong - CHIP-8 Emulator (amber) (saved pong-20230415-093005.png)"
+        );
+        status.notice = None;
+        status.rom_title = Some("Pong (1 player)".to_string());
+        assert_eq!(status.title(), "Pong (1 player) - CHIP-8 Emulator (amber)");
+    }
+
+    #[test]
+    fn keys_changed_while_paused_are_synced_on_resume() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(NOOP));
+        chip8.handle_key(&mut ram, 0x1, true);
+        chip8.handle_key(&mut ram, 0x2, true);
+
+        // while paused, 0x1 is released, 0x2 stays held, 0x3 is pressed and
+        // 0x4 is tapped
+        sync_hex_keys(&mut chip8, &mut ram, 0b0000_1100);
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.hex_keys_pressed, 0b0000_1100);
+        // the key event queue has the first presses, then the changes
+        assert_eq!(ram.get_work_area_u16(KEY_EVENT_COUNT_OFFSET), 4);
+        let queue_address = ram.interpreter_work_area_start_address() + KEY_EVENT_QUEUE_OFFSET;
+        assert_eq!(ram.bytes()[queue_address..][..4], [0x81, 0x82, 0x01, 0x83]);
+    }
+
+    #[test]
+    fn restart_is_the_same_as_a_fresh_start() {
+        let program = chip8_program_into_bytes!(
+            0x6005  // V0 = 5
+            0xF015  // timer = V0
+            0xF018  // tone timer = V0
+            0xA300  // I = 0x300
+            0xF055  // M[0x300] = V0, past the end of the program
+            0xA200  // I = 0x200
+            0xF055  // M[0x200] = V0, modifying the program
+            0xD005  // draw
+            0x2212  // call the halt below
+            0x1212
+        );
+        let (mut fresh_ram, mut fresh_chip8) = new_chip8_with_program(&program);
+        let (mut ram, mut chip8) = new_chip8_with_program(&program);
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        chip8.handle_key(&mut ram, 0xA, true);
+        chip8.step_n(&mut ram, 9).unwrap();
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!((state.timer, state.tone_timer), (5, 5));
+        assert_eq!(state.hex_keys_pressed, 1 << 0xA);
+        assert_ne!(ram.bytes(), fresh_ram.bytes());
+
+        restart(&mut chip8, &mut ram, &program, 0x200).unwrap();
+        assert_eq!(ram.bytes(), fresh_ram.bytes());
+        assert_eq!(ram.display_plane(1), fresh_ram.display_plane(1));
+        // stepping again runs the same as the fresh copy
+        fresh_chip8.set_timer_mode(&mut fresh_ram, TimerMode::Ticked);
+        chip8.step_n(&mut ram, 3).unwrap();
+        fresh_chip8.step_n(&mut fresh_ram, 3).unwrap();
+        assert_eq!(ram.bytes(), fresh_ram.bytes());
+    }
+
+    // The presses and releases forwarded for a sequence of key events, and
+    // the keys held at the end.
+    fn transitions(events: &[(u8, bool)]) -> (Vec<(u8, bool)>, u16) {
+        let mut held_keys = 0;
+        let mut forwarded = Vec::new();
+        for &(key, pressed) in events {
+            if let Some(keys) = hex_key_transition(held_keys, key, pressed) {
+                held_keys = keys;
+                forwarded.push((key, pressed));
+            }
+        }
+        (forwarded, held_keys)
+    }
+
+    #[test]
+    fn auto_repeated_presses_are_ignored() {
+        let events = [(0x5, true), (0x5, true), (0x5, true), (0x5, false)];
+        assert_eq!(transitions(&events), (vec![(0x5, true), (0x5, false)], 0));
+    }
+
+    #[test]
+    fn releasing_an_unheld_key_is_ignored() {
+        let events = [(0x5, true), (0x6, false), (0x5, true), (0x6, false)];
+        assert_eq!(transitions(&events), (vec![(0x5, true)], 1 << 0x5));
+    }
+
+    #[test]
+    fn overlapping_key_presses() {
+        let events = [
+            (0x1, true),
+            (0x2, true),
+            (0x1, true),
+            (0x1, false),
+            (0x2, true),
+            (0xF, true),
+            (0x2, false),
+        ];
+        assert_eq!(
+            transitions(&events),
+            (
+                vec![
+                    (0x1, true),
+                    (0x2, true),
+                    (0x1, false),
+                    (0xF, true),
+                    (0x2, false)
+                ],
+                1 << 0xF
+            )
+        );
+    }
+
+    #[test]
+    fn rgba_pixels_in_palette() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA204  // I = the sprite below
+            0xD001  // draw it at (0, 0)
+            0xA000  // sprite 0b1010_0000
+        ));
+        chip8.step_n(&mut ram, 2).unwrap();
+
+        let first_pixels = |palette| {
+            rgba_pixels_from_cosmac_display_buffer(&chip8, &ram, &palette, None)[..4 * 8].to_vec()
+        };
+        let [on, off] = [[0x00, 0x00, 0x00, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]];
+        assert_eq!(
+            first_pixels(Palette::PAPER_WHITE),
+            [on, off, on, off, off, off, off, off].concat()
+        );
+        let [on, off] = [[0xFF, 0xB0, 0x00, 0xFF], [0x00, 0x00, 0x00, 0xFF]];
+        assert_eq!(
+            first_pixels(Palette::AMBER),
+            [on, off, on, off, off, off, off, off].concat()
+        );
+
+        let pixels = rgba_pixels_from_cosmac_display_buffer(&chip8, &ram, &Palette::AMBER, None);
+        assert_eq!(pixels.len(), 4 * 64 * 32);
+        assert!(pixels[4 * 8..].chunks(4).all(|pixel| pixel == off));
+    }
+
+    #[test]
+    fn debug_panel_frame_sizes() {
+        let size = |width, height| DisplaySize { width, height };
+        assert_eq!(
+            debug_frame_size(size(64, 32)),
+            (128 + DEBUG_PANEL_WIDTH, 64)
+        );
+        assert_eq!(
+            debug_frame_size(size(128, 64)),
+            (128 + DEBUG_PANEL_WIDTH, 64)
+        );
+        assert_eq!(
+            debug_frame_size(size(64, 48)),
+            (128 + DEBUG_PANEL_WIDTH, 96)
+        );
+    }
+
+    #[test]
+    fn debug_panel_drawn_next_to_scaled_display() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA204  // I = the sprite below
+            0xD001  // draw it at (0, 0)
+            0x8000  // sprite 0b1000_0000
+        ));
+        chip8.step_n(&mut ram, 2).unwrap();
+        let palette = Palette::AMBER;
+        let display_size = chip8.display_size_for_render(&ram);
+        let display = rgba_pixels_from_cosmac_display_buffer(&chip8, &ram, &palette, None);
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        let frame = rgba_pixels_with_debug_panel(&display, display_size, &state, &palette);
+
+        let (width, height) = debug_frame_size(display_size);
+        assert_eq!(frame.len(), 4 * width * height);
+        let pixel = |x: usize, y: usize| &frame[4 * (y * width + x)..4 * (y * width + x + 1)];
+        // the lit pixel at (0, 0) is scaled up to a 2 by 2 square
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            assert_eq!(pixel(x, y), palette.on);
+        }
+        assert_eq!(pixel(2, 0), palette.off);
+        assert_eq!(pixel(0, 2), palette.off);
+
+        let mut panel = vec![0; 4 * width * height];
+        draw_debug_panel(
+            &mut panel,
+            width,
+            128,
+            &debug_lines(&state),
+            palette.on,
+            palette.off,
+        );
+        for row in 0..height {
+            let panel_row = 4 * (row * width + 128)..4 * (row + 1) * width;
+            assert_eq!(frame[panel_row.clone()], panel[panel_row]);
+        }
+    }
+
+    #[test]
+    fn rgba_pixels_with_phosphor_decay() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA206  // I = the sprite below
+            0xD001  // draw it at (0, 0)
+            0xD001  // erase it
+            0x8000  // sprite 0b1000_0000
+        ));
+        let mut phosphor = PhosphorFilter::new(4);
+        let palette = Palette::CLASSIC_GREEN;
+        chip8.step_n(&mut ram, 2).unwrap();
+        phosphor.update(ram.display());
+        let pixels =
+            rgba_pixels_from_cosmac_display_buffer(&chip8, &ram, &palette, Some(&phosphor));
+        assert_eq!(pixels[..4], palette.on);
+
+        chip8.step(&mut ram).unwrap();
+        phosphor.update(ram.display());
+        let pixels =
+            rgba_pixels_from_cosmac_display_buffer(&chip8, &ram, &palette, Some(&phosphor));
+        assert_eq!(pixels[..4], palette.faded(191));
+        assert_ne!(palette.faded(191), palette.off);
+    }
 }
@@ -0,0 +1,354 @@
+//! Running an [`Emulator`] on a thread of its own, apart from the window.
+//!
+//! The thread owns the emulator, and with it the CHIP-8 interpreter and its
+//! RAM. The window only forwards key events to it, and draws the frames and
+//! plays the tone that it sends back, so a slow redraw doesn't hold up the
+//! CHIP-8, and the CHIP-8 doesn't hold up the window.
+
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use winit::{
+    event::{KeyboardInput, ModifiersState},
+    event_loop::EventLoopProxy,
+};
+
+use crate::{
+    emulator::{Emulator, TickOutcome},
+    peripherals::{Tone, ToneState},
+    Result,
+};
+
+/// What the window sends to the emulator thread.
+#[derive(Debug, Clone, Copy)]
+pub enum EmulatorInput {
+    /// A key pressed or released on the host keyboard.
+    Key(KeyboardInput),
+    /// The modifier keys held down changed.
+    Modifiers(ModifiersState),
+    /// The window gained focus, or lost it.
+    Focused(bool),
+    /// The window was closed, so the emulator should save what should
+    /// outlive the run and stop.
+    Close,
+}
+
+/// What the emulator thread sends to the window.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulatorOutput {
+    /// A copy of the frame to draw, RGBA pixels in rows of `width`.
+    Frame {
+        pixels: Vec<u8>,
+        width: usize,
+        height: usize,
+    },
+    /// Start the tone, or stop it.
+    Tone(bool),
+    /// Play this XO-CHIP audio pattern, at this many samples per second,
+    /// whenever the tone is on.
+    TonePattern([u8; 16], f32),
+    /// Play the tone at this volume, from 0.0 for silent to 1.0 for full.
+    Volume(f32),
+    /// Set the window title to this.
+    Title(String),
+    /// The emulator stopped by itself, as the rom exited or an error came up,
+    /// so the thread should be [joined](EmulatorThread::join).
+    Stopped,
+}
+
+/// Where the emulator thread sends its output, e.g. the window's event loop.
+pub trait OutputSink: Send + 'static {
+    /// Send `output`, or drop it if nothing is listening any more.
+    fn send(&self, output: EmulatorOutput);
+}
+
+impl OutputSink for Sender<EmulatorOutput> {
+    fn send(&self, output: EmulatorOutput) {
+        let _ = Sender::send(self, output);
+    }
+}
+
+impl OutputSink for EventLoopProxy<EmulatorOutput> {
+    fn send(&self, output: EmulatorOutput) {
+        let _ = self.send_event(output);
+    }
+}
+
+/// Where the emulator thread gets its inputs and the time from, e.g. the
+/// window, over a channel, and the system clock.
+pub trait InputSource {
+    /// The time now.
+    fn now(&self) -> Instant;
+
+    /// Wait for the next input, until `deadline` if one is given.
+    ///
+    /// # Errors
+    /// Returns [`RecvTimeoutError::Timeout`] if the deadline passes first,
+    /// or [`RecvTimeoutError::Disconnected`] if no more input will come.
+    fn next_input(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> std::result::Result<EmulatorInput, RecvTimeoutError>;
+}
+
+impl InputSource for Receiver<EmulatorInput> {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn next_input(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> std::result::Result<EmulatorInput, RecvTimeoutError> {
+        match deadline {
+            Some(deadline) => self.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        }
+    }
+}
+
+/// An [`Emulator`] running on a thread of its own.
+pub struct EmulatorThread {
+    inputs: Sender<EmulatorInput>,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl EmulatorThread {
+    /// Run `emulator` on a new thread, sending what it draws and plays to
+    /// `sink`, until it stops by itself or is [closed](Self::close).
+    pub fn spawn(emulator: Emulator<ToneState>, sink: impl OutputSink) -> Self {
+        let (inputs, mut receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut emulator = emulator;
+            let result = run_emulator(&mut emulator, &mut receiver, &sink);
+            sink.send(EmulatorOutput::Stopped);
+            result
+        });
+        Self { inputs, handle }
+    }
+
+    /// Hand `input` to the emulator. Input sent after it stopped is dropped.
+    pub fn send(&self, input: EmulatorInput) {
+        let _ = self.inputs.send(input);
+    }
+
+    /// Close the emulator, as when the window is closed, and wait for the
+    /// thread to finish.
+    ///
+    /// # Errors
+    /// Returns the error that stopped the emulator, if one did, or the error
+    /// closing it, e.g. if the recorded inputs couldn't be saved.
+    pub fn close(self) -> Result<()> {
+        self.send(EmulatorInput::Close);
+        self.join()
+    }
+
+    /// Wait for the thread to finish, once the emulator has
+    /// [stopped](EmulatorOutput::Stopped) or been closed.
+    ///
+    /// # Errors
+    /// Returns the error that stopped the emulator, if one did.
+    pub fn join(self) -> Result<()> {
+        drop(self.inputs);
+        match self.handle.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+// The output last sent, so that only changes are sent again.
+#[derive(Default)]
+struct SentOutput {
+    title: String,
+    tone_on: bool,
+    pattern: Option<([u8; 16], f32)>,
+    volume: Option<f32>,
+}
+
+// Run `emulator` until it stops by itself, or the window closes or goes
+// away, then close it however it stopped. The emulator waits for inputs
+// between the instructions and ticks that are due.
+pub(crate) fn run_emulator(
+    emulator: &mut Emulator<ToneState>,
+    inputs: &mut impl InputSource,
+    sink: &impl OutputSink,
+) -> Result<()> {
+    let result = run_until_stopped(emulator, inputs, sink);
+    result.and(emulator.close())
+}
+
+fn run_until_stopped(
+    emulator: &mut Emulator<ToneState>,
+    inputs: &mut impl InputSource,
+    sink: &impl OutputSink,
+) -> Result<()> {
+    let mut sent = SentOutput::default();
+    loop {
+        let outcome = emulator.tick(inputs.now())?;
+        send_changes(emulator, sink, &mut sent);
+        let input = match outcome {
+            TickOutcome::Exit => return Ok(()),
+            TickOutcome::Wait => inputs.next_input(None),
+            TickOutcome::WaitUntil(time) => inputs.next_input(Some(time)),
+        };
+        match input {
+            Ok(EmulatorInput::Key(input)) => emulator.handle_key_event(input)?,
+            Ok(EmulatorInput::Modifiers(modifiers)) => emulator.set_modifiers(modifiers),
+            Ok(EmulatorInput::Focused(focused)) => emulator.set_focused(focused),
+            Ok(EmulatorInput::Close) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            Err(RecvTimeoutError::Timeout) => (),
+        }
+    }
+}
+
+// Send the frame if it needs drawing again, and the title, tone and volume
+// if they changed.
+fn send_changes(emulator: &mut Emulator<ToneState>, sink: &impl OutputSink, sent: &mut SentOutput) {
+    if emulator.take_redraw_request() {
+        let (width, height) = emulator.frame_size();
+        let mut pixels = vec![0; 4 * width * height];
+        emulator.render_into(&mut pixels);
+        sink.send(EmulatorOutput::Frame {
+            pixels,
+            width,
+            height,
+        });
+    }
+    let title = emulator.title();
+    if title != sent.title {
+        sink.send(EmulatorOutput::Title(title.clone()));
+        sent.title = title;
+    }
+    let tone = emulator.tone();
+    if let Some((pattern, playback_rate_hz)) = tone.pattern().filter(|&p| Some(p) != sent.pattern) {
+        sink.send(EmulatorOutput::TonePattern(pattern, playback_rate_hz));
+        sent.pattern = tone.pattern();
+    }
+    if tone.is_tone_on() != sent.tone_on {
+        sent.tone_on = tone.is_tone_on();
+        sink.send(EmulatorOutput::Tone(sent.tone_on));
+    }
+    if Some(tone.volume()) != sent.volume {
+        sent.volume = Some(tone.volume());
+        sink.send(EmulatorOutput::Volume(tone.volume()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{self, Receiver},
+        time::Duration,
+    };
+
+    use winit::event::VirtualKeyCode;
+
+    use super::{EmulatorInput, EmulatorOutput, EmulatorThread};
+    use crate::{
+        emulator::{Emulator, EmulatorConfig},
+        palette::Palette,
+        quirks::Chip8Variant,
+        rom::Rom,
+        run_loop_harness::key_input,
+        test_utils::TempFile,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn spawn_with_program(
+        program: &[u8],
+        config: EmulatorConfig,
+    ) -> (EmulatorThread, Receiver<EmulatorOutput>) {
+        let mut emulator = Emulator::new(config);
+        emulator
+            .load_rom(&Rom::new(program.to_vec()).unwrap())
+            .unwrap();
+        let (sink, outputs) = mpsc::channel();
+        (EmulatorThread::spawn(emulator, sink), outputs)
+    }
+
+    // The outputs received until one matches `is_wanted`, including it.
+    fn receive_until(
+        outputs: &Receiver<EmulatorOutput>,
+        is_wanted: impl Fn(&EmulatorOutput) -> bool,
+    ) -> Vec<EmulatorOutput> {
+        let mut received = Vec::new();
+        loop {
+            let output = outputs
+                .recv_timeout(TIMEOUT)
+                .expect("The emulator thread should send the output in time");
+            let done = is_wanted(&output);
+            received.push(output);
+            if done {
+                return received;
+            }
+        }
+    }
+
+    #[test]
+    fn thread_sends_frames_title_and_tone() {
+        let (thread, outputs) = spawn_with_program(
+            &chip8_program_into_bytes!(
+                0x6005  // V0 = 5
+                0xF018  // tone timer = V0
+                0x00E0  // clear the display
+                0xF00A  // wait for a key
+            ),
+            EmulatorConfig::new(),
+        );
+        let received = receive_until(&outputs, |output| *output == EmulatorOutput::Tone(false));
+        assert_eq!(
+            received[0],
+            EmulatorOutput::Frame {
+                pixels: Palette::default().off.repeat(64 * 32),
+                width: 64,
+                height: 32,
+            }
+        );
+        assert!(received.contains(&EmulatorOutput::Title("CHIP-8 Emulator".to_string())));
+        assert!(received.contains(&EmulatorOutput::Tone(true)));
+        assert!(received.contains(&EmulatorOutput::Volume(1.0)));
+
+        thread.close().unwrap();
+    }
+
+    #[test]
+    fn thread_stops_by_itself_when_rom_exits() {
+        let (thread, outputs) = spawn_with_program(
+            &chip8_program_into_bytes!(
+                0x00FD  // exit
+            ),
+            EmulatorConfig::new().variant(Chip8Variant::SuperChip1_1),
+        );
+        receive_until(&outputs, |output| *output == EmulatorOutput::Stopped);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn thread_handles_keys_and_saves_on_close() {
+        let autosave = TempFile::new("thread_handles_keys_and_saves_on_close.c8state");
+        let (thread, outputs) = spawn_with_program(
+            &chip8_program_into_bytes!(
+                0x7001  // V0 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new().autosave_path(autosave.path.clone()),
+        );
+        thread.send(EmulatorInput::Key(key_input(VirtualKeyCode::P, true)));
+        receive_until(&outputs, |output| {
+            *output == EmulatorOutput::Title("CHIP-8 Emulator (paused)".to_string())
+        });
+
+        // closing wakes the thread while it waits, paused, for input
+        thread.close().unwrap();
+        assert!(autosave.path.exists());
+        assert_eq!(
+            outputs.recv_timeout(TIMEOUT).unwrap(),
+            EmulatorOutput::Stopped
+        );
+    }
+}
@@ -1,24 +1,167 @@
-use std::fmt;
-// use std::io;
+use std::{fmt, io, mem};
 
 /// The error type used throughout this library.
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub enum Error {
-    // Io(io::Error),
+    /// Reading or writing a file failed.
+    Io(io::Error),
     EmptyChip8Program,
+    /// A rom with no bytes in it.
+    EmptyRom,
+    /// A rom too large for the program area of 4K RAM, with its size.
+    RomTooLarge(usize),
+    /// A token in a hex text rom that isn't a whole number of hex bytes, with
+    /// the line it is on, counting from 1.
+    InvalidRomHexToken {
+        line: usize,
+        token: String,
+    },
+    /// A rom database that couldn't be read, with the reason why.
+    InvalidRomDatabase(String),
+    /// A key map that couldn't be read or built, with the reason why.
+    InvalidKeyMap(String),
+    /// A rom config file that couldn't be read, with the reason why.
+    InvalidRomConfig(String),
     Chip8ProgramTooLarge(usize),
+    /// A CHIP-8 program was loaded at the given address, which is outside of
+    /// the program area of RAM.
+    ProgramStartAddressOutOfRange(usize),
     RamOverflow,
+    /// A memory image of the given size, which isn't the size of the RAM it
+    /// was loaded into.
+    MemoryImageSizeMismatch(usize),
+    /// A record of an Intel HEX memory image that couldn't be loaded, with
+    /// the line it is on, counting from 1, and the reason why.
+    InvalidIntelHex {
+        line: usize,
+        reason: &'static str,
+    },
+    /// A CHIP-8 instruction tried to read or write memory past the end of RAM.
+    MemoryOutOfBounds {
+        address: usize,
+    },
+    /// A write to RAM was blocked by write protection, at the first protected
+    /// address written to.
+    WriteProtected {
+        address: usize,
+    },
+    /// A CHIP-8 instruction would have set the program counter to the given
+    /// address, outside of the program area, or run past the end of RAM.
+    ProgramCounterOutOfRange(u16),
+    /// A CHIP-8 instruction would have left I at the given address, outside
+    /// of the memory CHIP-8 programs may use.
+    IAddressOutOfRange(u16),
+    /// A CHIP-8 program called a subroutine with no room left on the stack,
+    /// either with 12 calls on it already, the most the COSMAC VIP allows, or
+    /// with the stack pointer written over to point outside of it.
+    Chip8StackOverflow,
+    /// A CHIP-8 program returned from a subroutine with no calls on the stack.
+    Chip8StackUnderflow,
+    /// A CHIP-8 program called a COSMAC VIP machine language subroutine with
+    /// 0MMM, at the given address.
+    MachineLanguageCallUnsupported(u16),
+    /// A CHIP-8 program ran the given instruction, which isn't one of those
+    /// of the variant being emulated.
+    UnknownInstruction(u16),
+    /// The bytes given are not a valid save state.
+    InvalidSaveState,
+    /// The save state was written with a newer, unknown format version.
+    UnsupportedSaveStateVersion(u16),
+    /// The bytes given are not a valid input recording.
+    InvalidRecording,
+    /// The input recording was written with a newer, unknown format version.
+    UnsupportedRecordingVersion(u16),
+    /// The emulator window couldn't be created or drawn to, with the reason
+    /// why.
+    Window(String),
+    /// The tone couldn't be played through the audio output, with the
+    /// reason why.
+    Audio(String),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            // Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Io(err) => write!(f, "IO error: {}", err),
             Error::EmptyChip8Program => write!(f, "CHIP-8 program is empty!"),
+            Error::EmptyRom => write!(f, "Rom is empty!"),
+            Error::RomTooLarge(size) => write!(f, "Rom with size {size} bytes is too large!"),
+            Error::InvalidRomHexToken { line, token } => {
+                write!(f, "Rom has invalid hex \"{token}\" on line {line}.")
+            }
+            Error::InvalidRomDatabase(reason) => write!(f, "Invalid rom database: {reason}"),
+            Error::InvalidKeyMap(reason) => write!(f, "Invalid key map: {reason}"),
+            Error::InvalidRomConfig(reason) => write!(f, "Invalid rom config: {reason}"),
             Error::Chip8ProgramTooLarge(size) => {
                 write!(f, "CHIP-8 program with size {} bytes is too large!", size)
             }
+            Error::ProgramStartAddressOutOfRange(address) => {
+                write!(
+                    f,
+                    "CHIP-8 program start address {address:#06X} is outside of the program area."
+                )
+            }
             Error::RamOverflow => write!(f, "Operation would cause a write beyond the end of RAM."),
+            Error::MemoryImageSizeMismatch(size) => {
+                write!(f, "Memory image of {size} bytes is not the size of RAM.")
+            }
+            Error::InvalidIntelHex { line, reason } => {
+                write!(f, "Invalid Intel HEX on line {line}: {reason}.")
+            }
+            Error::MemoryOutOfBounds { address } => {
+                write!(
+                    f,
+                    "CHIP-8 instruction accessed address {address:#06X} beyond the end of RAM."
+                )
+            }
+            Error::WriteProtected { address } => {
+                write!(f, "Write to write protected address {address:#06X}.")
+            }
+            Error::ProgramCounterOutOfRange(address) => {
+                write!(
+                    f,
+                    "Attempt to set program counter to address {address:#06X} which is outside \
+                    of CHIP-8 program address range."
+                )
+            }
+            Error::IAddressOutOfRange(address) => {
+                write!(
+                    f,
+                    "Attempt to set I address to {address:#06X} which is outside of normal \
+                    operating range."
+                )
+            }
+            Error::Chip8StackOverflow => write!(
+                f,
+                "CHIP-8 stack overflow! COSMAC VIP only allows 12 levels of subroutine nesting."
+            ),
+            Error::Chip8StackUnderflow => write!(
+                f,
+                "Cannot return when not in a subroutine. CHIP-8 subroutine stack is empty!"
+            ),
+            Error::MachineLanguageCallUnsupported(address) => {
+                write!(
+                    f,
+                    "CHIP-8 program called a machine language subroutine at {address:#06X}, \
+                    which is not supported."
+                )
+            }
+            Error::UnknownInstruction(instruction) => {
+                write!(f, "Unknown CHIP-8 instruction {instruction:#06X}")
+            }
+            Error::InvalidSaveState => write!(f, "Not a valid save state."),
+            Error::UnsupportedSaveStateVersion(version) => {
+                write!(f, "Save state format version {version} is not supported.")
+            }
+            Error::InvalidRecording => write!(f, "Not a valid input recording."),
+            Error::UnsupportedRecordingVersion(version) => {
+                write!(
+                    f,
+                    "Input recording format version {version} is not supported."
+                )
+            }
+            Error::Window(reason) => write!(f, "Window error: {reason}"),
+            Error::Audio(reason) => write!(f, "Audio error: {reason}"),
         }
     }
 }
@@ -26,16 +169,61 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     // Don't implement `description` or `cause` trait methods as they are deprecated.
 
-    // fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-    //     match self {
-    //         Error::Io(err) => Some(err),
-    //         _ => None,
-    //     }
-    // }
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
-// impl From<io::Error> for Error {
-//     fn from(err: io::Error) -> Self {
-//         Self::Io(err)
-//     }
-// }
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl PartialEq for Error {
+    /// IO errors are equal when they are the same kind of error. Other errors
+    /// are equal when they are the same variant with the same data.
+    fn eq(&self, other: &Self) -> bool {
+        use Error::*;
+
+        match (self, other) {
+            (Io(err), Io(other_err)) => err.kind() == other_err.kind(),
+            (RomTooLarge(a), RomTooLarge(b))
+            | (Chip8ProgramTooLarge(a), Chip8ProgramTooLarge(b))
+            | (ProgramStartAddressOutOfRange(a), ProgramStartAddressOutOfRange(b))
+            | (MemoryImageSizeMismatch(a), MemoryImageSizeMismatch(b))
+            | (MemoryOutOfBounds { address: a }, MemoryOutOfBounds { address: b })
+            | (WriteProtected { address: a }, WriteProtected { address: b }) => a == b,
+            (ProgramCounterOutOfRange(a), ProgramCounterOutOfRange(b))
+            | (IAddressOutOfRange(a), IAddressOutOfRange(b))
+            | (MachineLanguageCallUnsupported(a), MachineLanguageCallUnsupported(b))
+            | (UnknownInstruction(a), UnknownInstruction(b))
+            | (UnsupportedSaveStateVersion(a), UnsupportedSaveStateVersion(b))
+            | (UnsupportedRecordingVersion(a), UnsupportedRecordingVersion(b)) => a == b,
+            (InvalidRomDatabase(a), InvalidRomDatabase(b))
+            | (InvalidKeyMap(a), InvalidKeyMap(b))
+            | (InvalidRomConfig(a), InvalidRomConfig(b))
+            | (Window(a), Window(b))
+            | (Audio(a), Audio(b)) => a == b,
+            (
+                InvalidRomHexToken { line, token },
+                InvalidRomHexToken {
+                    line: other_line,
+                    token: other_token,
+                },
+            ) => line == other_line && token == other_token,
+            (
+                InvalidIntelHex { line, reason },
+                InvalidIntelHex {
+                    line: other_line,
+                    reason: other_reason,
+                },
+            ) => line == other_line && reason == other_reason,
+            // the variants left have no data
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-//! Fonts as defined in the RCA COSMAC VIP CDP18S711 instruction manual.
+//! Fonts as defined in the RCA COSMAC VIP CDP18S711 instruction manual, plus
+//! the large digit font added by SCHIP 1.1.
 
 /// The bytes used for character pixels.
 pub const CHARACTER_BYTES: [u8; 51] = [
@@ -14,3 +15,22 @@ pub const CHARACTER_BYTES: [u8; 51] = [
 pub const CHARACTER_MAP: [u8; 16] = [
     0x20, 0x29, 0x12, 0x1A, 0x2E, 0x10, 0x14, 0x24, 0x16, 0x18, 0x1E, 0x08, 0x04, 0x0C, 0x00, 0x02,
 ];
+
+/// The bytes used for the large 8x10 SCHIP digit glyphs, 10 bytes for each of
+/// the digits 0-9 in order.
+#[rustfmt::skip]
+pub const BIG_CHARACTER_BYTES: [u8; 100] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+/// The number of bytes in each large SCHIP digit glyph.
+pub const BIG_CHARACTER_SIZE: usize = 10;
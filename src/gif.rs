@@ -0,0 +1,344 @@
+//! Animated GIF clips of the display.
+//!
+//! A [`GifRecorder`] collects the display as it is presented, once every
+//! 60Hz tick, and encodes the clip in a [`Palette`] when recording stops.
+//! Ticks where the display doesn't change extend the frame before, so a clip
+//! only holds the frames that differ.
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{
+    memory::{DisplaySize, DisplayView},
+    palette::Palette,
+};
+
+// GIF delays are in hundredths of a second, and most viewers slow down
+// frames shorter than 2/100ths of a second, so frames start on a 2/100ths
+// of a second boundary.
+const DELAY_UNIT_CS: usize = 2;
+const TICKS_PER_SECOND: usize = 60;
+
+/// The frames of a clip being recorded, see the [module](self) docs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GifRecorder {
+    max_ticks: usize,
+    ticks: usize,
+    // the size of the first frame, which all frames are scaled to
+    size: Option<DisplaySize>,
+    frames: Vec<GifFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct GifFrame {
+    // the palette index of each pixel, in rows
+    pixels: Vec<u8>,
+    // how many 60Hz ticks the frame was shown for
+    ticks: usize,
+}
+
+impl GifRecorder {
+    /// A recorder that holds at most `max_ticks` 60Hz ticks of frames, to
+    /// bound the memory used by a long recording.
+    pub fn new(max_ticks: usize) -> Self {
+        Self {
+            max_ticks,
+            ticks: 0,
+            size: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Add the display shown for the next tick, given by its two planes.
+    /// Frames of a different size to the first, e.g. after a switch between
+    /// lores and hires, are scaled to the size of the first.
+    ///
+    /// Returns false, and drops the frame, once the recorder is full.
+    pub fn push_frame(&mut self, first_plane: DisplayView, second_plane: DisplayView) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        let frame_size = DisplaySize {
+            width: first_plane.width(),
+            height: first_plane.height(),
+        };
+        let size = *self.size.get_or_insert(frame_size);
+        let pixels: Vec<u8> = first_plane
+            .pixels()
+            .zip(second_plane.pixels())
+            .map(|((_, _, first_on), (_, _, second_on))| first_on as u8 | (second_on as u8) << 1)
+            .collect();
+        let pixels = resize_pixels(&pixels, frame_size, size);
+
+        self.ticks += 1;
+        match self.frames.last_mut() {
+            Some(last) if last.pixels == pixels => last.ticks += 1,
+            _ => self.frames.push(GifFrame { pixels, ticks: 1 }),
+        }
+        true
+    }
+
+    /// Whether the recorder holds as many ticks as it can.
+    pub fn is_full(&self) -> bool {
+        self.ticks >= self.max_ticks
+    }
+
+    /// The number of ticks recorded.
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    /// The number of distinct frames recorded.
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Encode the clip as a looping GIF in `palette`, with each CHIP-8 pixel
+    /// drawn as a `scale` by `scale` square. Frames too short to be shown at
+    /// 50 frames a second are left out, keeping the clip in time.
+    ///
+    /// # Panics
+    /// Panics if `scale` is 0.
+    pub fn encode(&self, palette: &Palette, scale: usize) -> Vec<u8> {
+        assert!(scale > 0, "Clips should be at least a pixel per pixel");
+        let size = self.size.unwrap_or(DisplaySize {
+            width: 1,
+            height: 1,
+        });
+        let (width, height) = ((size.width * scale) as u16, (size.height * scale) as u16);
+
+        // each pixel is an index into the colors of the palette, for the
+        // combination of display planes it is lit on
+        let colors: Vec<u8> = [
+            palette.off,
+            palette.on,
+            palette.second_plane,
+            palette.both_planes,
+        ]
+        .iter()
+        .flat_map(|color| &color[..3])
+        .copied()
+        .collect();
+        let mut encoder = Encoder::new(Vec::new(), width, height, &colors)
+            .expect("Writing a GIF header to memory should not fail");
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .expect("Writing a GIF to memory should not fail");
+
+        let ticks: Vec<_> = self.frames.iter().map(|frame| frame.ticks).collect();
+        for (frame, delay) in self.frames.iter().zip(frame_delays(&ticks)) {
+            if delay == 0 {
+                continue;
+            }
+            let pixels = scale_pixels(&frame.pixels, size.width, scale);
+            let mut frame = Frame::from_indexed_pixels(width, height, &pixels, None);
+            frame.delay = delay;
+            encoder
+                .write_frame(&frame)
+                .expect("Writing a GIF to memory should not fail");
+        }
+        encoder
+            .into_inner()
+            .expect("Writing a GIF to memory should not fail")
+    }
+}
+
+/// The delay of each frame, in hundredths of a second, given how many 60Hz
+/// ticks each was shown for. Frames start on the multiple of
+/// `DELAY_UNIT_CS` nearest to when they were shown, so the delays add up to
+/// the length of the clip, and a frame that would have no delay is left out.
+pub(crate) fn frame_delays(ticks: &[usize]) -> Vec<u16> {
+    // when a frame starts, rounded to the nearest unit
+    let start_cs = |tick: usize| {
+        let units = (tick * 100 + TICKS_PER_SECOND * DELAY_UNIT_CS / 2)
+            / (TICKS_PER_SECOND * DELAY_UNIT_CS);
+        units * DELAY_UNIT_CS
+    };
+    let mut start_tick = 0;
+    ticks
+        .iter()
+        .map(|&frame_ticks| {
+            let end_tick = start_tick + frame_ticks;
+            let delay = start_cs(end_tick) - start_cs(start_tick);
+            start_tick = end_tick;
+            delay as u16
+        })
+        .collect()
+}
+
+// Nearest neighbour scaling of `pixels` from one display size to another.
+fn resize_pixels(pixels: &[u8], from: DisplaySize, to: DisplaySize) -> Vec<u8> {
+    if from == to {
+        return pixels.to_vec();
+    }
+    (0..to.height)
+        .flat_map(|y| {
+            (0..to.width).map(move |x| {
+                pixels[y * from.height / to.height * from.width + x * from.width / to.width]
+            })
+        })
+        .collect()
+}
+
+// Scale up pixels in rows of `width`, drawing each as a `scale` by `scale`
+// square.
+fn scale_pixels(pixels: &[u8], width: usize, scale: usize) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(pixels.len() * scale * scale);
+    for row in pixels.chunks(width) {
+        let scaled_row: Vec<u8> = row.iter().flat_map(|&pixel| vec![pixel; scale]).collect();
+        for _ in 0..scale {
+            scaled.extend(&scaled_row);
+        }
+    }
+    scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_delays, resize_pixels, GifRecorder};
+    use crate::{
+        memory::{DisplaySize, DisplayView, HIRES_DISPLAY_SIZE, LORES_DISPLAY_SIZE},
+        palette::Palette,
+    };
+
+    // A lores display plane with the pixels in `on` lit.
+    fn plane(on: &[(usize, usize)]) -> Vec<u8> {
+        let mut plane = vec![0; LORES_DISPLAY_SIZE.plane_size()];
+        for &(x, y) in on {
+            plane[y * LORES_DISPLAY_SIZE.row_size() + x / 8] |= 0x80 >> (x % 8);
+        }
+        plane
+    }
+
+    #[test]
+    fn delays_add_up_to_clip_length() {
+        // a frame a tick for a second is 50 frames of 2/100ths
+        let delays = frame_delays(&[1; 60]);
+        assert_eq!(delays.iter().map(|&d| d as usize).sum::<usize>(), 100);
+        assert_eq!(delays.iter().filter(|&&d| d == 0).count(), 10);
+        assert!(delays.iter().all(|&d| d == 0 || d == 2));
+
+        assert_eq!(frame_delays(&[60, 30, 6]), [100, 50, 10]);
+        assert!(frame_delays(&[]).is_empty());
+    }
+
+    #[test]
+    fn unchanged_frames_extend_the_last() {
+        let mut recorder = GifRecorder::new(10);
+        let blank = plane(&[]);
+        let dot = plane(&[(3, 4)]);
+        for frame in [&blank, &blank, &dot, &dot, &dot, &blank] {
+            assert!(recorder.push_frame(
+                DisplayView::new(frame, LORES_DISPLAY_SIZE),
+                DisplayView::new(&blank, LORES_DISPLAY_SIZE),
+            ));
+        }
+        assert_eq!(recorder.ticks(), 6);
+        assert_eq!(recorder.num_frames(), 3);
+        let ticks: Vec<_> = recorder.frames.iter().map(|frame| frame.ticks).collect();
+        assert_eq!(ticks, [2, 3, 1]);
+        assert_eq!(recorder.frames[1].pixels[4 * 64 + 3], 1);
+    }
+
+    #[test]
+    fn both_planes_are_recorded() {
+        let mut recorder = GifRecorder::new(10);
+        let first = plane(&[(0, 0), (1, 0)]);
+        let second = plane(&[(1, 0), (2, 0)]);
+        recorder.push_frame(
+            DisplayView::new(&first, LORES_DISPLAY_SIZE),
+            DisplayView::new(&second, LORES_DISPLAY_SIZE),
+        );
+        assert_eq!(recorder.frames[0].pixels[..4], [1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn recording_stops_when_full() {
+        let mut recorder = GifRecorder::new(2);
+        let blank = plane(&[]);
+        let view = DisplayView::new(&blank, LORES_DISPLAY_SIZE);
+        assert!(recorder.push_frame(view, view));
+        assert!(!recorder.is_full());
+        assert!(recorder.push_frame(view, view));
+        assert!(recorder.is_full());
+        assert!(!recorder.push_frame(view, view));
+        assert_eq!(recorder.ticks(), 2);
+    }
+
+    #[test]
+    fn frames_resized_to_first() {
+        let lores = [0, 1, 2, 3];
+        let from = DisplaySize {
+            width: 2,
+            height: 2,
+        };
+        let to = DisplaySize {
+            width: 4,
+            height: 2,
+        };
+        assert_eq!(resize_pixels(&lores, from, to), [0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(resize_pixels(&[0, 0, 1, 1, 2, 2, 3, 3], to, from), lores);
+
+        let mut recorder = GifRecorder::new(10);
+        let lores_plane = plane(&[(1, 1)]);
+        let hires_plane = vec![0xFF; HIRES_DISPLAY_SIZE.plane_size()];
+        recorder.push_frame(
+            DisplayView::new(&lores_plane, LORES_DISPLAY_SIZE),
+            DisplayView::new(&plane(&[]), LORES_DISPLAY_SIZE),
+        );
+        recorder.push_frame(
+            DisplayView::new(&hires_plane, HIRES_DISPLAY_SIZE),
+            DisplayView::new(
+                &vec![0; HIRES_DISPLAY_SIZE.plane_size()],
+                HIRES_DISPLAY_SIZE,
+            ),
+        );
+        assert_eq!(recorder.frames[1].pixels, vec![1; 64 * 32]);
+    }
+
+    #[test]
+    fn encode_clip() {
+        let mut recorder = GifRecorder::new(100);
+        let blank = plane(&[]);
+        let dot = plane(&[(63, 31)]);
+        for (frame, ticks) in [(&blank, 30), (&dot, 1), (&blank, 30)] {
+            for _ in 0..ticks {
+                recorder.push_frame(
+                    DisplayView::new(frame, LORES_DISPLAY_SIZE),
+                    DisplayView::new(&blank, LORES_DISPLAY_SIZE),
+                );
+            }
+        }
+        let gif = recorder.encode(&Palette::AMBER, 2);
+        assert!(gif.windows(11).any(|window| window == b"NETSCAPE2.0"));
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (128, 64));
+        let colors = decoder.global_palette().unwrap();
+        assert_eq!(&colors[..3], &Palette::AMBER.off[..3]);
+        assert_eq!(&colors[3..6], &Palette::AMBER.on[..3]);
+
+        // the frames, with their delays, the dot's tick rounded up to 2/100ths
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+        let delays: Vec<_> = frames.iter().map(|(delay, _)| *delay).collect();
+        assert_eq!(delays, [50, 2, 50]);
+
+        // the second frame's pixels
+        let pixels = &frames[1].1;
+        assert_eq!(pixels.len(), 128 * 64);
+        let lit: Vec<_> = (0..pixels.len()).filter(|&i| pixels[i] == 1).collect();
+        assert_eq!(
+            lit,
+            [
+                62 * 128 + 126,
+                62 * 128 + 127,
+                63 * 128 + 126,
+                63 * 128 + 127
+            ]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! The SHA-1 and CRC-32 hashes used to identify roms.
+//!
+//! Neither is used for security, only to match roms against the hashes
+//! listed in rom databases, which are almost always given as SHA-1 or CRC-32.
+
+use sha1::{Digest, Sha1};
+
+/// The SHA-1 digest of `bytes`.
+pub(crate) fn sha1(bytes: &[u8]) -> [u8; 20] {
+    Sha1::digest(bytes).into()
+}
+
+/// The CRC-32 of `bytes`, as used by zip and PNG.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    crc32fast::hash(bytes)
+}
+
+/// Write `bytes` as lowercase hex, the usual way to write a SHA-1 digest.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Read a SHA-1 digest written as hex in either case, or `None` if `hex`
+/// isn't 40 hex digits.
+pub(crate) fn sha1_from_hex(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut digest = [0; 20];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sha1, sha1_from_hex, to_hex};
+
+    #[test]
+    fn sha1_hex_round_trip() {
+        let digest = sha1(b"abc");
+        assert_eq!(
+            sha1_from_hex("A9993E364706816ABA3E25717850C26C9CD0D89D"),
+            Some(digest)
+        );
+        assert_eq!(sha1_from_hex(&to_hex(&digest)), Some(digest));
+        assert_eq!(sha1_from_hex("a9993e"), None);
+        assert_eq!(
+            sha1_from_hex("g9993e364706816aba3e25717850c26c9cd0d89d"),
+            None
+        );
+    }
+}
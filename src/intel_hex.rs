@@ -0,0 +1,162 @@
+//! The Intel HEX text format for memory images, as read and written by EPROM
+//! programmers.
+//!
+//! Each line is a record, `:LLAAAATTDD..CC` in hex, with a byte count `LL`,
+//! a big-endian address `AAAA`, a record type `TT`, the data bytes and a
+//! checksum `CC`, which makes the sum of all of the bytes of the record zero.
+//! Only data records and the end of file record are used, as COSMAC memory
+//! fits in 16-bit addresses.
+
+use std::fmt::Write;
+
+use crate::{Error, Result};
+
+const DATA_RECORD: u8 = 0x00;
+const END_OF_FILE_RECORD: u8 = 0x01;
+/// The number of data bytes written in each data record.
+const RECORD_SIZE: usize = 16;
+
+/// Encode `bytes`, which start at `start_address`, as Intel HEX data records
+/// followed by an end of file record.
+pub(crate) fn encode(bytes: &[u8], start_address: usize) -> String {
+    let mut text = String::new();
+    for (i, data) in bytes.chunks(RECORD_SIZE).enumerate() {
+        let address = (start_address + i * RECORD_SIZE) as u16;
+        write_record(&mut text, address, DATA_RECORD, data);
+    }
+    write_record(&mut text, 0, END_OF_FILE_RECORD, &[]);
+    text
+}
+
+fn write_record(text: &mut String, address: u16, record_type: u8, data: &[u8]) {
+    let mut record = vec![data.len() as u8];
+    record.extend(address.to_be_bytes());
+    record.push(record_type);
+    record.extend(data);
+    record.push(checksum(&record));
+
+    text.push(':');
+    for byte in record {
+        write!(text, "{byte:02X}").unwrap();
+    }
+    text.push('\n');
+}
+
+// The checksum of a record, the two's complement of the sum of its bytes.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        .wrapping_neg()
+}
+
+/// Decode the data records of Intel HEX `text`, up to its end of file
+/// record, as `(address, data)` pairs. Blank lines are skipped.
+///
+/// # Errors
+/// Returns [`Error::InvalidIntelHex`] for the first record that is malformed,
+/// has a bad checksum, has data beyond `memory_size`, or is of an unsupported
+/// type, or if there is no end of file record.
+pub(crate) fn decode(text: &str, memory_size: usize) -> Result<Vec<(usize, Vec<u8>)>> {
+    let mut records = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = |reason| Error::InvalidIntelHex {
+            line: line_index + 1,
+            reason,
+        };
+
+        let record = line
+            .strip_prefix(':')
+            .ok_or_else(|| invalid("record doesn't start with ':'"))?;
+        if record.len() % 2 != 0 || !record.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(invalid("record isn't whole hex bytes"));
+        }
+        let record: Vec<u8> = (0..record.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&record[i..i + 2], 16).unwrap())
+            .collect();
+        // byte count, 2 address bytes, record type and checksum
+        if record.len() < 5 || record.len() != record[0] as usize + 5 {
+            return Err(invalid("record length doesn't match its byte count"));
+        }
+        if checksum(&record) != 0 {
+            return Err(invalid("bad checksum"));
+        }
+
+        let address = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let data = &record[4..record.len() - 1];
+        match record[3] {
+            DATA_RECORD if address + data.len() > memory_size => {
+                return Err(invalid("data goes past the end of RAM"));
+            }
+            DATA_RECORD => records.push((address, data.to_vec())),
+            END_OF_FILE_RECORD => return Ok(records),
+            _ => return Err(invalid("unsupported record type")),
+        }
+    }
+    Err(Error::InvalidIntelHex {
+        line: text.lines().count() + 1,
+        reason: "no end of file record",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use crate::Error;
+
+    #[test]
+    fn encode_records() {
+        let bytes: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            encode(&bytes, 0x0200),
+            ":10020000000102030405060708090A0B0C0D0E0F76\n\
+            :0402100010111213A4\n\
+            :00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn decode_records() {
+        let text = "\
+            :10020000000102030405060708090A0B0C0D0E0F76\n\
+            \n\
+            :0402100010111213a4\r\n\
+            :00000001FF\n\
+            this is after the end of file record";
+        assert_eq!(
+            decode(text, 0x1000),
+            Ok(vec![
+                (0x0200, (0..16).collect()),
+                (0x0210, vec![0x10, 0x11, 0x12, 0x13]),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_records() {
+        let invalid = |text, line, reason| {
+            assert_eq!(
+                decode(text, 0x1000),
+                Err(Error::InvalidIntelHex { line, reason }),
+                "{text}"
+            );
+        };
+        invalid("0402100010111213A4", 1, "record doesn't start with ':'");
+        invalid(":0402100010111213G", 1, "record isn't whole hex bytes");
+        invalid(
+            ":0502100010111213A4",
+            1,
+            "record length doesn't match its byte count",
+        );
+        invalid(":0000", 1, "record length doesn't match its byte count");
+        invalid(":0402100010111213A5", 1, "bad checksum");
+        invalid(":020FFF00AAAA9C", 1, "data goes past the end of RAM");
+        invalid(":020000040000FA", 1, "unsupported record type");
+        invalid(":0402100010111213A4\n", 2, "no end of file record");
+    }
+}
@@ -1,4 +1,10 @@
-use std::{fmt::Debug, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Display, Write as _},
+    io::Write,
+    ops::{Range, RangeInclusive},
+    time::Duration,
+};
 
 #[cfg(test)]
 use mock_instant::Instant;
@@ -7,31 +13,58 @@ use mock_instant::Instant;
 use std::time::Instant;
 
 use crate::{
-    font::{CHARACTER_BYTES, CHARACTER_MAP},
+    bus::MemoryBus,
+    disassembler::{disassemble, opcode_family},
+    font::{BIG_CHARACTER_BYTES, BIG_CHARACTER_SIZE, CHARACTER_BYTES, CHARACTER_MAP},
     memory::{
-        CosmacRAM, DISPLAY_REFRESH_LAST_ADDRESS, DISPLAY_REFRESH_START_ADDRESS,
-        INTERPRETER_WORK_AREA_START_ADDRESS, MEMORY_SIZE, PROGRAM_START_ADDRESS,
-        STACK_START_ADDRESS,
+        CosmacRAM, DisplaySize, DisplayViewMut, VReg, HIRES_DISPLAY_SIZE, LORES_DISPLAY_SIZE,
+        MEMORY_SIZE, NUM_DISPLAY_PLANES, NUM_V_REGISTERS, PROGRAM_START_ADDRESS,
     },
+    opcode::Opcode,
+    quirks::{Chip8Variant, LoadStoreIncrement, Quirks},
+    recording::{RecordedInput, Recording},
     rng::Chip8Rng,
+    save_state::SaveState,
+    Error, Result,
 };
 
-#[cfg(debug_assertions)]
 use crate::debug::{
-    panic_if_chip8_stack_empty_on_subroutine_return, panic_if_chip8_stack_full,
-    panic_if_i_address_out_of_bounds, panic_if_pc_address_not_in_chip8_program_range,
+    check_chip8_stack_not_empty_on_subroutine_return, check_chip8_stack_not_full,
+    check_i_address_in_bounds, check_pc_address_in_chip8_program_range,
 };
 
 pub struct Chip8State<'a> {
     pub program_counter: u16,
-    pub instruction: u16,
+    /// The instruction at the program counter, or `None` if it would run past
+    /// the end of RAM.
+    pub instruction: Option<u16>,
     pub i: u16,
     pub stack_pointer: u16,
     pub timer: u16,
     pub tone_timer: u16,
     pub hex_key_status: u16,
+    pub hex_keys_pressed: u16,
     pub v_registers: &'a [u8],
-    pub display_buffer: &'a [u8],
+    /// The part of the stack in use, holding a 2 byte caller address for each
+    /// subroutine being executed.
+    pub stack: &'a [u8],
+    pub display_buffer: [&'a [u8]; NUM_DISPLAY_PLANES],
+}
+
+impl<'a> Chip8State<'a> {
+    /// The number of subroutines being executed.
+    pub fn call_depth(&self) -> usize {
+        self.stack.len() / 2
+    }
+
+    /// The addresses of the instructions that called the subroutines being
+    /// executed, outermost first.
+    pub fn caller_addresses(&self) -> Vec<u16> {
+        self.stack
+            .chunks_exact(2)
+            .map(|address| u16::from_be_bytes([address[0], address[1]]))
+            .collect()
+    }
 }
 
 impl<'a> Debug for Chip8State<'a> {
@@ -41,12 +74,21 @@ impl<'a> Debug for Chip8State<'a> {
                 "program_counter",
                 &format!("0x{:0>4X}", self.program_counter),
             )
-            .field("instruction", &format!("0x{:0>4X}", self.instruction))
+            .field(
+                "instruction",
+                &self
+                    .instruction
+                    .map(|instruction| format!("0x{instruction:0>4X}")),
+            )
             .field("i", &format!("0x{:0>4X}", self.i))
             .field("stack_pointer", &format!("0x{:0>4X}", self.stack_pointer))
             .field("TIMER", &format!("0x{:0>4X}", self.timer))
             .field("TONE TIMER", &format!("0x{:0>4X}", self.tone_timer))
             .field("HEX_KEY_STATUS", &format!("0x{:0>4X}", self.hex_key_status))
+            .field(
+                "HEX_KEYS_PRESSED",
+                &format!("0b{:0>16b}", self.hex_keys_pressed),
+            )
             .field("V0", &format!("0x{:0>4X}", self.v_registers[0]))
             .field("V1", &format!("0x{:0>4X}", self.v_registers[1]))
             .field("V2", &format!("0x{:0>4X}", self.v_registers[2]))
@@ -63,378 +105,1499 @@ impl<'a> Debug for Chip8State<'a> {
             .field("VD", &format!("0x{:0>4X}", self.v_registers[13]))
             .field("VE", &format!("0x{:0>4X}", self.v_registers[14]))
             .field("VF", &format!("0x{:0>4X}", self.v_registers[15]))
+            .field(
+                "Stack",
+                &self
+                    .caller_addresses()
+                    .iter()
+                    .map(|address| format!("0x{address:0>4X}"))
+                    .collect::<Vec<_>>(),
+            )
             .field("Display buffer", &format!("{:?}", self.display_buffer))
             .finish()
     }
 }
 
+/// A copy of [`Chip8State`] that doesn't borrow from RAM, so it can be kept
+/// around and compared with later states.
+///
+/// With the `serde` feature, it can be serialized with each display plane
+/// encoded as a base64 string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chip8StateOwned {
+    pub program_counter: u16,
+    pub instruction: Option<u16>,
+    pub i: u16,
+    pub stack_pointer: u16,
+    pub timer: u16,
+    pub tone_timer: u16,
+    pub hex_key_status: u16,
+    pub hex_keys_pressed: u16,
+    pub v_registers: [u8; NUM_V_REGISTERS],
+    pub stack: Vec<u8>,
+    /// Each display plane, which varies in size with the display.
+    #[cfg_attr(feature = "serde", serde(with = "base64_display_planes"))]
+    pub display_buffer: [Vec<u8>; NUM_DISPLAY_PLANES],
+}
+
+// Serde representation of the display planes as base64 strings, which are
+// far more compact in JSON than arrays of numbers.
+#[cfg(feature = "serde")]
+mod base64_display_planes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::memory::NUM_DISPLAY_PLANES;
+
+    pub fn serialize<S: Serializer>(
+        planes: &[Vec<u8>; NUM_DISPLAY_PLANES],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        planes
+            .iter()
+            .map(|plane| STANDARD.encode(plane))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[Vec<u8>; NUM_DISPLAY_PLANES], D::Error> {
+        let encoded = <[String; NUM_DISPLAY_PLANES]>::deserialize(deserializer)?;
+        let mut planes: [Vec<u8>; NUM_DISPLAY_PLANES] = Default::default();
+        for (plane, encoded) in planes.iter_mut().zip(&encoded) {
+            *plane = STANDARD.decode(encoded).map_err(D::Error::custom)?;
+        }
+        Ok(planes)
+    }
+}
+
+impl From<Chip8State<'_>> for Chip8StateOwned {
+    fn from(state: Chip8State<'_>) -> Self {
+        Self {
+            program_counter: state.program_counter,
+            instruction: state.instruction,
+            i: state.i,
+            stack_pointer: state.stack_pointer,
+            timer: state.timer,
+            tone_timer: state.tone_timer,
+            hex_key_status: state.hex_key_status,
+            hex_keys_pressed: state.hex_keys_pressed,
+            v_registers: state
+                .v_registers
+                .try_into()
+                .expect("There should be 16 V registers."),
+            stack: state.stack.to_vec(),
+            display_buffer: state.display_buffer.map(<[u8]>::to_vec),
+        }
+    }
+}
+
+impl Chip8StateOwned {
+    /// The fields and display bytes that differ between this state and
+    /// `other`.
+    pub fn diff(&self, other: &Self) -> StateDiff {
+        let mut fields = Vec::new();
+        if self.program_counter != other.program_counter {
+            fields.push(StateField::ProgramCounter);
+        }
+        if self.instruction != other.instruction {
+            fields.push(StateField::Instruction);
+        }
+        let words = [
+            (StateField::I, self.i, other.i),
+            (
+                StateField::StackPointer,
+                self.stack_pointer,
+                other.stack_pointer,
+            ),
+            (StateField::Timer, self.timer, other.timer),
+            (StateField::ToneTimer, self.tone_timer, other.tone_timer),
+            (
+                StateField::HexKeyStatus,
+                self.hex_key_status,
+                other.hex_key_status,
+            ),
+            (
+                StateField::HexKeysPressed,
+                self.hex_keys_pressed,
+                other.hex_keys_pressed,
+            ),
+        ];
+        for (field, a, b) in words {
+            if a != b {
+                fields.push(field);
+            }
+        }
+        for (x, (a, b)) in self.v_registers.iter().zip(other.v_registers).enumerate() {
+            if *a != b {
+                fields.push(StateField::V(x as u8));
+            }
+        }
+        if self.stack != other.stack {
+            fields.push(StateField::Stack);
+        }
+
+        // when the display size changes, the bytes only one of the planes
+        // has are all different
+        let mut display_bytes = Vec::new();
+        for (plane, (a, b)) in self
+            .display_buffer
+            .iter()
+            .zip(&other.display_buffer)
+            .enumerate()
+        {
+            for offset in 0..a.len().max(b.len()) {
+                if a.get(offset) != b.get(offset) {
+                    display_bytes.push((plane, offset));
+                }
+            }
+        }
+
+        StateDiff {
+            fields,
+            display_bytes,
+        }
+    }
+}
+
+/// A field of [`Chip8StateOwned`], other than the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateField {
+    ProgramCounter,
+    Instruction,
+    I,
+    StackPointer,
+    Timer,
+    ToneTimer,
+    HexKeyStatus,
+    HexKeysPressed,
+    /// The `VX` register.
+    V(u8),
+    /// The part of the stack in use.
+    Stack,
+}
+
+/// The differences between two [`Chip8StateOwned`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The fields that changed, in the order they are declared.
+    pub fields: Vec<StateField>,
+    /// The display bytes that changed, as `(plane, offset)` pairs.
+    pub display_bytes: Vec<(usize, usize)>,
+}
+
+impl StateDiff {
+    /// Whether the states were the same.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.display_bytes.is_empty()
+    }
+}
+
+/// What happened when [`Chip8Interpreter::step`] was called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The current instruction was executed, or is waiting for the display
+    /// with the `display_wait` quirk.
+    Executed,
+    /// A sprite was drawn or the display was scrolled.
+    DrewToDisplay,
+    /// The display was cleared, by 00E0 or by SCHIP switching between lores
+    /// and hires.
+    ClearedDisplay,
+    /// FX0A is waiting for a key to be pressed and released.
+    WaitingForKey,
+    /// FX18 started the tone.
+    SoundStarted,
+    /// The program exited with the SCHIP 00FD instruction. The program counter
+    /// stays on the exit instruction, so further steps exit again without
+    /// doing anything.
+    Exited,
+    /// The instruction jumped to or called itself, e.g. the `1MMM` loop many
+    /// programs finish with. Nothing but a reset can change the state of the
+    /// program from here on, apart from the timers counting down.
+    Halted,
+    /// The instruction at `pc` accessed `address`, which is being watched for
+    /// that `kind` of access. The instruction has been executed, and this is
+    /// reported in place of the outcome it would otherwise have had.
+    Watchpoint {
+        address: usize,
+        pc: u16,
+        kind: Watch,
+    },
+}
+
+/// The kind of memory access a watchpoint trips on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Read,
+    Write,
+}
+
+/// Why [`Chip8Interpreter::step_n`] or [`Chip8Interpreter::run_until_draw`]
+/// stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The maximum number of instructions ran.
+    StepLimit,
+    /// The program exited with 00FD.
+    Exited,
+    /// FX0A is waiting for a key.
+    WaitingForKey,
+    /// An instruction changed the display, e.g. DXYN or 00E0.
+    Drew,
+    /// The program is stuck in a jump or call to itself.
+    Halted,
+    /// An instruction accessed a watched address, as reported by
+    /// [`StepOutcome::Watchpoint`].
+    Watchpoint {
+        address: usize,
+        pc: u16,
+        kind: Watch,
+    },
+}
+
+/// The result of running a batch of instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepsReport {
+    /// The number of times the interpreter was stepped, including the step
+    /// that caused it to stop.
+    pub steps: usize,
+    pub stop_reason: StopReason,
+}
+
+/// Counts of the instructions executed since the interpreter was reset, see
+/// [`Chip8Interpreter::enable_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionStats {
+    /// The total number of instructions executed.
+    pub instructions: u64,
+    /// The number of instructions executed from each opcode family, keyed by
+    /// family, e.g. `"8XY4"`.
+    pub opcode_counts: HashMap<&'static str, u64>,
+    /// The number of sprite pixels drawn to the display by DXYN.
+    pub pixels_drawn: u64,
+    /// The number of DXYN instructions that caused a pixel collision.
+    pub collisions: u64,
+}
+
+impl ExecutionStats {
+    /// The opcode families and their counts, most executed first.
+    pub fn histogram(&self) -> Vec<(&'static str, u64)> {
+        let mut histogram: Vec<_> = self
+            .opcode_counts
+            .iter()
+            .map(|(&family, &count)| (family, count))
+            .collect();
+        histogram.sort_by(|(a_family, a_count), (b_family, b_count)| {
+            b_count.cmp(a_count).then(a_family.cmp(b_family))
+        });
+        histogram
+    }
+}
+
+impl Display for ExecutionStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const BAR_WIDTH: u64 = 40;
+
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "pixels drawn: {}", self.pixels_drawn)?;
+        writeln!(f, "collisions:   {}", self.collisions)?;
+        let histogram = self.histogram();
+        let max_count = histogram.first().map_or(0, |&(_, count)| count);
+        for (family, count) in histogram {
+            let percent = 100.0 * count as f64 / self.instructions as f64;
+            let bar = "#".repeat((BAR_WIDTH * count / max_count) as usize);
+            writeln!(f, "{family} {count:>10} {percent:>5.1}% {bar}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How well the decode cache is doing, see
+/// [`Chip8Interpreter::enable_decode_cache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeCacheStats {
+    /// The number of instructions found already decoded in the cache.
+    pub hits: u64,
+    /// The number of instructions decoded and added to the cache.
+    pub misses: u64,
+    /// The number of cached instructions removed because the program
+    /// overwrote them.
+    pub invalidations: u64,
+}
+
+/// How the delay and tone timers count down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimerMode {
+    /// The timers count down in real time, measured by the host clock each
+    /// time [`Chip8Interpreter::step`] is called.
+    #[default]
+    WallClock,
+    /// The timers only count down when [`Chip8Interpreter::tick_60hz`] is
+    /// called, which the host should do 60 times a second. This keeps
+    /// execution deterministic, e.g. when running headless.
+    Ticked,
+}
+
+/// What to do with 0MMM instructions, which call a COSMAC VIP machine
+/// language subroutine at address MMM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MachineLanguageCalls {
+    /// Return [`Error::MachineLanguageCallUnsupported`] from
+    /// [`Chip8Interpreter::step`].
+    #[default]
+    Error,
+    /// Skip the instruction, for programs that call a subroutine that does
+    /// nothing, e.g. 0000 at startup.
+    Ignore,
+}
+
 // Program counter address
 pub(crate) const CHARACTER_BYTES_ADDRESS: usize = 0x0000;
 pub(crate) const CHARACTER_MAP_ADDRESS: usize = CHARACTER_BYTES_ADDRESS + CHARACTER_BYTES.len();
-pub(crate) const PROGRAM_COUNTER_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS;
-pub(crate) const I_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS + 2;
-pub(crate) const STACK_POINTER_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS + 4;
-pub(crate) const TIMER_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS + 6;
-pub(crate) const TONE_TIMER_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS + 8;
-
-pub(crate) const HEX_KEY_STATUS_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS + 10;
+pub(crate) const BIG_CHARACTER_BYTES_ADDRESS: usize = CHARACTER_MAP_ADDRESS + CHARACTER_MAP.len();
+pub(crate) const PROGRAM_COUNTER_OFFSET: usize = 0;
+pub(crate) const I_OFFSET: usize = 2;
+pub(crate) const STACK_POINTER_OFFSET: usize = 4;
+pub(crate) const TIMER_OFFSET: usize = 6;
+pub(crate) const TONE_TIMER_OFFSET: usize = 8;
+
+pub(crate) const HEX_KEY_STATUS_OFFSET: usize = 10;
 const HEX_KEY_WAIT_FLAG: u16 = 0x1000;
+const HEX_KEY_TONE_FLAG: u16 = 0x2000; // tone sounding for the key held during FX0A
 const HEX_KEY_SEEN_WHILE_WAITING_FLAG: u16 = 0x0100;
-const HEX_KEY_DEPRESSED_FLAG: u16 = 0x0010;
-const HEX_KEY_LAST_PRESSED_MASK: u16 = 0x000F;
+const HEX_KEY_LAST_PRESSED_MASK: u16 = 0x000F; // key pressed while FX0A waits
+
+// V register that FX0A stores the key in, latched when the wait begins
+pub(crate) const HEX_KEY_WAIT_REGISTER_OFFSET: usize = 14;
+// Hex keys currently held down, one bit per key
+pub(crate) const HEX_KEYS_PRESSED_OFFSET: usize = 16;
+// Key presses and releases since FX0A began waiting, oldest first, so that a
+// key tapped between two steps isn't missed. One byte per event.
+pub(crate) const KEY_EVENT_COUNT_OFFSET: usize = 18;
+pub(crate) const KEY_EVENT_QUEUE_OFFSET: usize = 20;
+const KEY_EVENT_QUEUE_SIZE: usize = 8;
+const KEY_EVENT_PRESSED_FLAG: u8 = 0x80;
+const KEY_EVENT_KEY_MASK: u8 = 0x0F;
+
+// XO-CHIP display planes selected for drawing, one bit per plane
+pub(crate) const PLANE_MASK_OFFSET: usize = 12;
+const PLANE_MASK_ALL: u16 = 0b11;
+const PLANE_MASK_DEFAULT: u16 = 0b01;
+
+// 1 jiffy = 1/60 seconds, rounded down to the millisecond
+const JIFFY: Duration = Duration::from_millis(1000 / 60);
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Number of bytes in an XO-CHIP audio pattern (128 1-bit samples).
+pub(crate) const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_AUDIO_PITCH: u8 = 64; // plays the audio pattern at 4000 samples/second
+
+/// Number of SCHIP RPL user flags available to FX75/FX85.
+pub(crate) const NUM_RPL_FLAGS: usize = 8;
 
 pub struct Chip8Interpreter<T: Chip8Rng = fastrand::Rng> {
     rng: T,
+    quirks: Quirks,
+    // Whether the SCHIP and XO-CHIP extensions to the instruction set are
+    // available. Without them, their opcodes are unknown instructions.
+    schip_opcodes: bool,
+    xo_chip_opcodes: bool,
+    // Where the program is loaded, where it starts running and the display
+    // it starts with.
+    program_start_address: usize,
+    program_entry_address: usize,
+    initial_display_size: DisplaySize,
+    timer_mode: TimerMode,
+    machine_language_calls: MachineLanguageCalls,
+    // Only used in wall-clock timer mode
     timer_expiry: Option<Instant>,
     tone_expiry: Option<Instant>,
+    last_draw: Option<Instant>,
+    // Only used in ticked timer mode
+    drawn_since_tick: bool,
+    // SCHIP RPL user flags. On the HP48 these lived outside of CHIP-8 memory
+    // and survived between runs, so they are not part of CosmacRAM.
+    rpl_flags: [u8; NUM_RPL_FLAGS],
+    // XO-CHIP audio pattern and pitch. Like the sound hardware they control,
+    // these are not part of CosmacRAM.
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    audio_pitch: u8,
+    watchpoints: Vec<(Range<usize>, Watch)>,
+    pre_step_hook: Option<StepHook>,
+    post_step_hook: Option<StepHook>,
+    trace: Option<Box<dyn Write + Send>>,
+    stats: Option<ExecutionStats>,
+    coverage: Option<Vec<u32>>,
+    recording: Option<Recording>,
+    replay: Option<Replay>,
+    presented_frame: Option<PresentedFrame>,
+    decode_cache: Option<DecodeCache>,
+}
+
+/// An instruction and what it decodes to.
+#[derive(Clone, Copy)]
+struct DecodedInstruction {
+    instruction: u16,
+    opcode: Opcode,
+    family: &'static str,
+}
+
+/// Decoded instructions, indexed by address / 2. Instructions at odd
+/// addresses aren't cached.
+struct DecodeCache {
+    instructions: Vec<Option<DecodedInstruction>>,
+    stats: DecodeCacheStats,
+}
+
+/// A copy of the display, only updated when presented, for frontends to
+/// render from.
+struct PresentedFrame {
+    display_size: DisplaySize,
+    planes: [Vec<u8>; NUM_DISPLAY_PLANES],
+}
+
+impl PresentedFrame {
+    fn new(ram: &CosmacRAM) -> Self {
+        Self {
+            display_size: ram.display_size(),
+            planes: std::array::from_fn(|plane| ram.display_plane(plane).to_vec()),
+        }
+    }
+}
+
+/// The key presses and ticks left to apply while replaying a recording.
+struct Replay {
+    inputs: VecDeque<(u64, RecordedInput)>,
+    step: u64,
+    steps: u64,
+}
+
+/// What drawing a sprite to a display plane did. Rows are bitmasks with a
+/// bit for each row of the sprite, so that rows drawn to several planes can
+/// be combined.
+#[derive(Default)]
+struct DrawnSprite {
+    pixels_drawn: u32,
+    // rows with a pixel that was already set
+    collided_rows: u16,
+    // rows not drawn for being past the bottom of the display
+    clipped_rows: u16,
+}
+
+/// The memory accessed by an instruction, only tracked while something needs
+/// it.
+struct MemoryAccesses {
+    tracked: bool,
+    accesses: Vec<(Range<usize>, Watch)>,
+}
+
+impl MemoryAccesses {
+    fn push(&mut self, access: (Range<usize>, Watch)) {
+        if self.tracked {
+            self.accesses.push(access);
+        }
+    }
+}
+
+/// The registers compared before and after a step when tracing.
+struct TracedRegisters {
+    program_counter: u16,
+    /// `None` if the instruction would run past the end of RAM.
+    instruction: Option<u16>,
+    i: u16,
+    stack_pointer: u16,
+    v: [u8; 16],
+}
+
+impl TracedRegisters {
+    fn new(ram: &CosmacRAM) -> Self {
+        let program_counter = ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET);
+        Self {
+            program_counter,
+            instruction: ram.try_get_u16_at(program_counter as usize).ok(),
+            i: ram.get_work_area_u16(I_OFFSET),
+            stack_pointer: ram.get_work_area_u16(STACK_POINTER_OFFSET),
+            v: ram.get_v_registers().try_into().unwrap(),
+        }
+    }
+}
+
+/// A function called with the state of the interpreter around each step.
+pub type StepHook = Box<dyn FnMut(&Chip8State) + Send>;
+
+impl Chip8Interpreter<fastrand::Rng> {
+    /// Create an interpreter like [`Self::new`] with a random number generator
+    /// seeded with `seed`, so that runs of programs using CXKK can be
+    /// reproduced.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(fastrand::Rng::with_seed(seed))
+    }
 }
 
 impl<T: Chip8Rng> Chip8Interpreter<T> {
+    /// Create an interpreter with the behavior and instruction set of the
+    /// original COSMAC VIP interpreter. See [`Self::with_variant`] for the
+    /// SCHIP and XO-CHIP opcodes.
     pub fn new(rng: T) -> Self {
+        Self::with_quirks(rng, Quirks::cosmac_vip())
+    }
+
+    /// Create an interpreter with the instruction set of the original COSMAC
+    /// VIP interpreter, but with the behavior described by `quirks`.
+    pub fn with_quirks(rng: T, quirks: Quirks) -> Self {
         Self {
             rng,
+            quirks,
+            schip_opcodes: false,
+            xo_chip_opcodes: false,
+            program_start_address: PROGRAM_START_ADDRESS,
+            program_entry_address: PROGRAM_START_ADDRESS,
+            initial_display_size: LORES_DISPLAY_SIZE,
+            timer_mode: TimerMode::WallClock,
+            machine_language_calls: MachineLanguageCalls::Error,
             timer_expiry: None,
             tone_expiry: None,
+            last_draw: None,
+            drawn_since_tick: false,
+            rpl_flags: [0; NUM_RPL_FLAGS],
+            audio_pattern: None,
+            audio_pitch: DEFAULT_AUDIO_PITCH,
+            watchpoints: Vec::new(),
+            pre_step_hook: None,
+            post_step_hook: None,
+            trace: None,
+            stats: None,
+            coverage: None,
+            recording: None,
+            replay: None,
+            presented_frame: None,
+            decode_cache: None,
         }
     }
 
-    pub fn reset(&self, ram: &mut CosmacRAM) {
-        // reset all CHIP-8 interpreter state
-        ram.zero_out_range(STACK_START_ADDRESS..MEMORY_SIZE)
-            .expect("Should be ok to zero out this memory");
-        Chip8Interpreter::<T>::load_fonts(ram);
+    /// Create an interpreter that behaves like `variant`, with its quirks and
+    /// only the opcodes, fonts and display sizes it supports.
+    pub fn with_variant(rng: T, variant: Chip8Variant) -> Self {
+        Self {
+            schip_opcodes: variant.has_schip_opcodes(),
+            xo_chip_opcodes: variant.has_xo_chip_opcodes(),
+            program_start_address: variant.program_start_address(),
+            program_entry_address: variant.program_entry_address(),
+            initial_display_size: variant.initial_display_size(),
+            ..Self::with_quirks(rng, variant.quirks())
+        }
+    }
 
-        ram.set_u16_at(PROGRAM_COUNTER_ADDRESS, PROGRAM_START_ADDRESS as u16);
-        ram.set_u16_at(STACK_POINTER_ADDRESS, STACK_START_ADDRESS as u16);
+    /// Replace the quirks, e.g. with those recommended for a rom by a
+    /// [`RomDatabase`](crate::rom_database::RomDatabase).
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
     }
 
-    fn load_fonts(ram: &mut CosmacRAM) {
-        ram.load_bytes(&CHARACTER_BYTES, CHARACTER_BYTES_ADDRESS)
-            .expect("Should be ok to load font data data in low memory.");
-        ram.load_bytes(&CHARACTER_MAP, CHARACTER_MAP_ADDRESS)
-            .expect("Should be ok to load character map in low memory.");
+    /// Choose how the delay and tone timers count down. Timers that are
+    /// already running carry on counting down from their current value.
+    pub fn set_timer_mode(&mut self, ram: &mut CosmacRAM, timer_mode: TimerMode) {
+        self.sync_wall_clock_timers(ram);
+        self.timer_mode = timer_mode;
+        self.start_wall_clock_timers(ram);
+        self.last_draw = None;
+        self.drawn_since_tick = false;
     }
 
-    /// Execute the current CHIP-8 instruction, determined by the internal
-    /// CHIP-8 program counter, and advance the program counter to point to the
-    /// next instruction to execute.
-    ///
-    /// # Panics
-    /// Will panic if the CHIP-8 program being executed attempts to:
-    ///   - Set the CHIP-8 program counter, through a jump or otherwise, or `I` address
-    ///     to an address outside of the area of memory allowed for CHIP-8 programs.
-    ///   - Run an instruction that's not part of the CHIP-8 instruction set.
-    ///   - Exceed the capacity of the CHIP-8 stack by running more than 12 subroutines
-    ///     at once.
-    ///   - Return from a subroutine when not currently in a subroutine.
-    ///   - Execute a 0MMM instruction to call a machine code routine.
-    pub fn step(&mut self, ram: &mut CosmacRAM) {
-        let instruction_address = ram.get_u16_at(PROGRAM_COUNTER_ADDRESS) as usize;
-        let instruction = ram.get_u16_at(instruction_address);
+    /// Choose what to do with 0MMM machine language subroutine calls.
+    pub fn set_machine_language_calls(&mut self, machine_language_calls: MachineLanguageCalls) {
+        self.machine_language_calls = machine_language_calls;
+    }
 
-        if let Some(expiry) = self.timer_expiry {
-            let now = Instant::now();
-            let jiffies_left = if expiry <= now {
-                // 1 jiffy = 1/60 seconds
-                self.timer_expiry = None;
-                0
-            } else {
-                ((expiry - Instant::now()).as_millis() * 60) / 1000
-            };
-            ram.set_u16_at(TIMER_ADDRESS, jiffies_left as u16);
+    /// Count down the delay and tone timers by one jiffy. In
+    /// [`TimerMode::Ticked`] this should be called 60 times a second, in time
+    /// with the display refresh. It does nothing in [`TimerMode::WallClock`].
+    ///
+    /// Ticks are recorded when recording, and ignored while replaying a
+    /// recording, which replays its own ticks.
+    ///
+    /// With double buffering enabled, this also presents the display, in any
+    /// timer mode.
+    pub fn tick_60hz(&mut self, ram: &mut CosmacRAM) {
+        self.present(ram);
+        if self.timer_mode != TimerMode::Ticked || self.replay.is_some() {
+            return;
         }
-
-        if let Some(expiry) = self.tone_expiry {
-            let now = Instant::now();
-            let jiffies_left = if expiry <= now {
-                // 1 jiffy = 1/60 seconds
-                self.tone_expiry = None;
-                0
-            } else {
-                ((expiry - Instant::now()).as_millis() * 60) / 1000
-            };
-            ram.set_u16_at(TONE_TIMER_ADDRESS, jiffies_left as u16);
+        if let Some(recording) = &mut self.recording {
+            recording.push(RecordedInput::Tick);
         }
+        self.count_down_timers(ram);
+    }
 
-        let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-        if hex_key_status & HEX_KEY_WAIT_FLAG != 0 {
-            // FX07 instruction
-            // waiting for key press or release
-            if hex_key_status & HEX_KEY_DEPRESSED_FLAG != 0 {
-                // key currently pressed
-                ram.set_u16_at(
-                    HEX_KEY_STATUS_ADDRESS,
-                    hex_key_status | HEX_KEY_SEEN_WHILE_WAITING_FLAG,
-                );
+    fn count_down_timers(&mut self, ram: &mut CosmacRAM) {
+        for offset in [TIMER_OFFSET, TONE_TIMER_OFFSET] {
+            let jiffies = ram.get_work_area_u16(offset);
+            ram.set_work_area_u16(offset, jiffies.saturating_sub(1));
+        }
+        self.drawn_since_tick = false;
+    }
 
-                // update VX register for FX07 instruction.
-                let x = (instruction & 0x0F00) >> 8;
-                let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-                let key = hex_key_status & HEX_KEY_LAST_PRESSED_MASK;
+    /// Watch a range of memory for reads or writes by FX33, FX55, FX65, DXYN
+    /// and 00E0, which access memory through `I`, the V registers and the
+    /// display refresh page. When one of these instructions accesses the
+    /// range, [`Self::step`] returns [`StepOutcome::Watchpoint`].
+    ///
+    /// DXYN and 00E0 count as writing the whole display refresh page, and only
+    /// do so in lores mode while the first display plane is selected.
+    pub fn add_watchpoint(&mut self, range: Range<usize>, kind: Watch) {
+        self.watchpoints.push((range, kind));
+    }
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = key as u8;
-            } else if hex_key_status & HEX_KEY_SEEN_WHILE_WAITING_FLAG != 0 {
-                // seen key pressed and released following wait
+    /// Remove all watchpoints.
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
 
-                // reset flags
-                ram.set_u16_at(
-                    HEX_KEY_STATUS_ADDRESS,
-                    hex_key_status & !(HEX_KEY_WAIT_FLAG | HEX_KEY_SEEN_WHILE_WAITING_FLAG),
-                );
+    /// Call `hook` with the state of the interpreter before each step, e.g. to
+    /// trace the instructions being executed.
+    pub fn set_pre_step_hook(&mut self, hook: StepHook) {
+        self.pre_step_hook = Some(hook);
+    }
 
-                // complete FX07 instruction
-                let next_instruction_address = instruction_address.wrapping_add(2);
-                ram.set_u16_at(PROGRAM_COUNTER_ADDRESS, next_instruction_address as u16);
-            }
-            return;
-        }
+    /// Call `hook` with the state of the interpreter after each step.
+    pub fn set_post_step_hook(&mut self, hook: StepHook) {
+        self.post_step_hook = Some(hook);
+    }
 
-        let mut next_instruction_address = instruction_address.wrapping_add(2);
+    /// Remove the pre and post step hooks.
+    pub fn clear_step_hooks(&mut self) {
+        self.pre_step_hook = None;
+        self.post_step_hook = None;
+    }
 
-        match instruction {
-            op if op == 0x7000 => {
-                // NOOP
-            }
-            op if op & 0xF000 == 0x1000 => {
-                // Unconditional jump
-                let dest = op & 0x0FFF;
-                next_instruction_address = dest as usize;
-            }
-            op if op & 0xF000 == 0xB000 => {
-                // Unconditional jump with offset
-                let v0 = ram.get_v_registers()[0];
-                let dest = (op & 0x0FFF).wrapping_add(v0 as u16);
-                next_instruction_address = dest as usize;
-            }
-            op if op & 0xF000 == 0x2000 => {
-                // Execute subroutine
-                #[cfg(debug_assertions)]
-                panic_if_chip8_stack_full(ram);
+    /// Write a line to `w` for each executed instruction, containing the
+    /// address and value of the instruction, its disassembly and the new
+    /// values of the registers it modified, e.g.
+    ///
+    /// ```text
+    /// 0x0204 0x8124 ADD V1, V2      V1=0x46 VF=0x00
+    /// ```
+    ///
+    /// An instruction that would run past the end of RAM is traced as
+    /// `------ past end of RAM`.
+    ///
+    /// Tracing is disabled if writing to `w` fails.
+    pub fn enable_trace(&mut self, w: impl Write + Send + 'static) {
+        self.trace = Some(Box::new(w));
+    }
 
-                let dest_address = op & 0x0FFF;
-                let caller_address = ram.get_u16_at(PROGRAM_COUNTER_ADDRESS);
+    /// Stop tracing executed instructions.
+    pub fn disable_trace(&mut self) {
+        self.trace = None;
+    }
 
-                // Push where we are jumping from onto the stack
-                let sp = ram.get_u16_at(STACK_POINTER_ADDRESS);
-                ram.set_u16_at(sp as usize, caller_address);
-                ram.set_u16_at(STACK_POINTER_ADDRESS, sp + 2);
+    /// Start counting the instructions executed. See [`Self::stats`].
+    pub fn enable_stats(&mut self) {
+        self.stats = Some(ExecutionStats::default());
+    }
 
-                // Jump
-                next_instruction_address = dest_address as usize;
-            }
-            op if op == 0x00EE => {
-                // Return from subroutine
-                #[cfg(debug_assertions)]
-                panic_if_chip8_stack_empty_on_subroutine_return(ram);
+    /// Stop counting executed instructions and discard the counts.
+    pub fn disable_stats(&mut self) {
+        self.stats = None;
+    }
 
-                // Pop return address off stack
-                let sp = ram.get_u16_at(STACK_POINTER_ADDRESS) - 2;
-                ram.set_u16_at(STACK_POINTER_ADDRESS, sp);
-                let caller_address = ram.get_u16_at(sp as usize);
+    /// Counts of the instructions executed since stats were enabled or the
+    /// interpreter was reset. `None` if stats are disabled.
+    pub fn stats(&self) -> Option<&ExecutionStats> {
+        self.stats.as_ref()
+    }
 
-                // Jump
-                next_instruction_address = caller_address as usize + 2;
-            }
-            op if op & 0xF000 == 0x3000 => {
-                // Skip if VX == constant
-                let x = (op & 0x0F00) >> 8;
-                let vx = ram.get_v_registers()[x as usize];
-                let constant = (op & 0x00FF) as u8;
-                if vx == constant {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF000 == 0x4000 => {
-                // Skip if VX != constant
-                let x = (op & 0x0F00) >> 8;
-                let vx = ram.get_v_registers()[x as usize];
-                let constant = (op & 0x00FF) as u8;
-                if vx != constant {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF00F == 0x5000 => {
-                // Skip if VX == VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-                let vx = ram.get_v_registers()[x as usize];
-                let vy = ram.get_v_registers()[y as usize];
-                if vx == vy {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF00F == 0x9000 => {
-                // Skip if VX != VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-                let vx = ram.get_v_registers()[x as usize];
-                let vy = ram.get_v_registers()[y as usize];
-                if vx != vy {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF0FF == 0xE09E => {
-                // Skip if VX == Hex key (LSB)
-                let x = (op & 0x0F00) >> 8;
-                let vx = ram.get_v_registers()[x as usize];
-                let vx_lsb = vx & 0x0F;
-                let key: Option<u8> = Self::get_current_key_press(ram);
-                if key.is_some() && key.unwrap() == vx_lsb {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF0FF == 0xE0A1 => {
-                // Skip if VX != Hex key (LSB)
-                let x = (op & 0x0F00) >> 8;
-                let vx = ram.get_v_registers()[x as usize];
-                let vx_lsb = vx & 0x0F;
-                let key: Option<u8> = Self::get_current_key_press(ram);
-                if key.is_none() || key.unwrap() != vx_lsb {
-                    next_instruction_address = next_instruction_address.wrapping_add(2);
-                }
-            }
-            op if op & 0xF000 == 0x6000 => {
-                // Set VX = constant
-                let x = (op & 0x0F00) >> 8;
-                let constant = (op & 0x00FF) as u8;
+    /// Start counting how many times each address in the program area of
+    /// `ram` (0x200 - 0xE9F with 4K of RAM) is executed. See
+    /// [`Self::coverage`].
+    pub fn enable_coverage(&mut self, ram: &CosmacRAM) {
+        self.coverage = Some(vec![0; ram.program_max_size()]);
+    }
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = constant;
-            }
-            op if op & 0xF000 == 0xC000 => {
-                // Set VX = random bits.
-                let x = (op & 0x0F00) >> 8;
-                let mask = (op & 0x00FF) as u8;
+    /// Stop counting executed addresses and discard the counts.
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                let random_bits = self.rng.random_u8();
-                *vx = mask & random_bits;
-            }
-            op if op & 0xF000 == 0x7000 => {
-                // Set VX += constant
-                let x = (op & 0x0F00) >> 8;
-                let constant = (op & 0x00FF) as u8;
+    /// The number of times each address in the program area has been
+    /// executed since coverage was enabled or the interpreter was reset,
+    /// indexed from 0x200. Empty if coverage is disabled.
+    pub fn coverage(&self) -> &[u32] {
+        self.coverage.as_deref().unwrap_or_default()
+    }
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = vx.wrapping_add(constant);
+    /// The ranges of addresses in the program area that have never been
+    /// executed, e.g. dead code or data. Both bytes of an executed
+    /// instruction count as executed. Empty if coverage is disabled.
+    pub fn unexecuted_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let coverage = self.coverage();
+        for (offset, &count) in coverage.iter().enumerate() {
+            let executed = count > 0 || (offset > 0 && coverage[offset - 1] > 0);
+            if executed {
+                continue;
             }
-            op if op & 0xF00F == 0x8000 => {
-                // Set VX = VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = vy_val;
+            let address = PROGRAM_START_ADDRESS + offset;
+            match ranges.last_mut() {
+                Some(range) if range.end == address => range.end += 1,
+                _ => ranges.push(address..address + 1),
             }
-            op if op & 0xF00F == 0x8001 => {
-                // Set VX = VX | VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+        }
+        ranges
+    }
 
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx |= vy_val;
-            }
-            op if op & 0xF00F == 0x8002 => {
-                // Set VX = VX & VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+    /// Start recording the random numbers, key presses and ticks given to the
+    /// interpreter, replacing any recording in progress. Key presses are only
+    /// recorded when given through [`Self::handle_key`].
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+    }
 
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx &= vy_val;
-            }
-            op if op & 0xF00F == 0x8004 => {
-                // Set VX = VX + VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+    /// Stop recording, returning the recording if there was one in progress.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
 
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
+    /// Replay the key presses and ticks in `recording` over the next
+    /// [`Recording::steps`] steps, if any, ignoring the key presses and ticks given
+    /// by the host meanwhile. For an exact replay, the interpreter should use
+    /// the [`ReplayRng`] from [`Recording::replay_rng`] and start from the
+    /// same state as the recording did.
+    ///
+    /// [`ReplayRng`]: crate::recording::ReplayRng
+    pub fn start_replay(&mut self, recording: &Recording) {
+        self.replay = (recording.steps() > 0).then(|| Replay {
+            inputs: recording
+                .inputs()
+                .iter()
+                .filter(|(_, input)| !matches!(input, RecordedInput::Random(_)))
+                .copied()
+                .collect(),
+            step: 0,
+            steps: recording.steps(),
+        });
+    }
 
-                let (sum, carry) = vx.overflowing_add(vy_val);
-                *vx = sum;
+    /// Stop replaying a recording early.
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
 
-                let vf = &mut ram.get_v_registers_mut()[0xF];
-                *vf = if carry { 1 } else { 0 };
-            }
-            op if op & 0xF00F == 0x8005 => {
-                // Set VX = VX - VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+    /// Whether a recording is being replayed.
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
 
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
+    /// Record hex `key` as pressed or released, like [`Self::set_key_state`].
+    /// The key press is recorded when recording, and ignored while replaying
+    /// a recording.
+    pub fn handle_key(&mut self, ram: &mut CosmacRAM, key: u8, pressed: bool) {
+        if self.replay.is_some() {
+            return;
+        }
+        if let Some(recording) = &mut self.recording {
+            let key = key & 0x0F;
+            recording.push(RecordedInput::Key { key, pressed });
+        }
+        Self::set_key_state(ram, key, pressed);
+    }
 
-                let borrow = if *vx < vy_val { 0 } else { 1 };
-                *vx = vx.wrapping_sub(vy_val);
+    // Apply the recorded key presses and ticks that came before the current
+    // step.
+    fn apply_replay_inputs(&mut self, ram: &mut CosmacRAM) {
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        let mut inputs = Vec::new();
+        while let Some(&(_, input)) = replay.inputs.front().filter(|(s, _)| *s == replay.step) {
+            inputs.push(input);
+            replay.inputs.pop_front();
+        }
 
-                let vf = &mut ram.get_v_registers_mut()[0xF];
-                *vf = borrow;
+        for input in inputs {
+            match input {
+                RecordedInput::Key { key, pressed } => Self::set_key_state(ram, key, pressed),
+                RecordedInput::Tick => self.count_down_timers(ram),
+                RecordedInput::Random(_) => (),
             }
-            op if op & 0xF0FF == 0xF007 => {
-                // Set VX = timer
-                let x = (op & 0x0F00) >> 8;
-                let timer = ram.get_u16_at(TIMER_ADDRESS);
+        }
+    }
+
+    /// Keep the instructions decoded by each step in a cache, so that running
+    /// them again skips decoding. Instructions overwritten by the program are
+    /// removed from the cache, but the host must call
+    /// [`Self::invalidate_decode_cache`] after writing to the program itself,
+    /// other than by loading a program and resetting.
+    pub fn enable_decode_cache(&mut self) {
+        self.decode_cache = Some(DecodeCache {
+            instructions: vec![None; MEMORY_SIZE / 2],
+            stats: DecodeCacheStats::default(),
+        });
+    }
+
+    /// Stop caching decoded instructions.
+    pub fn disable_decode_cache(&mut self) {
+        self.decode_cache = None;
+    }
+
+    /// Remove all instructions from the decode cache.
+    pub fn invalidate_decode_cache(&mut self) {
+        if let Some(cache) = &mut self.decode_cache {
+            cache.instructions.fill(None);
+        }
+    }
+
+    /// Hits and misses of the decode cache since it was enabled. All zero if
+    /// the decode cache is disabled.
+    pub fn decode_cache_stats(&self) -> DecodeCacheStats {
+        self.decode_cache
+            .as_ref()
+            .map(|cache| cache.stats)
+            .unwrap_or_default()
+    }
+
+    // Decode the instruction at `address`, using the decode cache if enabled.
+    // Fails if the instruction runs past the end of RAM.
+    fn decode(&mut self, ram: &CosmacRAM, address: usize) -> Result<DecodedInstruction> {
+        let (schip_opcodes, xo_chip_opcodes) = (self.schip_opcodes, self.xo_chip_opcodes);
+        let decode = || {
+            let instruction = ram
+                .try_get_u16_at(address)
+                .map_err(|_| Error::ProgramCounterOutOfRange(address as u16))?;
+            Ok(DecodedInstruction {
+                instruction,
+                opcode: Opcode::decode(instruction, schip_opcodes, xo_chip_opcodes),
+                family: opcode_family(instruction),
+            })
+        };
+
+        let Some(cache) = &mut self.decode_cache else {
+            return decode();
+        };
+        if address & 1 != 0 {
+            return decode();
+        }
+        match cache.instructions.get_mut(address / 2) {
+            Some(Some(decoded)) => {
+                cache.stats.hits += 1;
+                Ok(*decoded)
+            }
+            Some(entry) => {
+                let decoded = decode()?;
+                cache.stats.misses += 1;
+                Ok(*entry.insert(decoded))
+            }
+            None => decode(),
+        }
+    }
+
+    // Remove the instructions overlapping `range` from the decode cache.
+    fn invalidate_decoded(&mut self, range: Range<usize>) {
+        let Some(cache) = &mut self.decode_cache else {
+            return;
+        };
+        // an instruction at an even address before the range overlaps it
+        for index in range.start / 2..range.end.div_ceil(2).min(cache.instructions.len()) {
+            if cache.instructions[index].take().is_some() {
+                cache.stats.invalidations += 1;
+            }
+        }
+    }
+
+    /// Keep a separate copy of the display for frontends to render from, which
+    /// is only updated by [`Self::present`] and [`Self::tick_60hz`]. Programs
+    /// that erase and redraw sprites then don't flicker, no matter which step
+    /// the frontend renders after.
+    ///
+    /// See [`Self::display_buffer_for_render`].
+    pub fn enable_double_buffering(&mut self, ram: &CosmacRAM) {
+        self.presented_frame = Some(PresentedFrame::new(ram));
+    }
+
+    /// Render straight from the display in `ram` again.
+    pub fn disable_double_buffering(&mut self) {
+        self.presented_frame = None;
+    }
+
+    /// Copy the display in `ram` to the presented frame, if double buffering
+    /// is enabled.
+    pub fn present(&mut self, ram: &CosmacRAM) {
+        if let Some(frame) = &mut self.presented_frame {
+            *frame = PresentedFrame::new(ram);
+        }
+    }
+
+    /// The display buffer of the first display plane that frontends should
+    /// render. This is the presented frame if double buffering is enabled,
+    /// otherwise the display in `ram`.
+    pub fn display_buffer_for_render<'a>(&'a self, ram: &'a CosmacRAM) -> &'a [u8] {
+        self.display_plane_for_render(ram, 0)
+    }
+
+    /// The display buffer of a display plane that frontends should render.
+    ///
+    /// # Panics
+    /// Panics if `plane >= NUM_DISPLAY_PLANES`.
+    pub fn display_plane_for_render<'a>(&'a self, ram: &'a CosmacRAM, plane: usize) -> &'a [u8] {
+        match &self.presented_frame {
+            Some(frame) => &frame.planes[plane],
+            None => ram.display_plane(plane),
+        }
+    }
+
+    /// The dimensions of the display that frontends should render.
+    pub fn display_size_for_render(&self, ram: &CosmacRAM) -> DisplaySize {
+        match &self.presented_frame {
+            Some(frame) => frame.display_size,
+            None => ram.display_size(),
+        }
+    }
+
+    /// Take a snapshot of `ram` and the interpreter, which can be restored
+    /// with [`Self::load_state`].
+    pub fn save_state(&self, ram: &CosmacRAM) -> SaveState {
+        let [timer, tone_timer] = [
+            (TIMER_OFFSET, self.timer_expiry),
+            (TONE_TIMER_OFFSET, self.tone_expiry),
+        ]
+        .map(|(offset, expiry)| match (self.timer_mode, expiry) {
+            (TimerMode::WallClock, Some(expiry)) => {
+                Self::jiffies_in(expiry.saturating_duration_since(Instant::now()))
+            }
+            _ => ram.get_work_area_u16(offset),
+        });
+
+        SaveState {
+            ram: ram.snapshot(),
+            timer,
+            tone_timer,
+            drawn_since_tick: self.drawn_since_tick,
+            rpl_flags: self.rpl_flags,
+            audio_pattern: self.audio_pattern,
+            audio_pitch: self.audio_pitch,
+            rom_crc32: None,
+        }
+    }
+
+    /// Restore `ram` and the interpreter to a snapshot taken by
+    /// [`Self::save_state`]. Timers that were running carry on counting down
+    /// from where they were when the snapshot was taken.
+    ///
+    /// # Errors
+    /// Returns [`Error::MemoryImageSizeMismatch`] if the snapshot was taken of
+    /// RAM of a different size to `ram`. When this occurs nothing is changed.
+    pub fn load_state(&mut self, ram: &mut CosmacRAM, state: &SaveState) -> Result<()> {
+        ram.restore(&state.ram)?;
+
+        ram.set_work_area_u16(TIMER_OFFSET, state.timer);
+        ram.set_work_area_u16(TONE_TIMER_OFFSET, state.tone_timer);
+        self.start_wall_clock_timers(ram);
+        self.last_draw = None;
+        self.drawn_since_tick = state.drawn_since_tick;
+        self.rpl_flags = state.rpl_flags;
+        self.audio_pattern = state.audio_pattern;
+        self.audio_pitch = state.audio_pitch;
+        self.invalidate_decode_cache();
+        self.present(ram);
+        Ok(())
+    }
+
+    /// The SCHIP RPL user flags, as saved by FX75.
+    pub fn rpl_flags(&self) -> &[u8; NUM_RPL_FLAGS] {
+        &self.rpl_flags
+    }
+
+    /// Set the SCHIP RPL user flags, e.g. to restore flags persisted by the
+    /// host from a previous run.
+    pub fn set_rpl_flags(&mut self, flags: &[u8; NUM_RPL_FLAGS]) {
+        self.rpl_flags = *flags;
+    }
+
+    /// The XO-CHIP audio pattern loaded by F002, or `None` if the program
+    /// hasn't loaded one and the plain tone should be used. Each bit is a
+    /// sample, played most significant bit first.
+    pub fn audio_pattern(&self) -> Option<&[u8; AUDIO_PATTERN_SIZE]> {
+        self.audio_pattern.as_ref()
+    }
+
+    /// The rate, in samples per second, that the audio pattern should be
+    /// played back at for the pitch set by FX3A.
+    pub fn audio_playback_rate_hz(&self) -> f32 {
+        4000.0 * 2f32.powf((self.audio_pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Reset the interpreter and the interpreter areas of `ram`, ready to run
+    /// the loaded program from the start.
+    ///
+    /// A replay in progress is stopped, as it no longer matches the state of
+    /// the program, and a recording in progress starts over, so that it
+    /// replays from the reset.
+    pub fn reset(&mut self, ram: &mut CosmacRAM) {
+        // reset all CHIP-8 interpreter state
+        ram.zero_out_range(ram.stack_start_address()..ram.memory_size())
+            .expect("Should be ok to zero out this memory");
+        self.load_fonts(ram);
+
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, self.program_entry_address as u16);
+        ram.set_work_area_u16(STACK_POINTER_OFFSET, ram.stack_start_address() as u16);
+
+        // the extra display planes live outside of the address space zeroed above
+        ram.set_display_size(self.initial_display_size);
+        ram.set_work_area_u16(PLANE_MASK_OFFSET, PLANE_MASK_DEFAULT);
+
+        // reset the interpreter state that lives outside of RAM, except for
+        // the RPL user flags which survive between runs
+        self.timer_expiry = None;
+        self.tone_expiry = None;
+        self.last_draw = None;
+        self.drawn_since_tick = false;
+        self.audio_pattern = None;
+        self.audio_pitch = DEFAULT_AUDIO_PITCH;
+        if let Some(stats) = &mut self.stats {
+            *stats = ExecutionStats::default();
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.fill(0);
+        }
+        self.replay = None;
+        if self.recording.is_some() {
+            self.recording = Some(Recording::new());
+        }
+        self.invalidate_decode_cache();
+        self.present(ram);
+    }
+
+    fn load_fonts(&self, ram: &mut CosmacRAM) {
+        ram.load_bytes_unprotected(&CHARACTER_BYTES, CHARACTER_BYTES_ADDRESS)
+            .expect("Should be ok to load font data data in low memory.");
+        ram.load_bytes_unprotected(&CHARACTER_MAP, CHARACTER_MAP_ADDRESS)
+            .expect("Should be ok to load character map in low memory.");
+        if self.schip_opcodes {
+            ram.load_bytes_unprotected(&BIG_CHARACTER_BYTES, BIG_CHARACTER_BYTES_ADDRESS)
+                .expect("Should be ok to load big font data in low memory.");
+        }
+    }
+
+    /// Execute the current CHIP-8 instruction, determined by the internal
+    /// CHIP-8 program counter, and advance the program counter to point to the
+    /// next instruction to execute.
+    ///
+    /// Instructions that have to wait (FX0A for a key, or DXYN for the display
+    /// with the `display_wait` quirk) leave the program counter where it is, so
+    /// calling `step` again retries them.
+    ///
+    /// Returns a [`StepOutcome`] saying what the instruction did, so that
+    /// frontends know when to redraw the display.
+    ///
+    /// Memory read and written through `I` goes through `bus`, which is
+    /// usually just the [`CosmacRAM`], see [`MemoryBus`]. The instruction,
+    /// registers and stack are read and written in the RAM behind it.
+    ///
+    /// # Errors
+    /// Returns [`Error::MemoryOutOfBounds`] if the instruction would read or
+    /// write memory past the end of RAM through `I` (DXYN, FX33, FX55, FX65 and
+    /// F002). The instruction is not executed and the program counter is left
+    /// on it.
+    ///
+    /// Returns [`Error::MachineLanguageCallUnsupported`] for a 0MMM instruction,
+    /// unless machine language calls are set to
+    /// [`MachineLanguageCalls::Ignore`]. The program counter is left on the
+    /// instruction.
+    ///
+    /// Returns [`Error::UnknownInstruction`] for an instruction that isn't
+    /// part of the instruction set, [`Error::Chip8StackOverflow`] for a call
+    /// with no room left on the stack, and [`Error::Chip8StackUnderflow`] for
+    /// a return when not in a subroutine, leaving the program counter on the
+    /// instruction.
+    ///
+    /// Returns [`Error::ProgramCounterOutOfRange`] if the instruction at the
+    /// program counter would run past the end of RAM, or would leave the
+    /// program counter outside of the program area, and
+    /// [`Error::IAddressOutOfRange`] if it would leave `I` outside of the
+    /// memory CHIP-8 programs may use, without running it.
+    pub fn step<B: MemoryBus>(&mut self, bus: &mut B) -> Result<StepOutcome> {
+        self.apply_replay_inputs(bus.ram_mut());
+        if let Some(hook) = &mut self.pre_step_hook {
+            hook(&Self::state(bus.ram()));
+        }
+        let registers_before = self
+            .trace
+            .is_some()
+            .then(|| TracedRegisters::new(bus.ram()));
+        let result = self.execute_instruction(bus);
+        let ram = bus.ram_mut();
+        if let Some(registers_before) = registers_before {
+            self.write_trace_line(ram, &registers_before);
+        }
+        if let Some(hook) = &mut self.post_step_hook {
+            hook(&Self::state(ram));
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.end_step();
+        }
+        if let Some(replay) = &mut self.replay {
+            replay.step += 1;
+            if replay.step == replay.steps {
+                // inputs given after the last recorded step
+                self.apply_replay_inputs(ram);
+                self.replay = None;
+            }
+        }
+        result
+    }
+
+    // Write what is left of the timers running against the host clock to RAM.
+    fn sync_wall_clock_timers(&mut self, ram: &mut CosmacRAM) {
+        if let Some(expiry) = self.timer_expiry {
+            let now = Instant::now();
+            let jiffies_left = if expiry <= now {
+                self.timer_expiry = None;
+                0
+            } else {
+                Self::jiffies_in(expiry - now)
+            };
+            ram.set_work_area_u16(TIMER_OFFSET, jiffies_left);
+        }
+
+        if let Some(expiry) = self.tone_expiry {
+            let now = Instant::now();
+            let jiffies_left = if expiry <= now {
+                self.tone_expiry = None;
+                0
+            } else {
+                Self::jiffies_in(expiry - now)
+            };
+            ram.set_work_area_u16(TONE_TIMER_OFFSET, jiffies_left);
+        }
+    }
+
+    // Start the timers in RAM counting down against the host clock from now,
+    // or stop them if the timers are ticked.
+    fn start_wall_clock_timers(&mut self, ram: &CosmacRAM) {
+        let now = Instant::now();
+        [self.timer_expiry, self.tone_expiry] = [TIMER_OFFSET, TONE_TIMER_OFFSET].map(|offset| {
+            let jiffies = ram.get_work_area_u16(offset);
+            (self.timer_mode == TimerMode::WallClock && jiffies > 0)
+                .then(|| now + Self::jiffies_duration(jiffies as u8))
+        });
+    }
+
+    // Instructions that read or write memory through `I` go through `bus`,
+    // so they re-borrow `ram` from it afterwards.
+    fn execute_instruction<B: MemoryBus>(&mut self, bus: &mut B) -> Result<StepOutcome> {
+        let ram = bus.ram_mut();
+        let instruction_address = ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET) as usize;
+
+        self.sync_wall_clock_timers(ram);
+
+        let hex_key_status = ram.get_work_area_u16(HEX_KEY_STATUS_OFFSET);
+        if hex_key_status & HEX_KEY_WAIT_FLAG != 0 {
+            // FX0A instruction
+            // waiting for a key to be pressed and released
+            let mut hex_key_status = hex_key_status;
+            let mut key_taken = false;
+            let mut key_released = false;
+            for event in Self::drain_key_events(ram) {
+                let key = event & KEY_EVENT_KEY_MASK;
+                let pressed = event & KEY_EVENT_PRESSED_FLAG != 0;
+                if hex_key_status & HEX_KEY_SEEN_WHILE_WAITING_FLAG == 0 {
+                    // the first key pressed following wait is the one FX0A takes
+                    if pressed {
+                        hex_key_status &= !HEX_KEY_LAST_PRESSED_MASK;
+                        hex_key_status |= HEX_KEY_SEEN_WHILE_WAITING_FLAG | key as u16;
+                        key_taken = true;
+                    }
+                } else if !pressed && key as u16 == hex_key_status & HEX_KEY_LAST_PRESSED_MASK {
+                    key_released = true;
+                    break;
+                }
+            }
+
+            let mut watchpoint = None;
+            if key_taken {
+                // key pressed following wait, update the VX register latched
+                // by the FX0A instruction. This is a write watchpoints see.
+                let x = VReg::from(ram.get_work_area_u16(HEX_KEY_WAIT_REGISTER_OFFSET) as u8);
+                let key = (hex_key_status & HEX_KEY_LAST_PRESSED_MASK) as u8;
+
+                ram.set_v(x, key);
+                let vx = Self::v_register_range(ram, x.index()..=x.index());
+                watchpoint = self.first_watched_access(&[(vx, Watch::Write)]);
+            }
+
+            // sound the tone while the key is held, without touching the tone timer
+            let key_held = hex_key_status & HEX_KEY_SEEN_WHILE_WAITING_FLAG != 0 && !key_released;
+            if key_held && self.quirks.key_wait_tone {
+                hex_key_status |= HEX_KEY_TONE_FLAG;
+            } else {
+                hex_key_status &= !HEX_KEY_TONE_FLAG;
+            }
+
+            if key_released {
+                // seen key pressed and released following wait
+
+                // reset flags
+                hex_key_status &= !(HEX_KEY_WAIT_FLAG | HEX_KEY_SEEN_WHILE_WAITING_FLAG);
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = (timer & 0xFF) as u8;
+                // complete FX0A instruction
+                let next_instruction_address = instruction_address.wrapping_add(2);
+                ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, next_instruction_address as u16);
+            }
+            ram.set_work_area_u16(HEX_KEY_STATUS_OFFSET, hex_key_status);
+            return Ok(if let Some((address, kind)) = watchpoint {
+                StepOutcome::Watchpoint {
+                    address,
+                    pc: instruction_address as u16,
+                    kind,
+                }
+            } else if key_released {
+                StepOutcome::Executed
+            } else {
+                StepOutcome::WaitingForKey
+            });
+        }
+
+        let DecodedInstruction {
+            instruction,
+            opcode,
+            family,
+        } = self.decode(ram, instruction_address)?;
+        // where the instruction leaves the program counter, and I if it
+        // moves it, is checked before it runs, so one out of range changes
+        // nothing
+        let (mut next_instruction_address, next_i) =
+            self.next_addresses(ram, opcode, instruction, instruction_address)?;
+        check_pc_address_in_chip8_program_range(
+            ram,
+            next_instruction_address as u16,
+            self.program_start_address,
+        )?;
+        if next_i != ram.get_work_area_u16(I_OFFSET) {
+            check_i_address_in_bounds(ram, next_i, self.quirks.i_overflow_sets_vf)?;
+        }
+        let mut outcome = StepOutcome::Executed;
+        // memory accessed by the instruction, for watchpoints and the decode
+        // cache
+        let mut accesses = MemoryAccesses {
+            tracked: !self.watchpoints.is_empty() || self.decode_cache.is_some(),
+            accesses: Vec::new(),
+        };
+
+        if let Some(stats) = &mut self.stats {
+            stats.instructions += 1;
+            *stats.opcode_counts.entry(family).or_default() += 1;
+        }
+        if let Some(coverage) = &mut self.coverage {
+            // addresses outside of the program area aren't counted
+            if let Some(count) =
+                coverage.get_mut(instruction_address.wrapping_sub(PROGRAM_START_ADDRESS))
+            {
+                *count += 1;
+            }
+        }
+
+        let op = instruction;
+        match opcode {
+            Opcode::Nop => {
+                // NOOP
+            }
+            Opcode::Jump => {
+                // Unconditional jump
+                if next_instruction_address == instruction_address {
+                    outcome = StepOutcome::Halted;
+                }
+            }
+            Opcode::JumpWithOffset
+            | Opcode::SkipIfEqualConstant
+            | Opcode::SkipIfNotEqualConstant
+            | Opcode::SkipIfEqual
+            | Opcode::SkipIfNotEqual
+            | Opcode::SkipIfKey
+            | Opcode::SkipIfNotKey => {
+                // Jump with offset, or skip the next instruction, only moving
+                // the program counter
+            }
+            Opcode::Call => {
+                // Execute subroutine
+                check_chip8_stack_not_full(ram)?;
+
+                let caller_address = ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET);
+
+                // Push where we are jumping from onto the stack
+                let sp = ram.get_work_area_u16(STACK_POINTER_OFFSET);
+                ram.set_u16_at(sp as usize, caller_address);
+                ram.set_work_area_u16(STACK_POINTER_OFFSET, sp + 2);
+
+                // Jump
+                if next_instruction_address == instruction_address {
+                    outcome = StepOutcome::Halted;
+                }
+            }
+            Opcode::Return => {
+                // Return from subroutine, popping the return address off the
+                // stack
+                let sp = ram.get_work_area_u16(STACK_POINTER_OFFSET) - 2;
+                ram.set_work_area_u16(STACK_POINTER_OFFSET, sp);
+            }
+            Opcode::SetConstant => {
+                // Set VX = constant
+                let x = VReg::from((op >> 8) as u8);
+                let constant = (op & 0x00FF) as u8;
+
+                ram.set_v(x, constant);
             }
-            op if op & 0xF0FF == 0xF00A => {
+            Opcode::SetRandom => {
+                // Set VX = random bits.
+                let x = VReg::from((op >> 8) as u8);
+                let mask = (op & 0x00FF) as u8;
+
+                let random_bits = self.rng.random_u8();
+                if let Some(recording) = &mut self.recording {
+                    recording.push(RecordedInput::Random(random_bits));
+                }
+                ram.set_v(x, mask & random_bits);
+            }
+            Opcode::AddConstant => {
+                // Set VX += constant
+                let x = VReg::from((op >> 8) as u8);
+                let constant = (op & 0x00FF) as u8;
+
+                ram.set_v(x, ram.v(x).wrapping_add(constant));
+            }
+            Opcode::Set => {
+                // Set VX = VY
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
+
+                let vy_val = ram.v(y);
+                ram.set_v(x, vy_val);
+            }
+            Opcode::Or => {
+                // Set VX = VX | VY
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
+
+                let vy_val = ram.v(y);
+                ram.set_v(x, ram.v(x) | vy_val);
+
+                if self.quirks.vf_reset_on_logic {
+                    ram.set_vf(0);
+                }
+            }
+            Opcode::And => {
+                // Set VX = VX & VY
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
+
+                let vy_val = ram.v(y);
+                ram.set_v(x, ram.v(x) & vy_val);
+
+                if self.quirks.vf_reset_on_logic {
+                    ram.set_vf(0);
+                }
+            }
+            Opcode::Add => {
+                // Set VX = VX + VY, VF set to carry bit
+                // Both operands are read before anything is written, and VF is
+                // written last, so the flag wins when X is F. The same goes for
+                // 8XY5 and 8XY7.
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
+
+                let vx_val = ram.v(x);
+                let vy_val = ram.v(y);
+                let (sum, carry) = vx_val.overflowing_add(vy_val);
+
+                ram.set_v(x, sum);
+                ram.set_vf(if carry { 1 } else { 0 });
+            }
+            Opcode::Subtract => {
+                // Set VX = VX - VY, VF set to borrow bit
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
+
+                let vx_val = ram.v(x);
+                let vy_val = ram.v(y);
+                let borrow = if vx_val < vy_val { 0 } else { 1 };
+
+                ram.set_v(x, vx_val.wrapping_sub(vy_val));
+                ram.set_vf(borrow);
+            }
+            Opcode::GetTimer => {
+                // Set VX = timer
+                let x = VReg::from((op >> 8) as u8);
+                let timer = ram.get_work_area_u16(TIMER_OFFSET);
+
+                ram.set_v(x, (timer & 0xFF) as u8);
+            }
+            Opcode::WaitForKey => {
                 // Set VX = hex key digit (wait for key press)
-                let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-                ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, hex_key_status | HEX_KEY_WAIT_FLAG);
+                let x = (op & 0x0F00) >> 8;
+                ram.set_work_area_u16(HEX_KEY_WAIT_REGISTER_OFFSET, x);
+                let hex_key_status = ram.get_work_area_u16(HEX_KEY_STATUS_OFFSET);
+                ram.set_work_area_u16(HEX_KEY_STATUS_OFFSET, hex_key_status | HEX_KEY_WAIT_FLAG);
+                // only keys pressed from now on count
+                ram.set_work_area_u16(KEY_EVENT_COUNT_OFFSET, 0);
 
-                // since program counter was advanced at the beginning of the function,
-                // we need to put it back.
-                next_instruction_address = instruction_address;
+                outcome = StepOutcome::WaitingForKey;
             }
-            op if op & 0xF0FF == 0xF015 => {
+            Opcode::SetTimer => {
                 // Set timer = VX (01 = 1/60 seconds)
-                let x = (op & 0x0F00) >> 8;
-                let jiffies = ram.get_v_registers()[x as usize];
+                let x = VReg::from((op >> 8) as u8);
+                let jiffies = ram.v(x);
 
-                self.timer_expiry =
-                    Some(Instant::now() + Duration::from_millis((jiffies as u64 * 1000) / 60));
-                ram.set_u16_at(TIMER_ADDRESS, jiffies as u16);
+                if self.timer_mode == TimerMode::WallClock {
+                    self.timer_expiry = Some(Instant::now() + Self::jiffies_duration(jiffies));
+                }
+                ram.set_work_area_u16(TIMER_OFFSET, jiffies as u16);
             }
-            op if op & 0xF0FF == 0xF018 => {
+            Opcode::SetToneTimer => {
                 // Set tone duration = VX (01 = 1/60 seconds)
-                let x = (op & 0x0F00) >> 8;
-                let jiffies = ram.get_v_registers()[x as usize];
+                let x = VReg::from((op >> 8) as u8);
+                let jiffies = ram.v(x);
+                let was_sounding = Self::is_tone_sounding(ram);
 
-                self.tone_expiry =
-                    Some(Instant::now() + Duration::from_millis((jiffies as u64 * 1000) / 60));
-                ram.set_u16_at(TONE_TIMER_ADDRESS, jiffies as u16);
+                if self.timer_mode == TimerMode::WallClock {
+                    self.tone_expiry = Some(Instant::now() + Self::jiffies_duration(jiffies));
+                }
+                ram.set_work_area_u16(TONE_TIMER_OFFSET, jiffies as u16);
+                if !was_sounding && Self::is_tone_sounding(ram) {
+                    outcome = StepOutcome::SoundStarted;
+                }
             }
-            op if op & 0xF000 == 0xA000 => {
+            Opcode::SetI => {
                 // Set I = 0MMM
-                let dest = op & 0x0FFF;
-                ram.set_u16_at(I_ADDRESS, dest);
+                ram.set_work_area_u16(I_OFFSET, next_i);
             }
-            op if op & 0xF0FF == 0xF01E => {
+            Opcode::AddToI => {
                 // Set I = I + VX
-                let x = (op & 0x0F00) >> 8;
-                let vx_val = ram.get_v_registers()[x as usize];
-
-                let i_val = ram.get_u16_at(I_ADDRESS);
-                ram.set_u16_at(I_ADDRESS, i_val.wrapping_add(vx_val as u16));
+                // quirk: Amiga sets VF = 1 if I goes past 0x0FFF, 0 otherwise
+                let x = VReg::from((op >> 8) as u8);
+                let i_val = ram
+                    .get_work_area_u16(I_OFFSET)
+                    .wrapping_add(ram.v(x) as u16);
+
+                ram.set_work_area_u16(I_OFFSET, next_i);
+                if self.quirks.i_overflow_sets_vf {
+                    ram.set_vf(if i_val > 0x0FFF { 1 } else { 0 });
+                }
             }
-            op if op & 0xF0FF == 0xF029 => {
+            Opcode::SetIToFont => {
                 // Set I = Address of 5-byte display pattern for LSD of VX
-                let x = (op & 0x0F00) >> 8;
-                let vx_val = ram.get_v_registers()[x as usize];
+                let x = VReg::from((op >> 8) as u8);
+                let vx_val = ram.v(x);
                 let hex_val = vx_val & 0x0F; // LSB of VX
 
                 let hex_glyph_address = ram.bytes()[CHARACTER_MAP_ADDRESS + hex_val as usize];
-                ram.set_u16_at(I_ADDRESS, hex_glyph_address as u16);
+                ram.set_work_area_u16(I_OFFSET, hex_glyph_address as u16);
             }
-            op if op & 0xF0FF == 0xF033 => {
+            Opcode::StoreDecimal => {
                 // Set MI = 3-decimal digit equivalent of VX (I unchanged)
-                let x = (op & 0x0F00) >> 8;
-                let mut vx_val = ram.get_v_registers()[x as usize];
+                let x = VReg::from((op >> 8) as u8);
+                let mut vx_val = ram.v(x);
 
                 let mut decimal_digits = [0u8; 3];
                 decimal_digits[0] = vx_val / 100;
@@ -443,244 +1606,778 @@ impl<T: Chip8Rng> Chip8Interpreter<T> {
                 vx_val -= decimal_digits[1] * 10;
                 decimal_digits[2] = vx_val;
 
-                let i_data = ram.get_u16_at(I_ADDRESS);
-                ram.load_bytes(&decimal_digits, i_data as usize)
-                    .expect("I register should point to valid memory location");
+                let i_data = ram.get_work_area_u16(I_OFFSET);
+                Self::check_i_range(ram, i_data as usize, decimal_digits.len())?;
+                accesses.push((
+                    Self::v_register_range(ram, x.index()..=x.index()),
+                    Watch::Read,
+                ));
+                let i_range = i_data as usize..i_data as usize + decimal_digits.len();
+                ram.check_write_protection(i_range.clone())?;
+                accesses.push((i_range.clone(), Watch::Write));
+                for (address, digit) in i_range.zip(decimal_digits) {
+                    bus.write(address, digit);
+                }
             }
-            op if op & 0xF0FF == 0xF055 => {
+            Opcode::Store => {
                 // Set MI = V0 : VX, I = I + X + 1
+                // quirk: CHIP-48 sets I = I + X, SCHIP leaves I unchanged
                 let x = (op & 0x0F00) >> 8;
-                let i = ram.get_u16_at(I_ADDRESS);
-
-                for x in 0..=x as usize {
-                    let vx_val = ram.get_v_registers()[x];
-                    ram.load_bytes(&[vx_val], i as usize + x)
-                        .expect("I register should point to valid memory location");
+                let i = ram.get_work_area_u16(I_OFFSET);
+                Self::check_i_range(ram, i as usize, x as usize + 1)?;
+                accesses.push((Self::v_register_range(ram, 0..=x as usize), Watch::Read));
+                let i_range = i as usize..i as usize + x as usize + 1;
+                ram.check_write_protection(i_range.clone())?;
+                accesses.push((i_range.clone(), Watch::Write));
+
+                let v_registers = ram.get_v_registers()[..=x as usize].to_vec();
+                for (address, value) in i_range.zip(v_registers) {
+                    bus.write(address, value);
                 }
 
-                ram.set_u16_at(I_ADDRESS, i + x + 1);
+                bus.ram_mut().set_work_area_u16(I_OFFSET, next_i);
             }
-            op if op & 0xF0FF == 0xF065 => {
+            Opcode::Load => {
                 // Set V0 : VX = MI, I = I + X + 1
+                // quirk: CHIP-48 sets I = I + X, SCHIP leaves I unchanged
                 let x = (op & 0x0F00) >> 8;
-                let i = ram.get_u16_at(I_ADDRESS);
-
-                for x in 0..=x as usize {
-                    let val = ram.bytes()[i as usize + x];
-                    ram.get_v_registers_mut()[x] = val;
-                }
-
-                ram.set_u16_at(I_ADDRESS, i + x + 1);
+                let i = ram.get_work_area_u16(I_OFFSET);
+                Self::check_i_range(ram, i as usize, x as usize + 1)?;
+                accesses.push((i as usize..i as usize + x as usize + 1, Watch::Read));
+                accesses.push((Self::v_register_range(ram, 0..=x as usize), Watch::Write));
+
+                let values: Vec<u8> = (0..=x as usize)
+                    .map(|offset| bus.read(i as usize + offset))
+                    .collect();
+
+                let ram = bus.ram_mut();
+                ram.get_v_registers_mut()[..=x as usize].copy_from_slice(&values);
+                ram.set_work_area_u16(I_OFFSET, next_i);
             }
-            op if op == 0x00E0 => {
-                // Erase the display buffer
-                ram.zero_out_range(
-                    DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256,
-                )
-                .expect("Zeroing the display buffer should be ok");
+            Opcode::ClearDisplay => {
+                // Erase the display buffer (XO-CHIP: only the selected planes)
+                for plane in Self::selected_planes(ram) {
+                    ram.display_plane_mut(plane).fill(0);
+                }
+                outcome = StepOutcome::ClearedDisplay;
+                if let Some(range) = Self::display_refresh_range_in_use(ram) {
+                    accesses.push((range, Watch::Write));
+                }
             }
-            op if op & 0xF000 == 0xD000 => {
+            Opcode::Draw => {
                 // DXYN instruction: show sprite pointed to by I at VX-VY coordinates
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+                // SCHIP: DXY0 shows a 16x16 sprite, stored as 2 bytes per row
+                // XO-CHIP: the sprite is drawn to each selected plane in turn,
+                // with the data for each plane following on from the last.
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
                 let n = (op & 0x000F) as u8;
-                let i = ram.get_u16_at(I_ADDRESS);
-
-                let pixel_col = ram.get_v_registers()[x as usize];
-                let pixel_row = ram.get_v_registers()[y as usize];
-
-                let byte_col = pixel_col / 8;
-                let pixel_col_offset = pixel_col % 8;
-                let byte_row = pixel_row;
-
-                let mut pixel_collision = false;
-                let mut current_display_byte_address =
-                    DISPLAY_REFRESH_START_ADDRESS + (byte_row as usize * 8) + byte_col as usize;
-                if pixel_row < 32 && pixel_col < 64 {
-                    for sprite_row in 0..n {
-                        if current_display_byte_address > DISPLAY_REFRESH_LAST_ADDRESS {
-                            break;
+                let i = ram.get_work_area_u16(I_OFFSET) as usize;
+                let (sprite_rows, sprite_row_bytes) = if n == 0 && self.schip_opcodes {
+                    (16, 2)
+                } else {
+                    (n, 1)
+                };
+                let sprite_size = sprite_rows as usize * sprite_row_bytes as usize;
+
+                // quirk: wait for the display refresh. Until a jiffy has
+                // passed since the last draw, leave the PC on this instruction.
+                let now = Instant::now();
+                let display_wait = self.quirks.display_wait
+                    && match self.timer_mode {
+                        TimerMode::WallClock => {
+                            matches!(self.last_draw, Some(last_draw) if now < last_draw + JIFFY)
                         }
+                        TimerMode::Ticked => self.drawn_since_tick,
+                    };
+                if display_wait {
+                    next_instruction_address = instruction_address;
+                } else {
+                    let pixel_col = ram.v(x);
+                    let pixel_row = ram.v(y);
+
+                    let num_planes = Self::selected_planes(ram).count();
+                    Self::check_i_range(ram, i, sprite_size * num_planes)?;
+                    accesses.push((i..i + sprite_size * num_planes, Watch::Read));
+                    if let Some(range) = Self::display_refresh_range_in_use(ram) {
+                        accesses.push((range, Watch::Write));
+                    }
+                    accesses.push((Self::v_register_range(ram, 0xF..=0xF), Watch::Write));
+
+                    let sprites: Vec<u8> = (i..i + sprite_size * num_planes)
+                        .map(|address| bus.read(address))
+                        .collect();
+                    let ram = bus.ram_mut();
+
+                    let mut pixels_drawn = 0;
+                    let mut collided_rows = 0;
+                    let mut clipped_rows = 0;
+                    let planes: Vec<usize> = Self::selected_planes(ram).collect();
+                    // a sprite of size zero has no rows to draw to any plane
+                    let plane_sprites = sprites.chunks(sprite_size.max(1));
+                    for (plane, sprite) in planes.into_iter().zip(plane_sprites) {
+                        let drawn = Self::draw_sprite(
+                            &mut ram.display_plane_view_mut(plane),
+                            sprite,
+                            sprite_row_bytes,
+                            pixel_col,
+                            pixel_row,
+                            self.quirks.sprite_wrapping,
+                        );
+                        pixels_drawn += drawn.pixels_drawn;
+                        collided_rows |= drawn.collided_rows;
+                        clipped_rows |= drawn.clipped_rows;
+                    }
+                    let pixel_collision = collided_rows != 0;
+                    // quirk: SCHIP counts the rows that collided or were
+                    // clipped, on any of the selected planes
+                    let vf = if self.quirks.hires_vf_counts_rows
+                        && ram.display_size() == HIRES_DISPLAY_SIZE
+                    {
+                        (collided_rows.count_ones() + clipped_rows.count_ones()) as u8
+                    } else {
+                        pixel_collision as u8
+                    };
+                    ram.set_vf(vf);
+                    if let Some(stats) = &mut self.stats {
+                        stats.pixels_drawn += pixels_drawn as u64;
+                        stats.collisions += pixel_collision as u64;
+                    }
+                    self.last_draw = Some(now);
+                    self.drawn_since_tick = true;
+                    outcome = StepOutcome::DrewToDisplay;
+                }
+            }
 
-                        // split the 8 pixels of the current row of the sprite into two
-                        // bytes aligned with the display buffer
-                        let sprite_pixel_row = ram.bytes()[(i + sprite_row as u16) as usize];
-                        let left_byte_pixels = sprite_pixel_row >> pixel_col_offset;
-                        let mut left_byte = ram.bytes()[current_display_byte_address];
-                        if (left_byte_pixels & left_byte) != 0 {
-                            pixel_collision = true;
-                        }
-                        left_byte ^= left_byte_pixels;
-                        ram.load_bytes(&[left_byte], current_display_byte_address)
-                            .expect(
-                                "Loading bytes into the display buffer should not cause an error",
-                            );
-                        if pixel_col_offset != 0 && byte_col < 7 {
-                            let right_byte_pixels = sprite_pixel_row << (8 - pixel_col_offset);
-                            let mut right_byte = ram.bytes()[current_display_byte_address + 1];
-                            if (right_byte_pixels & right_byte) != 0 {
-                                pixel_collision = true;
-                            }
-                            right_byte ^= right_byte_pixels;
-                            ram.load_bytes(&[right_byte], current_display_byte_address + 1)
-                                .expect("Loading bytes into the display buffer should not cause an error");
-                        }
+            // SUPER-CHIP OPCODES
+            // These opcodes were introduced by SCHIP on the HP48 and are not
+            // part of the RCA COSMAC VIP instruction set. They need to be
+            // matched before the 0MMM opcode below.
+            Opcode::ScrollDown => {
+                // Scroll display down N pixels
+                let n = op & 0x000F;
+                for plane in Self::selected_planes(ram) {
+                    ram.scroll_display_down(plane, n as usize);
+                }
+                outcome = StepOutcome::DrewToDisplay;
+            }
+            Opcode::ScrollRight => {
+                // Scroll display right 4 pixels
+                for plane in Self::selected_planes(ram) {
+                    ram.scroll_display_right(plane, 4);
+                }
+                outcome = StepOutcome::DrewToDisplay;
+            }
+            Opcode::ScrollLeft => {
+                // Scroll display left 4 pixels
+                for plane in Self::selected_planes(ram) {
+                    ram.scroll_display_left(plane, 4);
+                }
+                outcome = StepOutcome::DrewToDisplay;
+            }
+            Opcode::Exit => {
+                // Exit the interpreter
+                outcome = StepOutcome::Exited;
+            }
+            Opcode::Lores => {
+                // Switch to 64x32 lores display, clearing the display
+                ram.set_display_size(LORES_DISPLAY_SIZE);
+                outcome = StepOutcome::ClearedDisplay;
+            }
+            Opcode::Hires => {
+                // Switch to 128x64 hires display, clearing the display
+                ram.set_display_size(HIRES_DISPLAY_SIZE);
+                outcome = StepOutcome::ClearedDisplay;
+            }
+            Opcode::SetIToBigFont => {
+                // Set I = Address of 10-byte display pattern for LSD of VX
+                // Only the digits 0-9 have large glyphs.
+                let x = VReg::from((op >> 8) as u8);
+                let vx_val = ram.v(x);
+                let hex_val = vx_val & 0x0F; // LSB of VX
 
-                        // advance to the next row of pixels in the display buffer
-                        current_display_byte_address += 8;
-                    }
+                let big_glyph_address =
+                    BIG_CHARACTER_BYTES_ADDRESS + hex_val as usize * BIG_CHARACTER_SIZE;
+                ram.set_work_area_u16(I_OFFSET, big_glyph_address as u16);
+            }
+            Opcode::StoreRplFlags => {
+                // Set RPL user flags = V0 : VX (X <= 7)
+                let x = ((op & 0x0F00) >> 8).min(NUM_RPL_FLAGS as u16 - 1) as usize;
+                self.rpl_flags[..=x].copy_from_slice(&ram.get_v_registers()[..=x]);
+            }
+            Opcode::LoadRplFlags => {
+                // Set V0 : VX = RPL user flags (X <= 7)
+                let x = ((op & 0x0F00) >> 8).min(NUM_RPL_FLAGS as u16 - 1) as usize;
+                ram.get_v_registers_mut()[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+            }
+
+            // XO-CHIP OPCODES
+            // These opcodes were introduced by XO-CHIP (Octo) and are not part
+            // of the RCA COSMAC VIP instruction set.
+            Opcode::SelectPlanes => {
+                // Select display planes N (bit 0 = plane 1, bit 1 = plane 2)
+                let n = (op & 0x0F00) >> 8;
+                ram.set_work_area_u16(PLANE_MASK_OFFSET, n & PLANE_MASK_ALL);
+            }
+            Opcode::LoadAudioPattern => {
+                // Set audio pattern = MI : MI + 15 (I unchanged)
+                let i = ram.get_work_area_u16(I_OFFSET) as usize;
+                Self::check_i_range(ram, i, AUDIO_PATTERN_SIZE)?;
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = bus.read(i + offset);
                 }
-                ram.get_v_registers_mut()[0xF] = if pixel_collision { 1 } else { 0 };
+                self.audio_pattern = Some(pattern);
             }
-            op if op & 0xF000 == 0x0000 => {
+            Opcode::SetPitch => {
+                // Set audio pitch = VX
+                let x = VReg::from((op >> 8) as u8);
+                self.audio_pitch = ram.v(x);
+            }
+
+            Opcode::MachineLanguageCall => {
                 // Execute COSMAC VIP machine language subroutine
-                panic!(
-                    "Emulator does not support COSMAC VIP opcode 0MMM for jumping to \
-                    machine language subroutine."
-                )
+                if self.machine_language_calls == MachineLanguageCalls::Error {
+                    return Err(Error::MachineLanguageCallUnsupported(op & 0x0FFF));
+                }
             }
 
             // UNDOCUMENTED OPCODES
             // The 8XY3, 8XYE, 8XY6 and 8XY7 opcodes are not documented in the
             // RCA COSMAC VIP manual. However, the behaviour is present and
             // many CHIP-8 programs rely in these instructions.
-            op if op & 0xF00F == 0x8003 => {
+            Opcode::Xor => {
                 // Set VX = VX ^ VY
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
 
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx ^= vy_val;
+                let vy_val = ram.v(y);
+                ram.set_v(x, ram.v(x) ^ vy_val);
+
+                if self.quirks.vf_reset_on_logic {
+                    ram.set_vf(0);
+                }
             }
-            op if op & 0xF00F == 0x800E => {
+            Opcode::ShiftLeft => {
                 // Set VX = VY << 1, VF set to overflow bit
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-
-                let vy_val = ram.get_v_registers()[y as usize];
+                // quirk: CHIP-48/SCHIP shift VX in place (VX = VX << 1)
+                let x = VReg::from((op >> 8) as u8);
+                let y = if self.quirks.shift_uses_vx {
+                    x
+                } else {
+                    VReg::from((op >> 4) as u8)
+                };
+
+                let vy_val = ram.v(y);
                 let overflow_bit = if vy_val & 0b1000_0000 != 0 { 1 } else { 0 };
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = vy_val << 1;
+                ram.set_v(x, vy_val << 1);
 
-                let vf = &mut ram.get_v_registers_mut()[0xF];
-                *vf = overflow_bit;
+                ram.set_vf(overflow_bit);
             }
-            op if op & 0xF00F == 0x8006 => {
+            Opcode::ShiftRight => {
                 // Set VX = VY >> 1, VF set to overflow bit
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-
-                let vy_val = ram.get_v_registers()[y as usize];
+                // quirk: CHIP-48/SCHIP shift VX in place (VX = VX >> 1)
+                let x = VReg::from((op >> 8) as u8);
+                let y = if self.quirks.shift_uses_vx {
+                    x
+                } else {
+                    VReg::from((op >> 4) as u8)
+                };
+
+                let vy_val = ram.v(y);
                 let overflow_bit = vy_val & 0b0000_0001;
 
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
-                *vx = vy_val >> 1;
+                ram.set_v(x, vy_val >> 1);
 
-                let vf = &mut ram.get_v_registers_mut()[0xF];
-                *vf = overflow_bit;
+                ram.set_vf(overflow_bit);
             }
-            op if op & 0xF00F == 0x8007 => {
+            Opcode::SubtractReversed => {
                 // Set VX = VY - VX, VF set to borrow bit
-                let x = (op & 0x0F00) >> 8;
-                let y = (op & 0x00F0) >> 4;
-
-                let vy_val = ram.get_v_registers()[y as usize];
-                let vx = &mut ram.get_v_registers_mut()[x as usize];
+                let x = VReg::from((op >> 8) as u8);
+                let y = VReg::from((op >> 4) as u8);
 
-                let borrow = if vy_val < *vx { 0 } else { 1 };
-                *vx = vy_val.wrapping_sub(*vx);
+                let vx_val = ram.v(x);
+                let vy_val = ram.v(y);
+                let borrow = if vy_val < vx_val { 0 } else { 1 };
 
-                let vf = &mut ram.get_v_registers_mut()[0xF];
-                *vf = borrow;
-            }
-            _ => {
-                panic!("Unknown CHIP-8 instruction 0x{:0>4X}", instruction);
+                ram.set_v(x, vy_val.wrapping_sub(vx_val));
+                ram.set_vf(borrow);
             }
+            Opcode::Unknown => return Err(Error::UnknownInstruction(instruction)),
         };
 
-        #[cfg(debug_assertions)]
-        {
-            panic_if_pc_address_not_in_chip8_program_range(next_instruction_address as u16);
-            panic_if_i_address_out_of_bounds(ram.get_u16_at(I_ADDRESS));
+        for (range, kind) in &accesses.accesses {
+            if *kind == Watch::Write {
+                self.invalidate_decoded(range.clone());
+            }
         }
 
-        ram.set_u16_at(PROGRAM_COUNTER_ADDRESS, next_instruction_address as u16);
-    }
+        let ram = bus.ram_mut();
+        if let Some((address, kind)) = self.first_watched_access(&accesses.accesses) {
+            outcome = StepOutcome::Watchpoint {
+                address,
+                pc: instruction_address as u16,
+                kind,
+            };
+        }
 
-    pub fn _get_state(ram: &CosmacRAM) -> Chip8State {
-        let pc = ram.get_u16_at(PROGRAM_COUNTER_ADDRESS);
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, next_instruction_address as u16);
+        Ok(outcome)
+    }
 
-        Chip8State {
-            program_counter: pc,
-            instruction: ram.get_u16_at(pc as usize),
-            i: ram.get_u16_at(I_ADDRESS),
-            stack_pointer: ram.get_u16_at(STACK_POINTER_ADDRESS),
-            timer: ram.get_u16_at(TIMER_ADDRESS),
-            tone_timer: ram.get_u16_at(TONE_TIMER_ADDRESS),
-            hex_key_status: ram.get_u16_at(HEX_KEY_STATUS_ADDRESS),
-            v_registers: ram.get_v_registers(),
-            display_buffer: ram.display_buffer(),
-        }
+    /// Execute up to `n` instructions, stopping early if the program exits or
+    /// starts waiting for a key.
+    ///
+    /// # Errors
+    /// Stops at and returns the first error from [`Self::step`].
+    pub fn step_n<B: MemoryBus>(&mut self, bus: &mut B, n: usize) -> Result<StepsReport> {
+        self.run_steps(bus, n, false)
     }
 
-    fn get_current_key_press(ram: &CosmacRAM) -> Option<u8> {
-        let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-        if HEX_KEY_DEPRESSED_FLAG & hex_key_status == 0 {
-            None
+    /// Execute instructions until one changes the display, so that frontends
+    /// can redraw exactly when needed. Also stops early like [`Self::step_n`],
+    /// and after at most `max_steps` instructions.
+    ///
+    /// # Errors
+    /// Stops at and returns the first error from [`Self::step`].
+    pub fn run_until_draw<B: MemoryBus>(
+        &mut self,
+        bus: &mut B,
+        max_steps: usize,
+    ) -> Result<StepsReport> {
+        self.run_steps(bus, max_steps, true)
+    }
+
+    fn run_steps<B: MemoryBus>(
+        &mut self,
+        bus: &mut B,
+        max_steps: usize,
+        stop_on_draw: bool,
+    ) -> Result<StepsReport> {
+        for steps in 1..=max_steps {
+            let stop_reason = match self.step(bus)? {
+                StepOutcome::Watchpoint { address, pc, kind } => {
+                    Some(StopReason::Watchpoint { address, pc, kind })
+                }
+                StepOutcome::Exited => Some(StopReason::Exited),
+                StepOutcome::Halted => Some(StopReason::Halted),
+                StepOutcome::WaitingForKey => Some(StopReason::WaitingForKey),
+                StepOutcome::DrewToDisplay | StepOutcome::ClearedDisplay if stop_on_draw => {
+                    Some(StopReason::Drew)
+                }
+                _ => None,
+            };
+
+            if let Some(stop_reason) = stop_reason {
+                return Ok(StepsReport { steps, stop_reason });
+            }
+        }
+
+        Ok(StepsReport {
+            steps: max_steps,
+            stop_reason: StopReason::StepLimit,
+        })
+    }
+
+    // The time taken for a timer to count down `jiffies`, where 1 jiffy = 1/60
+    // seconds.
+    fn jiffies_duration(jiffies: u8) -> Duration {
+        Duration::from_nanos(jiffies as u64 * NANOS_PER_SECOND / 60)
+    }
+
+    // The number of jiffies a timer has left to count down in `duration`,
+    // rounded up so that a timer reads its full value for the whole of its
+    // first jiffy, and only reads 0 once it has expired.
+    fn jiffies_in(duration: Duration) -> u16 {
+        (duration.as_nanos() * 60).div_ceil(NANOS_PER_SECOND as u128) as u16
+    }
+
+    // Check that `len` bytes of memory starting at the `I` address `i` are
+    // all in RAM.
+    fn check_i_range(ram: &CosmacRAM, i: usize, len: usize) -> Result<()> {
+        if i + len > ram.memory_size() {
+            return Err(Error::MemoryOutOfBounds {
+                address: ram.memory_size().max(i),
+            });
+        }
+        Ok(())
+    }
+
+    fn write_trace_line(&mut self, ram: &CosmacRAM, before: &TracedRegisters) {
+        let Some(w) = &mut self.trace else {
+            return;
+        };
+        let after = TracedRegisters::new(ram);
+
+        let mut modified = String::new();
+        for (x, (old, new)) in before.v.iter().zip(after.v).enumerate() {
+            if *old != new {
+                write!(modified, " V{x:X}=0x{new:0>2X}").unwrap();
+            }
+        }
+        if before.i != after.i {
+            write!(modified, " I=0x{:0>4X}", after.i).unwrap();
+        }
+        if before.stack_pointer != after.stack_pointer {
+            write!(modified, " SP=0x{:0>4X}", after.stack_pointer).unwrap();
+        }
+        // The timers count down between steps, so only report them when set
+        // by the instruction.
+        match before.instruction.map(|instruction| instruction & 0xF0FF) {
+            Some(0xF015) => write!(
+                modified,
+                " DT=0x{:0>2X}",
+                ram.get_work_area_u16(TIMER_OFFSET)
+            )
+            .unwrap(),
+            Some(0xF018) => write!(
+                modified,
+                " ST=0x{:0>2X}",
+                ram.get_work_area_u16(TONE_TIMER_OFFSET)
+            )
+            .unwrap(),
+            _ => {}
+        }
+
+        let (instruction, mnemonic) = match before.instruction {
+            Some(instruction) => (format!("0x{instruction:0>4X}"), disassemble(instruction)),
+            None => ("------".to_string(), "past end of RAM".to_string()),
+        };
+        let line = format!(
+            "0x{:0>4X} {} {:<15}{}",
+            before.program_counter, instruction, mnemonic, modified
+        );
+        if writeln!(w, "{}", line.trim_end()).is_err() {
+            self.trace = None;
+        }
+    }
+
+    /// The state of the CHIP-8 program in `ram`.
+    pub fn state(ram: &CosmacRAM) -> Chip8State<'_> {
+        let pc = ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET);
+
+        Chip8State {
+            program_counter: pc,
+            instruction: ram.try_get_u16_at(pc as usize).ok(),
+            i: ram.get_work_area_u16(I_OFFSET),
+            stack_pointer: ram.get_work_area_u16(STACK_POINTER_OFFSET),
+            timer: ram.get_work_area_u16(TIMER_OFFSET),
+            tone_timer: ram.get_work_area_u16(TONE_TIMER_OFFSET),
+            hex_key_status: ram.get_work_area_u16(HEX_KEY_STATUS_OFFSET),
+            hex_keys_pressed: ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET),
+            v_registers: ram.get_v_registers(),
+            stack: ram.chip8_stack_bytes(),
+            display_buffer: [ram.display_plane(0), ram.display_plane(1)],
+        }
+    }
+
+    // Move I on after FX55/FX65 have stored/loaded V0 : VX.
+    // Where `instruction`, at `instruction_address`, leaves the program
+    // counter and I once it has run, without running it. Instructions that
+    // stay where they are, waiting for a key or exiting, leave the program
+    // counter on themselves, as does a draw waiting for the display refresh,
+    // which is left to `execute_instruction`.
+    fn next_addresses(
+        &self,
+        ram: &CosmacRAM,
+        opcode: Opcode,
+        instruction: u16,
+        instruction_address: usize,
+    ) -> Result<(usize, u16)> {
+        let op = instruction;
+        let x = VReg::from((op >> 8) as u8);
+        let y = VReg::from((op >> 4) as u8);
+        let constant = (op & 0x00FF) as u8;
+        let next = instruction_address.wrapping_add(2);
+        let skip_if = |condition: bool| match condition {
+            true => next.wrapping_add(2),
+            false => next,
+        };
+
+        let next_instruction_address = match opcode {
+            Opcode::Jump | Opcode::Call => (op & 0x0FFF) as usize,
+            Opcode::JumpWithOffset => {
+                // quirk: CHIP-48/SCHIP use VX, where X is the high nibble of the address
+                let offset_register = if self.quirks.jump_with_vx {
+                    x
+                } else {
+                    VReg::V0
+                };
+                (op & 0x0FFF).wrapping_add(ram.v(offset_register) as u16) as usize
+            }
+            Opcode::Return => {
+                check_chip8_stack_not_empty_on_subroutine_return(ram)?;
+                let sp = ram.get_work_area_u16(STACK_POINTER_OFFSET) - 2;
+                let caller_address = ram.get_u16_at(sp as usize);
+                caller_address as usize + 2
+            }
+            Opcode::SkipIfEqualConstant => skip_if(ram.v(x) == constant),
+            Opcode::SkipIfNotEqualConstant => skip_if(ram.v(x) != constant),
+            Opcode::SkipIfEqual => skip_if(ram.v(x) == ram.v(y)),
+            Opcode::SkipIfNotEqual => skip_if(ram.v(x) != ram.v(y)),
+            // the key is the low nibble of VX
+            Opcode::SkipIfKey => skip_if(Self::is_key_pressed(ram, ram.v(x) & 0x0F)),
+            Opcode::SkipIfNotKey => skip_if(!Self::is_key_pressed(ram, ram.v(x) & 0x0F)),
+            // an unknown instruction doesn't run, so goes nowhere
+            Opcode::WaitForKey | Opcode::Exit | Opcode::Unknown => instruction_address,
+            _ => next,
+        };
+
+        let i = ram.get_work_area_u16(I_OFFSET);
+        let next_i = match opcode {
+            Opcode::SetI => op & 0x0FFF,
+            // quirk: Amiga wraps I past 0x0FFF
+            Opcode::AddToI if self.quirks.i_overflow_sets_vf => {
+                i.wrapping_add(ram.v(x) as u16) & 0x0FFF
+            }
+            Opcode::AddToI => i.wrapping_add(ram.v(x) as u16),
+            // quirk: CHIP-48 sets I = I + X, SCHIP leaves I unchanged
+            Opcode::Store | Opcode::Load => match self.quirks.load_store_increment {
+                LoadStoreIncrement::XPlusOne => i + x as u16 + 1,
+                LoadStoreIncrement::X => i + x as u16,
+                LoadStoreIncrement::Unchanged => i,
+            },
+            _ => i,
+        };
+
+        Ok((next_instruction_address, next_i))
+    }
+
+    // The first watched address in `accesses`, in the order they were made.
+    fn first_watched_access(&self, accesses: &[(Range<usize>, Watch)]) -> Option<(usize, Watch)> {
+        accesses.iter().find_map(|(access, access_kind)| {
+            self.watchpoints
+                .iter()
+                .filter(|(watched, kind)| {
+                    kind == access_kind && watched.start < access.end && access.start < watched.end
+                })
+                .map(|(watched, _)| watched.start.max(access.start))
+                .min()
+                .map(|address| (address, *access_kind))
+        })
+    }
+
+    // The addresses of V registers `registers`.
+    fn v_register_range(ram: &CosmacRAM, registers: RangeInclusive<usize>) -> Range<usize> {
+        ram.v_registers_start_address() + registers.start()
+            ..ram.v_registers_start_address() + registers.end() + 1
+    }
+
+    // The display refresh page, if the display is drawn there, i.e. in lores
+    // mode with the first display plane selected.
+    fn display_refresh_range_in_use(ram: &CosmacRAM) -> Option<Range<usize>> {
+        let plane_mask = ram.get_work_area_u16(PLANE_MASK_OFFSET);
+        if ram.display_size() == LORES_DISPLAY_SIZE && plane_mask & 1 != 0 {
+            Some(ram.display_refresh_start_address()..ram.display_refresh_last_address() + 1)
         } else {
-            Some((hex_key_status & HEX_KEY_LAST_PRESSED_MASK) as u8)
+            None
         }
     }
 
-    pub fn set_current_key_press(ram: &mut CosmacRAM, current_key: Option<u8>) {
-        let mut hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
+    // The display planes selected for drawing by the XO-CHIP FN01 opcode.
+    fn selected_planes(ram: &CosmacRAM) -> impl Iterator<Item = usize> {
+        let plane_mask = ram.get_work_area_u16(PLANE_MASK_OFFSET);
+        (0..NUM_DISPLAY_PLANES).filter(move |plane| plane_mask & (1 << plane) != 0)
+    }
+
+    // XOR a sprite into a single display plane, clipping at the edges of the
+    // display or, if `wrap` is set, wrapping around to the opposite edge.
+    // Returns the number of sprite pixels drawn and the number of rows that
+    // switched a pixel off or were clipped by the bottom edge.
+    fn draw_sprite(
+        display: &mut DisplayViewMut,
+        sprite: &[u8],
+        sprite_row_bytes: u8,
+        pixel_col: u8,
+        pixel_row: u8,
+        wrap: bool,
+    ) -> DrawnSprite {
+        let display_rows = display.height();
+        let display_cols = display.width();
+        let (pixel_col, pixel_row) = if wrap {
+            (
+                pixel_col as usize % display_cols,
+                pixel_row as usize % display_rows,
+            )
+        } else {
+            (pixel_col as usize, pixel_row as usize)
+        };
+        let sprite_rows = sprite.len() / sprite_row_bytes as usize;
+        // the rows of the sprite from `first_row` on
+        let rows_from =
+            |first_row: usize| (first_row..sprite_rows).fold(0u16, |rows, row| rows | 1 << row);
+        if pixel_row >= display_rows {
+            return DrawnSprite {
+                clipped_rows: rows_from(0),
+                ..Default::default()
+            };
+        }
+        if pixel_col >= display_cols {
+            return DrawnSprite::default();
+        }
 
-        match current_key {
-            Some(key) => {
-                hex_key_status |= HEX_KEY_DEPRESSED_FLAG;
-                hex_key_status &= !HEX_KEY_LAST_PRESSED_MASK;
-                hex_key_status |= key as u16 & HEX_KEY_LAST_PRESSED_MASK;
+        let mut drawn = DrawnSprite::default();
+        for (sprite_row_index, sprite_row) in
+            sprite.chunks_exact(sprite_row_bytes as usize).enumerate()
+        {
+            let mut display_row_index = pixel_row + sprite_row_index;
+            if display_row_index >= display_rows {
+                if !wrap {
+                    // clipped by the bottom edge of the display
+                    drawn.clipped_rows = rows_from(sprite_row_index);
+                    break;
+                }
+                display_row_index %= display_rows;
             }
-            None => {
-                hex_key_status &= !HEX_KEY_DEPRESSED_FLAG;
+            let mut row_collision = false;
+            for (sprite_byte, &pixels) in sprite_row.iter().enumerate() {
+                let (pixels_drawn, collision) =
+                    display.xor_byte(pixel_col + 8 * sprite_byte, display_row_index, pixels, wrap);
+                drawn.pixels_drawn += pixels_drawn;
+                row_collision |= collision;
             }
+            drawn.collided_rows |= (row_collision as u16) << sprite_row_index;
+        }
+        drawn
+    }
+
+    fn is_key_pressed(ram: &CosmacRAM, key: u8) -> bool {
+        ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET) & (1 << (key & 0x0F)) != 0
+    }
+
+    /// Record hex `key` (0x0 - 0xF) as pressed or released. Any number of keys
+    /// can be held down at once.
+    ///
+    /// EX9E/EXA1 see the keys held at the time they run, but FX0A sees every
+    /// press and release since it began waiting, even if they happen between
+    /// two steps.
+    pub fn set_key_state(ram: &mut CosmacRAM, key: u8, pressed: bool) {
+        let key = key & 0x0F;
+        let key_bit = 1 << key;
+        let keys_pressed = ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET);
+        if (keys_pressed & key_bit != 0) == pressed {
+            // e.g. a repeated press from a held key
+            return;
+        }
+
+        if pressed {
+            ram.set_work_area_u16(HEX_KEYS_PRESSED_OFFSET, keys_pressed | key_bit);
+            Self::push_key_event(ram, KEY_EVENT_PRESSED_FLAG | key);
+        } else {
+            ram.set_work_area_u16(HEX_KEYS_PRESSED_OFFSET, keys_pressed & !key_bit);
+            Self::push_key_event(ram, key);
         }
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, hex_key_status);
+    }
+
+    // The number of events in the key event queue. The count lives in memory
+    // the program can write to, so it is clamped to the size of the queue.
+    fn key_event_count(ram: &CosmacRAM) -> usize {
+        (ram.get_work_area_u16(KEY_EVENT_COUNT_OFFSET) as usize).min(KEY_EVENT_QUEUE_SIZE)
+    }
+
+    // Add an event to the key event queue, dropping the oldest event when the
+    // queue is full.
+    fn push_key_event(ram: &mut CosmacRAM, event: u8) {
+        let count = Self::key_event_count(ram);
+        let queue_address = ram.interpreter_work_area_start_address() + KEY_EVENT_QUEUE_OFFSET;
+        let mut events = ram.bytes()[queue_address..][..KEY_EVENT_QUEUE_SIZE].to_vec();
+        if count == KEY_EVENT_QUEUE_SIZE {
+            events.remove(0);
+            events.push(event);
+        } else {
+            events[count] = event;
+        }
+
+        ram.load_bytes_unprotected(&events, queue_address)
+            .expect("Key event queue is in the interpreter work area.");
+        ram.set_work_area_u16(
+            KEY_EVENT_COUNT_OFFSET,
+            (count + 1).min(KEY_EVENT_QUEUE_SIZE) as u16,
+        );
+    }
+
+    // Remove and return all events in the key event queue, oldest first.
+    fn drain_key_events(ram: &mut CosmacRAM) -> Vec<u8> {
+        let count = Self::key_event_count(ram);
+        ram.set_work_area_u16(KEY_EVENT_COUNT_OFFSET, 0);
+        let queue_address = ram.interpreter_work_area_start_address() + KEY_EVENT_QUEUE_OFFSET;
+        ram.bytes()[queue_address..][..count].to_vec()
     }
 
     pub fn is_tone_sounding(ram: &CosmacRAM) -> bool {
         // according to the RCA COSMAC VIP manual, the speaker only responds to a
         // tone when the timer value is >= 2.
-        ram.get_u16_at(TONE_TIMER_ADDRESS) > 1
+        ram.get_work_area_u16(TONE_TIMER_OFFSET) > 1
+            || ram.get_work_area_u16(HEX_KEY_STATUS_OFFSET) & HEX_KEY_TONE_FLAG != 0
+    }
+
+    /// Whether FX0A is waiting for a hex key to be pressed and released.
+    pub fn is_waiting_for_key(ram: &CosmacRAM) -> bool {
+        ram.get_work_area_u16(HEX_KEY_STATUS_OFFSET) & HEX_KEY_WAIT_FLAG != 0
+    }
+
+    /// The register X that FX0A will set to the hex key, if it is waiting for
+    /// one.
+    pub fn key_wait_register(ram: &CosmacRAM) -> Option<u8> {
+        Self::is_waiting_for_key(ram)
+            .then(|| ram.get_work_area_u16(HEX_KEY_WAIT_REGISTER_OFFSET) as u8)
+    }
+
+    /// The instruction at the program counter, which will be executed by the
+    /// next step, or `None` if it would run past the end of RAM.
+    pub fn current_instruction(ram: &CosmacRAM) -> Option<u16> {
+        let pc = ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET);
+        ram.try_get_u16_at(pc as usize).ok()
     }
 
+    /// Whether the next step will change the display with DXYN or 00E0.
     pub fn is_on_draw_instruction(ram: &CosmacRAM) -> bool {
-        let pc = ram.get_u16_at(PROGRAM_COUNTER_ADDRESS);
-        let instruction = ram.get_u16_at(pc as usize);
-        instruction & 0xF000 == 0xD000
+        Self::current_instruction(ram)
+            .is_some_and(|instruction| instruction & 0xF000 == 0xD000 || instruction == 0x00E0)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{iter, time::Duration};
+    use std::{
+        iter,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
     use mock_instant::MockClock;
 
     use crate::{
+        font::{BIG_CHARACTER_BYTES, CHARACTER_BYTES, CHARACTER_MAP},
         interpreter::{
-            HEX_KEY_DEPRESSED_FLAG, HEX_KEY_LAST_PRESSED_MASK, HEX_KEY_STATUS_ADDRESS, I_ADDRESS,
-            PROGRAM_COUNTER_ADDRESS, TIMER_ADDRESS, TONE_TIMER_ADDRESS,
+            BIG_CHARACTER_BYTES_ADDRESS, CHARACTER_BYTES_ADDRESS, CHARACTER_MAP_ADDRESS,
+            HEX_KEYS_PRESSED_OFFSET, HEX_KEY_WAIT_REGISTER_OFFSET, I_OFFSET, PLANE_MASK_OFFSET,
+            PROGRAM_COUNTER_OFFSET, STACK_POINTER_OFFSET, TIMER_OFFSET, TONE_TIMER_OFFSET,
+        },
+        memory::{
+            CosmacRAM, FillPattern, Protection, DISPLAY_REFRESH_START_ADDRESS,
+            ETI_660_DISPLAY_SIZE, ETI_660_PROGRAM_START_ADDRESS, HIRES_DISPLAY_SIZE,
+            LORES_DISPLAY_SIZE, PROGRAM_START_ADDRESS, TWO_PAGE_HIRES_DISPLAY_SIZE,
+            V_REGISTERS_START_ADDRESS,
         },
-        memory::{CosmacRAM, DISPLAY_REFRESH_START_ADDRESS},
+        quirks::{Chip8Variant, LoadStoreIncrement, Quirks},
         rng::MockChip8Rng,
+        test_utils::{new_chip8_with_program, new_chip8_with_program_and_quirks},
     };
 
-    use super::Chip8Interpreter;
+    use super::{
+        Chip8Interpreter, Chip8StateOwned, DecodeCacheStats, ExecutionStats, MachineLanguageCalls,
+        StateDiff, StateField, StepOutcome, StepsReport, StopReason, TimerMode, Watch,
+        KEY_EVENT_COUNT_OFFSET, KEY_EVENT_QUEUE_SIZE,
+    };
+    use crate::{recording::Recording, save_state::SaveState, Error};
 
     const APPROX_JIFFY: Duration = Duration::from_millis(1000 / 60);
+    // 1/60 seconds, rounded up to the nanosecond so that advancing by a whole
+    // number of these always crosses a jiffy boundary
+    const EXACT_JIFFY: Duration = Duration::from_nanos(16_666_667);
+    const NANOSECOND: Duration = Duration::from_nanos(1);
     const MILLISECOND: Duration = Duration::from_millis(1);
 
     // Checks that a section of a CHIP-8 program steps through a sequence of
@@ -693,30 +2390,90 @@ mod tests {
         I: Iterator<Item = u16>,
     {
         for address in addresses {
-            assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), address);
-            chip8.step(ram);
+            assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), address);
+            chip8.step(ram).unwrap();
         }
     }
 
-    // Get a new CHIP-8 interpreter and RAM, reset and loaded with the provided
-    // CHIP-8 program.
-    fn new_chip8_with_program(program: &[u8]) -> (CosmacRAM, Chip8Interpreter<MockChip8Rng>) {
-        let rng = MockChip8Rng::new();
-        let mut ram = CosmacRAM::new();
-        let chip8 = Chip8Interpreter::new(rng);
-        ram.load_chip8_program(&program)
-            .expect("Should be ok to load this test program.");
+    #[test]
+    fn reset_keeps_fill_outside_of_interpreter_areas() {
+        let program = chip8_program_into_bytes!(
+            0x6005 // V0 = 5
+            0x220A // call 0x20A
+            0xD011 // draw the sprite at (V0, V1)
+            0x1206
+            0x8000 // sprite
+            0xA208 // I = 0x208
+            0x00EE
+        );
+        let mut ram = CosmacRAM::new_with_fill(FillPattern::Ones);
+        ram.load_chip8_program(&program).unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
         chip8.reset(&mut ram);
-        (ram, chip8)
+
+        // the fill is left in the unused program area and around the fonts
+        let program_end = PROGRAM_START_ADDRESS + program.len();
+        assert!(ram.bytes()[program_end..ram.stack_start_address()]
+            .iter()
+            .all(|&byte| byte == 0xFF));
+        assert_eq!(ram.bytes()[PROGRAM_START_ADDRESS - 1], 0xFF);
+        // while reset clears what the interpreter owns
+        assert!(
+            ram.bytes()[ram.stack_start_address()..ram.interpreter_work_area_start_address()]
+                .iter()
+                .all(|&byte| byte == 0)
+        );
+        assert!(ram.get_v_registers().iter().all(|&v| v == 0));
+        assert!(ram.display_buffer().iter().all(|&byte| byte == 0));
+
+        // and the program runs as it would in zeroed RAM
+        for _ in 0..7 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0206);
+        assert_eq!(ram.get_v_registers()[..2], [5, 0]);
+        assert_eq!(ram.display().get_pixel(5, 0), Some(true));
+    }
+
+    #[test]
+    fn run_program_in_2k_ram() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0x6005 // V0 = 5
+            0x220A // call 0x20A
+            0xD011 // draw the sprite at (V0, V1)
+            0x1206
+            0x8000 // sprite
+            0xA208 // I = 0x208
+            0x00EE
+        ))
+        .unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        chip8.reset(&mut ram);
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(STACK_POINTER_OFFSET), 0x06A2);
+        assert_eq!(ram.get_u16_at(0x06A0), 0x0202);
+
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.get_work_area_u16(STACK_POINTER_OFFSET), 0x06A0);
+        assert_eq!(ram.get_u16_at(0x06D0 + PROGRAM_COUNTER_OFFSET), 0x0206);
+        assert_eq!(ram.get_u16_at(0x06D0 + I_OFFSET), 0x0208);
+        assert_eq!(ram.bytes()[0x06F0], 5);
+        assert_eq!(ram.bytes()[0x0700], 0b0000_0100);
+        assert_eq!(ram.display_buffer()[0], 0b0000_0100);
     }
 
     #[test]
     fn jump() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x1234));
 
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0200);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0234);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0234);
     }
 
     #[test]
@@ -726,9 +2483,28 @@ mod tests {
         let v0 = &mut ram.get_v_registers_mut()[0];
         *v0 = 0xAA;
 
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0200);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0234 + 0xAA);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0234 + 0xAA);
+    }
+
+    #[test]
+    fn unconditional_jump_with_vx_offset() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xB234),
+            Quirks {
+                jump_with_vx: true,
+                ..Quirks::cosmac_vip()
+            },
+        );
+
+        // V0 is set too, to make sure it's VX that's used
+        ram.get_v_registers_mut()[0] = 0xAA;
+        ram.get_v_registers_mut()[2] = 0x11;
+
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0234 + 0x11);
     }
 
     #[test]
@@ -746,10 +2522,95 @@ mod tests {
     }
 
     #[test]
-    fn nested_subroutines() {
+    fn coverage_counts_executed_addresses() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x2204  // 0x0200, jump to 0x0204 subroutine
+            0x1208  // 0x0202, jump to end of program
+            NOOP    // 0x0204
+            0x00EE  // 0x0206, return from subroutine
+            0x1208  // 0x0208, loop forever
+            NOOP    // 0x020A, never reached
+            NOOP    // 0x020C, never reached
+        ));
+        assert!(chip8.coverage().is_empty());
+        assert!(chip8.unexecuted_ranges().is_empty());
+
+        chip8.enable_coverage(&ram);
+        for _ in 0..10 {
+            chip8.step(&mut ram).unwrap();
+        }
+
+        let coverage = chip8.coverage();
+        assert_eq!(coverage.len(), 0x0EA0 - 0x0200);
+        assert_eq!(
+            &coverage[..0x0E],
+            &[1, 0, 1, 0, 1, 0, 1, 0, 6, 0, 0, 0, 0, 0]
+        );
+        assert!(coverage[0x0E..].iter().all(|&count| count == 0));
+        assert_eq!(chip8.unexecuted_ranges(), vec![0x020A..0x0EA0]);
+
+        chip8.reset(&mut ram);
+        assert_eq!(chip8.coverage().len(), 0x0EA0 - 0x0200);
+        assert!(chip8.coverage().iter().all(|&count| count == 0));
+        assert_eq!(chip8.unexecuted_ranges(), vec![0x0200..0x0EA0]);
+
+        chip8.disable_coverage();
+        assert!(chip8.coverage().is_empty());
+    }
+
+    #[test]
+    fn coverage_covers_program_area_of_2k_ram() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x1200))
+            .unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        chip8.reset(&mut ram);
+
+        chip8.enable_coverage(&ram);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.coverage().len(), ram.program_max_size());
+        assert_eq!(
+            chip8.unexecuted_ranges(),
+            vec![0x0202..ram.program_last_address() + 1]
+        );
+    }
+
+    #[test]
+    fn state_shows_call_stack() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            // a program the dives into 12 nested subroutines then immediately
-            // returns from each.
+            0x2204  // 0x0200
+            NOOP    // 0x0202
+            0x2208  // 0x0204
+            0x00EE  // 0x0206
+            0x220C  // 0x0208
+            0x00EE  // 0x020A
+            NOOP    // 0x020C
+            0x00EE  // 0x020E
+        ));
+
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.call_depth(), 0);
+        assert!(state.caller_addresses().is_empty());
+
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.call_depth(), 3);
+        assert_eq!(state.caller_addresses(), vec![0x0200, 0x0204, 0x0208]);
+        assert!(format!("{state:?}").contains(r#"Stack: ["0x0200", "0x0204", "0x0208"]"#));
+
+        // NOOP then return from the innermost subroutine
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        let state = Chip8Interpreter::<MockChip8Rng>::state(&ram);
+        assert_eq!(state.caller_addresses(), vec![0x0200, 0x0204]);
+    }
+
+    // A program that dives into 12 nested subroutines then immediately returns
+    // from each.
+    fn nested_subroutines_program() -> Vec<u8> {
+        chip8_program_into_bytes!(
             0x2204      // 0x0200
             0x1232      // 0x0202
             0x2208      // 0x0204
@@ -776,1187 +2637,4171 @@ mod tests {
             0x00EE
             0x00EE
             NOOP        // 0x0232
-        ));
+        )
+    }
 
-        // build an iterator of the sequence of all instruction addresses
-        // expected when running the program
+    // The sequence of all instruction addresses expected when running
+    // `nested_subroutines_program`.
+    fn nested_subroutines_address_sequence() -> impl Iterator<Item = u16> {
         let expected_call_stack: Vec<u16> = (0..12).map(|i| 0x0200 + i * 4).collect();
-        let last_caller = expected_call_stack.last().unwrap();
+        let last_caller = *expected_call_stack.last().unwrap();
 
-        let filling_the_stack = expected_call_stack.iter().copied();
+        let filling_the_stack = expected_call_stack.clone().into_iter();
         let top_of_stack = iter::once(last_caller + 4);
-        let unwinding_the_stack = expected_call_stack
-            .iter()
-            .copied()
-            .rev()
-            .map(|addr| addr + 2);
+        let unwinding_the_stack = expected_call_stack.into_iter().rev().map(|addr| addr + 2);
         let final_jump = iter::once(0x0232);
-        let expected_address_sequence = filling_the_stack
+        filling_the_stack
             .chain(top_of_stack)
             .chain(unwinding_the_stack)
-            .chain(final_jump);
+            .chain(final_jump)
+    }
 
+    #[test]
+    fn nested_subroutines() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&nested_subroutines_program());
+        let expected_address_sequence = nested_subroutines_address_sequence();
         assert_address_sequence(expected_address_sequence, &mut chip8, &mut ram);
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_kk() {
+    fn chip8_stack_of_nested_subroutines() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&nested_subroutines_program());
+        let expected_call_stack: Vec<u16> = (0..12).map(|i| 0x0200 + i * 4).collect();
+        assert!(ram.chip8_stack().is_empty());
+
+        // fill the stack, checking it after each call
+        for depth in 1..=12 {
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.chip8_stack_depth(), depth);
+            assert_eq!(ram.chip8_stack(), expected_call_stack[..depth]);
+        }
+
+        // then unwind it
+        chip8.step(&mut ram).unwrap(); // innermost subroutine returns
+        for depth in (0..12).rev() {
+            assert_eq!(ram.chip8_stack(), expected_call_stack[..depth]);
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.chip8_stack_depth(), 0);
+    }
+
+    #[test]
+    fn chip8_stack_before_reset() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&nested_subroutines_program())
+            .unwrap();
+        assert!(ram.chip8_stack().is_empty());
+        assert_eq!(ram.chip8_stack_depth(), 0);
+    }
+
+    #[test]
+    fn step_hooks_observe_each_instruction() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&nested_subroutines_program());
+        let pre_step_pcs = Arc::new(Mutex::new(Vec::new()));
+        let post_step_pcs = Arc::new(Mutex::new(Vec::new()));
+        chip8.set_pre_step_hook(Box::new({
+            let pcs = Arc::clone(&pre_step_pcs);
+            move |state| pcs.lock().unwrap().push(state.program_counter)
+        }));
+        chip8.set_post_step_hook(Box::new({
+            let pcs = Arc::clone(&post_step_pcs);
+            move |state| pcs.lock().unwrap().push(state.program_counter)
+        }));
+
+        let expected_address_sequence: Vec<u16> = nested_subroutines_address_sequence().collect();
+        for _ in &expected_address_sequence {
+            chip8.step(&mut ram).unwrap();
+        }
+
+        assert_eq!(*pre_step_pcs.lock().unwrap(), expected_address_sequence);
+        let post_step_pcs = post_step_pcs.lock().unwrap();
+        assert_eq!(
+            post_step_pcs[..post_step_pcs.len() - 1],
+            expected_address_sequence[1..],
+            "Each post step PC should be the next pre step PC"
+        );
+        assert_eq!(*post_step_pcs.last().unwrap(), 0x0234);
+
+        chip8.clear_step_hooks();
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0232);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            pre_step_pcs.lock().unwrap().len(),
+            expected_address_sequence.len()
+        );
+    }
+
+    #[test]
+    fn step_hooks_with_program_counter_at_end_of_ram() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x1FFF));
+        let instructions = Arc::new(Mutex::new(Vec::new()));
+        chip8.set_pre_step_hook(Box::new({
+            let instructions = Arc::clone(&instructions);
+            move |state| instructions.lock().unwrap().push(state.instruction)
+        }));
+        chip8.set_post_step_hook(Box::new({
+            let instructions = Arc::clone(&instructions);
+            move |state| instructions.lock().unwrap().push(state.instruction)
+        }));
+
+        // the jump isn't allowed to leave the program area
+        assert_eq!(
+            chip8.step(&mut ram),
+            Err(Error::ProgramCounterOutOfRange(0x0FFF))
+        );
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0FFF);
+        assert_eq!(
+            chip8.step(&mut ram),
+            Err(Error::ProgramCounterOutOfRange(0x0FFF))
+        );
+
+        let instructions = instructions.lock().unwrap();
+        assert_eq!(instructions[0], Some(0x1FFF));
+        assert_eq!(instructions[2..], [None, None]);
+    }
+
+    /// A writer that can be inspected after being handed to the interpreter.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_executed_instructions() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6144  // 0x0200
+            0x6202  // 0x0202
+            0x8124  // 0x0204
+            0xA300  // 0x0206
+            0xF21E  // 0x0208
+            0x220E  // 0x020A
+            0x1212  // 0x020C
+            0xF115  // 0x020E
+            0x00EE  // 0x0210
+            NOOP    // 0x0212
+            NOOP
+        ));
+        let buffer = SharedBuffer::default();
+        chip8.enable_trace(buffer.clone());
+
+        for _ in 0..10 {
+            chip8.step(&mut ram).unwrap();
+        }
+        chip8.disable_trace();
+        chip8.step(&mut ram).unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace,
+            "\
+0x0200 0x6144 LD V1, 0x44     V1=0x44
+0x0202 0x6202 LD V2, 0x02     V2=0x02
+0x0204 0x8124 ADD V1, V2      V1=0x46
+0x0206 0xA300 LD I, 0x300     I=0x0300
+0x0208 0xF21E ADD I, V2       I=0x0302
+0x020A 0x220E CALL 0x20E      SP=0x0EA2
+0x020E 0xF115 LD DT, V1       DT=0x46
+0x0210 0x00EE RET             SP=0x0EA0
+0x020C 0x1212 JP 0x212
+0x0212 0x7000 NOP
+"
+        );
+    }
+
+    #[test]
+    fn trace_instruction_past_end_of_ram() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(NOOP));
+        let buffer = SharedBuffer::default();
+        chip8.enable_trace(buffer.clone());
+
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0FFF);
+        assert_eq!(
+            chip8.step(&mut ram),
+            Err(Error::ProgramCounterOutOfRange(0x0FFF))
+        );
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(trace, "0x0FFF ------ past end of RAM\n");
+    }
+
+    #[test]
+    fn execution_stats_count_opcode_families() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x7101  // 0x0200
+            0x1200  // 0x0202
+        ));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.stats(), None);
+
+        chip8.enable_stats();
+        for _ in 0..1000 {
+            chip8.step(&mut ram).unwrap();
+        }
+
+        let stats = chip8.stats().unwrap();
+        assert_eq!(stats.instructions, 1000);
+        assert_eq!(stats.opcode_counts["7XKK"], 500);
+        assert_eq!(stats.opcode_counts["1MMM"], 500);
+        assert_eq!(stats.opcode_counts.len(), 2);
+        assert_eq!(stats.histogram(), vec![("1MMM", 500), ("7XKK", 500)]);
+
+        chip8.reset(&mut ram);
+        assert_eq!(chip8.stats(), Some(&ExecutionStats::default()));
+
+        chip8.disable_stats();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.stats(), None);
+    }
+
+    #[test]
+    fn execution_stats_count_pixels_drawn_and_collisions() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD012
+            0xD012
+            NOOP
+        ));
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0, 0x81], 0x0300).unwrap();
+        chip8.enable_stats();
+
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+
+        let stats = chip8.stats().unwrap();
+        assert_eq!(stats.instructions, 3);
+        assert_eq!(stats.pixels_drawn, 12);
+        assert_eq!(stats.collisions, 1);
+        assert_eq!(
+            stats.to_string(),
+            "\
+instructions: 3
+pixels drawn: 12
+collisions:   1
+DXYN          2  66.7% ########################################
+7XKK          1  33.3% ####################
+"
+        );
+    }
+
+    #[test]
+    fn diff_states_around_step() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6305
+            0xD001
+            NOOP
+        ));
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
+
+        let before = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+        chip8.step(&mut ram).unwrap();
+        let after = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+        assert_eq!(
+            before.diff(&after),
+            StateDiff {
+                fields: vec![
+                    StateField::ProgramCounter,
+                    StateField::Instruction,
+                    StateField::V(3)
+                ],
+                display_bytes: vec![],
+            }
+        );
+        assert_eq!(after.v_registers[3], 0x05);
+
+        chip8.step(&mut ram).unwrap();
+        let after_draw = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+        let diff = after.diff(&after_draw);
+        assert_eq!(diff.display_bytes, vec![(0, 0)]);
+        assert!(!diff.fields.contains(&StateField::V(0xF)));
+
+        assert!(after_draw.diff(&after_draw.clone()).is_empty());
+        assert_ne!(before, after_draw);
+    }
+
+    #[test]
+    fn diff_states_with_different_display_sizes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x00FF
+            NOOP
+        ));
+
+        let before = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+        chip8.step(&mut ram).unwrap();
+        let after = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+
+        let diff = before.diff(&after);
+        let first_plane_diff: Vec<_> = diff
+            .display_bytes
+            .iter()
+            .filter(|(plane, _)| *plane == 0)
+            .map(|(_, offset)| *offset)
+            .collect();
+        assert_eq!(first_plane_diff, (256..1024).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_state_round_trip() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6305
+            0xD001
+            0x00FF
+            NOOP
+        ));
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
+
+        for _ in 0..3 {
+            let state = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+            let json = serde_json::to_string(&state).unwrap();
+            let deserialized: Chip8StateOwned = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized, state);
+            chip8.step(&mut ram).unwrap();
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_display_planes_are_base64() {
+        let (ram, _) = new_chip8_with_program(&chip8_program_into_bytes!(NOOP));
+        let state = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["display_buffer"][0], "A".repeat(342) + "==");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_quirks_round_trip() {
+        for variant in [
+            Chip8Variant::CosmacVip,
+            Chip8Variant::Chip48,
+            Chip8Variant::SuperChip1_1,
+            Chip8Variant::XoChip,
+        ] {
+            let quirks = variant.quirks();
+            let json = serde_json::to_string(&quirks).unwrap();
+            assert_eq!(serde_json::from_str::<Quirks>(&json).unwrap(), quirks);
+
+            let json = serde_json::to_string(&variant).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Chip8Variant>(&json).unwrap(),
+                variant
+            );
+        }
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_kk() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x3744  // 44 != 55, no skip expected
+            0x3755  // 44 == 55, skip expected
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x55;
+
+        let expected_address_sequence = [0x0200, 0x0202, 0x0206].into_iter();
+        assert_address_sequence(expected_address_sequence, &mut chip8, &mut ram);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_kk() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x4744  // 44 == 44, no skip expected
+            0x4755  // 55 != 44, skip expected
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x44;
+
+        let expected_address_sequence = [0x0200, 0x0202, 0x0206].into_iter();
+        assert_address_sequence(expected_address_sequence, &mut chip8, &mut ram);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x5120
+            NOOP
+            NOOP
+        ));
+
+        // V0 != V1
+        chip8.reset(&mut ram);
+        ram.get_v_registers_mut()[1] = 0x11;
+        ram.get_v_registers_mut()[2] = 0x22;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(0x0202, ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET));
+
+        // V0 == V1
+        chip8.reset(&mut ram);
+        ram.get_v_registers_mut()[1] = 0x11;
+        ram.get_v_registers_mut()[2] = 0x11;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(0x0204, ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET));
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x9120
+            NOOP
+            NOOP
+        ));
+
+        // V0 == V1
+        chip8.reset(&mut ram);
+        ram.get_v_registers_mut()[1] = 0x11;
+        ram.get_v_registers_mut()[2] = 0x11;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(0x0202, ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET));
+
+        // V0 != V1
+        chip8.reset(&mut ram);
+        ram.get_v_registers_mut()[1] = 0x11;
+        ram.get_v_registers_mut()[2] = 0x22;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(0x0204, ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET));
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_hex_key_depressed_and_eq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE79E
+            NOOP
+            NOOP
+        ));
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, true);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_hex_key_depressed_and_neq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE79E
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_hex_key_released_and_eq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE79E
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+                                             // no key depressed, but key 2 was last pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, false);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_eq_hex_key_released_and_neq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE79E
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+                                             // no key depressed, but key 1 was last pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, false);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
+
+    #[test]
+    fn multiple_hex_keys_held() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE49E
+            NOOP
+            0xE69E
+            NOOP
+            0xE49E
+            NOOP
+            0xE69E
+            NOOP
+        ));
+        ram.get_v_registers_mut()[4] = 0x04;
+        ram.get_v_registers_mut()[6] = 0x06;
+
+        // both keys held
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x4, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x6, true);
+        assert_eq!(ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET), 0b0101_0000);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0208);
+
+        // key 4 released, key 6 still held
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x4, false);
+        assert_eq!(ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET), 0b0100_0000);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x020A);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0210);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_hex_key_depressed_and_eq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE7A1
+            NOOP
+            NOOP
+        ));
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, true);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_hex_key_depressed_and_neq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE7A1
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_hex_key_released_and_eq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE7A1
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+                                             // no key depressed, but key 2 was last pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x2, false);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+    }
+
+    #[test]
+    fn skip_instruction_if_vx_neq_hex_key_released_and_neq() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xE7A1
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
+                                             // no key depressed, but key 1 was last pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, false);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+    }
+
+    #[test]
+    fn set_vx_register_constant() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6499
+            NOOP
+        ));
+
+        assert_eq!(ram.get_v_registers()[4], 0x00);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x99);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_random() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xC4A5
+            NOOP
+        ));
+
+        chip8.rng.expect_random_u8().return_const(0b0111_0111);
+
+        // 0xC4A5 gives a bitmask  -> 1010_0101
+        // random pattern from rng -> 0111_0111
+        // expected result ---------> 0010_0101
+        assert_eq!(ram.get_v_registers()[4], 0x00);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0b0010_0101);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_add_kk() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x74A5
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[4] = 0x07;
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[4], 0xA5 + 0x07);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8620
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[6] = 0x07;
+        ram.get_v_registers_mut()[2] = 0x42;
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[6], 0x42);
+        assert_eq!(ram.get_v_registers()[2], 0x42);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_bitwise_or_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8121
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[1] = 0b0011_0101;
+        ram.get_v_registers_mut()[2] = 0b0110_0110;
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[1], 0b0111_0111);
+        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_bitwise_and_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8122
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[1] = 0b0011_0101;
+        ram.get_v_registers_mut()[2] = 0b0110_0110;
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[1], 0b0010_0100);
+        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_add_vy_no_carry() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8124
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[0x1] = 0xF0;
+        ram.get_v_registers_mut()[0x2] = 0x0F;
+        ram.get_v_registers_mut()[0xF] = 0x55; // carry register
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[0x1], 0xFF);
+        assert_eq!(ram.get_v_registers()[0x2], 0x0F);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_add_vy_with_carry() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8124
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[0x1] = 0xFF;
+        ram.get_v_registers_mut()[0x2] = 0x03;
+        ram.get_v_registers_mut()[0xF] = 0x55; // carry register
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[0x1], 0x02);
+        assert_eq!(ram.get_v_registers()[0x2], 0x03);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_vx_sub_vy() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x8015
+            0x8235
+            0x8455
+            NOOP
+        ));
+
+        // vx == vy
+        ram.get_v_registers_mut()[0x0] = 0xF0;
+        ram.get_v_registers_mut()[0x1] = 0xF0;
+
+        // vx > vy
+        ram.get_v_registers_mut()[0x2] = 0xF0;
+        ram.get_v_registers_mut()[0x3] = 0x0F;
+
+        // vx < vy
+        ram.get_v_registers_mut()[0x4] = 0x0F;
+        ram.get_v_registers_mut()[0x5] = 0xF0;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x0], 0x00);
+        assert_eq!(ram.get_v_registers()[0x1], 0xF0);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x2], 0xE1);
+        assert_eq!(ram.get_v_registers()[0x3], 0x0F);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x4], 0x1F);
+        assert_eq!(ram.get_v_registers()[0x5], 0xF0);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
+    }
+
+    #[test]
+    fn set_vx_register_to_current_timer_value() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF315      // set the timer value = V3
+            0xF407      // set V4 = timer value
+            NOOP
+        ));
+        ram.get_v_registers_mut()[4] = 0xFF; // data to overwrite
+
+        // sets timer value to 77 jiffies
+        ram.get_v_registers_mut()[3] = 0x77;
+        chip8.step(&mut ram).unwrap();
+
+        MockClock::advance(9 * EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[4], 0x77 - 9);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x204);
+    }
+
+    #[test]
+    fn set_vx_register_to_current_hex_digit() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF40A
+            NOOP
+        ));
+
+        // last press was 9, no key currently pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x9, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x9, false);
+        ram.get_v_registers_mut()[4] = 0xFF;
+
+        // hex key not pressed yet, program counter doesn't move
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0xFF);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // hex key not pressed yet, program counter doesn't move
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0xFF);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // hex key not pressed yet, program counter doesn't move
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0xFF);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // 3 key pressed
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x3, true);
+
+        // key pressed, don't advance program counter yet!
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x03);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // key pressed, don't advance program counter yet!
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x03);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // key released, program continues
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x3, false);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x03);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_vx_register_to_newly_pressed_hex_digit() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF40A
+            NOOP
+        ));
+
+        // key 5 is already held when the wait begins
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x5, true);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        // key 8 is newly pressed, and wins over key 5 being released
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x8, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x5, false);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x08);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x8, false);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[4], 0x08);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn key_tapped_between_steps_completes_key_wait() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF30A
+            NOOP
+        ));
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x200);
+
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x7, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x7, false);
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[3], 0x07);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+        assert_eq!(ram.get_work_area_u16(HEX_KEYS_PRESSED_OFFSET), 0);
+    }
+
+    #[test]
+    fn key_event_count_written_by_program_is_clamped() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF30A
+            NOOP
+        ));
+
+        chip8.step(&mut ram).unwrap();
+        ram.set_work_area_u16(KEY_EVENT_COUNT_OFFSET, 0xFFFF);
+
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x7, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x7, false);
+        assert_eq!(
+            ram.get_work_area_u16(KEY_EVENT_COUNT_OFFSET) as usize,
+            KEY_EVENT_QUEUE_SIZE
+        );
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[3], 0x07);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+        assert_eq!(ram.get_work_area_u16(KEY_EVENT_COUNT_OFFSET), 0);
+    }
+
+    #[test]
+    fn key_wait_uses_register_latched_by_fx0a() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF40A
+            NOOP
+        ));
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(HEX_KEY_WAIT_REGISTER_OFFSET), 4);
+
+        // the memory at the program counter changes while waiting
+        ram.set_u16_at(PROGRAM_START_ADDRESS, 0xF70A);
+
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x3, true);
+        chip8.step(&mut ram).unwrap();
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x3, false);
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[4], 0x03);
+        assert_eq!(ram.get_v_registers()[7], 0x00);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn waiting_for_key() {
+        type Chip8 = Chip8Interpreter<MockChip8Rng>;
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF40A
+            NOOP
+        ));
+        assert!(!Chip8::is_waiting_for_key(&ram));
+        assert_eq!(Chip8::key_wait_register(&ram), None);
+
+        chip8.step(&mut ram).unwrap();
+        assert!(Chip8::is_waiting_for_key(&ram));
+        assert_eq!(Chip8::key_wait_register(&ram), Some(4));
+
+        Chip8::set_key_state(&mut ram, 0x3, true);
+        chip8.step(&mut ram).unwrap();
+        assert!(
+            Chip8::is_waiting_for_key(&ram),
+            "Still waiting until the key is released"
+        );
+
+        Chip8::set_key_state(&mut ram, 0x3, false);
+        chip8.step(&mut ram).unwrap();
+        assert!(!Chip8::is_waiting_for_key(&ram));
+        assert_eq!(Chip8::key_wait_register(&ram), None);
+    }
+
+    #[test]
+    fn set_timer_eq_vx_and_countdown() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF715
+            NOOP
+            NOOP
+            NOOP
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x02;
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x00);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+
+        MockClock::advance(EXACT_JIFFY - NANOSECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+
+        MockClock::advance(NANOSECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x01);
+
+        MockClock::advance(EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x00);
+
+        MockClock::advance(Duration::from_secs(1));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x00);
+
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x20A);
+    }
+
+    #[test]
+    fn timer_reads_full_value_in_first_millisecond_after_set() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF715
+            0xF718
+            NOOP
+        ));
+        ram.get_v_registers_mut()[7] = 0x02;
+
+        chip8.step(&mut ram).unwrap();
+        MockClock::advance(MILLISECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+
+        MockClock::advance(MILLISECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x02);
+    }
+
+    #[test]
+    fn set_tone_timer_eq_vx_and_countdown() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF718
+            NOOP
+            NOOP
+            NOOP
+            NOOP
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[7] = 0x02;
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x00);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x02);
+
+        MockClock::advance(EXACT_JIFFY - NANOSECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x02);
+
+        MockClock::advance(NANOSECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x01);
+
+        MockClock::advance(EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x00);
+
+        MockClock::advance(Duration::from_secs(1));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x00);
+
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x20A);
+    }
+
+    #[test]
+    fn timers_count_down_on_ticks() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF715
+            0xF818
+            NOOP
+            NOOP
+        ));
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        ram.get_v_registers_mut()[7] = 0x02;
+        ram.get_v_registers_mut()[8] = 0x03;
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x03);
+
+        // the host clock has no effect on the timers
+        MockClock::advance(Duration::from_secs(1));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x03);
+
+        chip8.tick_60hz(&mut ram);
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x01);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x02);
+
+        chip8.tick_60hz(&mut ram);
+        chip8.tick_60hz(&mut ram);
+        chip8.tick_60hz(&mut ram);
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x00);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0x00);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x208);
+    }
+
+    #[test]
+    fn set_vx_register_to_timer_value_after_ticks() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF315      // set the timer value = V3
+            0xF407      // set V4 = timer value
+            NOOP
+        ));
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        ram.get_v_registers_mut()[3] = 0x77;
+
+        chip8.step(&mut ram).unwrap();
+        for _ in 0..9 {
+            chip8.tick_60hz(&mut ram);
+        }
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_v_registers()[4], 0x77 - 9);
+    }
+
+    #[test]
+    fn tick_does_nothing_in_wall_clock_mode() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF715
+            NOOP
+        ));
+        ram.get_v_registers_mut()[7] = 0x02;
+
+        chip8.step(&mut ram).unwrap();
+        chip8.tick_60hz(&mut ram);
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0x02);
+    }
+
+    #[test]
+    fn switching_timer_mode_keeps_running_timers() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF715
+            0xF818
+            NOOP
+            NOOP
+            NOOP
+        ));
+        ram.get_v_registers_mut()[7] = 30;
+        ram.get_v_registers_mut()[8] = 40;
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+
+        // the host clock has run down the timers since they were last synced
+        MockClock::advance(10 * EXACT_JIFFY);
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 20);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 30);
+        chip8.tick_60hz(&mut ram);
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 19);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 29);
+
+        // and the host clock counts down from where the ticks left off
+        chip8.set_timer_mode(&mut ram, TimerMode::WallClock);
+        MockClock::advance(5 * EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 14);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 24);
+    }
+    #[test]
+    fn set_i_eq_const() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA123
+            NOOP
+        ));
+
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0000);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0123);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_i_eq_i_add_vx() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF41E
+            NOOP
+        ));
+
+        ram.set_work_area_u16(I_OFFSET, 0x0123);
+        ram.get_v_registers_mut()[4] = 0x45;
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0123 + 0x45);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+    }
+
+    #[test]
+    fn set_i_eq_vx_lsd_display_pattern() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF729  // use V7
+            NOOP
+        ));
+
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0000);
+        ram.get_v_registers_mut()[7] = 0x45; // LSB == 5 means we expect glyph for hex 5.
+
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+        let hex_5_address = ram.get_work_area_u16(I_OFFSET) as usize;
+        let glyph = &ram.bytes()[hex_5_address..][..5];
+        #[rustfmt::skip]
+        assert_eq!(glyph, &[
+            0b11110000,
+            0b10000000,
+            0b11110000,
+            0b00010000,
+            0b11110000,
+        ]);
+    }
+
+    #[test]
+    fn set_i_eq_vx_lsd_big_display_pattern() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF730  // use V7
+            NOOP
+        ));
+
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0000);
+        ram.get_v_registers_mut()[7] = 0x48; // LSB == 8 means we expect big glyph for 8.
+
+        chip8.step(&mut ram).unwrap();
+
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+        let big_8_address = ram.get_work_area_u16(I_OFFSET) as usize;
+        let glyph = &ram.bytes()[big_8_address..][..10];
+        #[rustfmt::skip]
+        assert_eq!(glyph, &[
+            0b00111100,
+            0b01111110,
+            0b11000011,
+            0b11000011,
+            0b01111110,
+            0b01111110,
+            0b11000011,
+            0b11000011,
+            0b01111110,
+            0b00111100,
+        ]);
+    }
+
+    #[test]
+    fn font_data_fits_in_interpreter_area() {
+        assert!(CHARACTER_BYTES_ADDRESS + CHARACTER_BYTES.len() <= CHARACTER_MAP_ADDRESS);
+        assert!(CHARACTER_MAP_ADDRESS + CHARACTER_MAP.len() <= BIG_CHARACTER_BYTES_ADDRESS);
+        assert!(BIG_CHARACTER_BYTES_ADDRESS + BIG_CHARACTER_BYTES.len() <= PROGRAM_START_ADDRESS);
+    }
+
+    #[test]
+    fn decimal_digits_into_write_protected_font_area() {
+        for protection in [Protection::NONE, Protection::FONT_AREA] {
+            let (mut ram, mut chip8) =
+                new_chip8_with_program(&chip8_program_into_bytes!(0xF133 NOOP));
+            ram.set_write_protection(protection);
+            ram.get_v_registers_mut()[1] = 234;
+            ram.set_work_area_u16(I_OFFSET, 0x0010);
+            let font_bytes = ram.bytes()[0x0010..][..3].to_vec();
+
+            let result = chip8.step(&mut ram);
+            if protection == Protection::FONT_AREA {
+                assert_eq!(result, Err(Error::WriteProtected { address: 0x0010 }));
+                assert_eq!(ram.bytes()[0x0010..][..3], font_bytes);
+                assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
+            } else {
+                assert!(result.is_ok());
+                assert_eq!(ram.bytes()[0x0010..][..3], [2, 3, 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn store_registers_into_write_protected_program_area() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0xF255 NOOP));
+        ram.set_write_protection(Protection::PROGRAM_AREA);
+        ram.get_v_registers_mut()[..3].copy_from_slice(&[1, 2, 3]);
+        ram.set_work_area_u16(I_OFFSET, 0x01FF);
+
+        assert_eq!(
+            chip8.step(&mut ram),
+            Err(Error::WriteProtected { address: 0x0200 })
+        );
+        assert_eq!(ram.bytes()[0x01FF], 0, "No registers should be stored");
+    }
+
+    #[test]
+    fn set_i_data_to_decimal_digits_of_vx() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF133
+            0xF233
+            0xF333
+            0xF433
+            NOOP
+        ));
+
+        ram.get_v_registers_mut()[1] = 234; // 3 digit test case
+        ram.get_v_registers_mut()[2] = 56; // 2 digit test case
+        ram.get_v_registers_mut()[3] = 7; // 1 digit test case
+        ram.get_v_registers_mut()[4] = 0; // zero test case
+        ram.set_work_area_u16(I_OFFSET, 0x0300); // write digits to memory address 0x0300
+
+        chip8.step(&mut ram).unwrap();
+        let result = &ram.bytes()[0x0300..][..3];
+        assert_eq!(result, &[2, 3, 4]);
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "I register should be unchanged"
+        );
+
+        chip8.step(&mut ram).unwrap();
+        let result = &ram.bytes()[0x0300..][..3];
+        assert_eq!(result, &[0, 5, 6]);
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "I register should be unchanged"
+        );
+
+        chip8.step(&mut ram).unwrap();
+        let result = &ram.bytes()[0x0300..][..3];
+        assert_eq!(result, &[0, 0, 7]);
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "I register should be unchanged"
+        );
+
+        chip8.step(&mut ram).unwrap();
+        let result = &ram.bytes()[0x0300..][..3];
+        assert_eq!(result, &[0, 0, 0]);
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "I register should be unchanged"
+        );
+    }
+
+    #[test]
+    fn set_i_data_to_vx_slice() {
+        // (quirk, expected value of I after the instruction)
+        let modes = [
+            (LoadStoreIncrement::XPlusOne, 0x0300 + 0xC + 1),
+            (LoadStoreIncrement::X, 0x0300 + 0xC),
+            (LoadStoreIncrement::Unchanged, 0x0300),
+        ];
+
+        for (load_store_increment, expected_i) in modes {
+            let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+                &chip8_program_into_bytes!(
+                    0xFC55
+                    NOOP
+                ),
+                Quirks {
+                    load_store_increment,
+                    ..Quirks::cosmac_vip()
+                },
+            );
+
+            // set each VX register to its index to generate some test data
+            let test_register_vals = [
+                0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
+            ];
+            ram.get_v_registers_mut()
+                .copy_from_slice(&test_register_vals);
+
+            // use I = 0x0300 and set some data at this location before executing the instruction
+            ram.set_work_area_u16(I_OFFSET, 0x0300);
+            ram.load_bytes(&[0xFF; 16], 0x0300).unwrap();
+
+            // execute the instruction
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+            // data pointed to by I should be updated
+            assert_eq!(
+                &ram.bytes()[0x0300..][..16],
+                &[
+                    0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xFF, 0xFF,
+                    0xFF
+                ]
+            );
+
+            // value of I is incremented by X + 1 on COSMAC VIP CHIP-8.
+            assert_eq!(
+                ram.get_work_area_u16(I_OFFSET),
+                expected_i,
+                "I for {load_store_increment:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn set_vx_slice_to_i_data() {
+        // (quirk, expected value of I after the instruction)
+        let modes = [
+            (LoadStoreIncrement::XPlusOne, 0x0300 + 0xC + 1),
+            (LoadStoreIncrement::X, 0x0300 + 0xC),
+            (LoadStoreIncrement::Unchanged, 0x0300),
+        ];
+
+        for (load_store_increment, expected_i) in modes {
+            let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+                &chip8_program_into_bytes!(
+                    0xFC65
+                    NOOP
+                ),
+                Quirks {
+                    load_store_increment,
+                    ..Quirks::cosmac_vip()
+                },
+            );
+
+            // set I data
+            ram.set_work_area_u16(I_OFFSET, 0x0300);
+            let test_data = [
+                0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
+            ];
+            ram.load_bytes(&test_data, 0x300).unwrap();
+
+            // Fill VX registers with existing data
+            ram.get_v_registers_mut().copy_from_slice(&[0xFF; 16]);
+
+            // execute the instruction
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+            // check data copied
+            assert_eq!(
+                ram.get_v_registers(),
+                &[
+                    0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xFF, 0xFF,
+                    0xFF
+                ]
+            );
+
+            // check I incremented
+            assert_eq!(
+                ram.get_work_area_u16(I_OFFSET),
+                expected_i,
+                "I for {load_store_increment:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn erase_display() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x00E0
+            NOOP
+        ));
+
+        // Set dummy data in the display refresh
+        ram.load_bytes(&[0xA5; 256], DISPLAY_REFRESH_START_ADDRESS)
+            .expect("256 bytes should fit in display refresh memory.");
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
+            &[0x00; 256]
+        );
+    }
+
+    #[test]
+    fn draw_sprite_of_size_zero() {
+        // the COSMAC VIP opcodes only, where DXY0 draws nothing rather than a
+        // 16x16 sprite
+        let mut ram = CosmacRAM::new();
+        let quirks = Quirks {
+            display_wait: false,
+            ..Quirks::cosmac_vip()
+        };
+        let mut chip8 = Chip8Interpreter::with_quirks(MockChip8Rng::new(), quirks);
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0xD120
+            NOOP
+        ))
+        .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xAA; 16], 0x0300).unwrap(); // dummy data that should not move to display buffer
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
+            &[0x00; 256],
+            "Display buffer should be unchanged for sprite of size zero"
+        );
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    // Load a 16x16 sprite at 0x0300 where every row is `row_pixels`.
+    fn load_16x16_sprite(ram: &mut CosmacRAM, row_pixels: [u8; 2]) {
+        let sprite: Vec<u8> = row_pixels.into_iter().cycle().take(32).collect();
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&sprite, 0x0300)
+            .expect("Sprite data should fit into RAM.");
+    }
+
+    #[test]
+    fn draw_16x16_sprite_with_vx_aligned_to_display_buffer_bytes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD120
+            NOOP
+        ));
+
+        load_16x16_sprite(&mut ram, [0xFF, 0x81]);
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+        ram.get_v_registers_mut()[1] = 8; // a horizontal pixel offset aligned to display buffer bytes
+        ram.get_v_registers_mut()[2] = 1; // second pixel row
+
+        // execute DXY0 instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..8],
+            &[0x00; 8],
+            "No pixels should be written to first row"
+        );
+        for row in 1..17 {
+            assert_eq!(
+                &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][row * 8..][..8],
+                &[0x00, 0xFF, 0x81, 0x00, 0x00, 0x00, 0x00, 0x00],
+                "Pixels should be written to the second and third bytes of row {row}"
+            );
+        }
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][17 * 8..256],
+            &[0x00; 256 - 17 * 8],
+            "No pixels should be written below the sprite"
+        );
+
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXY0 instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_16x16_sprite_with_vx_not_aligned_to_display_buffer_bytes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD120
+            0xD120
+            NOOP
+        ));
+
+        load_16x16_sprite(&mut ram, [0xFF, 0x81]);
+        ram.get_v_registers_mut()[1] = 4; // horizontal: fifth pixel
+        ram.get_v_registers_mut()[2] = 0;
+
+        // Expect the sprite rows to be spread over three display bytes:
+        //   0000_1111 1111_1000 0001_0000
+        chip8.step(&mut ram).unwrap();
+        for row in 0..16 {
+            assert_eq!(
+                &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][row * 8..][..8],
+                &[0x0F, 0xF8, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00],
+                "Pixels should be written to the first three bytes of row {row}"
+            );
+        }
+        assert_eq!(ram.get_v_registers()[0xF], 0x00);
+
+        // drawing the same sprite again erases it and collides
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
+            &[0x00; 256]
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x01,
+            "VF should be 0x01 since pixel collision occurred"
+        );
+    }
+
+    #[test]
+    fn draw_16x16_sprite_partially_cut_off_screen() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD120
+            NOOP
+        ));
+
+        load_16x16_sprite(&mut ram, [0xFF, 0xFF]);
+        ram.get_v_registers_mut()[1] = 52; // horizontal: only 12 pixels fit
+        ram.get_v_registers_mut()[2] = 24; // vertical: only 8 rows fit
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..24 * 8],
+            &[0x00; 24 * 8],
+            "Display buffer should be unchanged above the sprite"
+        );
+        for row in 24..32 {
+            assert_eq!(
+                &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][row * 8..][..8],
+                &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0F, 0xFF],
+                "Sprite should be clipped by the right edge on row {row}"
+            );
+        }
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "Clipped pixels should not cause a collision"
+        );
+    }
+
+    // Draws a 16x16 sprite in hires mode starting 4 pixels before the right
+    // edge of the display, returning the first row of the display.
+    fn draw_16x16_sprite_over_right_edge(sprite_wrapping: bool) -> Vec<u8> {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0x00FF  // hires
+                0xA300
+                0x607C  // V0 = 124
+                0x6100
+                0xD010
+            ),
+            Quirks {
+                sprite_wrapping,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.load_bytes(&[0xFF; 32], 0x300).unwrap();
+        chip8.step_n(&mut ram, 5).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 0x00);
+        ram.display_plane(0)[..16].to_vec()
+    }
+
+    #[test]
+    fn draw_16x16_sprite_over_right_edge_clipped() {
+        let mut expected_row = vec![0x00; 16];
+        expected_row[15] = 0x0F;
+        assert_eq!(draw_16x16_sprite_over_right_edge(false), expected_row);
+    }
+
+    #[test]
+    fn draw_16x16_sprite_over_right_edge_wrapped() {
+        let mut expected_row = vec![0x00; 16];
+        expected_row[0] = 0xFF;
+        expected_row[1] = 0xF0;
+        expected_row[15] = 0x0F;
+        assert_eq!(draw_16x16_sprite_over_right_edge(true), expected_row);
+    }
+
+    #[test]
+    fn draw_sprite_entirely_below_screen() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD12F
+            NOOP
+        ));
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xAA; 16], 0x0300).unwrap(); // dummy data that should not move to display buffer
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 0; // horizontal: on screen
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 32; // vertical: off screen (screen is 32 pixels high)
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
+            &[0x00; 256],
+            "Display buffer should be unchanged for sprite drawn off screen"
+        );
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_sprite_entirely_to_right_of_screen() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD12F
+            NOOP
+        ));
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xAA; 16], 0x0300).unwrap(); // dummy sprite data that should not move to display buffer
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 64; // horizontal: off screen (screen is 64 pixels wide)
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 0; // vertical: on screen
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
+            &[0x00; 256],
+            "Display buffer should be unchanged for sprite drawn off screen"
+        );
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_sprite_partially_cut_off_screen() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD12F
+            NOOP
+        ));
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF; 16], 0x0300).unwrap(); // dummy sprite data
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 63; // horizontal: last pixel
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 31; // vertical: last pixel
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..255],
+            &[0x00; 255],
+            "Display buffer should be unchanged where sprite not drawn"
+        );
+        assert_eq!(
+            ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][255],
+            0x01,
+            "Last pixel in buffer should be drawn"
+        );
+
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_sprite_within_screen_with_vx_aligned_to_display_buffer_bytes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD122
+            NOOP
+        ));
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF; 16], 0x0300).unwrap(); // dummy sprite data
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        // Make sure the sprite position is aligned to display buffer bytes
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 8; // a horizontal pixel offset aligned to display buffer bytes
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 1; // second pixel row
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        // Check pixels by checking the display buffer bytes.
+        // Each row is 64 pixels (8 bytes) wide.
+        // Since the VX coordinate is byte-aligned, we expect only the second
+        // byte for each of the 2 rows to be affected.
+        // This diagram shows the layout, where each X signifies a byte of
+        // display memory that should change by the DXYN instruction:
+        //   0 0 0 .
+        //   0 X 0 .
+        //   0 X 0 .
+        //   0 0 0 .
+        //   . . . .
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..8],
+            &[0x00; 8],
+            "No pixels should be written to first row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
+            &[0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Pixels should only be written to the second byte on the second row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
+            &[0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Pixels should only be written to the second byte on the third row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][24..32],
+            &[0x00; 8],
+            "No pixels should be written to fourth row"
+        );
+
+        // check registers
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_sprite_within_screen_with_vx_not_aligned_to_display_buffer_bytes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xD122
+            NOOP
+        ));
+
+        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
+            .expect("Should be able to zero out display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF; 16], 0x0300).unwrap(); // dummy sprite data
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+
+        // Make sure the sprite position crosses display buffer byte boundaries
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 2; // horizontal: third pixel
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 2; // vertical: third pixel
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        // Check pixels by checking the display buffer bytes.
+        // Each row is 64 pixels (8 bytes) wide.
+        // Expect the first two bytes for 2 rows to be affected.
+        // This diagram shows the layout, where each X signifies a byte of
+        // display memory that should change by the DXYN instruction:
+        //   0 0 0 .
+        //   0 0 0 .
+        //   X X 0 .
+        //   X X 0 .
+        //   0 0 0 .
+        //   . . . .
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
+            &[0x00; 8],
+            "No pixels should be written to second row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
+            &[0b0011_1111, 0b1100_0000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Pixels should be written to the first two bytes of third row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][24..32],
+            &[0b0011_1111, 0b1100_0000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Pixels should be written to the first two bytes of fourth row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][32..40],
+            &[0x00; 8],
+            "No pixels should be written to fifth row"
+        );
+
+        // the same pixels, one at a time
+        let display = ram.display();
+        for (x, y, on) in display.pixels() {
+            assert_eq!(
+                on,
+                (2..=9).contains(&x) && (2..=3).contains(&y),
+                "Pixel ({x}, {y})"
+            );
+            assert_eq!(display.get_pixel(x, y), Some(on));
+        }
+
+        // check registers
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "No pixels collisions so VF should be zero"
+        );
+    }
+
+    #[test]
+    fn draw_hex_glyph() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6005 // V0 = 5
+            0x6102 // V1 = 2
+            0xF029 // I = glyph for V0
+            0xD115 // draw at (V1, V1)
+            NOOP
+        ));
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+
+        let glyph = ["", "", "..####", "..#", "..####", ".....#", "..####"];
+        let expected: String = (0..32)
+            .map(|y| format!("{:.<64}\n", glyph.get(y).unwrap_or(&"")))
+            .collect();
+        assert_eq!(ram.display().to_ascii('#', '.'), expected);
+    }
+
+    #[test]
+    fn draw_sprite_at_right_edge() {
+        for wrap in [false, true] {
+            for pixel_col in 57..=63u8 {
+                let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+                    &chip8_program_into_bytes!(0xD011 NOOP),
+                    Quirks {
+                        sprite_wrapping: wrap,
+                        ..Quirks::cosmac_vip()
+                    },
+                );
+                ram.set_work_area_u16(I_OFFSET, 0x0300);
+                ram.load_bytes(&[0xFF], 0x0300).unwrap();
+                ram.get_v_registers_mut()[0] = pixel_col;
+                // a pixel at the left edge, where wrapped pixels land
+                ram.load_bytes(&[0x80], DISPLAY_REFRESH_START_ADDRESS)
+                    .unwrap();
+
+                chip8.step(&mut ram).unwrap();
+
+                let offset = pixel_col - 56;
+                let display_row = &ram.display_buffer()[..8];
+                let message = format!("x = {pixel_col}, wrap = {wrap}");
+                assert_eq!(display_row[7], 0xFF >> offset, "{message}");
+                assert_eq!(display_row[1..7], [0; 6], "{message}");
+                if wrap {
+                    assert_eq!(display_row[0], 0x80 ^ (0xFF << (8 - offset)), "{message}");
+                    assert_eq!(ram.get_v_registers()[0xF], 1, "{message}");
+                } else {
+                    assert_eq!(display_row[0], 0x80, "{message}");
+                    assert_eq!(ram.get_v_registers()[0xF], 0, "{message}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draw_sprite_xors_existing_data() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x3744  // 44 != 55, no skip expected
-            0x3755  // 44 == 55, skip expected
-            NOOP
+            0xD121
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x55;
+        ram.load_bytes(&[0xFF; 256], DISPLAY_REFRESH_START_ADDRESS)
+            .expect("Should be able to write to entire display refresh buffer.");
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xAA; 1], 0x0300).unwrap(); // dummy sprite data to check xor
+        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 1
 
-        let expected_address_sequence = [0x0200, 0x0202, 0x0206].into_iter();
-        assert_address_sequence(expected_address_sequence, &mut chip8, &mut ram);
+        // Make sure the sprite position crosses display buffer byte boundaries
+        let v1 = &mut ram.get_v_registers_mut()[1];
+        *v1 = 2; // horizontal: third pixel
+        let v2 = &mut ram.get_v_registers_mut()[2];
+        *v2 = 1; // vertical: second pixel
+
+        // execute DXYN instruction
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
+
+        // Check pixels by checking the display buffer bytes.
+        // Each row is 64 pixels (8 bytes) wide.
+        // Expect the first two bytes for 2 rows to be affected.
+        // This diagram shows the layout, where each X signifies a byte of
+        // display memory that should change by the DXYN instruction:
+        //   0 0 0 .
+        //   X X 0 .
+        //   0 0 0 .
+        //   . . . .
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..8],
+            &[0xFF; 8],
+            "No new pixels should be written to first row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
+            &[0b1101_0101, 0b0111_1111, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            "Pixels should be XORed to the first two bytes of second row"
+        );
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
+            &[0xFF; 8],
+            "No new pixels should be written to third row"
+        );
+
+        // check registers
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "DXYN instruction should leave I unchanged"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x01,
+            "VF should be 0x01 since pixel collision occurred"
+        );
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_kk() {
+    fn set_vx_register_vx_bitwise_xor_vy() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x4744  // 44 == 44, no skip expected
-            0x4755  // 55 != 44, skip expected
-            NOOP
+            0x8123
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x44;
+        ram.get_v_registers_mut()[1] = 0b0011_0101;
+        ram.get_v_registers_mut()[2] = 0b0110_0110;
+        chip8.step(&mut ram).unwrap();
 
-        let expected_address_sequence = [0x0200, 0x0202, 0x0206].into_iter();
-        assert_address_sequence(expected_address_sequence, &mut chip8, &mut ram);
+        assert_eq!(ram.get_v_registers()[1], 0b0101_0011);
+        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_vy() {
+    fn set_vx_register_vy_lshift() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x5120
-            NOOP
+            0x812E
+            0x811E
             NOOP
         ));
 
-        // V0 != V1
-        chip8.reset(&mut ram);
-        ram.get_v_registers_mut()[1] = 0x11;
-        ram.get_v_registers_mut()[2] = 0x22;
-
-        chip8.step(&mut ram);
-        assert_eq!(0x0202, ram.get_u16_at(PROGRAM_COUNTER_ADDRESS));
+        ram.get_v_registers_mut()[0x1] = 0x00;
+        ram.get_v_registers_mut()[0x2] = 0b0110_0110;
+        ram.get_v_registers_mut()[0xF] = 0xFF; // dummy value to be overwritten
 
-        // V0 == V1
-        chip8.reset(&mut ram);
-        ram.get_v_registers_mut()[1] = 0x11;
-        ram.get_v_registers_mut()[2] = 0x11;
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x1], 0b1100_1100); // vx = vy << 1
+        assert_eq!(ram.get_v_registers()[0x2], 0b0110_0110); // vy unchanged
+        assert_eq!(ram.get_v_registers()[0xF], 0x00); // no overflow
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
 
-        chip8.step(&mut ram);
-        assert_eq!(0x0204, ram.get_u16_at(PROGRAM_COUNTER_ADDRESS));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x1], 0b1001_1000); // vx = vx << 1
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // overflow
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x204);
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_vy() {
+    fn set_vx_register_vy_rshift() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x9120
-            NOOP
+            0x8126
+            0x8116
             NOOP
         ));
 
-        // V0 == V1
-        chip8.reset(&mut ram);
-        ram.get_v_registers_mut()[1] = 0x11;
-        ram.get_v_registers_mut()[2] = 0x11;
-
-        chip8.step(&mut ram);
-        assert_eq!(0x0202, ram.get_u16_at(PROGRAM_COUNTER_ADDRESS));
+        ram.get_v_registers_mut()[0x1] = 0x00;
+        ram.get_v_registers_mut()[0x2] = 0b0110_0110;
+        ram.get_v_registers_mut()[0xF] = 0xFF; // dummy value to be overwritten
 
-        // V0 != V1
-        chip8.reset(&mut ram);
-        ram.get_v_registers_mut()[1] = 0x11;
-        ram.get_v_registers_mut()[2] = 0x22;
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x1], 0b0011_0011); // vx = vy >> 1
+        assert_eq!(ram.get_v_registers()[0x2], 0b0110_0110); // vy unchanged
+        assert_eq!(ram.get_v_registers()[0xF], 0x00); // no overflow
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x202);
 
-        chip8.step(&mut ram);
-        assert_eq!(0x0204, ram.get_u16_at(PROGRAM_COUNTER_ADDRESS));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x1], 0b0001_1001); // vx = vx >> 1
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // overflow
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x204);
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_hex_key_depressed_and_eq() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE79E
-            NOOP
-            NOOP
-        ));
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0012); // key 2 currently pressed
+    fn arithmetic_with_vf_as_operand() {
+        // (instruction, V1, VF, expected V1, expected VF)
+        // The COSMAC VIP writes the result to VX before setting VF, so when X
+        // is F only the flag is left in VF.
+        let cases = [
+            (0x8F14, 0x20, 0xF0, 0x20, 0x01),
+            (0x8F14, 0x20, 0x10, 0x20, 0x00),
+            (0x81F4, 0x20, 0xF0, 0x10, 0x01),
+            (0x81F4, 0x20, 0x10, 0x30, 0x00),
+            (0x8F15, 0x20, 0x10, 0x20, 0x00),
+            (0x8F15, 0x10, 0x20, 0x10, 0x01),
+            (0x81F5, 0x20, 0x10, 0x10, 0x01),
+            (0x81F5, 0x10, 0x20, 0xF0, 0x00),
+            (0x8F17, 0x20, 0x10, 0x20, 0x01),
+            (0x8F17, 0x10, 0x20, 0x10, 0x00),
+            (0x81F7, 0x20, 0x10, 0xF0, 0x00),
+            (0x81F7, 0x10, 0x20, 0x10, 0x01),
+        ];
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0204);
+        for (instruction, v1, vf, expected_v1, expected_vf) in cases {
+            let program = [u16::to_be_bytes(instruction), u16::to_be_bytes(0x7000)].concat();
+            let (mut ram, mut chip8) = new_chip8_with_program(&program);
+            ram.get_v_registers_mut()[0x1] = v1;
+            ram.get_v_registers_mut()[0xF] = vf;
+
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(
+                ram.get_v_registers()[0x1],
+                expected_v1,
+                "V1 after {instruction:#06X}"
+            );
+            assert_eq!(
+                ram.get_v_registers()[0xF],
+                expected_vf,
+                "VF after {instruction:#06X}"
+            );
+        }
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_hex_key_depressed_and_neq() {
+    fn set_vx_register_vy_sub_vx() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE79E
-            NOOP
+            0x8017
+            0x8237
+            0x8457
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0011); // key 1 currently pressed
+        // vy == vx
+        ram.get_v_registers_mut()[0x0] = 0xF0;
+        ram.get_v_registers_mut()[0x1] = 0xF0;
+
+        // vy < vx
+        ram.get_v_registers_mut()[0x2] = 0xF0;
+        ram.get_v_registers_mut()[0x3] = 0x0F;
+
+        // vy > vx
+        ram.get_v_registers_mut()[0x4] = 0x0F;
+        ram.get_v_registers_mut()[0x5] = 0xF0;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x0], 0x00);
+        assert_eq!(ram.get_v_registers()[0x1], 0xF0);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x2], 0x1F);
+        assert_eq!(ram.get_v_registers()[0x3], 0x0F);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0202);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0x4], 0xE1);
+        assert_eq!(ram.get_v_registers()[0x5], 0xF0);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_hex_key_released_and_eq() {
+    fn scroll_display_down() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE79E
-            NOOP
+            0x00C0
+            0x00C1
+            0x00CF
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-                                             // no key depressed, but key 2 was last pressed
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0002);
+        ram.load_bytes(&[0xFF; 8], DISPLAY_REFRESH_START_ADDRESS)
+            .expect("Display row should fit into RAM.");
+
+        // scroll by zero does nothing
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_buffer()[..8], &[0xFF; 8]);
+        assert_eq!(&ram.display_buffer()[8..], &[0x00; 248]);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0202);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_buffer()[..8], &[0x00; 8]);
+        assert_eq!(&ram.display_buffer()[8..16], &[0xFF; 8]);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_buffer()[..128], &[0x00; 128]);
+        assert_eq!(&ram.display_buffer()[128..136], &[0xFF; 8]);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x206);
     }
 
     #[test]
-    fn skip_instruction_if_vx_eq_hex_key_released_and_neq() {
+    fn scroll_display_right_and_left() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE79E
-            NOOP
+            0x00FB
+            0x00FC
+            0x00FC
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-                                             // no key depressed, but key 1 was last pressed
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0001);
+        ram.load_bytes(
+            &[0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0F],
+            DISPLAY_REFRESH_START_ADDRESS,
+        )
+        .expect("Display row should fit into RAM.");
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.display_buffer()[..8],
+            &[0x0F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Right-most pixels should be scrolled off the display"
+        );
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.display_buffer()[..8],
+            &[0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0202);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.display_buffer()[..8],
+            &[0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            "Left-most pixels should be scrolled off the display"
+        );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x206);
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_hex_key_depressed_and_eq() {
+    fn save_and_restore_rpl_flags() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE7A1
-            NOOP
+            0xF375  // save V0 : V3
+            0xF385  // restore V0 : V3
             NOOP
         ));
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0012); // key 2 currently pressed
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0202);
+        ram.get_v_registers_mut()[..5].copy_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55]);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.rpl_flags(), &[0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0]);
+
+        // flags live outside of RAM, so they should survive a reset
+        chip8.reset(&mut ram);
+        assert_eq!(ram.get_v_registers(), &[0x00; 16]);
+        ram.get_v_registers_mut()[4] = 0xAA;
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x202);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            &ram.get_v_registers()[..5],
+            &[0x11, 0x22, 0x33, 0x44, 0xAA],
+            "Only V0 : V3 should be restored"
+        );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x204);
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_hex_key_depressed_and_neq() {
+    fn rpl_flags_clamp_x_to_seven() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE7A1
-            NOOP
+            0xFF75  // save V0 : VF, clamped to V0 : V7
+            0xFF85  // restore V0 : VF, clamped to V0 : V7
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0011); // key 1 currently pressed
+        let test_register_vals = [
+            0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
+        ];
+        ram.get_v_registers_mut()
+            .copy_from_slice(&test_register_vals);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.rpl_flags(), &[0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7]);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0204);
+        ram.get_v_registers_mut().copy_from_slice(&[0xFF; 16]);
+        chip8.set_rpl_flags(&[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17]);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            ram.get_v_registers(),
+            &[
+                0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+                0xFF, 0xFF
+            ]
+        );
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_hex_key_released_and_eq() {
+    fn draw_scroll_then_collide() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE7A1
-            NOOP
+            0xD121  // draw at (V1, V2)
+            0x00C1  // scroll down 1 pixel
+            0x00FB  // scroll right 4 pixels
+            0xD121  // draw at (V1, V2) again, no collision expected
+            0xD341  // draw at (V3, V4), collision expected
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-                                             // no key depressed, but key 2 was last pressed
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0002);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0], 0x0300).unwrap(); // 4 pixels wide sprite
+        ram.get_v_registers_mut()[1] = 6;
+        ram.get_v_registers_mut()[2] = 0;
+        ram.get_v_registers_mut()[3] = 10; // scrolled 4 pixels right
+        ram.get_v_registers_mut()[4] = 1; // scrolled 1 pixel down
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_buffer()[..8], &[0x00; 8]);
+        assert_eq!(
+            &ram.display_buffer()[8..16],
+            &[0x00, 0x3C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "Sprite drawn where it started should not collide with the scrolled sprite"
+        );
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0204);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x01,
+            "Sprite drawn where the scrolled sprite ended up should collide"
+        );
+        assert_eq!(&ram.display_buffer()[8..16], &[0x00; 8]);
     }
 
     #[test]
-    fn skip_instruction_if_vx_neq_hex_key_released_and_neq() {
+    fn draw_sprite_to_first_plane_only() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xE7A1
-            NOOP
+            0xD011
+            0xD011
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x42; // LSB is hex key 2
-                                             // no key depressed, but key 1 was last pressed
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0001);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0, 0x0F], 0x0300).unwrap();
+
+        // plane 1 is selected after reset
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_plane(0)[..8], &[0xF0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x0204);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0), &[0x00; 256]);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01);
     }
 
     #[test]
-    fn set_vx_register_constant() {
+    fn draw_sprite_to_second_plane_only() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x6499
+            0xF201
+            0xD011
+            0xD011
             NOOP
         ));
 
-        assert_eq!(ram.get_v_registers()[4], 0x00);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0x99);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0, 0x0F], 0x0300).unwrap();
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0), &[0x00; 256]);
+        assert_eq!(&ram.display_plane(1)[..8], &[0xF0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0), &[0x00; 256]);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01);
     }
 
     #[test]
-    fn set_vx_register_random() {
+    fn draw_sprite_to_both_planes() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xC4A5
+            0xD011
+            0xF301
+            0xD011
             NOOP
         ));
 
-        chip8.rng.expect_random_u8().return_const(0b0111_0111);
+        // with both planes selected, the sprite data for the second plane
+        // follows straight on from the data for the first
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0x0F, 0xF0], 0x0300).unwrap();
 
-        // 0xC4A5 gives a bitmask  -> 1010_0101
-        // random pattern from rng -> 0111_0111
-        // expected result ---------> 0010_0101
-        assert_eq!(ram.get_v_registers()[4], 0x00);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0b0010_0101);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        // put some pixels on plane 1 only
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0)[0], 0x0F);
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            ram.display_plane(0)[0],
+            0x00,
+            "First plane should be drawn with the first sprite byte"
+        );
+        assert_eq!(
+            ram.display_plane(1)[0],
+            0xF0,
+            "Second plane should be drawn with the second sprite byte"
+        );
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x01,
+            "Collision on the first plane should set VF"
+        );
     }
 
     #[test]
-    fn set_vx_register_vx_add_kk() {
+    fn draw_sprite_to_both_planes_collides_per_plane() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x74A5
+            0xD011
+            0xF301
+            0xD011
             NOOP
         ));
 
-        ram.get_v_registers_mut()[4] = 0x07;
-        chip8.step(&mut ram);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0, 0x0F], 0x0300).unwrap();
 
-        assert_eq!(ram.get_v_registers()[4], 0xA5 + 0x07);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
-    }
+        // plane 1 gets 0xF0, then both planes are drawn with 0xF0 and 0x0F
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0)[0], 0x00);
+        assert_eq!(ram.display_plane(1)[0], 0x0F);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01);
 
-    #[test]
-    fn set_vx_register_vy() {
+        // drawing only to plane 2 where plane 1 pixels used to be doesn't collide
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8620
+            0xD011
+            0xF201
+            0xD011
             NOOP
         ));
-
-        ram.get_v_registers_mut()[6] = 0x07;
-        ram.get_v_registers_mut()[2] = 0x42;
-        chip8.step(&mut ram);
-
-        assert_eq!(ram.get_v_registers()[6], 0x42);
-        assert_eq!(ram.get_v_registers()[2], 0x42);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xF0], 0x0300).unwrap();
+
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0)[0], 0xF0);
+        assert_eq!(ram.display_plane(1)[0], 0xF0);
+        assert_eq!(
+            ram.get_v_registers()[0xF],
+            0x00,
+            "Pixels on another plane should not cause a collision"
+        );
     }
 
     #[test]
-    fn set_vx_register_vx_bitwise_or_vy() {
+    fn draw_sprite_to_no_planes() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8121
+            0xF001
+            0xD011
             NOOP
         ));
 
-        ram.get_v_registers_mut()[1] = 0b0011_0101;
-        ram.get_v_registers_mut()[2] = 0b0110_0110;
-        chip8.step(&mut ram);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
+        ram.get_v_registers_mut()[0xF] = 0xAA;
 
-        assert_eq!(ram.get_v_registers()[1], 0b0111_0111);
-        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0), &[0x00; 256]);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+        assert_eq!(ram.get_v_registers()[0xF], 0x00);
     }
 
     #[test]
-    fn set_vx_register_vx_bitwise_and_vy() {
+    fn clear_display_only_clears_selected_planes() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8122
+            0xF301
+            0xD011
+            0xF201
+            0x00E0
             NOOP
         ));
 
-        ram.get_v_registers_mut()[1] = 0b0011_0101;
-        ram.get_v_registers_mut()[2] = 0b0110_0110;
-        chip8.step(&mut ram);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF, 0xFF], 0x0300).unwrap();
 
-        assert_eq!(ram.get_v_registers()[1], 0b0010_0100);
-        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(0)[0], 0xFF);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
     }
 
     #[test]
-    fn set_vx_register_vx_add_vy_no_carry() {
+    fn current_instruction_is_at_program_counter() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8124
+            0x6001
+            0xD125
             NOOP
         ));
 
-        ram.get_v_registers_mut()[0x1] = 0xF0;
-        ram.get_v_registers_mut()[0x2] = 0x0F;
-        ram.get_v_registers_mut()[0xF] = 0x55; // carry register
-        chip8.step(&mut ram);
+        assert_eq!(
+            Chip8Interpreter::<MockChip8Rng>::current_instruction(&ram),
+            Some(0x6001)
+        );
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            Chip8Interpreter::<MockChip8Rng>::current_instruction(&ram),
+            Some(0xD125)
+        );
+    }
 
-        assert_eq!(ram.get_v_registers()[0x1], 0xFF);
-        assert_eq!(ram.get_v_registers()[0x2], 0x0F);
-        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+    #[test]
+    fn no_current_instruction_past_end_of_ram() {
+        let (mut ram, _) = new_chip8_with_program(&chip8_program_into_bytes!(0xD125));
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0FFF);
+
+        assert_eq!(
+            Chip8Interpreter::<MockChip8Rng>::current_instruction(&ram),
+            None
+        );
+        assert!(!Chip8Interpreter::<MockChip8Rng>::is_on_draw_instruction(
+            &ram
+        ));
     }
 
     #[test]
-    fn set_vx_register_vx_add_vy_with_carry() {
+    fn on_draw_instruction() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8124
+            0x6001
+            0xD125
+            0x00E0
             NOOP
         ));
 
-        ram.get_v_registers_mut()[0x1] = 0xFF;
-        ram.get_v_registers_mut()[0x2] = 0x03;
-        ram.get_v_registers_mut()[0xF] = 0x55; // carry register
-        chip8.step(&mut ram);
-
-        assert_eq!(ram.get_v_registers()[0x1], 0x02);
-        assert_eq!(ram.get_v_registers()[0x2], 0x03);
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        let expected = [
+            (0x6001, false),
+            (0xD125, true),
+            (0x00E0, true),
+            (0x7000, false),
+        ];
+        for (instruction, is_draw) in expected {
+            assert_eq!(
+                Chip8Interpreter::<MockChip8Rng>::is_on_draw_instruction(&ram),
+                is_draw,
+                "{instruction:#06X}"
+            );
+            chip8.step(&mut ram).unwrap();
+        }
     }
 
     #[test]
-    fn set_vx_register_vx_sub_vy() {
+    fn restore_save_state() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8015
-            0x8235
-            0x8455
-            NOOP
+            0x00FF  // 0x0200, hires
+            0xF029  // 0x0202, I = glyph for V0
+            0xD015  // 0x0204
+            0x7001  // 0x0206
+            0x7102  // 0x0208
+            0xF015  // 0x020A
+            0xF218  // 0x020C
+            0x1202  // 0x020E
         ));
 
-        // vx == vy
-        ram.get_v_registers_mut()[0x0] = 0xF0;
-        ram.get_v_registers_mut()[0x1] = 0xF0;
-
-        // vx > vy
-        ram.get_v_registers_mut()[0x2] = 0xF0;
-        ram.get_v_registers_mut()[0x3] = 0x0F;
-
-        // vx < vy
-        ram.get_v_registers_mut()[0x4] = 0x0F;
-        ram.get_v_registers_mut()[0x5] = 0xF0;
+        fn run(chip8: &mut Chip8Interpreter<MockChip8Rng>, ram: &mut CosmacRAM) {
+            for _ in 0..100 {
+                chip8.step(ram).unwrap();
+                MockClock::advance(EXACT_JIFFY / 4);
+            }
+        }
+        run(&mut chip8, &mut ram);
+        ram.get_v_registers_mut()[2] = 0x80;
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x0], 0x00);
-        assert_eq!(ram.get_v_registers()[0x1], 0xF0);
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+        let state = chip8.save_state(&ram);
+        run(&mut chip8, &mut ram);
+        let expected_state = Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram));
+        let expected_ram = ram.bytes().to_vec();
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x2], 0xE1);
-        assert_eq!(ram.get_v_registers()[0x3], 0x0F);
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        let mut ram = CosmacRAM::new();
+        let state = SaveState::from_bytes(&state.to_bytes()).unwrap();
+        chip8.load_state(&mut ram, &state).unwrap();
+        run(&mut chip8, &mut ram);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x4], 0x1F);
-        assert_eq!(ram.get_v_registers()[0x5], 0xF0);
-        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
+        assert_eq!(
+            Chip8StateOwned::from(Chip8Interpreter::<MockChip8Rng>::state(&ram)),
+            expected_state
+        );
+        assert_eq!(ram.bytes(), expected_ram.as_slice());
+        assert_eq!(ram.display_size(), HIRES_DISPLAY_SIZE);
     }
 
     #[test]
-    fn set_vx_register_to_current_timer_value() {
+    fn decode_cache_sees_self_modifying_code() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF315      // set the timer value = V3
-            0xF407      // set V4 = timer value
-            NOOP
+            0x120A  // 0x0200
+            NOOP    // 0x0202
+            NOOP    // 0x0204
+            NOOP    // 0x0206
+            NOOP    // 0x0208
+            0x7201  // 0x020A, overwritten with 0x7305
+            0x3201  // 0x020C
+            0x120E  // 0x020E
+            0xA20A  // 0x0210
+            0x6073  // 0x0212
+            0x6105  // 0x0214
+            0xF155  // 0x0216, store 0x7305 at 0x020A
+            0x120A  // 0x0218
         ));
-        ram.get_v_registers_mut()[4] = 0xFF; // data to overwrite
+        chip8.enable_decode_cache();
+        for _ in 0..9 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.get_v_registers()[2], 1);
+        assert_eq!(ram.get_v_registers()[3], 5);
+        assert_eq!(chip8.decode_cache_stats().invalidations, 1);
 
-        // sets timer value to 77 jiffies
-        ram.get_v_registers_mut()[3] = 0x77;
-        chip8.step(&mut ram);
+        for _ in 0..16 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.get_v_registers()[2], 1);
+        assert_eq!(ram.get_v_registers()[3], 15);
+        let stats = chip8.decode_cache_stats();
+        assert_eq!(stats.misses, 11);
+        assert_eq!(stats.hits, 14);
+        assert_eq!(stats.invalidations, 3);
+    }
 
-        MockClock::advance(9 * APPROX_JIFFY);
-        chip8.step(&mut ram);
+    #[test]
+    fn decode_cache_does_not_change_execution() {
+        let program = chip8_program_into_bytes!(
+            0x6005  // 0x0200
+            0xF029  // 0x0202
+            0xC0FF  // 0x0204
+            0xD015  // 0x0206
+            0x8104  // 0x0208
+            0xF133  // 0x020A
+            0x1204  // 0x020C
+        );
+        let run = |decode_cache: bool| {
+            let mut ram = CosmacRAM::new();
+            ram.load_chip8_program(&program).unwrap();
+            let mut chip8 = Chip8Interpreter::with_seed(7);
+            if decode_cache {
+                chip8.enable_decode_cache();
+            }
+            chip8.enable_stats();
+            chip8.reset(&mut ram);
+            for _ in 0..500 {
+                chip8.step(&mut ram).unwrap();
+            }
+            (
+                ram.bytes().to_vec(),
+                chip8.stats().cloned(),
+                chip8.decode_cache_stats(),
+            )
+        };
 
-        assert_eq!(ram.get_v_registers()[4], 0x77 - 9);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x204);
+        let (ram, stats, cache_stats) = run(false);
+        assert_eq!(cache_stats, DecodeCacheStats::default());
+        let (cached_ram, cached_stats, cache_stats) = run(true);
+        assert_eq!(cached_ram, ram);
+        assert_eq!(cached_stats, stats);
+        assert_eq!(cache_stats.misses, 7);
+        assert_eq!(cache_stats.hits, 493);
     }
 
     #[test]
-    fn set_vx_register_to_current_hex_digit() {
+    fn double_buffering_hides_redraws() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF40A
-            NOOP
+            0x6005  // 0x0200
+            0xF029  // 0x0202, I = glyph for V0
+            0xD015  // 0x0204
+            0x00E0  // 0x0206
+            0xD015  // 0x0208
+            0x1206  // 0x020A
         ));
+        chip8.enable_double_buffering(&ram);
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        chip8.present(&ram);
+        let frame = ram.display_buffer().to_vec();
+        assert!(frame.iter().any(|&byte| byte != 0));
+
+        let mut seen_blank_display = false;
+        for _ in 0..10 {
+            for _ in 0..3 {
+                chip8.step(&mut ram).unwrap();
+                seen_blank_display |= ram.display_buffer().iter().all(|&byte| byte == 0);
+                assert_eq!(chip8.display_buffer_for_render(&ram), frame);
+            }
+            chip8.present(&ram);
+        }
+        assert!(seen_blank_display);
 
-        // last press was 9, no key currently pressed
-        ram.set_u16_at(HEX_KEY_STATUS_ADDRESS, 0x0009);
-        ram.get_v_registers_mut()[4] = 0xFF;
+        // ticks present the display too
+        chip8.step(&mut ram).unwrap();
+        chip8.tick_60hz(&mut ram);
+        assert!(chip8
+            .display_buffer_for_render(&ram)
+            .iter()
+            .all(|&byte| byte == 0));
 
-        // hex key not pressed yet, program counter doesn't move
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0xFF);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x200);
+        chip8.disable_double_buffering();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.display_buffer_for_render(&ram), frame);
+    }
 
-        // hex key not pressed yet, program counter doesn't move
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0xFF);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x200);
+    #[test]
+    fn same_seed_same_random_numbers() {
+        fn v_register_history(seed: u64) -> Vec<Vec<u8>> {
+            let mut ram = CosmacRAM::new();
+            ram.load_chip8_program(&chip8_program_into_bytes!(
+                0xC0FF
+                0xC1FF
+                0xC20F
+                0xC3F0
+                0x1200
+            ))
+            .unwrap();
+            let mut chip8 = Chip8Interpreter::with_seed(seed);
+            chip8.reset(&mut ram);
+            (0..100)
+                .map(|_| {
+                    chip8.step(&mut ram).unwrap();
+                    ram.get_v_registers().to_vec()
+                })
+                .collect()
+        }
 
-        // hex key not pressed yet, program counter doesn't move
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0xFF);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x200);
+        assert_eq!(v_register_history(1234), v_register_history(1234));
+        assert_ne!(v_register_history(1234), v_register_history(5678));
+    }
 
-        // 3 key pressed
-        let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-        ram.set_u16_at(
-            HEX_KEY_STATUS_ADDRESS,
-            hex_key_status & !HEX_KEY_LAST_PRESSED_MASK | HEX_KEY_DEPRESSED_FLAG | 0x03,
+    #[test]
+    fn replay_recording() {
+        let program = chip8_program_into_bytes!(
+            0xC03F  // 0x0200, V0 = random x
+            0xC11F  // 0x0202, V1 = random y
+            0xF20A  // 0x0204, V2 = key
+            0xF229  // 0x0206, I = glyph for V2
+            0xD015  // 0x0208
+            0x1200  // 0x020A
         );
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&program).unwrap();
+        let mut chip8 = Chip8Interpreter::new(fastrand::Rng::with_seed(1));
+        chip8.reset(&mut ram);
+        chip8.start_recording();
+        for step in 0..200u8 {
+            match step % 10 {
+                3 => chip8.handle_key(&mut ram, step % 16, true),
+                7 => chip8.handle_key(&mut ram, step % 16, false),
+                _ => (),
+            }
+            chip8.step(&mut ram).unwrap();
+        }
+        let recording = chip8.stop_recording().unwrap();
+        let recording = Recording::from_bytes(&recording.to_bytes()).unwrap();
+        assert_eq!(recording.steps(), 200);
+        assert!(ram.display_buffer().iter().any(|&byte| byte != 0));
+
+        let mut replay_ram = CosmacRAM::new();
+        replay_ram.load_chip8_program(&program).unwrap();
+        let mut chip8 = Chip8Interpreter::new(recording.replay_rng());
+        chip8.reset(&mut replay_ram);
+        chip8.start_replay(&recording);
+        for step in 0..200u8 {
+            // live key presses are ignored during the replay
+            chip8.handle_key(&mut replay_ram, 0x5, step % 2 == 0);
+            chip8.step(&mut replay_ram).unwrap();
+        }
+        assert!(!chip8.is_replaying());
 
-        // key pressed, don't advance program counter yet!
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0x03);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x200);
-
-        // key pressed, don't advance program counter yet!
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0x03);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x200);
+        assert_eq!(replay_ram.display_buffer(), ram.display_buffer());
+        assert_eq!(replay_ram.get_v_registers(), ram.get_v_registers());
+    }
 
-        // key released, program continues
-        let hex_key_status = ram.get_u16_at(HEX_KEY_STATUS_ADDRESS);
-        ram.set_u16_at(
-            HEX_KEY_STATUS_ADDRESS,
-            hex_key_status & !HEX_KEY_DEPRESSED_FLAG,
+    #[test]
+    fn reset_stops_replay_and_restarts_recording() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x7001
+            0x1200
+        ));
+        chip8.start_recording();
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+        chip8.reset(&mut ram);
+        chip8.step(&mut ram).unwrap();
+        let recording = chip8.stop_recording().unwrap();
+        assert_eq!(
+            recording.steps(),
+            1,
+            "Only the steps since the reset should be recorded"
         );
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[4], 0x03);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        chip8.start_replay(&recording);
+        assert!(chip8.is_replaying());
+        chip8.reset(&mut ram);
+        assert!(!chip8.is_replaying());
     }
 
     #[test]
-    fn set_timer_eq_vx_and_countdown() {
+    fn reset_stops_running_timers() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF715
-            NOOP
-            NOOP
-            NOOP
+            0x60F0  // 4 seconds
+            0xF015
+            0xF018
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x02;
-        assert_eq!(ram.get_u16_at(TIMER_ADDRESS), 0x00);
-
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TIMER_ADDRESS), 0x02);
-
-        MockClock::advance(APPROX_JIFFY - MILLISECOND);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TIMER_ADDRESS), 0x01);
-
-        MockClock::advance(2 * MILLISECOND);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TIMER_ADDRESS), 0x00);
-
-        MockClock::advance(Duration::from_secs(1));
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TIMER_ADDRESS), 0x00);
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        MockClock::advance(EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TIMER_OFFSET), 0xEF);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0xEF);
 
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x208);
+        chip8.reset(&mut ram);
+        MockClock::advance(EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(
+            ram.get_work_area_u16(TIMER_OFFSET),
+            0x00,
+            "Timer from before the reset should not keep counting down"
+        );
+        assert_eq!(
+            ram.get_work_area_u16(TONE_TIMER_OFFSET),
+            0x00,
+            "Tone timer from before the reset should not keep counting down"
+        );
+        assert!(!Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
     }
 
     #[test]
-    fn set_tone_timer_eq_vx_and_countdown() {
+    fn reset_restores_default_audio() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF718
-            NOOP
-            NOOP
-            NOOP
+            0x6070
+            0xF03A
+            0xA300
+            0xF002
             NOOP
         ));
 
-        ram.get_v_registers_mut()[7] = 0x02;
-        assert_eq!(ram.get_u16_at(TONE_TIMER_ADDRESS), 0x00);
-
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TONE_TIMER_ADDRESS), 0x02);
-
-        MockClock::advance(APPROX_JIFFY - MILLISECOND);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TONE_TIMER_ADDRESS), 0x01);
-
-        MockClock::advance(2 * MILLISECOND);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TONE_TIMER_ADDRESS), 0x00);
-
-        MockClock::advance(Duration::from_secs(1));
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(TONE_TIMER_ADDRESS), 0x00);
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert!(chip8.audio_pattern().is_some());
 
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x208);
+        let default_rate = Chip8Interpreter::new(MockChip8Rng::new()).audio_playback_rate_hz();
+        assert_ne!(chip8.audio_playback_rate_hz(), default_rate);
+        chip8.reset(&mut ram);
+        assert!(chip8.audio_pattern().is_none());
+        assert_eq!(chip8.audio_playback_rate_hz(), default_rate);
     }
 
     #[test]
-    fn set_i_eq_const() {
+    fn reset_selects_first_plane_and_clears_second() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xA123
+            0xF201
+            0xD011
             NOOP
         ));
 
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0000);
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0123);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_plane(1)[0], 0xFF);
+
+        chip8.reset(&mut ram);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+        assert_eq!(ram.get_work_area_u16(PLANE_MASK_OFFSET), 0b01);
     }
 
     #[test]
-    fn set_i_eq_i_add_vx() {
+    fn load_audio_pattern() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF41E
+            0xF002
             NOOP
         ));
 
-        ram.set_u16_at(I_ADDRESS, 0x0123);
-        ram.get_v_registers_mut()[4] = 0x45;
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0123 + 0x45);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        let pattern: Vec<u8> = (0..16).collect();
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&pattern, 0x0300).unwrap();
+
+        assert_eq!(chip8.audio_pattern(), None);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.audio_pattern().unwrap(), &pattern[..]);
+        assert_eq!(
+            ram.get_work_area_u16(I_OFFSET),
+            0x0300,
+            "I should be unchanged"
+        );
     }
 
     #[test]
-    fn set_i_eq_vx_lsd_display_pattern() {
+    fn set_audio_pitch() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF729  // use V7
+            0xF53A
+            0xF63A
             NOOP
         ));
 
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0000);
-        ram.get_v_registers_mut()[7] = 0x45; // LSB == 5 means we expect glyph for hex 5.
+        ram.get_v_registers_mut()[5] = 112;
+        ram.get_v_registers_mut()[6] = 16;
 
-        chip8.step(&mut ram);
+        assert_eq!(chip8.audio_playback_rate_hz(), 4000.0);
 
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
-        let hex_5_address = ram.get_u16_at(I_ADDRESS) as usize;
-        let glyph = &ram.bytes()[hex_5_address..][..5];
-        #[rustfmt::skip]
-        assert_eq!(glyph, &[
-            0b11110000,
-            0b10000000,
-            0b11110000,
-            0b00010000,
-            0b11110000,
-        ]);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.audio_playback_rate_hz(), 8000.0);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(chip8.audio_playback_rate_hz(), 2000.0);
     }
 
+    // QUIRKS
+    // Each quirk is tested by running the same program with the quirk off
+    // and on.
+    const QUIRKS_OFF: Quirks = Quirks {
+        shift_uses_vx: false,
+        load_store_increment: LoadStoreIncrement::Unchanged,
+        jump_with_vx: false,
+        sprite_wrapping: false,
+        vf_reset_on_logic: false,
+        display_wait: false,
+        i_overflow_sets_vf: false,
+        key_wait_tone: false,
+        hires_vf_counts_rows: false,
+    };
+
     #[test]
-    fn set_i_data_to_decimal_digits_of_vx() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xF133
-            0xF233
-            0xF333
-            0xF433
-            NOOP
-        ));
+    fn shift_quirk_off_shifts_vy() {
+        let (mut ram, mut chip8) =
+            new_chip8_with_program_and_quirks(&chip8_program_into_bytes!(0x8126 NOOP), QUIRKS_OFF);
+        ram.get_v_registers_mut()[1] = 0b0000_0010;
+        ram.get_v_registers_mut()[2] = 0b0000_0101;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[1], 0b0000_0010);
+        assert_eq!(ram.get_v_registers()[2], 0b0000_0101);
+        assert_eq!(ram.get_v_registers()[0xF], 1);
+    }
 
-        ram.get_v_registers_mut()[1] = 234; // 3 digit test case
-        ram.get_v_registers_mut()[2] = 56; // 2 digit test case
-        ram.get_v_registers_mut()[3] = 7; // 1 digit test case
-        ram.get_v_registers_mut()[4] = 0; // zero test case
-        ram.set_u16_at(I_ADDRESS, 0x0300); // write digits to memory address 0x0300
+    #[test]
+    fn shift_quirk_on_shifts_vx() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0x8126 NOOP),
+            Quirks {
+                shift_uses_vx: true,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.get_v_registers_mut()[1] = 0b0000_0010;
+        ram.get_v_registers_mut()[2] = 0b0000_0101;
 
-        chip8.step(&mut ram);
-        let result = &ram.bytes()[0x0300..][..3];
-        assert_eq!(result, &[2, 3, 4]);
-        assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "I register should be unchanged"
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[1], 0b0000_0001);
+        assert_eq!(ram.get_v_registers()[2], 0b0000_0101);
+        assert_eq!(ram.get_v_registers()[0xF], 0);
+    }
+
+    #[test]
+    fn shift_quirk_left_and_right() {
+        // (shift_uses_vx, instruction, expected VX, expected VF)
+        let cases = [
+            (false, 0x8126, 0b0100_0000, 1),
+            (true, 0x8126, 0b0100_0001, 0),
+            (false, 0x812E, 0b0000_0010, 1),
+            (true, 0x812E, 0b0000_0100, 1),
+        ];
+
+        for (shift_uses_vx, instruction, expected_vx, expected_vf) in cases {
+            let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+                &[u16::to_be_bytes(instruction), u16::to_be_bytes(0x7000)].concat(),
+                Quirks {
+                    shift_uses_vx,
+                    ..QUIRKS_OFF
+                },
+            );
+            ram.get_v_registers_mut()[1] = 0b1000_0010;
+            ram.get_v_registers_mut()[2] = 0b1000_0001;
+            ram.get_v_registers_mut()[0xF] = 0xAA;
+
+            chip8.step(&mut ram).unwrap();
+            let case = format!("shift_uses_vx: {shift_uses_vx}, instruction: 0x{instruction:0>4X}");
+            assert_eq!(ram.get_v_registers()[1], expected_vx, "VX for {case}");
+            assert_eq!(ram.get_v_registers()[2], 0b1000_0001, "VY for {case}");
+            assert_eq!(ram.get_v_registers()[0xF], expected_vf, "VF for {case}");
+        }
+    }
+
+    #[test]
+    fn load_store_quirk_off_leaves_i_unchanged() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xF255 0xF265 NOOP),
+            QUIRKS_OFF,
         );
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
 
-        chip8.step(&mut ram);
-        let result = &ram.bytes()[0x0300..][..3];
-        assert_eq!(result, &[0, 5, 6]);
-        assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "I register should be unchanged"
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0300);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0300);
+    }
+
+    #[test]
+    fn load_store_quirk_on_increments_i() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xF255 0xF265 NOOP),
+            Quirks {
+                load_store_increment: LoadStoreIncrement::XPlusOne,
+                ..QUIRKS_OFF
+            },
         );
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
 
-        chip8.step(&mut ram);
-        let result = &ram.bytes()[0x0300..][..3];
-        assert_eq!(result, &[0, 0, 7]);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0303);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0306);
+    }
+
+    #[test]
+    fn jump_quirk_off_adds_v0() {
+        let (mut ram, mut chip8) =
+            new_chip8_with_program_and_quirks(&chip8_program_into_bytes!(0xB234), QUIRKS_OFF);
+        ram.get_v_registers_mut()[0] = 0x10;
+        ram.get_v_registers_mut()[2] = 0x20;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0244);
+    }
+
+    #[test]
+    fn jump_quirk_on_adds_vx() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xB234),
+            Quirks {
+                jump_with_vx: true,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.get_v_registers_mut()[0] = 0x10;
+        ram.get_v_registers_mut()[2] = 0x20;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0254);
+    }
+
+    #[test]
+    fn sprite_wrapping_quirk_off_clips_sprite() {
+        let (mut ram, mut chip8) =
+            new_chip8_with_program_and_quirks(&chip8_program_into_bytes!(0xD012 NOOP), QUIRKS_OFF);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF, 0xFF], 0x0300).unwrap();
+        ram.get_v_registers_mut()[0] = 60; // 4 pixels from the right edge
+        ram.get_v_registers_mut()[1] = 31; // bottom row
+
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "I register should be unchanged"
+            &ram.display_buffer()[31 * 8..],
+            &[0, 0, 0, 0, 0, 0, 0, 0x0F]
         );
+        assert_eq!(&ram.display_buffer()[..31 * 8], &[0x00; 31 * 8]);
+    }
 
-        chip8.step(&mut ram);
-        let result = &ram.bytes()[0x0300..][..3];
-        assert_eq!(result, &[0, 0, 0]);
+    #[test]
+    fn sprite_wrapping_quirk_on_wraps_sprite() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xD012 NOOP),
+            Quirks {
+                sprite_wrapping: true,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF, 0xFF], 0x0300).unwrap();
+        ram.get_v_registers_mut()[0] = 60; // 4 pixels from the right edge
+        ram.get_v_registers_mut()[1] = 31; // bottom row
+
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "I register should be unchanged"
+            &ram.display_buffer()[31 * 8..],
+            &[0xF0, 0, 0, 0, 0, 0, 0, 0x0F]
         );
+        assert_eq!(&ram.display_buffer()[..8], &[0xF0, 0, 0, 0, 0, 0, 0, 0x0F]);
+        assert_eq!(&ram.display_buffer()[8..31 * 8], &[0x00; 30 * 8]);
     }
 
     #[test]
-    fn set_i_data_to_vx_slice() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xFC55
-            NOOP
-        ));
-
-        // set each VX register to its index to generate some test data
-        let test_register_vals = [
-            0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
-        ];
-        ram.get_v_registers_mut()
-            .copy_from_slice(&test_register_vals);
-
-        // use I = 0x0300 and set some data at this location before executing the instruction
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xFF; 16], 0x0300).unwrap();
-
-        dbg!(&ram.bytes()[0x0300..][..16]);
+    fn vf_reset_quirk_off_leaves_vf() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0x8121 0x8122 0x8123 NOOP),
+            QUIRKS_OFF,
+        );
 
-        // execute the instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        for _ in 0..3 {
+            ram.get_v_registers_mut()[0xF] = 0xAA;
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_v_registers()[0xF], 0xAA);
+        }
+    }
 
-        // data pointed to by I should be updated
-        assert_eq!(
-            &ram.bytes()[0x0300..][..16],
-            &[0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xFF, 0xFF, 0xFF]
+    #[test]
+    fn vf_reset_quirk_on_resets_vf() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0x8121 0x8122 0x8123 NOOP),
+            Quirks {
+                vf_reset_on_logic: true,
+                ..QUIRKS_OFF
+            },
         );
 
-        // value of I should be incremented on COSMAC VIP CHIP-8.
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0300 + 0xC + 1);
+        for _ in 0..3 {
+            ram.get_v_registers_mut()[0xF] = 0xAA;
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_v_registers()[0xF], 0x00);
+        }
     }
 
     #[test]
-    fn set_vx_slice_to_i_data() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xFC65
-            NOOP
-        ));
+    fn display_wait_quirk_off_draws_immediately() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xD011 0xD011 NOOP),
+            QUIRKS_OFF,
+        );
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
 
-        // set I data
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        let test_data = [
-            0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
-        ];
-        ram.load_bytes(&test_data, 0x300);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+        assert_eq!(ram.display_buffer()[0], 0x00);
+    }
 
-        // Fill VX registers with existing data
-        ram.get_v_registers_mut().copy_from_slice(&[0xFF; 16]);
+    #[test]
+    fn display_wait_quirk_on_waits_for_next_jiffy() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xD011 0xD011 NOOP),
+            Quirks {
+                display_wait: true,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
 
-        // execute the instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        // nothing has been drawn yet, so the first draw goes straight through
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+        assert_eq!(ram.display_buffer()[0], 0xFF);
 
-        // check data copied
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
         assert_eq!(
-            ram.get_v_registers(),
-            &[0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xFF, 0xFF, 0xFF]
+            ram.display_buffer()[0],
+            0xFF,
+            "Sprite should not be drawn yet"
         );
 
-        // check I incremented
-        assert_eq!(ram.get_u16_at(I_ADDRESS), 0x0300 + 0xC + 1);
+        MockClock::advance(APPROX_JIFFY - MILLISECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+
+        MockClock::advance(MILLISECOND);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+        assert_eq!(ram.display_buffer()[0], 0x00);
+        assert_eq!(ram.get_v_registers()[0xF], 0x01);
     }
 
     #[test]
-    fn erase_display() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x00E0
-            NOOP
-        ));
+    fn display_wait_quirk_on_waits_for_next_tick() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(0xD011 0xD011 NOOP),
+            Quirks {
+                display_wait: true,
+                ..QUIRKS_OFF
+            },
+        );
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
 
-        // Set dummy data in the display refresh
-        ram.load_bytes(&[0xA5; 256], DISPLAY_REFRESH_START_ADDRESS)
-            .expect("256 bytes should fit in display refresh memory.");
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        MockClock::advance(Duration::from_secs(1));
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+        assert_eq!(ram.display_buffer()[0], 0xFF);
+
+        chip8.tick_60hz(&mut ram);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0204);
+        assert_eq!(ram.display_buffer()[0], 0x00);
+    }
 
-        assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
-            &[0x00; 256]
-        );
+    #[test]
+    fn i_overflow_quirk_off_leaves_vf() {
+        // Going past the program area without the quirk is caught by debug
+        // checks, so only the boundary below it is tested here.
+        let (mut ram, mut chip8) =
+            new_chip8_with_program_and_quirks(&chip8_program_into_bytes!(0xF11E NOOP), QUIRKS_OFF);
+        let last_address = ram.program_last_address() as u16;
+        ram.set_work_area_u16(I_OFFSET, last_address - 1);
+        ram.get_v_registers_mut()[1] = 1;
+        ram.get_v_registers_mut()[0xF] = 0xAA;
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), last_address);
+        assert_eq!(ram.get_v_registers()[0xF], 0xAA);
     }
 
     #[test]
-    fn draw_sprite_of_size_zero() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD120
-            NOOP
-        ));
+    fn i_overflow_quirk_on_sets_vf() {
+        // (I, expected I after adding VX = 1, expected VF)
+        let cases = [(0x0FFE, 0x0FFF, 0), (0x0FFF, 0x0000, 1)];
+
+        for (i, expected_i, expected_vf) in cases {
+            let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+                &chip8_program_into_bytes!(0xF11E NOOP),
+                Quirks {
+                    i_overflow_sets_vf: true,
+                    ..QUIRKS_OFF
+                },
+            );
+            ram.set_work_area_u16(I_OFFSET, i);
+            ram.get_v_registers_mut()[1] = 1;
+            ram.get_v_registers_mut()[0xF] = 0xAA;
+
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_work_area_u16(I_OFFSET), expected_i);
+            assert_eq!(ram.get_v_registers()[0xF], expected_vf);
+        }
+    }
 
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xAA; 16], 0x0300); // dummy data that should not move to display buffer
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+    // Draws a 16x16 sprite in hires mode with its bottom 8 rows off the
+    // display, then draws it again over itself.
+    fn draw_clipped_big_sprite(quirks: Quirks) -> (CosmacRAM, Chip8Interpreter<MockChip8Rng>) {
+        let (mut ram, chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0x00FF  // hires
+                0xA300
+                0x6000
+                0x6138  // V1 = 56
+                0xD010
+                0xD010
+            ),
+            quirks,
+        );
+        ram.load_bytes(&[0xFF; 32], 0x300).unwrap();
+        (ram, chip8)
+    }
 
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+    #[test]
+    fn hires_vf_counts_rows_quirk_off_sets_collision_flag() {
+        let (mut ram, mut chip8) = draw_clipped_big_sprite(QUIRKS_OFF);
+        chip8.step_n(&mut ram, 5).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 0);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 1);
+    }
 
+    #[test]
+    fn hires_vf_counts_rows_quirk_on_counts_clipped_and_collided_rows() {
+        let (mut ram, mut chip8) = draw_clipped_big_sprite(Quirks {
+            hires_vf_counts_rows: true,
+            ..QUIRKS_OFF
+        });
+        chip8.step_n(&mut ram, 5).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 8, "8 rows clipped");
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
-            &[0x00; 256],
-            "Display buffer should be unchanged for sprite of size zero"
+            ram.get_v_registers()[0xF],
+            16,
+            "8 rows collided and 8 rows clipped"
+        );
+    }
+
+    #[test]
+    fn hires_vf_counts_rows_quirk_only_applies_in_hires_mode() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0xA300
+                0x6000
+                0x611C  // V1 = 28
+                0xD018
+                0xD018
+            ),
+            Quirks {
+                hires_vf_counts_rows: true,
+                ..QUIRKS_OFF
+            },
+        );
+        ram.load_bytes(&[0xFF; 8], 0x300).unwrap();
+        chip8.step_n(&mut ram, 4).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 0);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0xF], 1);
+    }
+
+    #[test]
+    fn hires_vf_counts_rows_once_across_planes() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0x00FF  // hires
+                0xF301  // select both planes
+                0xA300
+                0x6000
+                0x613C  // V1 = 60
+                0xD018
+                0xD018
+            ),
+            Quirks {
+                hires_vf_counts_rows: true,
+                ..QUIRKS_OFF
+            },
         );
+        ram.load_bytes(&[0xFF; 16], 0x300).unwrap();
+        chip8.step_n(&mut ram, 6).unwrap();
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
+            ram.get_v_registers()[0xF],
+            4,
+            "4 rows clipped on both planes"
         );
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
             ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+            8,
+            "4 rows collided and 4 rows clipped on both planes"
         );
     }
 
     #[test]
-    fn draw_sprite_entirely_below_screen() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD12F
-            NOOP
-        ));
-
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xAA; 16], 0x0300); // dummy data that should not move to display buffer
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+    fn key_wait_tone_quirk_off_stays_silent() {
+        let (mut ram, mut chip8) =
+            new_chip8_with_program_and_quirks(&chip8_program_into_bytes!(0xF00A NOOP), QUIRKS_OFF);
+
+        chip8.step(&mut ram).unwrap();
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        chip8.step(&mut ram).unwrap();
+        assert!(!Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+    }
 
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 0; // horizontal: on screen
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 32; // vertical: off screen (screen is 32 pixels high)
+    #[test]
+    fn key_wait_tone_quirk_on_sounds_while_key_held() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0x6004  // V0 = 4
+                0xF018  // tone for 4 jiffies
+                0xF10A
+                NOOP
+            ),
+            Quirks {
+                key_wait_tone: true,
+                ..QUIRKS_OFF
+            },
+        );
 
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert!(Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+
+        // tone set by FX18 runs out while waiting
+        MockClock::advance(3 * EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 1);
+        assert!(!Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+
+        // key held, tone sounds without touching the tone timer
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        chip8.step(&mut ram).unwrap();
+        assert!(Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 1);
+
+        MockClock::advance(EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        assert!(Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 0);
+
+        // key released, tone stops and the program continues
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, false);
+        chip8.step(&mut ram).unwrap();
+        assert!(!Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x206);
+    }
 
-        assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
-            &[0x00; 256],
-            "Display buffer should be unchanged for sprite drawn off screen"
-        );
-        assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
-        );
-        assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+    #[test]
+    fn key_wait_tone_keeps_fx18_tone_timer() {
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0x6014  // V0 = 20
+                0xF018  // tone for 20 jiffies
+                0xF10A
+                NOOP
+            ),
+            Quirks {
+                key_wait_tone: true,
+                ..QUIRKS_OFF
+            },
         );
+
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        MockClock::advance(5 * EXACT_JIFFY);
+        chip8.step(&mut ram).unwrap();
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, false);
+        chip8.step(&mut ram).unwrap();
+
+        // still sounding for the rest of the FX18 tone
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x206);
+        assert_eq!(ram.get_work_area_u16(TONE_TIMER_OFFSET), 15);
+        assert!(Chip8Interpreter::<MockChip8Rng>::is_tone_sounding(&ram));
     }
 
     #[test]
-    fn draw_sprite_entirely_to_right_of_screen() {
+    fn switch_between_lores_and_hires() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD12F
+            0x00FF
+            0xD011
+            0x00FE
             NOOP
         ));
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
+        ram.load_bytes(&[0xFF], 0x0300).unwrap();
+        ram.get_v_registers_mut()[0] = 124; // past the right edge in lores
+        ram.get_v_registers_mut()[1] = 63; // past the bottom edge in lores
+
+        assert_eq!(ram.display_size(), LORES_DISPLAY_SIZE);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_size(), HIRES_DISPLAY_SIZE);
+
+        // the sprite is clipped by the right edge of the 128x64 display
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.display_buffer()[1008..1023], &[0; 15]);
+        assert_eq!(ram.display_buffer()[1023], 0x0F);
+        assert_eq!(&ram.display_buffer()[..1008], &[0; 1008]);
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_size(), LORES_DISPLAY_SIZE);
+        assert_eq!(ram.display_buffer(), &[0; 256]);
+    }
 
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xAA; 16], 0x0300); // dummy sprite data that should not move to display buffer
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
-
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 64; // horizontal: off screen (screen is 64 pixels wide)
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 0; // vertical: on screen
-
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+    #[test]
+    fn new_interpreter_has_only_cosmac_vip_opcodes() {
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x00FF 0xF001))
+            .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
 
+        // without SCHIP, 00FF calls a machine language subroutine
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..256],
-            &[0x00; 256],
-            "Display buffer should be unchanged for sprite drawn off screen"
-        );
-        assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
-        );
-        assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+            chip8.step(&mut ram),
+            Err(Error::MachineLanguageCallUnsupported(0x00FF))
         );
+
+        let mut chip8 = Chip8Interpreter::with_quirks(MockChip8Rng::new(), Quirks::xo_chip());
+        chip8.reset(&mut ram);
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0202);
+        assert_eq!(chip8.step(&mut ram), Err(Error::UnknownInstruction(0xF001)));
     }
 
     #[test]
-    fn draw_sprite_partially_cut_off_screen() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD12F
+    fn variants_differ_in_quirks_and_opcodes() {
+        // (variant, VX after 8126, I after F165, hires available, DXYN waits
+        // for the display)
+        let cases = [
+            (Chip8Variant::CosmacVip, 0x02, 0x0302, false, true),
+            (Chip8Variant::Chip48, 0x01, 0x0301, false, false),
+            (Chip8Variant::SuperChip1_1, 0x01, 0x0300, true, false),
+            (Chip8Variant::XoChip, 0x02, 0x0302, true, false),
+        ];
+
+        for (variant, expected_vx, expected_i, has_hires, display_wait) in cases {
+            assert_eq!(
+                variant.quirks().display_wait,
+                display_wait,
+                "display wait for {variant:?}"
+            );
+            let mut ram = CosmacRAM::new();
+            let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), variant);
+            ram.load_chip8_program(&chip8_program_into_bytes!(
+                0x8126
+                0xF165
+                0x00FF
+                NOOP
+            ))
+            .expect("Should be ok to load this test program.");
+            chip8.reset(&mut ram);
+
+            ram.get_v_registers_mut()[1] = 0x03;
+            ram.get_v_registers_mut()[2] = 0x05;
+            ram.set_work_area_u16(I_OFFSET, 0x0300);
+
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(ram.get_v_registers()[1], expected_vx, "VX for {variant:?}");
+            chip8.step(&mut ram).unwrap();
+            assert_eq!(
+                ram.get_work_area_u16(I_OFFSET),
+                expected_i,
+                "I for {variant:?}"
+            );
+
+            assert_eq!(
+                chip8.step(&mut ram).is_ok(),
+                has_hires,
+                "00FF for {variant:?}"
+            );
+            if has_hires {
+                assert_eq!(ram.display_size(), HIRES_DISPLAY_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn chip48_load_store_leaves_i_on_last_register() {
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::Chip48);
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0xF355
+            0xF065
+            0xF265
             NOOP
-        ));
+        ))
+        .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
 
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xFF; 16], 0x0300); // dummy sprite data
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
+        ram.get_v_registers_mut()[..4].copy_from_slice(&[0xA0, 0xA1, 0xA2, 0xA3]);
+        ram.set_work_area_u16(I_OFFSET, 0x0300);
 
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 63; // horizontal: last pixel
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 31; // vertical: last pixel
+        // I = I + X, so I points at the copy of V3
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.bytes()[0x0300..][..4], &[0xA0, 0xA1, 0xA2, 0xA3]);
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0303);
 
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        // X = 0 leaves I where it is
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.get_v_registers()[0], 0xA3);
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0303);
 
-        assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..255],
-            &[0x00; 255],
-            "Display buffer should be unchanged where sprite not drawn"
-        );
-        assert_eq!(
-            ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][255],
-            0x01,
-            "Last pixel in buffer should be drawn"
-        );
+        ram.set_work_area_u16(I_OFFSET, 0x0301);
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(&ram.get_v_registers()[..3], &[0xA1, 0xA2, 0xA3]);
+        assert_eq!(ram.get_work_area_u16(I_OFFSET), 0x0303);
+    }
 
+    #[test]
+    fn chip48_has_no_scroll_opcodes() {
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::Chip48);
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0x00FB
+            NOOP
+        ))
+        .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
+
+        // without SCHIP, 00FB is a call to a machine language subroutine
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
-        );
-        assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+            chip8.step(&mut ram),
+            Err(Error::MachineLanguageCallUnsupported(0x00FB))
         );
     }
 
     #[test]
-    fn draw_sprite_within_screen_with_vx_aligned_to_display_buffer_bytes() {
-        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD122
+    fn two_page_hires_program() {
+        // The ROM's jump to the interpreter patch, the patch (zeroed out here)
+        // and then the CHIP-8 program at 0x02C0.
+        let mut program = chip8_program_into_bytes!(0x1260);
+        program.resize(0x02C0 - PROGRAM_START_ADDRESS, 0);
+        program.extend(chip8_program_into_bytes!(
+            0x6020  // V0 = 32
+            0x6128  // V1 = 40, below the bottom of the lores display
+            0xA300
+            0xD012
+            0x00E0
             NOOP
         ));
 
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xFF; 16], 0x0300); // dummy sprite data
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
-
-        // Make sure the sprite position is aligned to display buffer bytes
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 8; // a horizontal pixel offset aligned to display buffer bytes
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 1; // second pixel row
+        let mut ram = CosmacRAM::new();
+        let mut chip8 =
+            Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::HiresChip8);
+        ram.load_chip8_program(&program)
+            .expect("Should be ok to load this test program.");
+        ram.load_bytes(&[0xFF, 0x81], 0x0300).unwrap();
+        chip8.reset(&mut ram);
 
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x02C0);
+        assert_eq!(ram.display_size(), TWO_PAGE_HIRES_DISPLAY_SIZE);
+        assert_eq!(ram.display_buffer().len(), 512);
 
-        // Check pixels by checking the display buffer bytes.
-        // Each row is 64 pixels (8 bytes) wide.
-        // Since the VX coordinate is byte-aligned, we expect only the second
-        // byte for each of the 2 rows to be affected.
-        // This diagram shows the layout, where each X signifies a byte of
-        // display memory that should change by the DXYN instruction:
-        //   0 0 0 .
-        //   0 X 0 .
-        //   0 X 0 .
-        //   0 0 0 .
-        //   . . . .
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+        let mut expected_display = [0; 512];
+        expected_display[40 * 8 + 4] = 0xFF;
+        expected_display[41 * 8 + 4] = 0x81;
+        assert_eq!(ram.display_buffer(), &expected_display);
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..8],
-            &[0x00; 8],
-            "No pixels should be written to first row"
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..],
+            &[0; 256],
+            "Both display pages should be outside of the display refresh area"
         );
+
+        chip8.step(&mut ram).unwrap();
+        assert_eq!(ram.display_buffer(), &[0; 512]);
+    }
+
+    #[test]
+    fn access_beyond_end_of_ram_is_an_error() {
+        let out_of_bounds = || Error::MemoryOutOfBounds { address: 0x1000 };
+        for (instruction, error) in [
+            (0xD013, out_of_bounds()),
+            (0xF033, out_of_bounds()),
+            // FX55 and FX65 would also leave I past the end of RAM, which is
+            // checked first
+            (0xF255, Error::IAddressOutOfRange(0x1001)),
+            (0xF265, Error::IAddressOutOfRange(0x1001)),
+            (0xF002, out_of_bounds()),
+        ] {
+            let program = [u16::to_be_bytes(instruction), u16::to_be_bytes(0x7000)].concat();
+            let (mut ram, mut chip8) = new_chip8_with_program(&program);
+            ram.set_work_area_u16(I_OFFSET, 0x0FFE);
+            let ram_before = ram.bytes().to_vec();
+
+            assert_eq!(chip8.step(&mut ram), Err(error), "{instruction:#06X}");
+            assert_eq!(ram.bytes(), ram_before.as_slice(), "{instruction:#06X}");
+        }
+    }
+
+    #[test]
+    fn instruction_leaving_pc_or_i_out_of_range_changes_nothing() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x6107  // V1 = 7
+            0xF155  // store V0 and V1 at I, leaving I past the program area
+            0x2FFE  // call a subroutine past the program area
+        ));
+        let last_address = ram.program_last_address() as u16;
+        ram.set_work_area_u16(I_OFFSET, last_address - 1);
+        chip8.step(&mut ram).unwrap();
+
+        let ram_before = ram.bytes().to_vec();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
-            &[0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-            "Pixels should only be written to the second byte on the second row"
+            chip8.step(&mut ram),
+            Err(Error::IAddressOutOfRange(last_address + 1))
         );
+        assert_eq!(ram.bytes(), ram_before.as_slice());
+
+        ram.set_work_area_u16(PROGRAM_COUNTER_OFFSET, 0x0204);
+        let ram_before = ram.bytes().to_vec();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
-            &[0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-            "Pixels should only be written to the second byte on the third row"
+            chip8.step(&mut ram),
+            Err(Error::ProgramCounterOutOfRange(0x0FFE))
         );
-        assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][24..32],
-            &[0x00; 8],
-            "No pixels should be written to fourth row"
+        assert_eq!(ram.bytes(), ram_before.as_slice());
+        assert_eq!(ram.chip8_stack_depth(), 0);
+    }
+
+    #[test]
+    fn step_outcomes() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA300
+            0xD001
+            0x00E0
+            0x6005
+            0xF018
+            0xF018
+            0xF10A
+            NOOP
+        ));
+
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::DrewToDisplay));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::ClearedDisplay));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::SoundStarted));
+        assert_eq!(
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Executed),
+            "Tone is already sounding"
         );
 
-        // check registers
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::WaitingForKey));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::WaitingForKey));
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, true);
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0x1, false);
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x020E);
+    }
+
+    #[test]
+    fn watchpoint_on_fx55_write() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA300
+            0xF255
+            0xA310
+            0xF255
+            NOOP
+        ));
+        chip8.add_watchpoint(0x0312..0x0320, Watch::Write);
+        chip8.add_watchpoint(0x0300..0x0310, Watch::Read);
+
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Executed),
+            "Write outside of the watched range, and read watchpoints don't trip on writes"
         );
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Watchpoint {
+                address: 0x0312,
+                pc: 0x0206,
+                kind: Watch::Write
+            })
+        );
+        assert_eq!(
+            ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET),
+            0x0208,
+            "The instruction should have been executed"
         );
     }
 
     #[test]
-    fn draw_sprite_within_screen_with_vx_not_aligned_to_display_buffer_bytes() {
+    fn watchpoint_on_v_registers_and_display() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD122
+            0xA300
+            0xF165
+            0x00E0
             NOOP
         ));
+        chip8.add_watchpoint(
+            V_REGISTERS_START_ADDRESS + 1..V_REGISTERS_START_ADDRESS + 2,
+            Watch::Write,
+        );
+        chip8.add_watchpoint(
+            DISPLAY_REFRESH_START_ADDRESS + 8..DISPLAY_REFRESH_START_ADDRESS + 9,
+            Watch::Write,
+        );
 
-        ram.zero_out_range(DISPLAY_REFRESH_START_ADDRESS..DISPLAY_REFRESH_START_ADDRESS + 256)
-            .expect("Should be able to zero out display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xFF; 16], 0x0300); // dummy sprite data
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 0
-
-        // Make sure the sprite position crosses display buffer byte boundaries
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 2; // horizontal: third pixel
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 2; // vertical: third pixel
-
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
-
-        // Check pixels by checking the display buffer bytes.
-        // Each row is 64 pixels (8 bytes) wide.
-        // Expect the first two bytes for 2 rows to be affected.
-        // This diagram shows the layout, where each X signifies a byte of
-        // display memory that should change by the DXYN instruction:
-        //   0 0 0 .
-        //   0 0 0 .
-        //   X X 0 .
-        //   X X 0 .
-        //   0 0 0 .
-        //   . . . .
+        chip8.step(&mut ram).unwrap();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
-            &[0x00; 8],
-            "No pixels should be written to second row"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Watchpoint {
+                address: V_REGISTERS_START_ADDRESS + 1,
+                pc: 0x0202,
+                kind: Watch::Write
+            })
         );
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
-            &[0b0011_1111, 0b1100_0000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-            "Pixels should be written to the first two bytes of third row"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Watchpoint {
+                address: DISPLAY_REFRESH_START_ADDRESS + 8,
+                pc: 0x0204,
+                kind: Watch::Write
+            })
         );
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][24..32],
-            &[0b0011_1111, 0b1100_0000, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
-            "Pixels should be written to the first two bytes of fourth row"
+            ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET),
+            0x0206,
+            "The instruction should have been executed"
         );
+
+        chip8.clear_watchpoints();
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+    }
+
+    #[test]
+    fn step_n_stops_on_watchpoint() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xA300
+            0x7001
+            0xF055
+            0x1202
+        ));
+        chip8.add_watchpoint(0x0300..0x0301, Watch::Write);
+
+        let report = chip8.step_n(&mut ram, 10).unwrap();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][32..40],
-            &[0x00; 8],
-            "No pixels should be written to fifth row"
+            report,
+            StepsReport {
+                steps: 3,
+                stop_reason: StopReason::Watchpoint {
+                    address: 0x0300,
+                    pc: 0x0204,
+                    kind: Watch::Write
+                },
+            }
         );
+        assert_eq!(ram.bytes()[0x0300], 1);
+    }
 
-        // check registers
+    #[test]
+    fn watchpoint_on_key_wait_write() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0xF30A
+            NOOP
+        ));
+        chip8.add_watchpoint(
+            V_REGISTERS_START_ADDRESS + 3..V_REGISTERS_START_ADDRESS + 4,
+            Watch::Write,
+        );
+
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::WaitingForKey));
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0xB, true);
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::Watchpoint {
+                address: V_REGISTERS_START_ADDRESS + 3,
+                pc: 0x0200,
+                kind: Watch::Write
+            }),
+            "Taking the key writes it to VX"
         );
+        assert_eq!(ram.get_v_registers()[3], 0xB);
         assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x00,
-            "No pixels collisions so VF should be zero"
+            chip8.step(&mut ram),
+            Ok(StepOutcome::WaitingForKey),
+            "The key is only written once, while it's held"
         );
+        Chip8Interpreter::<MockChip8Rng>::set_key_state(&mut ram, 0xB, false);
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
     }
 
     #[test]
-    fn draw_sprite_xors_existing_data() {
+    fn step_n_stops_at_step_limit() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0xD121
-            NOOP
+            0x7001
+            0x1200
         ));
 
-        ram.load_bytes(&[0xFF; 256], DISPLAY_REFRESH_START_ADDRESS)
-            .expect("Should be able to write to entire display refresh buffer.");
-        ram.set_u16_at(I_ADDRESS, 0x0300);
-        ram.load_bytes(&[0xAA; 1], 0x0300); // dummy sprite data to check xor
-        ram.get_v_registers_mut()[0xF] = 0xAA; // dummy VF value that should be overwritten to 1
+        let report = chip8.step_n(&mut ram, 5).unwrap();
+        assert_eq!(
+            report,
+            StepsReport {
+                steps: 5,
+                stop_reason: StopReason::StepLimit
+            }
+        );
+        assert_eq!(ram.get_v_registers()[0], 3);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
 
-        // Make sure the sprite position crosses display buffer byte boundaries
-        let v1 = &mut ram.get_v_registers_mut()[1];
-        *v1 = 2; // horizontal: third pixel
-        let v2 = &mut ram.get_v_registers_mut()[2];
-        *v2 = 1; // vertical: second pixel
+    #[test]
+    fn step_n_stops_on_key_wait() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            NOOP
+            0xF00A
+            NOOP
+        ));
 
-        // execute DXYN instruction
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        let report = chip8.step_n(&mut ram, 10).unwrap();
+        assert_eq!(
+            report,
+            StepsReport {
+                steps: 2,
+                stop_reason: StopReason::WaitingForKey
+            }
+        );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
 
-        // Check pixels by checking the display buffer bytes.
-        // Each row is 64 pixels (8 bytes) wide.
-        // Expect the first two bytes for 2 rows to be affected.
-        // This diagram shows the layout, where each X signifies a byte of
-        // display memory that should change by the DXYN instruction:
-        //   0 0 0 .
-        //   X X 0 .
-        //   0 0 0 .
-        //   . . . .
+    #[test]
+    fn step_n_stops_on_exit() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            NOOP
+            0x00FD
+        ));
+
+        let report = chip8.step_n(&mut ram, 10).unwrap();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][..8],
-            &[0xFF; 8],
-            "No new pixels should be written to first row"
+            report,
+            StepsReport {
+                steps: 2,
+                stop_reason: StopReason::Exited
+            }
+        );
+    }
+
+    #[test]
+    fn step_n_stops_on_error() {
+        // I can only be left at the top of memory with the FX1E overflow quirk
+        let (mut ram, mut chip8) = new_chip8_with_program_and_quirks(
+            &chip8_program_into_bytes!(
+                0xAFFE
+                0xF033
+                NOOP
+            ),
+            Quirks {
+                i_overflow_sets_vf: true,
+                ..Quirks::cosmac_vip()
+            },
         );
+
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][8..16],
-            &[0b1101_0101, 0b0111_1111, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
-            "Pixels should be XORed to the first two bytes of second row"
+            chip8.step_n(&mut ram, 10),
+            Err(Error::MemoryOutOfBounds { address: 0x1000 })
         );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
+    }
+
+    #[test]
+    fn run_until_draw_stops_after_draw() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x00E0
+            0xA300
+            NOOP
+            0xD001
+            NOOP
+        ));
+
+        let report = chip8.run_until_draw(&mut ram, 10).unwrap();
         assert_eq!(
-            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..][16..24],
-            &[0xFF; 8],
-            "No new pixels should be written to third row"
+            report,
+            StepsReport {
+                steps: 1,
+                stop_reason: StopReason::Drew
+            }
         );
 
-        // check registers
+        let report = chip8.run_until_draw(&mut ram, 10).unwrap();
         assert_eq!(
-            ram.get_u16_at(I_ADDRESS),
-            0x0300,
-            "DXYN instruction should leave I unchanged"
+            report,
+            StepsReport {
+                steps: 3,
+                stop_reason: StopReason::Drew
+            }
         );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0208);
+    }
+
+    #[test]
+    fn run_until_draw_stops_at_max_steps() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x7001
+            0x1200
+        ));
+
+        let report = chip8.run_until_draw(&mut ram, 100).unwrap();
         assert_eq!(
-            ram.get_v_registers()[0xF],
-            0x01,
-            "VF should be 0x01 since pixel collision occurred"
+            report,
+            StepsReport {
+                steps: 100,
+                stop_reason: StopReason::StepLimit
+            }
         );
     }
 
     #[test]
-    fn set_vx_register_vx_bitwise_xor_vy() {
+    fn exit_program() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8123
-            NOOP
+            0x6001
+            0x00FD
         ));
 
-        ram.get_v_registers_mut()[1] = 0b0011_0101;
-        ram.get_v_registers_mut()[2] = 0b0110_0110;
-        chip8.step(&mut ram);
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Exited));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
 
-        assert_eq!(ram.get_v_registers()[1], 0b0101_0011);
-        assert_eq!(ram.get_v_registers()[2], 0b0110_0110);
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        let ram_after_exit = ram.bytes().to_vec();
+        for _ in 0..3 {
+            assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Exited));
+        }
+        assert_eq!(ram.bytes(), ram_after_exit.as_slice());
     }
 
     #[test]
-    fn set_vx_register_vy_lshift() {
+    fn machine_language_call_is_an_error_by_default() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x812E
-            0x811E
+            0x0000
             NOOP
         ));
 
-        ram.get_v_registers_mut()[0x1] = 0x00;
-        ram.get_v_registers_mut()[0x2] = 0b0110_0110;
-        ram.get_v_registers_mut()[0xF] = 0xFF; // dummy value to be overwritten
+        assert_eq!(
+            chip8.step(&mut ram),
+            Err(Error::MachineLanguageCallUnsupported(0x0000))
+        );
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
+    }
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x1], 0b1100_1100); // vx = vy << 1
-        assert_eq!(ram.get_v_registers()[0x2], 0b0110_0110); // vy unchanged
-        assert_eq!(ram.get_v_registers()[0xF], 0x00); // no overflow
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+    #[test]
+    fn ignore_machine_language_calls() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
+            0x0000
+            0x0ABC
+            0x6001
+            NOOP
+        ));
+        chip8.set_machine_language_calls(MachineLanguageCalls::Ignore);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x1], 0b1001_1000); // vx = vx << 1
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // overflow
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x204);
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0206);
+        assert_eq!(ram.get_v_registers()[0], 0x01);
     }
 
     #[test]
-    fn set_vx_register_vy_rshift() {
+    fn jump_to_self_halts() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8126
-            0x8116
-            NOOP
+            0x6001
+            0x1202
         ));
 
-        ram.get_v_registers_mut()[0x1] = 0x00;
-        ram.get_v_registers_mut()[0x2] = 0b0110_0110;
-        ram.get_v_registers_mut()[0xF] = 0xFF; // dummy value to be overwritten
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Halted));
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Halted));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0202);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x1], 0b0011_0011); // vx = vy >> 1
-        assert_eq!(ram.get_v_registers()[0x2], 0b0110_0110); // vy unchanged
-        assert_eq!(ram.get_v_registers()[0xF], 0x00); // no overflow
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x202);
+        chip8.reset(&mut ram);
+        assert_eq!(
+            chip8.step_n(&mut ram, 10),
+            Ok(StepsReport {
+                steps: 2,
+                stop_reason: StopReason::Halted
+            })
+        );
+    }
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x1], 0b0001_1001); // vx = vx >> 1
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // overflow
-        assert_eq!(ram.get_u16_at(PROGRAM_COUNTER_ADDRESS), 0x204);
+    #[test]
+    fn call_to_self_halts() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x2200));
+
+        assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Halted));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
     }
 
     #[test]
-    fn set_vx_register_vy_sub_vx() {
+    fn polling_loop_does_not_halt() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
-            0x8017
-            0x8237
-            0x8457
-            NOOP
+            0x6002  // 0x0200
+            0xF015  // 0x0202
+            0xF007  // 0x0204, poll the timer
+            0x3000  // 0x0206
+            0x1204  // 0x0208, until it runs out
+            NOOP    // 0x020A
         ));
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
 
-        // vy == vx
-        ram.get_v_registers_mut()[0x0] = 0xF0;
-        ram.get_v_registers_mut()[0x1] = 0xF0;
+        let report = chip8.step_n(&mut ram, 100).unwrap();
+        assert_eq!(report.stop_reason, StopReason::StepLimit);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0208);
 
-        // vy < vx
-        ram.get_v_registers_mut()[0x2] = 0xF0;
-        ram.get_v_registers_mut()[0x3] = 0x0F;
+        // the loop ends once the timer runs out
+        chip8.tick_60hz(&mut ram);
+        chip8.tick_60hz(&mut ram);
+        for _ in 0..3 {
+            assert_eq!(chip8.step(&mut ram), Ok(StepOutcome::Executed));
+        }
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x020A);
+    }
 
-        // vy > vx
-        ram.get_v_registers_mut()[0x4] = 0x0F;
-        ram.get_v_registers_mut()[0x5] = 0xF0;
+    #[test]
+    fn eti_660_program() {
+        let program = chip8_program_into_bytes!(
+            0x6005  // V0 = 5
+            0x2606  // call the subroutine at 0x0606
+            0x1604  // loop
+            0x7001  // V0 += 1
+            0x00EE
+        );
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x0], 0x00);
-        assert_eq!(ram.get_v_registers()[0x1], 0xF0);
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::Eti660);
+        ram.load_chip8_program_at(&program, ETI_660_PROGRAM_START_ADDRESS)
+            .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x2], 0x1F);
-        assert_eq!(ram.get_v_registers()[0x3], 0x0F);
-        assert_eq!(ram.get_v_registers()[0xF], 0x00); // carry should be zero
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0600);
+        assert_eq!(ram.display_size(), ETI_660_DISPLAY_SIZE);
+        assert_eq!(ram.display_buffer().len(), 384);
 
-        chip8.step(&mut ram);
-        assert_eq!(ram.get_v_registers()[0x4], 0xE1);
-        assert_eq!(ram.get_v_registers()[0x5], 0xF0);
-        assert_eq!(ram.get_v_registers()[0xF], 0x01); // carry should be one
+        for _ in 0..4 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.get_v_registers()[0], 6);
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0604);
+    }
+
+    #[test]
+    fn call_with_full_stack() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x2200));
+
+        for _ in 0..12 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(ram.chip8_stack_depth(), 12);
+        assert_eq!(chip8.step(&mut ram), Err(Error::Chip8StackOverflow));
+    }
+
+    #[test]
+    fn call_with_stack_pointer_outside_stack() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x2200));
+        ram.set_work_area_u16(STACK_POINTER_OFFSET, 0xFFF0);
+
+        assert_eq!(chip8.step(&mut ram), Err(Error::Chip8StackOverflow));
+    }
+
+    #[test]
+    fn return_with_empty_stack() {
+        let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(0x00EE));
+
+        assert_eq!(chip8.step(&mut ram), Err(Error::Chip8StackUnderflow));
+        // the stack pointer is left alone
+        assert_eq!(
+            ram.get_work_area_u16(STACK_POINTER_OFFSET) as usize,
+            ram.stack_start_address()
+        );
+    }
+
+    #[test]
+    fn eti_660_jump_below_program_area() {
+        let program = chip8_program_into_bytes!(0x1200);
+
+        let mut ram = CosmacRAM::new();
+        let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::Eti660);
+        ram.load_chip8_program_at(&program, ETI_660_PROGRAM_START_ADDRESS)
+            .expect("Should be ok to load this test program.");
+        chip8.reset(&mut ram);
+
+        assert!(matches!(
+            chip8.step(&mut ram),
+            Err(Error::ProgramCounterOutOfRange(0x0200))
+        ));
     }
 
     #[test]
-    #[should_panic(expected = "Unknown CHIP-8 instruction 0x9001")]
-    fn panic_on_unknown_opcode() {
+    fn error_on_unknown_opcode() {
         let (mut ram, mut chip8) = new_chip8_with_program(&chip8_program_into_bytes!(
             0x9001
             NOOP
         ));
 
-        chip8.step(&mut ram);
+        assert_eq!(chip8.step(&mut ram), Err(Error::UnknownInstruction(0x9001)));
+        assert_eq!(ram.get_work_area_u16(PROGRAM_COUNTER_OFFSET), 0x0200);
     }
 }
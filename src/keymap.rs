@@ -0,0 +1,397 @@
+//! Which host keyboard keys press the 16 CHIP-8 hex keys.
+//!
+//! The default [`KeyMap`] lays the COSMAC VIP keypad over the keys in the
+//! positions of these keys on a QWERTY keyboard:
+//!
+//! ```text
+//! 1 2 3 C      1 2 3 4
+//! 4 5 6 D  ->  Q W E R
+//! 7 8 9 E      A S D F
+//! A 0 B F      Z X C V
+//! ```
+//!
+//! It binds physical keys by their scancodes, so the keypad stays a 4x4 grid
+//! on other layouts, e.g. on AZERTY it is `1 2 3 4`, `A Z E R`, `Q S D F`
+//! and `W X C V`.
+//!
+//! Other bindings can be read from a file with a line for each hex key. A
+//! quoted name binds the key with that name as winit calls it, wherever it is
+//! in the layout, and a number binds the physical key with that scancode:
+//!
+//! ```text
+//! # the keys labelled 1 and Z, and the key right of the left shift key
+//! 1 = "Key1"
+//! 4 = "Z"
+//! A = 0x2C
+//! ```
+//!
+//! Hex keys that aren't listed are left unbound.
+
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use winit::event::{ScanCode, VirtualKeyCode};
+
+use crate::{Error, Result};
+
+/// The named keys that hex keys can be bound to, in the order they are listed
+/// in error messages.
+#[rustfmt::skip]
+const HOST_KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        Left, Up, Right, Down, Space, Return, Back, Tab,
+        Minus, Equals, LBracket, RBracket, Semicolon, Apostrophe, Comma, Period, Slash, Backslash,
+    ]
+};
+
+/// The scancodes of the physical keys bound to hex keys 0 to F by default:
+/// X, 1, 2, 3, Q, W, E, A, S, D, Z, C, 4, R, F and V on a QWERTY keyboard.
+/// winit gives PC set 1 scancodes, except on macOS.
+#[cfg(not(target_os = "macos"))]
+pub(crate) const DEFAULT_SCANCODES: [ScanCode; 16] = [
+    0x2D, 0x02, 0x03, 0x04, 0x10, 0x11, 0x12, 0x1E, 0x1F, 0x20, 0x2C, 0x2E, 0x05, 0x13, 0x21, 0x2F,
+];
+#[cfg(target_os = "macos")]
+pub(crate) const DEFAULT_SCANCODES: [ScanCode; 16] = [
+    0x07, 0x12, 0x13, 0x14, 0x0C, 0x0D, 0x0E, 0x00, 0x01, 0x02, 0x06, 0x08, 0x15, 0x0F, 0x03, 0x09,
+];
+
+/// A key on the host keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HostKey {
+    /// The key with this name in the keyboard layout, wherever it is.
+    Virtual(VirtualKeyCode),
+    /// The physical key with this scancode, whatever its name in the layout.
+    Scancode(ScanCode),
+}
+
+impl fmt::Display for HostKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostKey::Virtual(key_code) => write!(f, "{key_code:?}"),
+            HostKey::Scancode(scancode) => write!(f, "scancode {scancode:#04X}"),
+        }
+    }
+}
+
+impl From<VirtualKeyCode> for HostKey {
+    fn from(key_code: VirtualKeyCode) -> Self {
+        HostKey::Virtual(key_code)
+    }
+}
+
+/// A binding of each CHIP-8 hex key to at most one host key. No host key
+/// presses more than one hex key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    host_keys: [Option<HostKey>; 16],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            host_keys: DEFAULT_SCANCODES.map(|scancode| Some(HostKey::Scancode(scancode))),
+        }
+    }
+}
+
+impl KeyMap {
+    /// A key map with the bindings in `bindings`, from hex key to host key.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidKeyMap`] if a hex key is above `0xF`, or if a
+    /// host key is bound to more than one hex key.
+    pub fn from_map(bindings: &HashMap<u8, HostKey>) -> Result<Self> {
+        let mut key_map = Self {
+            host_keys: [None; 16],
+        };
+        let mut bindings: Vec<_> = bindings.iter().collect();
+        bindings.sort();
+        for (&hex_key, &host_key) in bindings {
+            if hex_key > 0xF {
+                return Err(Error::InvalidKeyMap(format!(
+                    "{hex_key:#X} is not a hex key"
+                )));
+            }
+            key_map.bind(hex_key, host_key)?;
+        }
+        Ok(key_map)
+    }
+
+    /// Read a key map from text in the format described in the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidKeyMap`] for the first line that isn't a
+    /// binding of a hex key to a known host key, or that binds a hex key or
+    /// host key already bound.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut key_map = Self {
+            host_keys: [None; 16],
+        };
+        for (line_index, line) in text.lines().enumerate() {
+            let invalid =
+                |reason: String| Error::InvalidKeyMap(format!("line {}: {reason}", line_index + 1));
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (hex_key, host_key) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected `HEX_KEY = \"KEY\"`, found `{line}`")))?;
+            let hex_key = hex_key.trim();
+            let hex_key = u8::from_str_radix(hex_key, 16)
+                .ok()
+                .filter(|&key| key <= 0xF && hex_key.len() == 1)
+                .ok_or_else(|| invalid(format!("\"{hex_key}\" is not a hex key")))?;
+            let host_key = host_key.trim();
+            let host_key = match host_key
+                .strip_prefix('"')
+                .and_then(|name| name.strip_suffix('"'))
+            {
+                Some(name) => HostKey::Virtual(virtual_key_from_name(name).ok_or_else(|| {
+                    invalid(format!(
+                        "unknown key \"{name}\", expected one of {}",
+                        virtual_key_names().join(", ")
+                    ))
+                })?),
+                None => HostKey::Scancode(parse_scancode(host_key).ok_or_else(|| {
+                    invalid(format!(
+                        "expected a quoted key name or a scancode, found {host_key}"
+                    ))
+                })?),
+            };
+
+            if key_map.host_keys[hex_key as usize].is_some() {
+                return Err(invalid(format!("hex key {hex_key:X} is bound twice")));
+            }
+            key_map.bind(hex_key, host_key).map_err(|err| match err {
+                Error::InvalidKeyMap(reason) => invalid(reason),
+                err => err,
+            })?;
+        }
+        Ok(key_map)
+    }
+
+    /// Read a key map from a file, see [`KeyMap::parse`].
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be read, or the same errors as
+    /// [`KeyMap::parse`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // Bind `hex_key` to `host_key`, replacing its current binding.
+    fn bind(&mut self, hex_key: u8, host_key: HostKey) -> Result<()> {
+        let bound = self
+            .host_keys
+            .iter()
+            .position(|&key| key == Some(host_key))
+            .filter(|&bound| bound != hex_key as usize);
+        if let Some(bound) = bound {
+            return Err(Error::InvalidKeyMap(format!(
+                "{host_key} is bound to both hex keys {bound:X} and {hex_key:X}"
+            )));
+        }
+        self.host_keys[hex_key as usize] = Some(host_key);
+        Ok(())
+    }
+
+    /// The hex key pressed by the host key with `scancode` and
+    /// `virtual_keycode`, as given by winit for a keyboard event, if any. A
+    /// binding of the scancode wins over a binding of the key code.
+    pub fn hex_key(
+        &self,
+        scancode: ScanCode,
+        virtual_keycode: Option<VirtualKeyCode>,
+    ) -> Option<u8> {
+        let position = |host_key| {
+            self.host_keys
+                .iter()
+                .position(|&key| key == Some(host_key))
+                .map(|hex_key| hex_key as u8)
+        };
+        position(HostKey::Scancode(scancode))
+            .or_else(|| virtual_keycode.and_then(|key_code| position(HostKey::Virtual(key_code))))
+    }
+
+    /// The host key that presses `hex_key`, if it is bound.
+    ///
+    /// # Panics
+    /// Panics if `hex_key > 0xF`.
+    pub fn host_key(&self, hex_key: u8) -> Option<HostKey> {
+        self.host_keys[hex_key as usize]
+    }
+}
+
+fn virtual_key_names() -> Vec<String> {
+    HOST_KEYS.iter().map(|key| format!("{key:?}")).collect()
+}
+
+fn virtual_key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    HOST_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{key:?}") == name)
+}
+
+// A scancode written in decimal, or in hex with a 0x prefix.
+fn parse_scancode(text: &str) -> Option<ScanCode> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => ScanCode::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use winit::event::VirtualKeyCode;
+
+    use super::{HostKey, KeyMap, DEFAULT_SCANCODES};
+    use crate::Error;
+
+    #[test]
+    fn default_key_map_uses_scancodes() {
+        let key_map = KeyMap::default();
+        for (hex_key, scancode) in (0..16).zip(DEFAULT_SCANCODES) {
+            assert_eq!(key_map.hex_key(scancode, None), Some(hex_key));
+            assert_eq!(key_map.host_key(hex_key), Some(HostKey::Scancode(scancode)));
+            // the physical key decides, whatever the layout calls it
+            assert_eq!(
+                key_map.hex_key(scancode, Some(VirtualKeyCode::Numpad5)),
+                Some(hex_key)
+            );
+        }
+        assert_eq!(key_map.hex_key(0x06, Some(VirtualKeyCode::Key5)), None);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn default_key_map_is_the_qwerty_grid() {
+        // the PC set 1 scancodes of the rows of keys on a QWERTY keyboard
+        let keypad = [
+            [(0x1, 0x02), (0x2, 0x03), (0x3, 0x04), (0xC, 0x05)],
+            [(0x4, 0x10), (0x5, 0x11), (0x6, 0x12), (0xD, 0x13)],
+            [(0x7, 0x1E), (0x8, 0x1F), (0x9, 0x20), (0xE, 0x21)],
+            [(0xA, 0x2C), (0x0, 0x2D), (0xB, 0x2E), (0xF, 0x2F)],
+        ];
+        let key_map = KeyMap::default();
+        for (hex_key, scancode) in keypad.into_iter().flatten() {
+            assert_eq!(key_map.hex_key(scancode, None), Some(hex_key));
+        }
+
+        let text = "1 = 2\n2 = 3\n3 = 4\nC = 5\n\
+            4 = 0x10\n5 = 0x11\n6 = 0x12\nD = 0x13\n\
+            7 = 0x1E\n8 = 0x1F\n9 = 0x20\nE = 0x21\n\
+            A = 0x2C\n0 = 0x2D\nB = 0x2E\nF = 0x2F\n";
+        assert_eq!(KeyMap::parse(text), Ok(key_map));
+    }
+
+    #[test]
+    fn parse_key_map() {
+        let text = "
+            # AZERTY
+            1 = \"Key1\"
+            4 = \"A\"   # top row
+            7 = \"Q\"
+            a=\"W\"
+            b = 0x2e
+            F = 47
+        ";
+        let key_map = KeyMap::parse(text).unwrap();
+        assert_eq!(key_map.hex_key(0x10, Some(VirtualKeyCode::A)), Some(0x4));
+        assert_eq!(key_map.hex_key(0x1E, Some(VirtualKeyCode::Q)), Some(0x7));
+        assert_eq!(key_map.hex_key(0x2E, None), Some(0xB));
+        assert_eq!(key_map.hex_key(0x2F, Some(VirtualKeyCode::V)), Some(0xF));
+        assert_eq!(
+            key_map.host_key(0xA),
+            Some(HostKey::Virtual(VirtualKeyCode::W))
+        );
+        assert_eq!(key_map.host_key(0xB), Some(HostKey::Scancode(0x2E)));
+        assert_eq!(key_map.host_key(0x2), None);
+        assert_eq!(key_map.hex_key(0x03, Some(VirtualKeyCode::Key2)), None);
+    }
+
+    #[test]
+    fn invalid_key_maps() {
+        let invalid = |text, reason: &str| {
+            assert_eq!(
+                KeyMap::parse(text),
+                Err(Error::InvalidKeyMap(reason.to_string())),
+                "{text}"
+            );
+        };
+        invalid(
+            "1 \"Key1\"",
+            "line 1: expected `HEX_KEY = \"KEY\"`, found `1 \"Key1\"`",
+        );
+        invalid("\n10 = \"Key1\"", "line 2: \"10\" is not a hex key");
+        invalid("G = \"Key1\"", "line 1: \"G\" is not a hex key");
+        invalid(
+            "1 = Key1",
+            "line 1: expected a quoted key name or a scancode, found Key1",
+        );
+        invalid(
+            "1 = \"Key1\"\n1 = \"Key2\"",
+            "line 2: hex key 1 is bound twice",
+        );
+        invalid(
+            "1 = \"Key1\"\n2 = \"Key1\"",
+            "line 2: Key1 is bound to both hex keys 1 and 2",
+        );
+        invalid(
+            "1 = 0x2C\n2 = 44",
+            "line 2: scancode 0x2C is bound to both hex keys 1 and 2",
+        );
+    }
+
+    #[test]
+    fn unknown_key_name_lists_valid_names() {
+        let Err(Error::InvalidKeyMap(reason)) = KeyMap::parse("1 = \"Kee1\"") else {
+            panic!("Kee1 isn't a key");
+        };
+        assert!(
+            reason.starts_with("line 1: unknown key \"Kee1\", expected one of Key1, Key2,"),
+            "{reason}"
+        );
+        assert!(reason.contains(", Numpad0, "), "{reason}");
+        assert!(reason.ends_with(", Slash, Backslash"), "{reason}");
+    }
+
+    #[test]
+    fn key_map_from_map() {
+        let bindings = HashMap::from([
+            (0x5, VirtualKeyCode::Up.into()),
+            (0x8, HostKey::Scancode(0x50)),
+        ]);
+        let key_map = KeyMap::from_map(&bindings).unwrap();
+        assert_eq!(key_map.hex_key(0x48, Some(VirtualKeyCode::Up)), Some(0x5));
+        assert_eq!(key_map.hex_key(0x50, Some(VirtualKeyCode::Down)), Some(0x8));
+        assert_eq!(key_map.host_key(0x0), None);
+
+        let bindings = HashMap::from([
+            (0x5, VirtualKeyCode::Up.into()),
+            (0x8, VirtualKeyCode::Up.into()),
+        ]);
+        assert_eq!(
+            KeyMap::from_map(&bindings),
+            Err(Error::InvalidKeyMap(
+                "Up is bound to both hex keys 5 and 8".to_string()
+            ))
+        );
+        let bindings = HashMap::from([(0x10, VirtualKeyCode::Up.into())]);
+        assert_eq!(
+            KeyMap::from_map(&bindings),
+            Err(Error::InvalidKeyMap("0x10 is not a hex key".to_string()))
+        );
+    }
+}
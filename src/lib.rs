@@ -3,21 +3,47 @@
 mod test_utils;
 
 // Modules
+pub mod bus;
+pub mod disassembler;
+pub mod display_filter;
 pub mod emulator;
+pub mod emulator_thread;
 mod error;
 mod font;
-mod interpreter;
+pub mod gif;
+mod hash;
+mod intel_hex;
+pub mod interpreter;
+pub mod keymap;
 pub mod memory;
+pub mod opcode;
+mod overlay;
+pub mod palette;
 pub mod peripherals;
+mod quirks;
+pub mod recording;
+pub mod rewind;
 mod rng;
+pub mod rom;
+pub mod rom_config;
+pub mod rom_database;
+#[cfg(test)]
+mod run_loop_harness;
+pub mod save_state;
+pub mod scheduler;
+pub mod screenshot;
+pub mod speed;
 
 // Reexports
 pub use error::Error;
+pub use quirks::{Chip8Variant, LoadStoreIncrement, Quirks};
+#[cfg(feature = "rand")]
+pub use rng::RandRng;
+pub use rng::{Chip8Rng, LfsrRng, RngAlgorithm};
 
 // Private helpers
 type Result<T> = std::result::Result<T, Error>;
 
-#[cfg(debug_assertions)]
 mod debug;
 
 // #[cfg(debug_assertions)]
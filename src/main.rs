@@ -1,35 +1,160 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-};
+use std::path::Path;
 
-use chip8_emulator::emulator;
+use chip8_emulator::{
+    emulator::{self, EmulatorConfig},
+    keymap::KeyMap,
+    recording::Recording,
+    rom::Rom,
+    rom_config::RomConfig,
+    rom_database::RomInfo,
+};
 
 fn main() {
     let config = cli::parse_args();
 
-    let chip8_program = File::open(&config.chip8_program_path)
-        .and_then(|file| BufReader::new(file).bytes().collect());
-    let chip8_program: Vec<u8> = match chip8_program {
+    let rom = match Rom::from_file(&config.chip8_program_path) {
         Err(e) => {
             eprintln!("{}: {}", config.chip8_program_path, e);
             std::process::exit(1);
         }
-        Ok(bytes) => bytes,
+        Ok(rom) => rom,
     };
 
-    if let Err(e) = emulator::run(&chip8_program) {
+    let rom_info = lookup_rom_info(&config, &rom);
+    let variant = rom_info
+        .as_ref()
+        .and_then(|info| info.recommended_variant)
+        .unwrap_or_default();
+    let rom_title = rom_info.as_ref().map(|info| info.title.clone());
+    let quirks = rom_info.and_then(|info| info.recommended_quirks);
+
+    // settings from the file next to the rom, with those on the command line
+    // winning over them
+    let rom_config = match RomConfig::path_for(&config.chip8_program_path) {
+        None => RomConfig::default(),
+        Some(path) => match RomConfig::from_file(&path) {
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            Ok(rom_config) => rom_config,
+        },
+    }
+    .overridden_by(config.rom_config);
+
+    // SCHIP RPL user flags and save states are persisted next to the rom
+    let rpl_flags_path = Path::new(&config.chip8_program_path).with_extension("rpl");
+    let autosave_path = Path::new(&config.chip8_program_path).with_extension("autosave.c8state");
+
+    let mut emulator_config = EmulatorConfig::new()
+        .variant(variant)
+        .rpl_flags_path(rpl_flags_path)
+        .save_states_path(&config.chip8_program_path)
+        .autosave_path(autosave_path)
+        .resume(config.resume)
+        .turbo_mode(config.turbo_mode)
+        .pause_on_focus_loss(!config.run_in_background);
+    if let Some(title) = rom_title {
+        emulator_config = emulator_config.rom_title(title);
+    }
+    if let Some(quirks) = quirks {
+        emulator_config = emulator_config.quirks(quirks);
+    }
+    // a variant other than the recommended one drops the recommended quirks
+    emulator_config = emulator_config.rom_config(&rom_config);
+    if let Some(seed) = config.seed {
+        emulator_config = emulator_config.seed(seed);
+    }
+    emulator_config = emulator_config.rng(config.rng);
+    if let Some(percent) = config.volume_percent {
+        emulator_config = emulator_config.volume(percent);
+    }
+    if let Some(decay_frames) = config.phosphor_decay_frames {
+        emulator_config = emulator_config.phosphor_decay(decay_frames);
+    }
+    if let Some(path) = &config.record_inputs_path {
+        emulator_config = emulator_config.record_inputs(path);
+    }
+    if let Some(path) = &config.play_inputs_path {
+        match std::fs::read(path)
+            .map_err(Into::into)
+            .and_then(|bytes| Recording::from_bytes(&bytes))
+        {
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+            Ok(recording) => {
+                emulator_config = emulator_config
+                    .play_inputs(recording)
+                    .stop_after_playback(config.stop_after_play)
+            }
+        }
+    }
+    if let Some(path) = &rom_config.key_map_path {
+        match KeyMap::from_file(path) {
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+            Ok(key_map) => emulator_config = emulator_config.key_map(key_map),
+        }
+    }
+
+    if let Err(e) = emulator::run_with_config(&rom, emulator_config) {
         eprintln!("emulator error: {}", e);
         std::process::exit(1);
     }
+    if let Some(path) = &config.record_inputs_path {
+        println!("Saved inputs {path}");
+    }
+}
+
+#[cfg(feature = "serde")]
+fn lookup_rom_info(config: &cli::Config, rom: &Rom) -> Option<RomInfo> {
+    use chip8_emulator::rom_database::RomDatabase;
+
+    let path = config.rom_database_path.as_ref()?;
+    match RomDatabase::from_file(path) {
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            None
+        }
+        Ok(database) => database.lookup(rom).cloned(),
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn lookup_rom_info(_config: &cli::Config, _rom: &Rom) -> Option<RomInfo> {
+    None
 }
 
 mod cli {
-    use clap::Parser;
+    use std::path::PathBuf;
+
+    use chip8_emulator::{
+        palette::Palette, rom_config::RomConfig, speed::TurboMode, Chip8Variant, RngAlgorithm,
+    };
+    use clap::{Parser, ValueEnum};
 
     #[derive(Debug)]
     pub struct Config {
         pub chip8_program_path: String,
+        /// The settings given here for the rom, which win over those in its
+        /// config file.
+        pub rom_config: RomConfig,
+        pub seed: Option<u64>,
+        pub rng: RngAlgorithm,
+        pub phosphor_decay_frames: Option<u8>,
+        pub turbo_mode: TurboMode,
+        pub volume_percent: Option<u8>,
+        pub run_in_background: bool,
+        pub resume: bool,
+        pub record_inputs_path: Option<String>,
+        pub play_inputs_path: Option<String>,
+        pub stop_after_play: bool,
+        #[cfg(feature = "serde")]
+        pub rom_database_path: Option<String>,
     }
 
     #[derive(Parser)]
@@ -38,12 +163,180 @@ mod cli {
         /// Path to the rom to emulate
         #[arg(name = "chip8_program_path", value_name = "CHIP-8_PROGRAM_PATH")]
         chip8_program_path: String,
+
+        /// CHIP-8 interpreter the rom was written for [default: the one set
+        /// in the rom's config file, or recommended by the rom database, or
+        /// cosmac-vip]
+        #[arg(long, value_enum)]
+        variant: Option<Variant>,
+
+        /// Seed for the random number generator, to reproduce a run
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Random number generator used by CXKK: fastrand's, or a 16-bit LFSR
+        /// seeded with the low 16 bits of the seed
+        #[arg(long, value_enum, default_value = "fastrand")]
+        rng: Rng,
+
+        /// File binding the hex keys to keyboard keys, e.g. `A = "Z"` on each
+        /// line
+        #[arg(long, value_name = "PATH")]
+        key_map: Option<PathBuf>,
+
+        /// Colors to draw the display in [default: paper-white]
+        #[arg(long, value_enum)]
+        palette: Option<PaletteName>,
+
+        /// Number of CHIP-8 instructions run each second [default: 700]
+        #[arg(long, value_name = "RATE", value_parser = clap::value_parser!(u64).range(1..))]
+        ips: Option<u64>,
+
+        /// Pitch of the tone, in Hz, for variants without XO-CHIP audio
+        /// patterns [default: 440]
+        #[arg(long, value_name = "HZ")]
+        tone_hz: Option<u32>,
+
+        /// Fade pixels out over this many frames once they are turned off,
+        /// to reduce flicker
+        #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u8).range(1..))]
+        phosphor: Option<u8>,
+
+        /// What holding the turbo key (`) runs faster: everything, as if time
+        /// passed faster, or only the instructions, with the timers kept to
+        /// 60Hz
+        #[arg(long, value_enum, default_value = "everything")]
+        turbo: Turbo,
+
+        /// Volume of the tone, in percent [default: 100]
+        #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+        volume: Option<u8>,
+
+        /// Keep running while the window doesn't have focus, rather than
+        /// pausing until it gets it back
+        #[arg(long)]
+        run_in_background: bool,
+
+        /// Carry on from the state saved when the window was last closed on
+        /// this rom
+        #[arg(long)]
+        resume: bool,
+
+        /// Record the hex keys pressed, and everything else needed to replay
+        /// the run exactly, to this file when the window is closed
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["resume", "play"])]
+        record: Option<String>,
+
+        /// Play back a run recorded with `--record`, ignoring the hex keys on
+        /// the keyboard until the recording ends
+        #[arg(long, value_name = "FILE", conflicts_with = "resume")]
+        play: Option<String>,
+
+        /// Close the window when the recording played back ends, rather than
+        /// carrying on with the keyboard
+        #[arg(long, requires = "play")]
+        stop_after_play: bool,
+
+        /// JSON rom database used to recognise the rom and pick its variant
+        #[cfg(feature = "serde")]
+        #[arg(long, value_name = "PATH")]
+        rom_database: Option<String>,
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum Variant {
+        CosmacVip,
+        HiresChip8,
+        Eti660,
+        Chip48,
+        Schip,
+        XoChip,
+    }
+
+    impl From<Variant> for Chip8Variant {
+        fn from(variant: Variant) -> Self {
+            match variant {
+                Variant::CosmacVip => Chip8Variant::CosmacVip,
+                Variant::HiresChip8 => Chip8Variant::HiresChip8,
+                Variant::Eti660 => Chip8Variant::Eti660,
+                Variant::Chip48 => Chip8Variant::Chip48,
+                Variant::Schip => Chip8Variant::SuperChip1_1,
+                Variant::XoChip => Chip8Variant::XoChip,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum Rng {
+        Fastrand,
+        Lfsr,
+    }
+
+    impl From<Rng> for RngAlgorithm {
+        fn from(rng: Rng) -> Self {
+            match rng {
+                Rng::Fastrand => RngAlgorithm::Fastrand,
+                Rng::Lfsr => RngAlgorithm::Lfsr,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum Turbo {
+        Everything,
+        InstructionsOnly,
+    }
+
+    impl From<Turbo> for TurboMode {
+        fn from(turbo: Turbo) -> Self {
+            match turbo {
+                Turbo::Everything => TurboMode::Everything,
+                Turbo::InstructionsOnly => TurboMode::InstructionsOnly,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, ValueEnum)]
+    enum PaletteName {
+        PaperWhite,
+        Green,
+        Amber,
+    }
+
+    impl From<PaletteName> for Palette {
+        fn from(name: PaletteName) -> Self {
+            match name {
+                PaletteName::PaperWhite => Palette::PAPER_WHITE,
+                PaletteName::Green => Palette::CLASSIC_GREEN,
+                PaletteName::Amber => Palette::AMBER,
+            }
+        }
     }
 
     pub fn parse_args() -> Config {
         let args = Args::parse();
         Config {
             chip8_program_path: args.chip8_program_path,
+            rom_config: RomConfig {
+                variant: args.variant.map(Into::into),
+                instructions_per_second: args.ips,
+                palette: args.palette.map(Into::into),
+                key_map_path: args.key_map,
+                tone_hz: args.tone_hz,
+                ..RomConfig::default()
+            },
+            seed: args.seed,
+            rng: args.rng.into(),
+            phosphor_decay_frames: args.phosphor,
+            turbo_mode: args.turbo.into(),
+            volume_percent: args.volume,
+            run_in_background: args.run_in_background,
+            resume: args.resume,
+            record_inputs_path: args.record,
+            play_inputs_path: args.play,
+            stop_after_play: args.stop_after_play,
+            #[cfg(feature = "serde")]
+            rom_database_path: args.rom_database,
         }
     }
 }
@@ -75,36 +75,450 @@
 //! cycle).
 //!
 //! The last page of RAM is used by the CHIP-8 interpreter for display refresh.
+//!
+//! # Display planes
+//! XO-CHIP extends the display with a second bit plane, giving 4 colors. The
+//! display refresh page holds the first plane, as it always has. The second
+//! plane has no home in the COSMAC address space, so `CosmacRAM` stores it
+//! alongside the addressable memory where CHIP-8 programs can't reach it.
+//!
+//! # Hires display
+//! SCHIP's 128x64 hires mode needs 1K per display plane, which is more than
+//! the display refresh page can hold. While in hires mode both planes are
+//! stored outside of the addressable memory, the same as the second plane.
+//! The same goes for the two display pages of the 64x64 hires CHIP-8
+//! interpreter patch for the COSMAC VIP, and the 64x48 display of the ETI-660.
+//!
+//! # Write protection
+//! On the COSMAC VIP nothing stops a CHIP-8 program from writing over the
+//! interpreter or itself. [`CosmacRAM::set_write_protection`] can protect
+//! these areas to catch programs that do so by mistake, e.g. an FX55 that
+//! scribbles over the font glyphs in the interpreter area.
 
-use std::ops::Range;
+use std::{
+    fmt, fs,
+    ops::{BitOr, BitOrAssign, Range},
+    path::Path,
+};
 
-use crate::{interpreter::I_ADDRESS, Error, Result};
-const _SMALL_MEMORY_SIZE: usize = 0x0800; // The 2K system
+use crate::{
+    intel_hex,
+    interpreter::{I_OFFSET, STACK_POINTER_OFFSET},
+    rom::Rom,
+    Error, Result,
+};
+const SMALL_MEMORY_SIZE: usize = 0x0800; // The 2K system
 const LARGE_MEMORY_SIZE: usize = 0x1000; // The beefier 4K system
+/// The size of the largest memory, the 4K system.
 pub const MEMORY_SIZE: usize = LARGE_MEMORY_SIZE;
 
+const STACK_SIZE: usize = 48;
+const INTERPRETER_WORK_AREA_SIZE: usize = 48;
+const DISPLAY_REFRESH_SIZE: usize = 256;
+
 pub const MEMORY_START_ADDRESS: usize = 0x000;
 pub const PROGRAM_START_ADDRESS: usize = 0x200;
-pub const STACK_START_ADDRESS: usize = 0xEA0;
-pub const INTERPRETER_WORK_AREA_START_ADDRESS: usize = 0x0ED0;
-pub const DISPLAY_REFRESH_START_ADDRESS: usize = 0xF00;
-pub const DISPLAY_REFRESH_LAST_ADDRESS: usize = 0xFFF;
+pub const ETI_660_PROGRAM_START_ADDRESS: usize = 0x600;
+
+// The addresses below are for the 4K system. The `CosmacRAM` methods of the
+// same names give the addresses for the size of a particular RAM.
+pub const STACK_START_ADDRESS: usize = INTERPRETER_WORK_AREA_START_ADDRESS - STACK_SIZE;
+pub const INTERPRETER_WORK_AREA_START_ADDRESS: usize =
+    DISPLAY_REFRESH_START_ADDRESS - INTERPRETER_WORK_AREA_SIZE;
+pub const DISPLAY_REFRESH_START_ADDRESS: usize = MEMORY_SIZE - DISPLAY_REFRESH_SIZE;
+pub const DISPLAY_REFRESH_LAST_ADDRESS: usize = MEMORY_SIZE - 1;
+pub const NUM_DISPLAY_PLANES: usize = 2;
+pub const LORES_DISPLAY_SIZE: DisplaySize = DisplaySize {
+    width: 64,
+    height: 32,
+};
+pub const HIRES_DISPLAY_SIZE: DisplaySize = DisplaySize {
+    width: 128,
+    height: 64,
+};
+pub const TWO_PAGE_HIRES_DISPLAY_SIZE: DisplaySize = DisplaySize {
+    width: 64,
+    height: 64,
+};
+pub const ETI_660_DISPLAY_SIZE: DisplaySize = DisplaySize {
+    width: 64,
+    height: 48,
+};
+/// The display sizes supported by [`CosmacRAM`].
+pub(crate) const DISPLAY_SIZES: [DisplaySize; 4] = [
+    LORES_DISPLAY_SIZE,
+    HIRES_DISPLAY_SIZE,
+    TWO_PAGE_HIRES_DISPLAY_SIZE,
+    ETI_660_DISPLAY_SIZE,
+];
+/// The memory sizes supported by [`CosmacRAM`].
+pub(crate) const MEMORY_SIZES: [usize; 2] = [SMALL_MEMORY_SIZE, LARGE_MEMORY_SIZE];
+const MAX_DISPLAY_PLANE_SIZE: usize = HIRES_DISPLAY_SIZE.plane_size();
 pub const NUM_V_REGISTERS: usize = 16;
 pub const V_REGISTERS_START_ADDRESS: usize = DISPLAY_REFRESH_START_ADDRESS - NUM_V_REGISTERS;
 
 pub const PROGRAM_LAST_ADDRESS: usize = STACK_START_ADDRESS - 1;
 pub const PROGRAM_MAX_SIZE: usize = PROGRAM_LAST_ADDRESS - PROGRAM_START_ADDRESS + 1;
+pub const ROM_START_ADDRESS: usize = PROGRAM_START_ADDRESS;
+pub const ROM_LAST_ADDRESS: usize = PROGRAM_LAST_ADDRESS;
+
+/// Areas of RAM that can be protected from writes by
+/// [`CosmacRAM::set_write_protection`]. Areas are combined with `|`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Protection(u8);
+
+impl Protection {
+    /// No areas protected.
+    pub const NONE: Self = Self(0);
+    /// The CHIP-8 interpreter area below the program, `0x000` - `0x1FF`, which
+    /// holds the font glyphs.
+    pub const FONT_AREA: Self = Self(1 << 0);
+    /// The user program area, from `0x200` up to the CHIP-8 stack.
+    pub const PROGRAM_AREA: Self = Self(1 << 1);
+
+    /// Whether all of the areas in `other` are protected by `self`.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Protection {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Protection {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The dimensions of the display in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplaySize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DisplaySize {
+    /// Number of bytes in a row of a display plane, at 1 bit per pixel.
+    pub const fn row_size(&self) -> usize {
+        self.width / 8
+    }
+
+    /// Number of bytes in a display plane.
+    pub const fn plane_size(&self) -> usize {
+        self.row_size() * self.height
+    }
+}
+
+/// A CHIP-8 `VX` register. VF doubles as the flag register, which carry,
+/// borrow and collision flags are written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum VReg {
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    VA,
+    VB,
+    VC,
+    VD,
+    VE,
+    VF,
+}
+
+impl VReg {
+    /// All of the registers, from V0 to VF.
+    pub const ALL: [VReg; NUM_V_REGISTERS] = [
+        VReg::V0,
+        VReg::V1,
+        VReg::V2,
+        VReg::V3,
+        VReg::V4,
+        VReg::V5,
+        VReg::V6,
+        VReg::V7,
+        VReg::V8,
+        VReg::V9,
+        VReg::VA,
+        VReg::VB,
+        VReg::VC,
+        VReg::VD,
+        VReg::VE,
+        VReg::VF,
+    ];
+
+    /// The number of the register, 0 for V0 up to 15 for VF.
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl From<u8> for VReg {
+    /// The register numbered by the low nibble of `x`, so the X and Y of an
+    /// instruction can be passed in without masking, e.g. `(op >> 8) as u8`.
+    fn from(x: u8) -> Self {
+        Self::ALL[(x & 0x0F) as usize]
+    }
+}
+
+// The index of the byte holding pixel (`x`, `y`) in a display plane of
+// `size`, and the mask of the pixel's bit in that byte. The leftmost pixel of
+// each byte is its most significant bit.
+fn pixel_position(size: DisplaySize, x: usize, y: usize) -> Option<(usize, u8)> {
+    (x < size.width && y < size.height).then(|| (y * size.row_size() + x / 8, 0x80 >> (x % 8)))
+}
+
+/// A read-only view of a display plane as pixels, where `(0, 0)` is the top
+/// left pixel. Returned by [`CosmacRAM::display`].
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayView<'a> {
+    plane: &'a [u8],
+    size: DisplaySize,
+}
+
+impl<'a> DisplayView<'a> {
+    /// View the packed bytes of a display plane, e.g. from
+    /// [`CosmacRAM::display_plane`], as pixels.
+    ///
+    /// # Panics
+    /// Panics if `plane` is not `size.plane_size()` bytes.
+    pub fn new(plane: &'a [u8], size: DisplaySize) -> Self {
+        assert_eq!(
+            plane.len(),
+            size.plane_size(),
+            "Display plane size mismatch"
+        );
+        Self { plane, size }
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.size.height
+    }
+
+    /// Whether the pixel at (`x`, `y`) is on, or `None` if it is off the
+    /// display.
+    pub fn get_pixel(&self, x: u8, y: u8) -> Option<bool> {
+        let (index, mask) = pixel_position(self.size, x as usize, y as usize)?;
+        Some(self.plane[index] & mask != 0)
+    }
+
+    /// Every pixel as `(x, y, on)`, row by row from the top left.
+    pub fn pixels(&self) -> impl Iterator<Item = (u8, u8, bool)> + 'a {
+        let view = *self;
+        (0..view.height()).flat_map(move |y| {
+            (0..view.width()).map(move |x| {
+                let (index, mask) = pixel_position(view.size, x, y).unwrap();
+                (x as u8, y as u8, view.plane[index] & mask != 0)
+            })
+        })
+    }
+
+    /// Draw the display as text, one line per row of pixels, using `on` and
+    /// `off` for each pixel. Every line ends with a newline.
+    pub fn to_ascii(&self, on: char, off: char) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        for (x, _, pixel) in self.pixels() {
+            ascii.push(if pixel { on } else { off });
+            if x as usize == self.width() - 1 {
+                ascii.push('\n');
+            }
+        }
+        ascii
+    }
+}
+
+impl fmt::Display for DisplayView<'_> {
+    /// Draws the display with `█` for pixels that are on, see
+    /// [`DisplayView::to_ascii`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_ascii('█', ' '))
+    }
+}
+
+/// A view of a display plane as pixels that can be drawn to, for DXYN.
+pub(crate) struct DisplayViewMut<'a> {
+    plane: &'a mut [u8],
+    size: DisplaySize,
+}
+
+impl DisplayViewMut<'_> {
+    pub(crate) fn width(&self) -> usize {
+        self.size.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.size.height
+    }
+
+    /// XOR the 8 pixels of a sprite byte into row `y`, starting at pixel
+    /// column `x`, which may be past the right edge of the display. Pixels
+    /// past the right edge are clipped or, if `wrap` is set, wrapped around to
+    /// the left edge. Returns the number of pixels drawn and whether any pixel
+    /// was switched off.
+    ///
+    /// # Panics
+    /// Panics if row `y` is off the display.
+    pub(crate) fn xor_byte(&mut self, x: usize, y: usize, pixels: u8, wrap: bool) -> (u32, bool) {
+        assert!(y < self.size.height, "Row should be on the display.");
+        let row_size = self.size.row_size();
+        let row_start = y * row_size;
+        // split the pixels into two bytes aligned with the display buffer
+        let offset = x % 8;
+        let left_byte_pixels = pixels >> offset;
+        let right_byte_pixels = match offset {
+            0 => 0,
+            _ => pixels << (8 - offset),
+        };
+
+        let mut pixels_drawn = 0;
+        let mut collision = false;
+        for (mut byte_col, pixels) in [(x / 8, left_byte_pixels), (x / 8 + 1, right_byte_pixels)] {
+            if byte_col >= row_size {
+                if !wrap {
+                    // clipped by the right edge of the display
+                    continue;
+                }
+                byte_col %= row_size;
+            }
+            let display_byte = &mut self.plane[row_start + byte_col];
+            collision |= pixels & *display_byte != 0;
+            *display_byte ^= pixels;
+            pixels_drawn += pixels.count_ones();
+        }
+        (pixels_drawn, collision)
+    }
+}
+
+/// An owned copy of the contents of a [`CosmacRAM`], including the display
+/// planes outside of the addressable memory. Taken by [`CosmacRAM::snapshot`]
+/// and restored by [`CosmacRAM::restore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RamSnapshot {
+    pub(crate) data: Vec<u8>,
+    pub(crate) display_size: DisplaySize,
+    pub(crate) display_planes: [Vec<u8>; NUM_DISPLAY_PLANES],
+}
+
+impl RamSnapshot {
+    /// The addressable memory in the snapshot.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The dimensions of the display in the snapshot.
+    pub fn display_size(&self) -> DisplaySize {
+        self.display_size
+    }
+
+    /// The addresses whose values differ between `self` and `other`, in
+    /// order, each with its value in `self` and then in `other`. Only
+    /// addressable memory is compared, up to the end of the smaller snapshot.
+    pub fn diff(&self, other: &RamSnapshot) -> Vec<(usize, u8, u8)> {
+        self.data
+            .iter()
+            .zip(&other.data)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(address, (&old, &new))| (address, old, new))
+            .collect()
+    }
+}
 
-/// Main memory used by the CHIP-8 interpreter. Follows COSMAC VIP layout.
+/// What fresh RAM is filled with by [`CosmacRAM::fill`].
+///
+/// The VIP powered up with garbage in its RAM, so a ROM that reads memory it
+/// never wrote only works by luck. Filling RAM with something other than
+/// zeroes flushes such ROMs out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FillPattern {
+    /// Every byte is 0x00.
+    #[default]
+    Zero,
+    /// Every byte is 0xFF.
+    Ones,
+    /// Random bytes, the same for the same seed.
+    Random { seed: u64 },
+}
+
+/// Main memory used by the CHIP-8 interpreter. Follows COSMAC VIP layout, for
+/// either the 2K or the 4K system.
+///
+/// With the `serde` feature, RAM can be serialized with its memory and display
+/// planes as base64 strings in human-readable formats like JSON, and as byte
+/// arrays in binary formats.
 pub struct CosmacRAM {
-    data: [u8; MEMORY_SIZE],
+    data: Vec<u8>,
+    write_protection: Protection,
+    display_size: DisplaySize,
+    // Display planes that don't live in the display refresh area, outside of
+    // the addressable memory. In lores mode only the second plane is used.
+    extended_display_planes: [[u8; MAX_DISPLAY_PLANE_SIZE]; NUM_DISPLAY_PLANES],
 }
 
 impl CosmacRAM {
-    /// Create 4K of COSMAC RAM, zero-initialized.
+    /// Create 4K of COSMAC RAM, zero-initialized, with a 64x32 display.
     pub fn new() -> Self {
+        Self::new_4k()
+    }
+
+    /// Create the 2K of COSMAC RAM the VIP shipped with, zero-initialized,
+    /// with a 64x32 display. This leaves 1184 bytes for a CHIP-8 program.
+    pub fn new_2k() -> Self {
+        Self::with_memory_size(SMALL_MEMORY_SIZE)
+    }
+
+    /// Create 4K of COSMAC RAM, zero-initialized, with a 64x32 display. This
+    /// leaves 3232 bytes for a CHIP-8 program.
+    pub fn new_4k() -> Self {
+        Self::with_memory_size(LARGE_MEMORY_SIZE)
+    }
+
+    /// Create 4K of COSMAC RAM filled with `pattern`, with a 64x32 display.
+    /// Resetting the interpreter still zeroes the stack, work area and
+    /// display refresh, and loads the fonts, but the rest of RAM keeps the
+    /// pattern until the program writes to it.
+    pub fn new_with_fill(pattern: FillPattern) -> Self {
+        let mut ram = Self::new();
+        ram.fill(pattern);
+        ram
+    }
+
+    /// Fill all of the addressable memory with `pattern`, ignoring write
+    /// protection. This is meant for fresh RAM, before a program is loaded.
+    pub fn fill(&mut self, pattern: FillPattern) {
+        match pattern {
+            FillPattern::Zero => self.data.fill(0x00),
+            FillPattern::Ones => self.data.fill(0xFF),
+            FillPattern::Random { seed } => {
+                let rng = fastrand::Rng::with_seed(seed);
+                self.data.iter_mut().for_each(|byte| *byte = rng.u8(..));
+            }
+        }
+    }
+
+    fn with_memory_size(memory_size: usize) -> Self {
         Self {
-            data: [0; MEMORY_SIZE],
+            data: vec![0; memory_size],
+            write_protection: Protection::NONE,
+            display_size: LORES_DISPLAY_SIZE,
+            extended_display_planes: [[0; MAX_DISPLAY_PLANE_SIZE]; NUM_DISPLAY_PLANES],
         }
     }
 
@@ -113,15 +527,141 @@ impl CosmacRAM {
         &self.data
     }
 
+    /// Take a copy of the contents of RAM, which can be restored with
+    /// [`Self::restore`].
+    pub fn snapshot(&self) -> RamSnapshot {
+        RamSnapshot {
+            data: self.data.clone(),
+            display_size: self.display_size,
+            display_planes: [0, 1].map(|plane| self.display_plane(plane).to_vec()),
+        }
+    }
+
+    /// Restore the contents of RAM from a snapshot taken by
+    /// [`Self::snapshot`]. Write protection is ignored and left unchanged.
+    ///
+    /// # Errors
+    /// Returns [`Error::MemoryImageSizeMismatch`] if the snapshot was taken of
+    /// RAM of a different size. When this occurs no change is made to the RAM.
+    pub fn restore(&mut self, snapshot: &RamSnapshot) -> Result<()> {
+        if snapshot.data.len() != self.memory_size() {
+            return Err(Error::MemoryImageSizeMismatch(snapshot.data.len()));
+        }
+        self.set_display_size(snapshot.display_size);
+        self.data.copy_from_slice(&snapshot.data);
+        for (plane, data) in snapshot.display_planes.iter().enumerate() {
+            self.display_plane_mut(plane).copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    /// The number of bytes of addressable memory, `0x0800` for the 2K system
+    /// or `0x1000` for the 4K system.
+    pub fn memory_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The first address of the CHIP-8 stack.
+    pub fn stack_start_address(&self) -> usize {
+        self.interpreter_work_area_start_address() - STACK_SIZE
+    }
+
+    /// The first address of the CHIP-8 interpreter work area.
+    pub fn interpreter_work_area_start_address(&self) -> usize {
+        self.display_refresh_start_address() - INTERPRETER_WORK_AREA_SIZE
+    }
+
+    /// The first address of the display refresh page.
+    pub fn display_refresh_start_address(&self) -> usize {
+        self.memory_size() - DISPLAY_REFRESH_SIZE
+    }
+
+    /// The last address of the display refresh page, which is the last
+    /// address of RAM.
+    pub fn display_refresh_last_address(&self) -> usize {
+        self.memory_size() - 1
+    }
+
+    /// The address of register V0, at the end of the interpreter work area.
+    pub fn v_registers_start_address(&self) -> usize {
+        self.display_refresh_start_address() - NUM_V_REGISTERS
+    }
+
+    /// The last address a CHIP-8 program can be loaded into.
+    pub fn program_last_address(&self) -> usize {
+        self.stack_start_address() - 1
+    }
+
+    /// The size of the largest CHIP-8 program that fits into RAM.
+    pub fn program_max_size(&self) -> usize {
+        self.program_last_address() - PROGRAM_START_ADDRESS + 1
+    }
+
+    /// The areas of RAM protected from writes.
+    pub fn write_protection(&self) -> Protection {
+        self.write_protection
+    }
+
+    /// Protect areas of RAM from writes. Writes into a protected area by
+    /// [`Self::load_bytes`], [`Self::try_set_u16_at`] and
+    /// [`Self::zero_out_range`], and so by CHIP-8 instructions that write
+    /// through `I`, fail with [`Error::WriteProtected`]. Loading a CHIP-8
+    /// program is still allowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use chip8_emulator::{memory::{CosmacRAM, Protection}, Error};
+    /// let mut ram = CosmacRAM::new();
+    /// ram.set_write_protection(Protection::FONT_AREA | Protection::PROGRAM_AREA);
+    /// assert_eq!(
+    ///     ram.load_bytes(&[0xFF], 0x0010),
+    ///     Err(Error::WriteProtected { address: 0x0010 })
+    /// );
+    /// ```
+    pub fn set_write_protection(&mut self, protection: Protection) {
+        self.write_protection = protection;
+    }
+
+    // Check that none of `address_range` is write protected, returning the
+    // first protected address if some of it is.
+    pub(crate) fn check_write_protection(&self, address_range: Range<usize>) -> Result<()> {
+        let areas = [
+            (
+                Protection::FONT_AREA,
+                MEMORY_START_ADDRESS..PROGRAM_START_ADDRESS,
+            ),
+            (
+                Protection::PROGRAM_AREA,
+                PROGRAM_START_ADDRESS..self.stack_start_address(),
+            ),
+        ];
+        let protected_address = areas
+            .into_iter()
+            .filter(|(protection, area)| {
+                self.write_protection.contains(*protection)
+                    && !address_range.is_empty()
+                    && area.start < address_range.end
+                    && address_range.start < area.end
+            })
+            .map(|(_, area)| area.start.max(address_range.start))
+            .min();
+        match protected_address {
+            Some(address) => Err(Error::WriteProtected { address }),
+            None => Ok(()),
+        }
+    }
+
     /// Zero out a block of memory addresses.
     ///
     /// # Errors
     /// Returns [`Error::RamOverflow`] if the range extends beyond the address
-    /// space. When this occurs no change is made to the RAM.
+    /// space, or [`Error::WriteProtected`] if it includes a write protected
+    /// address. When this occurs no change is made to the RAM.
     pub fn zero_out_range(&mut self, address_range: Range<usize>) -> Result<()> {
-        if address_range.end > MEMORY_SIZE {
+        if address_range.end > self.memory_size() {
             return Err(Error::RamOverflow);
         }
+        self.check_write_protection(address_range.clone())?;
 
         for val in self.data[address_range].iter_mut() {
             *val = 0;
@@ -143,10 +683,79 @@ impl CosmacRAM {
     /// ```
     ///
     /// # Errors
-    /// Returns [`Error::RamOverflow`] if bytes cannot fit into RAM at the given offset.
-    /// When this occurs no change is made to the RAM.
+    /// Returns [`Error::RamOverflow`] if bytes cannot fit into RAM at the given
+    /// offset, or [`Error::WriteProtected`] if they would be written to a write
+    /// protected address. When this occurs no change is made to the RAM.
     pub fn load_bytes(&mut self, bytes: &[u8], ram_offset: usize) -> Result<()> {
-        if ram_offset + bytes.len() > MEMORY_SIZE {
+        if ram_offset.saturating_add(bytes.len()) > self.memory_size() {
+            return Err(Error::RamOverflow);
+        }
+        self.check_write_protection(ram_offset..ram_offset + bytes.len())?;
+        self.data[ram_offset..][..bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Write all of the addressable memory to a file as a raw memory image,
+    /// e.g. to look through after a crash. The display buffers of hires
+    /// modes are kept outside of the addressable memory, so aren't included.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be written.
+    pub fn dump_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, &self.data)?;
+        Ok(())
+    }
+
+    /// Replace all of the addressable memory with a raw memory image from a
+    /// file, such as one written by [`Self::dump_to_file`]. Write protection
+    /// doesn't apply, as the whole of memory is replaced.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be read, or
+    /// [`Error::MemoryImageSizeMismatch`] if it isn't the size of RAM. When
+    /// this occurs no change is made to the RAM.
+    pub fn load_from_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let image = fs::read(path)?;
+        if image.len() != self.memory_size() {
+            return Err(Error::MemoryImageSizeMismatch(image.len()));
+        }
+        self.data.copy_from_slice(&image);
+        Ok(())
+    }
+
+    /// Write the bytes of RAM in `range` as Intel HEX, in records of 16
+    /// bytes followed by an end of file record.
+    ///
+    /// # Panics
+    /// Panics if `range` goes past the end of RAM.
+    pub fn to_intel_hex(&self, range: Range<usize>) -> String {
+        intel_hex::encode(&self.data[range.clone()], range.start)
+    }
+
+    /// Load an Intel HEX memory image, such as one written by
+    /// [`Self::to_intel_hex`]. Only data and end of file records are
+    /// supported.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidIntelHex`] if a record is malformed, has a bad
+    /// checksum, or would write past the end of RAM, or
+    /// [`Error::WriteProtected`] if a record would write to a write protected
+    /// address. When this occurs no change is made to the RAM.
+    pub fn load_intel_hex(&mut self, text: &str) -> Result<()> {
+        let records = intel_hex::decode(text, self.memory_size())?;
+        for (address, data) in &records {
+            self.check_write_protection(*address..address + data.len())?;
+        }
+        for (address, data) in records {
+            self.data[address..][..data.len()].copy_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// Same as [`Self::load_bytes`], but ignoring write protection, for the
+    /// interpreter's own writes, e.g. loading the fonts.
+    pub(crate) fn load_bytes_unprotected(&mut self, bytes: &[u8], ram_offset: usize) -> Result<()> {
+        if ram_offset.saturating_add(bytes.len()) > self.memory_size() {
             return Err(Error::RamOverflow);
         }
         self.data[ram_offset..][..bytes.len()].copy_from_slice(bytes);
@@ -171,13 +780,43 @@ impl CosmacRAM {
     /// assert!(ram.load_chip8_program(&program).is_ok());
     /// ```
     pub fn load_chip8_program(&mut self, chip8_program: &[u8]) -> Result<()> {
-        if chip8_program.is_empty() {
+        self.load_chip8_program_at(chip8_program, PROGRAM_START_ADDRESS)
+    }
+
+    /// Load a rom into the pages of memory expected by a CHIP-8 interpreter.
+    ///
+    /// # Errors
+    /// Returns [`Error::Chip8ProgramTooLarge`] if the rom doesn't fit into the
+    /// program area, which can happen with 2K of RAM.
+    pub fn load_rom(&mut self, rom: &Rom) -> Result<()> {
+        self.load_chip8_program(rom.bytes())
+    }
+
+    /// Load a CHIP-8 program, given in bytes, into memory starting at
+    /// `start_address` instead of the usual `0x200`, e.g. at
+    /// [`ETI_660_PROGRAM_START_ADDRESS`] for ETI-660 programs. The program must
+    /// fit between `start_address` and the CHIP-8 stack.
+    ///
+    /// # Errors
+    /// Returns [`Error::ProgramStartAddressOutOfRange`] if `start_address` is
+    /// not in the program area, which runs from `0x200` up to the CHIP-8
+    /// stack, or [`Error::Chip8ProgramTooLarge`] if the program doesn't fit
+    /// in the rest of the program area. Can also return
+    /// [`Error::EmptyChip8Program`].
+    pub fn load_chip8_program_at(
+        &mut self,
+        chip8_program: &[u8],
+        start_address: usize,
+    ) -> Result<()> {
+        if !(PROGRAM_START_ADDRESS..=self.program_last_address()).contains(&start_address) {
+            return Err(Error::ProgramStartAddressOutOfRange(start_address));
+        } else if chip8_program.is_empty() {
             return Err(Error::EmptyChip8Program);
-        } else if PROGRAM_START_ADDRESS + chip8_program.len() - 1 > PROGRAM_LAST_ADDRESS {
+        } else if chip8_program.len() > self.program_last_address() - start_address + 1 {
             return Err(Error::Chip8ProgramTooLarge(chip8_program.len()));
         }
 
-        self.data[PROGRAM_START_ADDRESS..][..chip8_program.len()].copy_from_slice(chip8_program);
+        self.data[start_address..][..chip8_program.len()].copy_from_slice(chip8_program);
         Ok(())
     }
 
@@ -185,24 +824,251 @@ impl CosmacRAM {
     /// are each a single byte in size and stored stored sequentially from V0 to
     /// VF. This slice is 16 bytes in size.
     pub fn get_v_registers(&self) -> &[u8] {
-        &self.data[V_REGISTERS_START_ADDRESS..][..NUM_V_REGISTERS]
+        let start = self.v_registers_start_address();
+        &self.data[start..][..NUM_V_REGISTERS]
     }
 
     /// Get the slice of RAM that holds the CHIP-8 `VX` registers mutably.
     pub fn get_v_registers_mut(&mut self) -> &mut [u8] {
-        &mut self.data[V_REGISTERS_START_ADDRESS..][..NUM_V_REGISTERS]
+        let start = self.v_registers_start_address();
+        &mut self.data[start..][..NUM_V_REGISTERS]
+    }
+
+    /// The value of register `reg`.
+    pub fn v(&self, reg: VReg) -> u8 {
+        self.get_v_registers()[reg.index()]
     }
 
-    /// Get the slice of RAM that holds the CHIP-8 display buffer.
+    /// Set register `reg` to `value`.
+    pub fn set_v(&mut self, reg: VReg, value: u8) {
+        self.get_v_registers_mut()[reg.index()] = value;
+    }
+
+    /// The value of the flag register VF.
+    pub fn vf(&self) -> u8 {
+        self.v(VReg::VF)
+    }
+
+    /// Set the flag register VF to `value`.
+    pub fn set_vf(&mut self, value: u8) {
+        self.set_v(VReg::VF, value);
+    }
+
+    /// Get the display buffer of the first display plane. Outside of hires
+    /// mode this is the display refresh area of RAM.
     pub fn display_buffer(&self) -> &[u8] {
-        &self.data[DISPLAY_REFRESH_START_ADDRESS..=DISPLAY_REFRESH_LAST_ADDRESS]
+        self.display_plane(0)
+    }
+
+    /// The current dimensions of the display.
+    pub fn display_size(&self) -> DisplaySize {
+        self.display_size
+    }
+
+    /// Change the dimensions of the display, clearing all display planes.
+    ///
+    /// # Panics
+    /// Panics if `display_size` is not one of [`LORES_DISPLAY_SIZE`],
+    /// [`HIRES_DISPLAY_SIZE`], [`TWO_PAGE_HIRES_DISPLAY_SIZE`] or
+    /// [`ETI_660_DISPLAY_SIZE`].
+    pub(crate) fn set_display_size(&mut self, display_size: DisplaySize) {
+        assert!(
+            DISPLAY_SIZES.contains(&display_size),
+            "Unsupported display size {display_size:?}"
+        );
+        // clear the planes of both the old and new display sizes
+        for plane in 0..NUM_DISPLAY_PLANES {
+            self.display_plane_mut(plane).fill(0);
+        }
+        self.display_size = display_size;
+        for plane in 0..NUM_DISPLAY_PLANES {
+            self.display_plane_mut(plane).fill(0);
+        }
+    }
+
+    /// Get the display buffer for a display plane. In lores mode plane `0` is
+    /// the display refresh area of RAM and plane `1` is the XO-CHIP second
+    /// plane. Each buffer is `display_size().plane_size()` bytes.
+    ///
+    /// # Panics
+    /// Panics if `plane >= NUM_DISPLAY_PLANES`.
+    pub fn display_plane(&self, plane: usize) -> &[u8] {
+        assert!(plane < NUM_DISPLAY_PLANES, "No display plane {plane}");
+        if plane == 0 && self.display_size == LORES_DISPLAY_SIZE {
+            &self.data[self.display_refresh_start_address()..]
+        } else {
+            &self.extended_display_planes[plane][..self.display_size.plane_size()]
+        }
+    }
+
+    /// View the first display plane as pixels.
+    pub fn display(&self) -> DisplayView<'_> {
+        self.display_plane_view(0)
+    }
+
+    /// View a display plane as pixels.
+    ///
+    /// # Panics
+    /// Panics if `plane >= NUM_DISPLAY_PLANES`.
+    pub fn display_plane_view(&self, plane: usize) -> DisplayView<'_> {
+        DisplayView::new(self.display_plane(plane), self.display_size)
+    }
+
+    /// View a display plane as pixels mutably.
+    pub(crate) fn display_plane_view_mut(&mut self, plane: usize) -> DisplayViewMut<'_> {
+        let size = self.display_size;
+        DisplayViewMut {
+            plane: self.display_plane_mut(plane),
+            size,
+        }
+    }
+
+    /// Get the display buffer for a display plane mutably.
+    pub(crate) fn display_plane_mut(&mut self, plane: usize) -> &mut [u8] {
+        assert!(plane < NUM_DISPLAY_PLANES, "No display plane {plane}");
+        if plane == 0 && self.display_size == LORES_DISPLAY_SIZE {
+            let start = self.display_refresh_start_address();
+            &mut self.data[start..]
+        } else {
+            &mut self.extended_display_planes[plane][..self.display_size.plane_size()]
+        }
+    }
+
+    /// Scroll the contents of a display plane down by `n` pixel rows. Rows
+    /// scrolled past the bottom of the display are lost and the rows vacated
+    /// at the top are cleared.
+    pub fn scroll_display_down(&mut self, plane: usize, n: usize) {
+        let row_size = self.display_size.row_size();
+        let display = self.display_plane_mut(plane);
+        let shift = (n * row_size).min(display.len());
+        let len = display.len();
+        display.copy_within(..len - shift, shift);
+        display[..shift].fill(0);
+    }
+
+    /// Scroll the contents of a display plane right by `n` pixels. Pixels
+    /// scrolled past the right edge of the display are lost and the columns
+    /// vacated on the left are cleared.
+    pub fn scroll_display_right(&mut self, plane: usize, n: usize) {
+        let byte_shift = n / 8;
+        let bit_shift = n % 8;
+        let row_size = self.display_size.row_size();
+        for row in self.display_plane_mut(plane).chunks_exact_mut(row_size) {
+            // work from the right so each source byte is read before it's overwritten
+            for i in (0..row.len()).rev() {
+                let high_bits = match i.checked_sub(byte_shift) {
+                    Some(src) => row[src] >> bit_shift,
+                    None => 0,
+                };
+                let carried_bits = match i.checked_sub(byte_shift + 1) {
+                    Some(src) if bit_shift != 0 => row[src] << (8 - bit_shift),
+                    _ => 0,
+                };
+                row[i] = high_bits | carried_bits;
+            }
+        }
+    }
+
+    /// Scroll the contents of a display plane left by `n` pixels. Pixels
+    /// scrolled past the left edge of the display are lost and the columns
+    /// vacated on the right are cleared.
+    pub fn scroll_display_left(&mut self, plane: usize, n: usize) {
+        let byte_shift = n / 8;
+        let bit_shift = n % 8;
+        let row_size = self.display_size.row_size();
+        for row in self.display_plane_mut(plane).chunks_exact_mut(row_size) {
+            // work from the left so each source byte is read before it's overwritten
+            for i in 0..row.len() {
+                let low_bits = match row.get(i + byte_shift) {
+                    Some(src) => src << bit_shift,
+                    None => 0,
+                };
+                let carried_bits = match row.get(i + byte_shift + 1) {
+                    Some(src) if bit_shift != 0 => src >> (8 - bit_shift),
+                    _ => 0,
+                };
+                row[i] = low_bits | carried_bits;
+            }
+        }
+    }
+
+    /// The program area as `(address, instruction)` pairs, from
+    /// [`PROGRAM_START_ADDRESS`] up to [`Self::program_last_address`] in
+    /// steps of two. Instructions are read big endian. RAM doesn't know how
+    /// long the loaded program is, so this walks the whole program area,
+    /// which will be zeroes after the end of the program.
+    ///
+    /// A trailing odd byte is read as the high byte of an instruction with a
+    /// low byte of zero.
+    pub fn program_words(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.data[PROGRAM_START_ADDRESS..=self.program_last_address()]
+            .chunks(2)
+            .enumerate()
+            .map(|(i, word)| {
+                let address = PROGRAM_START_ADDRESS + 2 * i;
+                let instruction = u16::from_be_bytes([word[0], word.get(1).copied().unwrap_or(0)]);
+                (address as u16, instruction)
+            })
+    }
+
+    /// The addresses pushed onto the CHIP-8 stack by the subroutine calls
+    /// being executed, outermost first. Each is the address of the 2NNN
+    /// instruction that made the call, and 00EE returns to the instruction
+    /// after it.
+    ///
+    /// The stack is empty if the stack pointer is outside of the stack, e.g.
+    /// before the interpreter has been reset.
+    pub fn chip8_stack(&self) -> Vec<u16> {
+        self.chip8_stack_bytes()
+            .chunks_exact(2)
+            .map(|address| u16::from_be_bytes([address[0], address[1]]))
+            .collect()
+    }
+
+    /// The number of subroutine calls being executed.
+    pub fn chip8_stack_depth(&self) -> usize {
+        self.chip8_stack_bytes().len() / 2
+    }
+
+    /// The part of the CHIP-8 stack in use.
+    pub(crate) fn chip8_stack_bytes(&self) -> &[u8] {
+        let stack_pointer = self.get_work_area_u16(STACK_POINTER_OFFSET) as usize;
+        let stack_range = self.stack_start_address()..=self.interpreter_work_area_start_address();
+        if stack_range.contains(&stack_pointer) {
+            &self.data[self.stack_start_address()..stack_pointer]
+        } else {
+            &[]
+        }
     }
 
     pub fn get_i_data(&self) -> &[u8] {
-        let i = self.get_u16_at(I_ADDRESS);
+        let i = self.get_work_area_u16(I_OFFSET);
         &self.bytes()[i as usize..][..16]
     }
 
+    /// Read a u16 from two sequential bytes in RAM, which is big endian. Does
+    /// not check alignment of address.
+    ///
+    /// # Errors
+    /// Returns [`Error::RamOverflow`] if either byte is beyond the end of RAM.
+    pub fn try_get_u16_at(&self, address: usize) -> Result<u16> {
+        match self.data.get(address..address.saturating_add(2)) {
+            Some(bytes) => Ok(u16::from_be_bytes(bytes.try_into().unwrap())),
+            None => Err(Error::RamOverflow),
+        }
+    }
+
+    /// Write a u16 to two sequential bytes in RAM in big endian. Does not
+    /// check alignment of address.
+    ///
+    /// # Errors
+    /// Returns [`Error::RamOverflow`] if either byte is beyond the end of RAM,
+    /// or [`Error::WriteProtected`] if either byte is write protected. When
+    /// this occurs no change is made to the RAM.
+    pub fn try_set_u16_at(&mut self, address: usize, value: u16) -> Result<()> {
+        self.load_bytes(&value.to_be_bytes(), address)
+    }
+
     /// Grab a u16 from two sequential bytes in the COSMAC RAM, which is big endian.
     /// Does not check alignment of address. Panics if accessing out of bounds memory.
     pub(crate) fn get_u16_at(&self, address: usize) -> u16 {
@@ -211,12 +1077,25 @@ impl CosmacRAM {
     }
 
     /// Write a u16 to two sequential bytes in the COSMAC RAM in big endian.
-    /// Does not check alignment of address. Panics if accessing out of bounds memory.
+    /// Does not check alignment of address or write protection. Panics if
+    /// accessing out of bounds memory.
     pub(crate) fn set_u16_at(&mut self, address: usize, value: u16) {
         let bytes = u16::to_be_bytes(value);
-        self.load_bytes(&bytes, address)
+        self.load_bytes_unprotected(&bytes, address)
             .expect("CHIP-8 interpreter should not write beyond bounds of RAM.");
     }
+
+    /// Grab a u16 from the interpreter work area, `offset` bytes from its
+    /// start.
+    pub(crate) fn get_work_area_u16(&self, offset: usize) -> u16 {
+        self.get_u16_at(self.interpreter_work_area_start_address() + offset)
+    }
+
+    /// Write a u16 to the interpreter work area, `offset` bytes from its
+    /// start.
+    pub(crate) fn set_work_area_u16(&mut self, offset: usize, value: u16) {
+        self.set_u16_at(self.interpreter_work_area_start_address() + offset, value)
+    }
 }
 
 impl Default for CosmacRAM {
@@ -226,49 +1105,227 @@ impl Default for CosmacRAM {
     }
 }
 
-#[cfg(test)]
-mod tests {
+#[cfg(feature = "serde")]
+mod serde_ram {
+    use std::fmt;
 
-    use crate::Error;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{
+        de::{Error as _, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
 
     use super::{
-        CosmacRAM, DISPLAY_REFRESH_START_ADDRESS, INTERPRETER_WORK_AREA_START_ADDRESS, MEMORY_SIZE,
-        MEMORY_START_ADDRESS, PROGRAM_LAST_ADDRESS, PROGRAM_MAX_SIZE, PROGRAM_START_ADDRESS,
-        STACK_START_ADDRESS, V_REGISTERS_START_ADDRESS,
+        CosmacRAM, DisplaySize, Protection, RamSnapshot, DISPLAY_SIZES, MEMORY_SIZES,
+        NUM_DISPLAY_PLANES,
     };
 
-    #[test]
-    fn memory_boundaries() {
-        assert_eq!(MEMORY_SIZE, 4096);
-        assert_eq!(MEMORY_SIZE - DISPLAY_REFRESH_START_ADDRESS, 256);
-        assert_eq!(
-            DISPLAY_REFRESH_START_ADDRESS - INTERPRETER_WORK_AREA_START_ADDRESS,
-            48
-        );
-        assert_eq!(
-            INTERPRETER_WORK_AREA_START_ADDRESS - STACK_START_ADDRESS,
-            48
-        );
+    // The serde representation of `CosmacRAM`.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "CosmacRAM")]
+    struct SerdeRam {
+        memory: Bytes,
+        display_size: DisplaySize,
+        display_planes: [Bytes; NUM_DISPLAY_PLANES],
+        write_protection: Protection,
+    }
 
-        // CHIP-8 programs are allowed to use an extra 2048 bytes when using 4K of RAM instead of 2K.
-        assert_eq!(STACK_START_ADDRESS - PROGRAM_START_ADDRESS, 1184 + 2048);
-        assert_eq!(PROGRAM_LAST_ADDRESS, STACK_START_ADDRESS - 1);
-        assert_eq!(PROGRAM_START_ADDRESS - MEMORY_START_ADDRESS, 512);
+    // A block of bytes, as a base64 string in human-readable formats, which is
+    // far more compact in JSON than an array of numbers.
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.serialize_str(&STANDARD.encode(&self.0))
+            } else {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
     }
 
-    #[test]
-    fn ram_overflow() {
-        let program = [0x00, 0x00];
-        let mut ram = CosmacRAM::new();
-        assert!(ram.load_bytes(&program, MEMORY_SIZE).is_err());
-        assert!(ram.load_bytes(&program, MEMORY_SIZE - 1).is_err());
-        assert!(ram.load_bytes(&program, MEMORY_SIZE - 2).is_ok());
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                let encoded = String::deserialize(deserializer)?;
+                STANDARD
+                    .decode(encoded)
+                    .map(Bytes)
+                    .map_err(D::Error::custom)
+            } else {
+                deserializer.deserialize_byte_buf(BytesVisitor).map(Bytes)
+            }
+        }
     }
 
-    #[test]
-    fn load_into_ram() {
-        let program = chip8_program_into_bytes!(
-            0xA300 0x6080 0xF055 0x6000 0xA300 0xD001 0x120C
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(bytes)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::new();
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+
+    impl Serialize for CosmacRAM {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            SerdeRam {
+                memory: Bytes(self.data.clone()),
+                display_size: self.display_size,
+                display_planes: [0, 1].map(|plane| Bytes(self.display_plane(plane).to_vec())),
+                write_protection: self.write_protection,
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for CosmacRAM {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let SerdeRam {
+                memory: Bytes(data),
+                display_size,
+                display_planes,
+                write_protection,
+            } = SerdeRam::deserialize(deserializer)?;
+
+            if !MEMORY_SIZES.contains(&data.len()) {
+                return Err(D::Error::invalid_length(
+                    data.len(),
+                    &"2048 or 4096 bytes of memory",
+                ));
+            }
+            if !DISPLAY_SIZES.contains(&display_size) {
+                return Err(D::Error::custom(format_args!(
+                    "unsupported display size {}x{}",
+                    display_size.width, display_size.height
+                )));
+            }
+            let display_planes = display_planes.map(|Bytes(plane)| plane);
+            if let Some(plane) = display_planes
+                .iter()
+                .find(|plane| plane.len() != display_size.plane_size())
+            {
+                return Err(D::Error::invalid_length(
+                    plane.len(),
+                    &format!("{} bytes of display plane", display_size.plane_size()).as_str(),
+                ));
+            }
+
+            let mut ram = CosmacRAM::with_memory_size(data.len());
+            ram.restore(&RamSnapshot {
+                data,
+                display_size,
+                display_planes,
+            })
+            .expect("RAM is the size of the snapshot.");
+            ram.set_write_protection(write_protection);
+            Ok(ram)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+
+    use crate::{test_utils::TempFile, Error};
+
+    use super::{
+        CosmacRAM, FillPattern, Protection, VReg, DISPLAY_REFRESH_START_ADDRESS,
+        ETI_660_PROGRAM_START_ADDRESS, HIRES_DISPLAY_SIZE, INTERPRETER_WORK_AREA_START_ADDRESS,
+        LORES_DISPLAY_SIZE, MEMORY_SIZE, MEMORY_START_ADDRESS, PROGRAM_LAST_ADDRESS,
+        PROGRAM_MAX_SIZE, PROGRAM_START_ADDRESS, STACK_START_ADDRESS, V_REGISTERS_START_ADDRESS,
+    };
+
+    // Get RAM with a single row of the display buffer set to `row_pixels`.
+    fn ram_with_display_row(row: usize, row_pixels: [u8; 8]) -> CosmacRAM {
+        let mut ram = CosmacRAM::new();
+        ram.load_bytes(&row_pixels, DISPLAY_REFRESH_START_ADDRESS + row * 8)
+            .expect("Display row should fit into RAM.");
+        ram
+    }
+
+    #[test]
+    fn memory_boundaries() {
+        // CHIP-8 programs are allowed to use an extra 2048 bytes when using 4K of RAM instead of 2K.
+        for (ram, memory_size, program_area_size) in [
+            (CosmacRAM::new_2k(), 2048, 1184),
+            (CosmacRAM::new_4k(), 4096, 1184 + 2048),
+        ] {
+            assert_eq!(ram.memory_size(), memory_size);
+            assert_eq!(ram.bytes().len(), memory_size);
+            assert_eq!(ram.memory_size() - ram.display_refresh_start_address(), 256);
+            assert_eq!(ram.display_refresh_last_address(), ram.memory_size() - 1);
+            assert_eq!(
+                ram.display_refresh_start_address() - ram.interpreter_work_area_start_address(),
+                48
+            );
+            assert_eq!(
+                ram.interpreter_work_area_start_address() - ram.stack_start_address(),
+                48
+            );
+            assert_eq!(
+                ram.display_refresh_start_address() - ram.v_registers_start_address(),
+                16
+            );
+
+            assert_eq!(
+                ram.stack_start_address() - PROGRAM_START_ADDRESS,
+                program_area_size
+            );
+            assert_eq!(ram.program_last_address(), ram.stack_start_address() - 1);
+            assert_eq!(ram.program_max_size(), program_area_size);
+            assert_eq!(PROGRAM_START_ADDRESS - MEMORY_START_ADDRESS, 512);
+        }
+
+        // the global addresses are for the 4K system
+        let ram = CosmacRAM::new_4k();
+        assert_eq!(MEMORY_SIZE, ram.memory_size());
+        assert_eq!(STACK_START_ADDRESS, ram.stack_start_address());
+        assert_eq!(
+            INTERPRETER_WORK_AREA_START_ADDRESS,
+            ram.interpreter_work_area_start_address()
+        );
+        assert_eq!(
+            DISPLAY_REFRESH_START_ADDRESS,
+            ram.display_refresh_start_address()
+        );
+        assert_eq!(V_REGISTERS_START_ADDRESS, ram.v_registers_start_address());
+        assert_eq!(PROGRAM_LAST_ADDRESS, ram.program_last_address());
+        assert_eq!(PROGRAM_MAX_SIZE, ram.program_max_size());
+    }
+
+    #[test]
+    fn ram_overflow() {
+        let program = [0x00, 0x00];
+        let mut ram = CosmacRAM::new();
+        assert!(ram.load_bytes(&program, MEMORY_SIZE).is_err());
+        assert!(ram.load_bytes(&program, MEMORY_SIZE - 1).is_err());
+        assert!(ram.load_bytes(&program, MEMORY_SIZE - 2).is_ok());
+    }
+
+    #[test]
+    fn load_into_ram() {
+        let program = chip8_program_into_bytes!(
+            0xA300 0x6080 0xF055 0x6000 0xA300 0xD001 0x120C
         );
 
         let mut ram = CosmacRAM::new();
@@ -330,6 +1387,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chip8_program_too_big_for_2k() {
+        let mut ram = CosmacRAM::new_2k();
+        assert_eq!(
+            ram.load_chip8_program(&[0x00; 1184 + 1]).unwrap_err(),
+            Error::Chip8ProgramTooLarge(1184 + 1)
+        );
+        assert!(ram.load_chip8_program(&[0x00; 1184]).is_ok());
+        assert_eq!(ram.load_bytes(&[0x00], 0x0800), Err(Error::RamOverflow));
+    }
+
+    #[test]
+    fn eti_660_program_too_big() {
+        let program_area_size = PROGRAM_LAST_ADDRESS - ETI_660_PROGRAM_START_ADDRESS + 1;
+        let program_too_big = vec![0x00; program_area_size + 1];
+        let program_max_size = vec![0x00; program_area_size];
+        let mut ram = CosmacRAM::new();
+
+        assert_eq!(
+            ram.load_chip8_program_at(&program_too_big, ETI_660_PROGRAM_START_ADDRESS)
+                .unwrap_err(),
+            Error::Chip8ProgramTooLarge(program_area_size + 1)
+        );
+        assert!(ram
+            .load_chip8_program_at(&program_max_size, ETI_660_PROGRAM_START_ADDRESS)
+            .is_ok());
+    }
+
+    #[test]
+    fn chip8_program_at_top_of_program_area() {
+        let mut ram = CosmacRAM::new_2k();
+        let last_address = ram.program_last_address();
+        assert!(ram.load_chip8_program_at(&[0x12], last_address).is_ok());
+        assert_eq!(
+            ram.load_chip8_program_at(&[0x12, 0x34], last_address),
+            Err(Error::Chip8ProgramTooLarge(2))
+        );
+        assert_eq!(ram.bytes()[last_address + 1], 0x00);
+    }
+
+    #[test]
+    fn chip8_program_start_address_out_of_range() {
+        let mut ram = CosmacRAM::new();
+        for start_address in [0x0000, 0x01FF, STACK_START_ADDRESS, MEMORY_SIZE, usize::MAX] {
+            assert_eq!(
+                ram.load_chip8_program_at(&[0x12, 0x34], start_address),
+                Err(Error::ProgramStartAddressOutOfRange(start_address))
+            );
+        }
+        assert!(ram.bytes().iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn load_chip8_program_at_start_address() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program_at(&[0x12, 0x34], ETI_660_PROGRAM_START_ADDRESS)
+            .unwrap();
+        assert_eq!(ram.get_u16_at(ETI_660_PROGRAM_START_ADDRESS), 0x1234);
+        assert_eq!(ram.get_u16_at(PROGRAM_START_ADDRESS), 0x0000);
+    }
+
     #[test]
     fn load_bytes_does_not_trash_other_memory() {
         let original_data = [0x01, 0x02, 0x03, 0x04, 0x05];
@@ -355,7 +1473,6 @@ mod tests {
         ram.load_bytes(&original_data, 0)
             .expect("Loading these bytes should not fail!");
 
-        let mut ram = ram;
         ram.data[0] = 0x42;
         assert_eq!(
             original_data[0], 0x01,
@@ -363,6 +1480,277 @@ mod tests {
         )
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_chip8_program(&[0x12, 0x34, 0x56]).unwrap();
+        ram.set_display_size(HIRES_DISPLAY_SIZE);
+        ram.display_plane_mut(1)[100] = 0xAA;
+        ram.set_write_protection(Protection::FONT_AREA);
+
+        let json = serde_json::to_string(&ram).unwrap();
+        let from_json: CosmacRAM = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.snapshot(), ram.snapshot());
+        assert_eq!(from_json.write_protection(), Protection::FONT_AREA);
+
+        let binary = bincode::serialize(&ram).unwrap();
+        let from_binary: CosmacRAM = bincode::deserialize(&binary).unwrap();
+        assert_eq!(from_binary.snapshot(), ram.snapshot());
+        assert!(
+            binary.len() < 2048 + 2 * 1024 + 100,
+            "Memory should be serialized as bytes, took {} bytes",
+            binary.len()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_memory_is_base64() {
+        let json = serde_json::to_value(CosmacRAM::new()).unwrap();
+        assert_eq!(
+            json["memory"].as_str().map(str::len),
+            Some(4096 / 3 * 4 + 4)
+        );
+        assert!(json["display_planes"][0].is_string());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_wrong_memory_size() {
+        let mut json = serde_json::to_value(CosmacRAM::new()).unwrap();
+        json["memory"] = serde_json::Value::from("AAAA"); // 3 bytes
+        let err = serde_json::from_value::<CosmacRAM>(json)
+            .err()
+            .expect("3 bytes of memory should be rejected");
+        assert!(
+            err.to_string().contains("2048 or 4096 bytes"),
+            "Unclear error: {err}"
+        );
+
+        let mut json = serde_json::to_value(CosmacRAM::new()).unwrap();
+        json["display_planes"][1] = serde_json::Value::from("AAAA");
+        assert!(serde_json::from_value::<CosmacRAM>(json).is_err());
+    }
+
+    #[test]
+    fn display_view() {
+        let ram = ram_with_display_row(1, [0x80, 0, 0, 0, 0, 0, 0, 0x01]);
+        let display = ram.display();
+        assert_eq!((display.width(), display.height()), (64, 32));
+        assert_eq!(display.get_pixel(0, 1), Some(true));
+        assert_eq!(display.get_pixel(1, 1), Some(false));
+        assert_eq!(display.get_pixel(63, 1), Some(true));
+        assert_eq!(display.get_pixel(0, 0), Some(false));
+
+        assert_eq!(display.get_pixel(64, 1), None);
+        assert_eq!(display.get_pixel(0, 32), None);
+        assert_eq!(display.get_pixel(255, 255), None);
+
+        assert_eq!(display.pixels().count(), 64 * 32);
+        assert_eq!(
+            display
+                .pixels()
+                .filter(|&(_, _, on)| on)
+                .collect::<Vec<_>>(),
+            [(0, 1, true), (63, 1, true)]
+        );
+    }
+
+    #[test]
+    fn display_view_to_ascii() {
+        let ram = ram_with_display_row(1, [0xC0, 0, 0, 0, 0, 0, 0, 0x01]);
+        let ascii = ram.display().to_ascii('#', '.');
+        let lines: Vec<_> = ascii.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0], ".".repeat(64));
+        assert_eq!(lines[1], format!("##{}#", ".".repeat(61)));
+        assert!(ascii.ends_with('\n'));
+
+        assert_eq!(ram.display().to_string(), ram.display().to_ascii('█', ' '));
+    }
+
+    #[test]
+    fn hires_display_view() {
+        let mut ram = CosmacRAM::new();
+        ram.set_display_size(HIRES_DISPLAY_SIZE);
+        ram.display_plane_mut(1)[16 * 63 + 15] = 0x01;
+        let display = ram.display_plane_view(1);
+        assert_eq!((display.width(), display.height()), (128, 64));
+        assert_eq!(display.get_pixel(127, 63), Some(true));
+        assert_eq!(display.get_pixel(128, 63), None);
+        assert_eq!(display.pixels().last(), Some((127, 63, true)));
+        assert_eq!(ram.display().get_pixel(127, 63), Some(false));
+
+        let ascii = display.to_ascii('#', '.');
+        assert_eq!(ascii.lines().count(), 64);
+        assert_eq!(
+            ascii.lines().last(),
+            Some(format!("{}#", ".".repeat(127)).as_str())
+        );
+    }
+
+    #[test]
+    fn xor_byte_into_display_view() {
+        let mut ram = ram_with_display_row(0, [0x00, 0x00, 0, 0, 0, 0, 0, 0xF0]);
+        let mut display = ram.display_plane_view_mut(0);
+        assert_eq!(display.xor_byte(4, 0, 0xFF, false), (8, false));
+        assert_eq!(display.xor_byte(58, 0, 0xFF, false), (6, true));
+        assert_eq!(display.xor_byte(60, 1, 0xFF, true), (8, false));
+        assert_eq!(
+            &ram.display_buffer()[..16],
+            &[0x0F, 0xF0, 0, 0, 0, 0, 0, 0xCF, 0xF0, 0, 0, 0, 0, 0, 0, 0x0F]
+        );
+    }
+
+    #[test]
+    fn xor_byte_past_right_edge_of_display() {
+        let mut ram = CosmacRAM::new();
+        let mut display = ram.display_plane_view_mut(0);
+        assert_eq!(display.xor_byte(68, 0, 0xFF, false), (0, false));
+        assert_eq!(display.xor_byte(68, 1, 0xFF, true), (8, false));
+        assert_eq!(&ram.display_buffer()[..8], &[0; 8]);
+        assert_eq!(&ram.display_buffer()[8..10], &[0x0F, 0xF0]);
+    }
+
+    #[test]
+    fn restore_snapshot() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_chip8_program(&[0x12, 0x34, 0x56]).unwrap();
+        ram.set_display_size(HIRES_DISPLAY_SIZE);
+        ram.display_plane_mut(0)[10] = 0xAA;
+        ram.display_plane_mut(1)[20] = 0x55;
+        let snapshot = ram.snapshot();
+
+        ram.load_bytes(&[0xFF; 0x100], 0x0300).unwrap();
+        ram.set_u16_at(0x0200, 0xABCD);
+        ram.set_display_size(LORES_DISPLAY_SIZE);
+        ram.display_plane_mut(0)[0] = 0xFF;
+        assert_ne!(ram.snapshot(), snapshot);
+
+        ram.restore(&snapshot).unwrap();
+        assert_eq!(ram.snapshot(), snapshot);
+        assert_eq!(ram.bytes(), snapshot.bytes());
+        assert_eq!(ram.display_size(), HIRES_DISPLAY_SIZE);
+        assert_eq!(ram.display_plane(0)[10], 0xAA);
+        assert_eq!(ram.display_plane(1)[20], 0x55);
+    }
+
+    #[test]
+    fn restore_lores_snapshot() {
+        let mut ram = CosmacRAM::new();
+        ram.load_bytes(&[0x81; 8], DISPLAY_REFRESH_START_ADDRESS)
+            .unwrap();
+        ram.display_plane_mut(1)[0] = 0x42;
+        let snapshot = ram.snapshot();
+
+        let mut other_ram = CosmacRAM::new();
+        other_ram.set_display_size(HIRES_DISPLAY_SIZE);
+        other_ram.load_bytes(&[0x99; 16], 0x0F00).unwrap();
+        other_ram.restore(&snapshot).unwrap();
+        assert_eq!(other_ram.bytes(), ram.bytes());
+        assert_eq!(other_ram.display_buffer(), ram.display_buffer());
+        assert_eq!(other_ram.display_plane(1), ram.display_plane(1));
+    }
+
+    #[test]
+    fn restore_snapshot_of_different_size() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_bytes(&[0x12, 0x34], 0x0300).unwrap();
+        let before = ram.snapshot();
+
+        assert_eq!(
+            ram.restore(&CosmacRAM::new_4k().snapshot()),
+            Err(Error::MemoryImageSizeMismatch(0x1000))
+        );
+        assert_eq!(ram.snapshot(), before);
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let mut ram = CosmacRAM::new();
+        ram.load_bytes(&[0x11, 0x22], 0x0300).unwrap();
+        let before = ram.snapshot();
+        ram.load_bytes(&[0x11, 0x33, 0x44], 0x0300).unwrap();
+        ram.set_u16_at(0x0FFE, 0x0001);
+        let after = ram.snapshot();
+
+        assert_eq!(
+            before.diff(&after),
+            [
+                (0x0301, 0x22, 0x33),
+                (0x0302, 0x00, 0x44),
+                (0x0FFF, 0x00, 0x01)
+            ]
+        );
+        assert_eq!(after.diff(&before)[0], (0x0301, 0x33, 0x22));
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn write_protection() {
+        let mut ram = CosmacRAM::new_2k();
+        assert_eq!(ram.write_protection(), Protection::NONE);
+        assert!(ram.load_bytes(&[0xFF], 0x01FF).is_ok());
+
+        ram.set_write_protection(Protection::FONT_AREA);
+        assert_eq!(
+            ram.load_bytes(&[0x00; 4], 0x01FE),
+            Err(Error::WriteProtected { address: 0x01FE })
+        );
+        assert_eq!(ram.bytes()[0x01FF], 0xFF, "RAM should be unchanged");
+        assert!(ram.load_bytes(&[0xFF; 4], 0x0200).is_ok());
+        assert!(ram.load_bytes(&[], 0x0100).is_ok());
+
+        ram.set_write_protection(Protection::FONT_AREA | Protection::PROGRAM_AREA);
+        assert!(ram
+            .write_protection()
+            .contains(Protection::FONT_AREA | Protection::PROGRAM_AREA));
+        assert_eq!(
+            ram.try_set_u16_at(0x069F, 0x1234),
+            Err(Error::WriteProtected { address: 0x069F })
+        );
+        assert_eq!(
+            ram.zero_out_range(0x0600..0x0800),
+            Err(Error::WriteProtected { address: 0x0600 })
+        );
+        assert!(ram.try_set_u16_at(0x06A0, 0x1234).is_ok());
+        assert!(
+            ram.load_chip8_program(&[0x12, 0x00]).is_ok(),
+            "Loading a program should ignore write protection"
+        );
+
+        ram.set_write_protection(Protection::PROGRAM_AREA);
+        assert!(ram.load_bytes(&[0x00], 0x01FF).is_ok());
+    }
+
+    #[test]
+    fn checked_u16_access() {
+        for mut ram in [CosmacRAM::new_2k(), CosmacRAM::new_4k()] {
+            let last_word = ram.memory_size() - 2;
+            let last_byte = ram.memory_size() - 1;
+            let past_end = ram.memory_size();
+
+            assert_eq!(ram.try_set_u16_at(last_word, 0x1234), Ok(()));
+            assert_eq!(ram.try_get_u16_at(last_word), Ok(0x1234));
+
+            assert_eq!(
+                ram.try_set_u16_at(last_byte, 0xABCD),
+                Err(Error::RamOverflow)
+            );
+            assert_eq!(ram.try_get_u16_at(last_byte), Err(Error::RamOverflow));
+            assert_eq!(ram.bytes()[last_byte], 0x34, "RAM should be unchanged");
+
+            assert_eq!(
+                ram.try_set_u16_at(past_end, 0xABCD),
+                Err(Error::RamOverflow)
+            );
+            assert_eq!(ram.try_get_u16_at(past_end), Err(Error::RamOverflow));
+            assert_eq!(ram.try_get_u16_at(usize::MAX), Err(Error::RamOverflow));
+            assert_eq!(ram.try_set_u16_at(usize::MAX, 0), Err(Error::RamOverflow));
+        }
+    }
+
     #[test]
     fn u16_to_u8_conversion() {
         let bytes = chip8_program_into_bytes!(0x1122 0x3344);
@@ -389,4 +1777,374 @@ mod tests {
         mut_registers[1] = 0x42;
         assert_eq!(&ram.get_v_registers()[..3], &[0x11, 0x42, 0x33]);
     }
+
+    #[test]
+    fn fill_patterns() {
+        assert!(CosmacRAM::new_with_fill(FillPattern::Zero)
+            .bytes()
+            .iter()
+            .all(|&byte| byte == 0));
+        assert_eq!(
+            CosmacRAM::new_with_fill(FillPattern::Zero).bytes(),
+            CosmacRAM::new().bytes()
+        );
+        assert!(CosmacRAM::new_with_fill(FillPattern::Ones)
+            .bytes()
+            .iter()
+            .all(|&byte| byte == 0xFF));
+
+        let random = CosmacRAM::new_with_fill(FillPattern::Random { seed: 42 });
+        assert_eq!(
+            random.bytes(),
+            CosmacRAM::new_with_fill(FillPattern::Random { seed: 42 }).bytes(),
+            "The same seed should give the same fill"
+        );
+        assert_ne!(
+            random.bytes(),
+            CosmacRAM::new_with_fill(FillPattern::Random { seed: 43 }).bytes()
+        );
+        assert!(random.bytes().iter().any(|&byte| byte != 0));
+
+        let mut ram = CosmacRAM::new_2k();
+        ram.fill(FillPattern::Ones);
+        assert_eq!(ram.bytes(), [0xFF; 0x0800]);
+    }
+
+    #[test]
+    fn memory_image_file_round_trip() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x00E0 0xA22A 0x600C 0xD01F))
+            .unwrap();
+        ram.display_plane_mut(0).fill(0xA5);
+        let file = TempFile::new("round-trip.bin");
+        ram.dump_to_file(&file.path).unwrap();
+        assert_eq!(fs::read(&file.path).unwrap().len(), MEMORY_SIZE);
+
+        let mut loaded = CosmacRAM::new();
+        loaded.load_from_file(&file.path).unwrap();
+        assert_eq!(loaded.bytes(), ram.bytes());
+        assert_eq!(loaded.display_buffer(), ram.display_buffer());
+    }
+
+    #[test]
+    fn truncated_memory_image_file() {
+        let file = TempFile::with_bytes("truncated.bin", &[0xFF; MEMORY_SIZE - 1]);
+        let mut ram = CosmacRAM::new();
+        assert_eq!(
+            ram.load_from_file(&file.path),
+            Err(Error::MemoryImageSizeMismatch(MEMORY_SIZE - 1))
+        );
+        assert_eq!(
+            ram.bytes(),
+            CosmacRAM::new().bytes(),
+            "RAM should be unchanged"
+        );
+
+        // a 4K image doesn't fit 2K RAM
+        let file = TempFile::with_bytes("4k.bin", &[0xFF; MEMORY_SIZE]);
+        assert_eq!(
+            CosmacRAM::new_2k().load_from_file(&file.path),
+            Err(Error::MemoryImageSizeMismatch(MEMORY_SIZE))
+        );
+    }
+
+    #[test]
+    fn intel_hex_round_trip() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(
+            &(0..=255)
+                .cycle()
+                .take(PROGRAM_MAX_SIZE)
+                .collect::<Vec<u8>>(),
+        )
+        .unwrap();
+        let program_area = PROGRAM_START_ADDRESS..PROGRAM_LAST_ADDRESS + 1;
+        let hex = ram.to_intel_hex(program_area.clone());
+        assert_eq!(hex.lines().count(), PROGRAM_MAX_SIZE / 16 + 1);
+        assert!(hex.starts_with(":10020000000102030405060708090A0B0C0D0E0F76\n"));
+
+        let mut loaded = CosmacRAM::new();
+        loaded.load_intel_hex(&hex).unwrap();
+        assert_eq!(
+            loaded.bytes()[program_area.clone()],
+            ram.bytes()[program_area]
+        );
+        assert_eq!(
+            loaded.bytes()[..PROGRAM_START_ADDRESS],
+            ram.bytes()[..PROGRAM_START_ADDRESS]
+        );
+    }
+
+    #[test]
+    fn intel_hex_with_bad_checksum() {
+        let mut ram = CosmacRAM::new();
+        let before = ram.bytes().to_vec();
+        assert_eq!(
+            ram.load_intel_hex(":0402100010111213A4\n:0402140014151617A5\n:00000001FF\n"),
+            Err(Error::InvalidIntelHex {
+                line: 2,
+                reason: "bad checksum"
+            })
+        );
+        assert_eq!(ram.bytes(), before, "RAM should be unchanged");
+    }
+
+    #[test]
+    fn intel_hex_past_end_of_2k_ram() {
+        let mut ram = CosmacRAM::new_2k();
+        assert_eq!(
+            ram.load_intel_hex(":0207FF00AAAAA4\n:00000001FF\n"),
+            Err(Error::InvalidIntelHex {
+                line: 1,
+                reason: "data goes past the end of RAM"
+            })
+        );
+        let mut ram = CosmacRAM::new();
+        ram.load_intel_hex(":020FFE00AAAA9D\n:00000001FF\n")
+            .unwrap();
+        assert_eq!(ram.bytes()[0x0FFE..], [0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn intel_hex_into_write_protected_area() {
+        let mut ram = CosmacRAM::new();
+        ram.set_write_protection(Protection::PROGRAM_AREA);
+        assert_eq!(
+            ram.load_intel_hex(":0402100010111213A4\n:00000001FF\n"),
+            Err(Error::WriteProtected { address: 0x0210 })
+        );
+    }
+
+    #[test]
+    fn program_words() {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x00E0 0xA22A 0x600C 0xD01F))
+            .unwrap();
+
+        assert_eq!(
+            ram.program_words().take(5).collect::<Vec<_>>(),
+            vec![
+                (0x0200, 0x00E0),
+                (0x0202, 0xA22A),
+                (0x0204, 0x600C),
+                (0x0206, 0xD01F),
+                (0x0208, 0x0000),
+            ]
+        );
+        assert_eq!(ram.program_words().count(), PROGRAM_MAX_SIZE / 2);
+        assert_eq!(
+            ram.program_words().last().unwrap().0 as usize,
+            PROGRAM_LAST_ADDRESS - 1
+        );
+
+        let ram = CosmacRAM::new_2k();
+        assert_eq!(
+            ram.program_words().last().unwrap().0 as usize,
+            ram.program_last_address() - 1
+        );
+    }
+
+    #[test]
+    fn v_register_from_nibble() {
+        assert_eq!(VReg::from(0x0), VReg::V0);
+        assert_eq!(VReg::from(0xA), VReg::VA);
+        assert_eq!(VReg::from(0xF), VReg::VF);
+        assert_eq!(VReg::from(0x1F), VReg::VF, "Only the low nibble counts");
+        for (i, reg) in VReg::ALL.into_iter().enumerate() {
+            assert_eq!(reg.index(), i);
+            assert_eq!(VReg::from(i as u8), reg);
+        }
+    }
+
+    #[test]
+    fn typed_v_register_access() {
+        let mut ram = CosmacRAM::new();
+        ram.set_v(VReg::V3, 0x42);
+        ram.set_vf(1);
+        assert_eq!(ram.v(VReg::V3), 0x42);
+        assert_eq!(ram.vf(), 1);
+        assert_eq!(ram.v(VReg::VF), 1);
+        assert_eq!(ram.get_v_registers()[3], 0x42);
+        assert_eq!(ram.get_v_registers()[0xF], 1);
+
+        ram.get_v_registers_mut()[0xA] = 0x99;
+        assert_eq!(ram.v(VReg::VA), 0x99);
+    }
+
+    #[test]
+    fn scroll_display_down_by_zero() {
+        let mut ram = ram_with_display_row(3, [0xA5; 8]);
+        ram.scroll_display_down(0, 0);
+        assert_eq!(
+            ram.display_buffer(),
+            ram_with_display_row(3, [0xA5; 8]).display_buffer()
+        );
+    }
+
+    #[test]
+    fn scroll_display_down_by_one() {
+        let mut ram = ram_with_display_row(3, [0xA5; 8]);
+        ram.load_bytes(&[0xFF; 8], DISPLAY_REFRESH_START_ADDRESS)
+            .expect("Display row should fit into RAM.");
+
+        ram.scroll_display_down(0, 1);
+
+        assert_eq!(
+            &ram.display_buffer()[..8],
+            &[0x00; 8],
+            "Top row should be cleared"
+        );
+        assert_eq!(&ram.display_buffer()[8..16], &[0xFF; 8]);
+        assert_eq!(&ram.display_buffer()[16..32], &[0x00; 16]);
+        assert_eq!(&ram.display_buffer()[32..40], &[0xA5; 8]);
+        assert_eq!(&ram.display_buffer()[40..], &[0x00; 216]);
+    }
+
+    #[test]
+    fn scroll_display_down_by_fifteen() {
+        let mut ram = ram_with_display_row(0, [0x81; 8]);
+        ram.scroll_display_down(0, 15);
+        assert_eq!(
+            ram.display_buffer(),
+            ram_with_display_row(15, [0x81; 8]).display_buffer()
+        );
+    }
+
+    #[test]
+    fn scroll_display_down_off_the_bottom() {
+        let mut ram = ram_with_display_row(20, [0x81; 8]);
+        ram.scroll_display_down(0, 15);
+        assert_eq!(ram.display_buffer(), &[0x00; 256]);
+
+        let mut ram = ram_with_display_row(31, [0x81; 8]);
+        ram.scroll_display_down(0, 1);
+        assert_eq!(ram.display_buffer(), &[0x00; 256]);
+    }
+
+    #[test]
+    fn scroll_display_right() {
+        let mut ram = ram_with_display_row(5, [0x0F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x01, 0x8F]);
+
+        ram.scroll_display_right(0, 0);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0x0F, 0xF0, 0x00, 0x00, 0x00, 0x00, 0x01, 0x8F]
+        );
+
+        ram.scroll_display_right(0, 1);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0x07, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC7],
+            "Bits should carry into the next byte and fall off the right edge"
+        );
+
+        ram.scroll_display_right(0, 4);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0x00, 0x7F, 0x80, 0x00, 0x00, 0x00, 0x00, 0x0C]
+        );
+        assert_eq!(&ram.display_buffer()[..40], &[0x00; 40]);
+        assert_eq!(&ram.display_buffer()[48..], &[0x00; 208]);
+    }
+
+    #[test]
+    fn scroll_display_right_off_the_edge() {
+        let mut ram = ram_with_display_row(0, [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0F]);
+        ram.scroll_display_right(0, 4);
+        assert_eq!(ram.display_buffer(), &[0x00; 256]);
+
+        let mut ram = ram_with_display_row(0, [0xFF; 8]);
+        ram.scroll_display_right(0, 15);
+        assert_eq!(
+            &ram.display_buffer()[..8],
+            &[0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn scroll_display_left() {
+        let mut ram = ram_with_display_row(5, [0xF1, 0x80, 0x00, 0x00, 0x00, 0x00, 0x0F, 0xF0]);
+
+        ram.scroll_display_left(0, 0);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0xF1, 0x80, 0x00, 0x00, 0x00, 0x00, 0x0F, 0xF0]
+        );
+
+        ram.scroll_display_left(0, 1);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0xE3, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F, 0xE0],
+            "Bits should carry into the previous byte and fall off the left edge"
+        );
+
+        ram.scroll_display_left(0, 4);
+        assert_eq!(
+            &ram.display_buffer()[40..48],
+            &[0x30, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFE, 0x00]
+        );
+        assert_eq!(&ram.display_buffer()[..40], &[0x00; 40]);
+        assert_eq!(&ram.display_buffer()[48..], &[0x00; 208]);
+    }
+
+    #[test]
+    fn scroll_display_left_off_the_edge() {
+        let mut ram = ram_with_display_row(31, [0xF0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        ram.scroll_display_left(0, 4);
+        assert_eq!(ram.display_buffer(), &[0x00; 256]);
+
+        let mut ram = ram_with_display_row(31, [0xFF; 8]);
+        ram.scroll_display_left(0, 15);
+        assert_eq!(
+            &ram.display_buffer()[248..],
+            &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x00]
+        );
+    }
+
+    #[test]
+    fn scroll_second_display_plane() {
+        let mut ram = CosmacRAM::new();
+        ram.display_plane_mut(1)[..8].copy_from_slice(&[0xF0; 8]);
+
+        ram.scroll_display_down(1, 1);
+        ram.scroll_display_right(1, 4);
+        assert_eq!(&ram.display_plane(1)[..8], &[0x00; 8]);
+        assert_eq!(&ram.display_plane(1)[8..16], &[0x0F; 8]);
+
+        ram.scroll_display_left(1, 8);
+        assert_eq!(
+            &ram.display_plane(1)[8..16],
+            &[0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x0F, 0x00]
+        );
+        assert_eq!(
+            ram.display_buffer(),
+            &[0x00; 256],
+            "First display plane should be unaffected"
+        );
+    }
+
+    #[test]
+    fn hires_display_planes() {
+        let mut ram = CosmacRAM::new();
+        ram.load_bytes(&[0xFF; 256], DISPLAY_REFRESH_START_ADDRESS)
+            .expect("Display refresh area should fit into RAM.");
+
+        ram.set_display_size(HIRES_DISPLAY_SIZE);
+        assert_eq!(ram.display_plane(0), &[0x00; 1024]);
+        assert_eq!(ram.display_plane(1), &[0x00; 1024]);
+
+        // drawing in hires mode doesn't touch the display refresh area
+        ram.display_plane_mut(0)[1023] = 0x01;
+        ram.scroll_display_left(0, 1);
+        assert_eq!(ram.display_buffer()[1022..], [0x00, 0x02]);
+        assert_eq!(
+            &ram.bytes()[DISPLAY_REFRESH_START_ADDRESS..],
+            &[0x00; 256],
+            "Switching display size should clear the display refresh area"
+        );
+
+        ram.set_display_size(LORES_DISPLAY_SIZE);
+        assert_eq!(ram.display_plane(0), &[0x00; 256]);
+        assert_eq!(ram.display_plane(1), &[0x00; 256]);
+    }
 }
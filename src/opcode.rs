@@ -0,0 +1,189 @@
+//! Decoding CHIP-8 instructions into the operation they perform.
+
+/// The operation a CHIP-8 instruction performs, named after its effect. The
+/// operands are left in the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// 7000
+    Nop,
+    /// 1MMM
+    Jump,
+    /// BMMM
+    JumpWithOffset,
+    /// 2MMM
+    Call,
+    /// 00EE
+    Return,
+    /// 3XKK
+    SkipIfEqualConstant,
+    /// 4XKK
+    SkipIfNotEqualConstant,
+    /// 5XY0
+    SkipIfEqual,
+    /// 9XY0
+    SkipIfNotEqual,
+    /// EX9E
+    SkipIfKey,
+    /// EXA1
+    SkipIfNotKey,
+    /// 6XKK
+    SetConstant,
+    /// CXKK
+    SetRandom,
+    /// 7XKK
+    AddConstant,
+    /// 8XY0
+    Set,
+    /// 8XY1
+    Or,
+    /// 8XY2
+    And,
+    /// 8XY3
+    Xor,
+    /// 8XY4
+    Add,
+    /// 8XY5
+    Subtract,
+    /// 8XY6
+    ShiftRight,
+    /// 8XY7
+    SubtractReversed,
+    /// 8XYE
+    ShiftLeft,
+    /// FX07
+    GetTimer,
+    /// FX0A
+    WaitForKey,
+    /// FX15
+    SetTimer,
+    /// FX18
+    SetToneTimer,
+    /// AMMM
+    SetI,
+    /// FX1E
+    AddToI,
+    /// FX29
+    SetIToFont,
+    /// FX33
+    StoreDecimal,
+    /// FX55
+    Store,
+    /// FX65
+    Load,
+    /// 00E0
+    ClearDisplay,
+    /// DXYN
+    Draw,
+    /// 00CN (SCHIP)
+    ScrollDown,
+    /// 00FB (SCHIP)
+    ScrollRight,
+    /// 00FC (SCHIP)
+    ScrollLeft,
+    /// 00FD (SCHIP)
+    Exit,
+    /// 00FE (SCHIP)
+    Lores,
+    /// 00FF (SCHIP)
+    Hires,
+    /// FX30 (SCHIP)
+    SetIToBigFont,
+    /// FX75 (SCHIP)
+    StoreRplFlags,
+    /// FX85 (SCHIP)
+    LoadRplFlags,
+    /// FN01 (XO-CHIP)
+    SelectPlanes,
+    /// F002 (XO-CHIP)
+    LoadAudioPattern,
+    /// FX3A (XO-CHIP)
+    SetPitch,
+    /// 0MMM
+    MachineLanguageCall,
+    /// Not a CHIP-8 instruction.
+    Unknown,
+}
+
+impl Opcode {
+    /// Decode `instruction`, which is only an SCHIP or XO-CHIP instruction if
+    /// those extensions to the instruction set are available.
+    pub fn decode(instruction: u16, schip_opcodes: bool, xo_chip_opcodes: bool) -> Self {
+        match instruction {
+            0x7000 => Opcode::Nop,
+            op if op & 0xF000 == 0x1000 => Opcode::Jump,
+            op if op & 0xF000 == 0xB000 => Opcode::JumpWithOffset,
+            op if op & 0xF000 == 0x2000 => Opcode::Call,
+            0x00EE => Opcode::Return,
+            op if op & 0xF000 == 0x3000 => Opcode::SkipIfEqualConstant,
+            op if op & 0xF000 == 0x4000 => Opcode::SkipIfNotEqualConstant,
+            op if op & 0xF00F == 0x5000 => Opcode::SkipIfEqual,
+            op if op & 0xF00F == 0x9000 => Opcode::SkipIfNotEqual,
+            op if op & 0xF0FF == 0xE09E => Opcode::SkipIfKey,
+            op if op & 0xF0FF == 0xE0A1 => Opcode::SkipIfNotKey,
+            op if op & 0xF000 == 0x6000 => Opcode::SetConstant,
+            op if op & 0xF000 == 0xC000 => Opcode::SetRandom,
+            op if op & 0xF000 == 0x7000 => Opcode::AddConstant,
+            op if op & 0xF00F == 0x8000 => Opcode::Set,
+            op if op & 0xF00F == 0x8001 => Opcode::Or,
+            op if op & 0xF00F == 0x8002 => Opcode::And,
+            op if op & 0xF00F == 0x8003 => Opcode::Xor,
+            op if op & 0xF00F == 0x8004 => Opcode::Add,
+            op if op & 0xF00F == 0x8005 => Opcode::Subtract,
+            op if op & 0xF00F == 0x8006 => Opcode::ShiftRight,
+            op if op & 0xF00F == 0x8007 => Opcode::SubtractReversed,
+            op if op & 0xF00F == 0x800E => Opcode::ShiftLeft,
+            op if op & 0xF0FF == 0xF007 => Opcode::GetTimer,
+            op if op & 0xF0FF == 0xF00A => Opcode::WaitForKey,
+            op if op & 0xF0FF == 0xF015 => Opcode::SetTimer,
+            op if op & 0xF0FF == 0xF018 => Opcode::SetToneTimer,
+            op if op & 0xF000 == 0xA000 => Opcode::SetI,
+            op if op & 0xF0FF == 0xF01E => Opcode::AddToI,
+            op if op & 0xF0FF == 0xF029 => Opcode::SetIToFont,
+            op if op & 0xF0FF == 0xF033 => Opcode::StoreDecimal,
+            op if op & 0xF0FF == 0xF055 => Opcode::Store,
+            op if op & 0xF0FF == 0xF065 => Opcode::Load,
+            0x00E0 => Opcode::ClearDisplay,
+            op if op & 0xF000 == 0xD000 => Opcode::Draw,
+            op if schip_opcodes && op & 0xFFF0 == 0x00C0 => Opcode::ScrollDown,
+            op if schip_opcodes && op == 0x00FB => Opcode::ScrollRight,
+            op if schip_opcodes && op == 0x00FC => Opcode::ScrollLeft,
+            op if schip_opcodes && op == 0x00FD => Opcode::Exit,
+            op if schip_opcodes && op == 0x00FE => Opcode::Lores,
+            op if schip_opcodes && op == 0x00FF => Opcode::Hires,
+            op if schip_opcodes && op & 0xF0FF == 0xF030 => Opcode::SetIToBigFont,
+            op if schip_opcodes && op & 0xF0FF == 0xF075 => Opcode::StoreRplFlags,
+            op if schip_opcodes && op & 0xF0FF == 0xF085 => Opcode::LoadRplFlags,
+            op if xo_chip_opcodes && op & 0xF0FF == 0xF001 => Opcode::SelectPlanes,
+            op if xo_chip_opcodes && op == 0xF002 => Opcode::LoadAudioPattern,
+            op if xo_chip_opcodes && op & 0xF0FF == 0xF03A => Opcode::SetPitch,
+            op if op & 0xF000 == 0x0000 => Opcode::MachineLanguageCall,
+            _ => Opcode::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Opcode;
+
+    #[test]
+    fn decode_instructions() {
+        assert_eq!(Opcode::decode(0x7000, false, false), Opcode::Nop);
+        assert_eq!(Opcode::decode(0x7001, false, false), Opcode::AddConstant);
+        assert_eq!(Opcode::decode(0x00E0, false, false), Opcode::ClearDisplay);
+        assert_eq!(Opcode::decode(0x8126, false, false), Opcode::ShiftRight);
+        assert_eq!(Opcode::decode(0xF155, false, false), Opcode::Store);
+        assert_eq!(Opcode::decode(0x5121, false, false), Opcode::Unknown);
+    }
+
+    #[test]
+    fn decode_extended_instructions() {
+        assert_eq!(Opcode::decode(0x00FF, true, false), Opcode::Hires);
+        assert_eq!(
+            Opcode::decode(0x00FF, false, false),
+            Opcode::MachineLanguageCall
+        );
+        assert_eq!(Opcode::decode(0xF201, false, true), Opcode::SelectPlanes);
+        assert_eq!(Opcode::decode(0xF201, true, false), Opcode::Unknown);
+    }
+}
@@ -0,0 +1,496 @@
+//! Text drawn over the display by the emulator, e.g. the FPS counter, in a
+//! tiny 3x5 pixel font.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{disassembler::disassemble, interpreter::Chip8State};
+
+/// The width of a character, in pixels.
+pub(crate) const CHAR_WIDTH: usize = 3;
+/// The height of a character, in pixels.
+pub(crate) const CHAR_HEIGHT: usize = 5;
+/// The horizontal distance from one character to the next, leaving a
+/// column of space between them.
+pub(crate) const CHAR_ADVANCE: usize = CHAR_WIDTH + 1;
+/// The vertical distance from one line of text to the next.
+pub(crate) const LINE_ADVANCE: usize = CHAR_HEIGHT + 1;
+
+/// The width of the debug panel, in pixels, enough for a row of four V
+/// registers with a border.
+pub(crate) const DEBUG_PANEL_WIDTH: usize = 96;
+/// The height that the debug panel needs for its lines of text, in pixels.
+/// The display is scaled up to be at least this tall next to it.
+pub(crate) const DEBUG_PANEL_HEIGHT: usize = 64;
+// the V registers shown on each line of the debug panel
+const V_REGISTERS_PER_LINE: usize = 4;
+// the caller addresses shown on each line of the debug panel
+const CALLERS_PER_LINE: usize = 4;
+
+// The rows of each character, top to bottom, with the leftmost pixel in
+// bit 2.
+#[rustfmt::skip]
+const FONT: [(char, [u8; CHAR_HEIGHT]); 47] = [
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+    ('?', [0b111, 0b001, 0b011, 0b000, 0b010]),
+];
+
+// The rows of `c`, with lowercase letters drawn as uppercase and unknown
+// characters as `?`.
+fn glyph(c: char) -> [u8; CHAR_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    FONT.iter()
+        .find(|&&(font_char, _)| font_char == c)
+        .or_else(|| FONT.iter().find(|&&(font_char, _)| font_char == '?'))
+        .unwrap()
+        .1
+}
+
+/// The width in pixels of `text` drawn on one line, without the space after
+/// the last character.
+pub(crate) fn text_width(text: &str) -> usize {
+    (text.chars().count() * CHAR_ADVANCE).saturating_sub(1)
+}
+
+/// Draw `text` in `color` into `frame`, RGBA pixels in rows of
+/// `frame_width`, with its top left corner at (x, y). Only the pixels of the
+/// characters are drawn, and anything outside of the frame is clipped.
+pub(crate) fn draw_text(
+    frame: &mut [u8],
+    frame_width: usize,
+    (x, y): (usize, usize),
+    text: &str,
+    color: [u8; 4],
+) {
+    let frame_height = frame.len() / 4 / frame_width;
+    for (i, c) in text.chars().enumerate() {
+        let char_x = x + i * CHAR_ADVANCE;
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..CHAR_WIDTH {
+                let (pixel_x, pixel_y) = (char_x + column, y + row);
+                if bits & (0b100 >> column) == 0
+                    || pixel_x >= frame_width
+                    || pixel_y >= frame_height
+                {
+                    continue;
+                }
+                let index = 4 * (pixel_y * frame_width + pixel_x);
+                frame[index..index + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Fill the rectangle `width` by `height` pixels with its top left corner at
+/// (x, y) with `color`, clipped to `frame` as for [`draw_text`].
+pub(crate) fn fill_rect(
+    frame: &mut [u8],
+    frame_width: usize,
+    (x, y): (usize, usize),
+    (width, height): (usize, usize),
+    color: [u8; 4],
+) {
+    let frame_height = frame.len() / 4 / frame_width;
+    for pixel_y in y..(y + height).min(frame_height) {
+        for pixel_x in x..(x + width).min(frame_width) {
+            let index = 4 * (pixel_y * frame_width + pixel_x);
+            frame[index..index + 4].copy_from_slice(&color);
+        }
+    }
+}
+
+/// Draw the frames and instructions run per second, and the number of
+/// redraws skipped as the display didn't change, in `text_color` in the top
+/// left corner of `frame`, on a `background` box to keep them readable over
+/// the display.
+pub(crate) fn draw_rates(
+    frame: &mut [u8],
+    frame_width: usize,
+    (frames_per_second, instructions_per_second): (f64, f64),
+    redraws_skipped: u64,
+    text_color: [u8; 4],
+    background: [u8; 4],
+) {
+    let lines = [
+        format!("{frames_per_second:.0} FPS"),
+        format!("{instructions_per_second:.0} IPS"),
+        format!("{redraws_skipped} SKIP"),
+    ];
+    let width = lines.iter().map(|line| text_width(line)).max().unwrap_or(0);
+    fill_rect(
+        frame,
+        frame_width,
+        (0, 0),
+        (width + 2, lines.len() * LINE_ADVANCE + 1),
+        background,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            frame,
+            frame_width,
+            (1, 1 + i * LINE_ADVANCE),
+            line,
+            text_color,
+        );
+    }
+}
+
+/// The lines of text that the debug panel shows for `state`: the program
+/// counter with the instruction there and its mnemonic (dashes if it would run
+/// past the end of RAM), I and the timers,
+/// the V registers, then the addresses that called the subroutines being
+/// run, outermost first.
+pub(crate) fn debug_lines(state: &Chip8State) -> Vec<String> {
+    let (instruction, mnemonic) = match state.instruction {
+        Some(instruction) => (format!("{instruction:04X}"), disassemble(instruction)),
+        None => ("----".to_string(), "PAST END OF RAM".to_string()),
+    };
+    let mut lines = vec![
+        format!("PC {:04X} {instruction}", state.program_counter),
+        mnemonic,
+        format!(
+            "I {:04X} DT {:02X} ST {:02X}",
+            state.i, state.timer, state.tone_timer
+        ),
+    ];
+    for (row, registers) in state.v_registers.chunks(V_REGISTERS_PER_LINE).enumerate() {
+        let registers: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X} {value:02X}", row * V_REGISTERS_PER_LINE + i))
+            .collect();
+        lines.push(registers.join(" "));
+    }
+
+    let callers = state.caller_addresses();
+    if callers.is_empty() {
+        lines.push("SP -".to_string());
+    }
+    for (row, addresses) in callers.chunks(CALLERS_PER_LINE).enumerate() {
+        let addresses: Vec<String> = addresses
+            .iter()
+            .map(|address| format!("{address:04X}"))
+            .collect();
+        let label = if row == 0 { "SP" } else { "  " };
+        lines.push(format!("{label} {}", addresses.join(" ")));
+    }
+    lines
+}
+
+/// Draw the debug panel showing `lines` in `text_color` into `frame`, from
+/// column `x` to the right edge, on a `background` with a line down its left
+/// edge to set it apart from the display. Lines that don't fit are clipped.
+pub(crate) fn draw_debug_panel(
+    frame: &mut [u8],
+    frame_width: usize,
+    x: usize,
+    lines: &[String],
+    text_color: [u8; 4],
+    background: [u8; 4],
+) {
+    let frame_height = frame.len() / 4 / frame_width;
+    fill_rect(
+        frame,
+        frame_width,
+        (x, 0),
+        (frame_width - x, frame_height),
+        background,
+    );
+    fill_rect(frame, frame_width, (x, 0), (1, frame_height), text_color);
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            frame,
+            frame_width,
+            (x + 2, 1 + i * LINE_ADVANCE),
+            line,
+            text_color,
+        );
+    }
+}
+
+/// The rate at which a count goes up, e.g. of frames drawn, averaged over
+/// the last `window` of samples.
+#[derive(Debug, Clone)]
+pub(crate) struct RateMeter {
+    window: Duration,
+    // when the count was sampled, and what it was, oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateMeter {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record that the count was `count` at `now`. Samples older than the
+    /// window are dropped, except the newest of them, which starts the
+    /// window.
+    pub(crate) fn record(&mut self, now: Instant, count: u64) {
+        self.samples.push_back((now, count));
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|&(time, _)| now.duration_since(time) >= self.window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The average number of counts a second over the samples recorded, or
+    /// 0 until there are samples far enough apart.
+    pub(crate) fn rate(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start, start_count)), Some(&(end, end_count))) if end > start => {
+                end_count.saturating_sub(start_count) as f64 / (end - start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Forget all samples, e.g. when the count starts again from 0.
+    pub(crate) fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        debug_lines, draw_debug_panel, draw_rates, draw_text, fill_rect, text_width, RateMeter,
+        CHAR_ADVANCE, DEBUG_PANEL_HEIGHT, DEBUG_PANEL_WIDTH, LINE_ADVANCE,
+    };
+    use crate::interpreter::Chip8State;
+
+    const ON: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    // Draw into a blank frame, returning it as rows of `#` and `.` pixels.
+    fn draw(width: usize, height: usize, position: (usize, usize), text: &str) -> Vec<String> {
+        let mut frame = vec![0; 4 * width * height];
+        draw_text(&mut frame, width, position, text, ON);
+        frame
+            .chunks(4 * width)
+            .map(|row| {
+                row.chunks(4)
+                    .map(|pixel| if pixel == ON { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draw_digit() {
+        assert_eq!(
+            draw(5, 7, (1, 1), "2"),
+            [".....", ".###.", "...#.", ".###.", ".#...", ".###.", "....."]
+        );
+    }
+
+    #[test]
+    fn draw_text_with_spacing() {
+        assert_eq!(
+            draw(8, 5, (0, 0), "1a"),
+            [".#...#..", "##..#.#.", ".#..###.", ".#..#.#.", "###.#.#."]
+        );
+        assert_eq!(text_width("1a"), 2 * CHAR_ADVANCE - 1);
+        assert_eq!(text_width(""), 0);
+    }
+
+    #[test]
+    fn text_is_clipped_to_the_frame() {
+        assert_eq!(draw(4, 3, (2, 0), "80"), ["..##", "..#.", "..##"]);
+        assert_eq!(draw(2, 2, (3, 3), "8"), ["..", ".."]);
+    }
+
+    #[test]
+    fn unknown_characters_drawn_as_question_marks() {
+        assert_eq!(draw(3, 5, (0, 0), "~"), draw(3, 5, (0, 0), "?"));
+    }
+
+    #[test]
+    fn fill_rect_clipped() {
+        let mut frame = vec![0; 4 * 3 * 2];
+        fill_rect(&mut frame, 3, (1, 1), (5, 5), ON);
+        assert_eq!(frame[..4 * 4], [0; 16]);
+        assert_eq!(frame[4 * 4..], [ON, ON].concat());
+    }
+
+    #[test]
+    fn rates_drawn_in_top_left_corner() {
+        const OFF: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+        let (width, height) = (64, 32);
+        let mut frame = vec![0; 4 * width * height];
+        draw_rates(&mut frame, width, (59.6, 700.2), 12, ON, OFF);
+
+        // "700 IPS" is the widest line, with a pixel of border around them all
+        let mut expected = vec![0; 4 * width * height];
+        fill_rect(
+            &mut expected,
+            width,
+            (0, 0),
+            (text_width("700 IPS") + 2, 19),
+            OFF,
+        );
+        draw_text(&mut expected, width, (1, 1), "60 FPS", ON);
+        draw_text(&mut expected, width, (1, 7), "700 IPS", ON);
+        draw_text(&mut expected, width, (1, 13), "12 SKIP", ON);
+        assert_eq!(frame, expected);
+        assert_eq!(frame[4 * (30 * width)..4 * (30 * width + 1)], [0; 4]);
+    }
+
+    fn state<'a>(v_registers: &'a [u8], stack: &'a [u8]) -> Chip8State<'a> {
+        Chip8State {
+            program_counter: 0x0234,
+            instruction: Some(0xD125),
+            i: 0x0300,
+            stack_pointer: 0x0ECC,
+            timer: 0x3C,
+            tone_timer: 0x05,
+            hex_key_status: 0,
+            hex_keys_pressed: 0,
+            v_registers,
+            stack,
+            display_buffer: [&[], &[]],
+        }
+    }
+
+    #[test]
+    fn debug_lines_for_state() {
+        let v_registers: Vec<u8> = (0..16).map(|i| i * 0x11).collect();
+        assert_eq!(
+            debug_lines(&state(&v_registers, &[0x02, 0x02, 0x02, 0x10])),
+            [
+                "PC 0234 D125",
+                "DRW V1, V2, 5",
+                "I 0300 DT 3C ST 05",
+                "V0 00 V1 11 V2 22 V3 33",
+                "V4 44 V5 55 V6 66 V7 77",
+                "V8 88 V9 99 VA AA VB BB",
+                "VC CC VD DD VE EE VF FF",
+                "SP 0202 0210",
+            ]
+        );
+        assert_eq!(debug_lines(&state(&v_registers, &[]))[7], "SP -");
+
+        let mut past_end_of_ram = state(&v_registers, &[]);
+        past_end_of_ram.instruction = None;
+        assert_eq!(
+            debug_lines(&past_end_of_ram)[..2],
+            ["PC 0234 ----", "PAST END OF RAM"]
+        );
+
+        let stack: Vec<u8> = (0..5u16).flat_map(|i| (0x200 + i).to_be_bytes()).collect();
+        assert_eq!(
+            debug_lines(&state(&v_registers, &stack))[7..],
+            ["SP 0200 0201 0202 0203", "   0204"]
+        );
+    }
+
+    #[test]
+    fn debug_lines_fit_the_panel() {
+        let v_registers = [0xFF; 16];
+        // the deepest stack the COSMAC VIP has room for
+        let stack = [0x0F; 24];
+        let lines = debug_lines(&state(&v_registers, &stack));
+        assert!(lines.len() * LINE_ADVANCE < DEBUG_PANEL_HEIGHT);
+        for line in &lines {
+            assert!(text_width(line) + 3 <= DEBUG_PANEL_WIDTH, "{line}");
+        }
+    }
+
+    #[test]
+    fn debug_panel_right_of_display() {
+        const OFF: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+        let (width, height) = (20, 8);
+        let mut frame = vec![0; 4 * width * height];
+        let lines = ["1".to_string(), "2".to_string()];
+        draw_debug_panel(&mut frame, width, 10, &lines, ON, OFF);
+
+        let mut expected = vec![0; 4 * width * height];
+        fill_rect(&mut expected, width, (10, 0), (10, height), OFF);
+        fill_rect(&mut expected, width, (10, 0), (1, height), ON);
+        draw_text(&mut expected, width, (12, 1), "1", ON);
+        draw_text(&mut expected, width, (12, 7), "2", ON);
+        assert_eq!(frame, expected);
+        // the display is left alone
+        assert!(frame
+            .chunks(4 * width)
+            .all(|row| row[..4 * 10].iter().all(|&byte| byte == 0)));
+    }
+
+    #[test]
+    fn rate_over_rolling_window() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(Duration::from_secs(1));
+        assert_eq!(meter.rate(), 0.0);
+        meter.record(start, 0);
+        assert_eq!(meter.rate(), 0.0);
+
+        // 60 a second for the first 2 seconds, then 30 a second
+        for tick in 1..=120 {
+            meter.record(start + Duration::from_millis(tick * 1000 / 60), tick);
+        }
+        assert!((meter.rate() - 60.0).abs() < 1.0, "{}", meter.rate());
+        for tick in 1..=30 {
+            meter.record(
+                start + Duration::from_secs(2) + Duration::from_millis(tick * 1000 / 30),
+                120 + tick,
+            );
+        }
+        assert!((meter.rate() - 30.0).abs() < 1.0, "{}", meter.rate());
+
+        meter.clear();
+        assert_eq!(meter.rate(), 0.0);
+    }
+}
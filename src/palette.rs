@@ -0,0 +1,153 @@
+//! The colors the display is drawn in.
+
+/// The RGBA colors of pixels, for each combination of the display planes
+/// they are lit on. Programs that only use the first plane are drawn in
+/// [`Palette::on`] and [`Palette::off`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Pixels lit on no planes.
+    pub off: [u8; 4],
+    /// Pixels lit on the first plane only.
+    pub on: [u8; 4],
+    /// Pixels lit on the XO-CHIP second plane only.
+    pub second_plane: [u8; 4],
+    /// Pixels lit on both planes.
+    pub both_planes: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::PAPER_WHITE
+    }
+}
+
+impl Palette {
+    /// Black on white, like ink on paper.
+    pub const PAPER_WHITE: Self = Self::new([0x00, 0x00, 0x00, 0xFF], [0xFF, 0xFF, 0xFF, 0xFF]);
+    /// Green on black, like a monochrome monitor.
+    pub const CLASSIC_GREEN: Self = Self::new([0x33, 0xFF, 0x33, 0xFF], [0x00, 0x00, 0x00, 0xFF]);
+    /// Amber on black, like an amber monochrome monitor.
+    pub const AMBER: Self = Self::new([0xFF, 0xB0, 0x00, 0xFF], [0x00, 0x00, 0x00, 0xFF]);
+
+    /// The built-in palettes, with their names.
+    pub const PRESETS: [(&'static str, Self); 3] = [
+        ("paper white", Self::PAPER_WHITE),
+        ("classic green", Self::CLASSIC_GREEN),
+        ("amber", Self::AMBER),
+    ];
+
+    /// A palette of `on` pixels on an `off` background. Pixels lit on the
+    /// XO-CHIP second plane are a third of the way from `off` to `on`, and
+    /// pixels lit on both planes are two thirds of the way.
+    pub const fn new(on: [u8; 4], off: [u8; 4]) -> Self {
+        Self {
+            off,
+            on,
+            second_plane: blend(off, on, 1, 3),
+            both_planes: blend(off, on, 2, 3),
+        }
+    }
+
+    /// The color of a pixel lit on the given planes.
+    pub fn color(&self, first_plane_on: bool, second_plane_on: bool) -> [u8; 4] {
+        match (first_plane_on, second_plane_on) {
+            (false, false) => self.off,
+            (true, false) => self.on,
+            (false, true) => self.second_plane,
+            (true, true) => self.both_planes,
+        }
+    }
+
+    /// The color of a pixel on the first plane only, glowing at `intensity`
+    /// from 0 ([`Palette::off`]) to 255 ([`Palette::on`]).
+    pub fn faded(&self, intensity: u8) -> [u8; 4] {
+        blend(self.off, self.on, intensity as i32, 255)
+    }
+}
+
+/// The palettes to cycle through starting from `palette`, with their names,
+/// and the index of `palette`. These are the presets, after `palette` itself
+/// if it isn't one of them.
+pub(crate) fn palette_cycle(palette: Palette) -> (Vec<(&'static str, Palette)>, usize) {
+    let mut palettes = Palette::PRESETS.to_vec();
+    match palettes.iter().position(|&(_, preset)| preset == palette) {
+        Some(index) => (palettes, index),
+        None => {
+            palettes.insert(0, ("custom", palette));
+            (palettes, 0)
+        }
+    }
+}
+
+/// The index of the palette after the one at `index` in `palettes`, going
+/// back to the first after the last.
+pub(crate) fn next_palette(palettes: &[(&str, Palette)], index: usize) -> usize {
+    (index + 1) % palettes.len()
+}
+
+// The color `numerator / denominator` of the way from `from` to `to`.
+const fn blend(from: [u8; 4], to: [u8; 4], numerator: i32, denominator: i32) -> [u8; 4] {
+    let mut color = [0; 4];
+    let mut i = 0;
+    while i < 4 {
+        color[i] =
+            (from[i] as i32 + (to[i] as i32 - from[i] as i32) * numerator / denominator) as u8;
+        i += 1;
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_palette, palette_cycle, Palette};
+
+    #[test]
+    fn paper_white_shades_of_grey() {
+        let palette = Palette::default();
+        assert_eq!(palette.color(false, false), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(palette.color(true, false), [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(palette.color(false, true), [0xAA, 0xAA, 0xAA, 0xFF]);
+        assert_eq!(palette.color(true, true), [0x55, 0x55, 0x55, 0xFF]);
+    }
+
+    #[test]
+    fn second_plane_colors_blend_on_and_off() {
+        let palette = Palette::new([0x30, 0xC0, 0x00, 0xFF], [0x00, 0x60, 0x90, 0xFF]);
+        assert_eq!(palette.second_plane, [0x10, 0x80, 0x60, 0xFF]);
+        assert_eq!(palette.both_planes, [0x20, 0xA0, 0x30, 0xFF]);
+    }
+
+    #[test]
+    fn faded_colors() {
+        let palette = Palette::AMBER;
+        assert_eq!(palette.faded(0), palette.off);
+        assert_eq!(palette.faded(255), palette.on);
+        assert_eq!(palette.faded(51), [0x33, 0x23, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn cycle_through_presets() {
+        let (palettes, index) = palette_cycle(Palette::CLASSIC_GREEN);
+        assert_eq!(palettes, Palette::PRESETS);
+        assert_eq!(index, 1);
+
+        let index = next_palette(&palettes, index);
+        assert_eq!(palettes[index], ("amber", Palette::AMBER));
+        let index = next_palette(&palettes, index);
+        assert_eq!(palettes[index], ("paper white", Palette::PAPER_WHITE));
+    }
+
+    #[test]
+    fn cycle_from_custom_palette() {
+        let custom = Palette::new([0x12, 0x34, 0x56, 0xFF], [0x00, 0x00, 0x00, 0xFF]);
+        let (palettes, index) = palette_cycle(custom);
+        assert_eq!(palettes.len(), Palette::PRESETS.len() + 1);
+        assert_eq!(palettes[index], ("custom", custom));
+
+        let mut seen = vec![index];
+        for _ in 0..palettes.len() {
+            seen.push(next_palette(&palettes, *seen.last().unwrap()));
+        }
+        assert_eq!(seen, [0, 1, 2, 3, 0]);
+    }
+}
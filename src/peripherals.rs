@@ -1,8 +1,17 @@
 // use std::cell::Cell;
 
-use std::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
 
-use rodio::{source, OutputStream, Sink, Source};
+use rodio::{source, OutputStream, OutputStreamHandle, Sink, Source};
+
+use crate::{Error, Result};
+
+const PATTERN_OUTPUT_SAMPLE_RATE: u32 = 44_100;
+// the change in volume for each press of a volume key, in percent
+const VOLUME_STEP_PERCENT: u8 = 10;
 
 pub trait Tone {
     fn start_tone(&self) {}
@@ -10,19 +19,40 @@ pub trait Tone {
     fn is_tone_on(&self) -> bool {
         false
     }
+    /// Play the tone at `volume`, from 0.0 for silent to 1.0 for full.
+    fn set_volume(&self, _volume: f32) {}
+    /// The volume the tone plays at, full unless set.
+    fn volume(&self) -> f32 {
+        1.0
+    }
+}
+
+/// A tone that can play back an XO-CHIP audio pattern in place of its
+/// regular sound.
+pub trait SampledTone: Tone {
+    /// Play `pattern`, 128 1-bit samples with the most significant bit first,
+    /// on a loop at `playback_rate_hz` samples per second whenever the tone is
+    /// on.
+    fn set_pattern(&self, pattern: &[u8; 16], playback_rate_hz: f32);
 }
 
 pub struct Beeper {
     _stream: OutputStream,
-    sink: rodio::Sink,
+    stream_handle: OutputStreamHandle,
+    sink: RefCell<Sink>,
+    pattern: RefCell<Option<([u8; 16], f32)>>,
 }
 
 impl Beeper {
-    pub fn new(freq_hz: u32) -> Self {
-        let (_stream, stream_handle) = OutputStream::try_default()
-            .expect("Should be able to obtain an output stream for audio");
-        let sink = Sink::try_new(&stream_handle)
-            .expect("Should be able to create Sink from output stream.");
+    /// A beeper playing a tone at `freq_hz` through the default audio output,
+    /// starting off.
+    ///
+    /// # Errors
+    /// Returns [`Error::Audio`] if there is no audio output to play through.
+    pub fn new(freq_hz: u32) -> Result<Self> {
+        let (_stream, stream_handle) =
+            OutputStream::try_default().map_err(|e| Error::Audio(e.to_string()))?;
+        let sink = Sink::try_new(&stream_handle).map_err(|e| Error::Audio(e.to_string()))?;
         sink.pause();
 
         let source = source::SineWave::new(freq_hz as f32)
@@ -31,20 +61,268 @@ impl Beeper {
             .amplify(0.20);
         sink.append(source);
 
-        Self { _stream, sink }
+        Ok(Self {
+            _stream,
+            stream_handle,
+            sink: RefCell::new(sink),
+            pattern: RefCell::new(None),
+        })
     }
 }
 
 impl Tone for Beeper {
     fn is_tone_on(&self) -> bool {
-        !self.sink.is_paused()
+        !self.sink.borrow().is_paused()
     }
 
     fn start_tone(&self) {
-        self.sink.play();
+        self.sink.borrow().play();
     }
 
     fn stop_tone(&self) {
-        self.sink.pause();
+        self.sink.borrow().pause();
+    }
+
+    fn set_volume(&self, volume: f32) {
+        self.sink.borrow().set_volume(volume);
+    }
+
+    fn volume(&self) -> f32 {
+        self.sink.borrow().volume()
+    }
+}
+
+impl SampledTone for Beeper {
+    fn set_pattern(&self, pattern: &[u8; 16], playback_rate_hz: f32) {
+        if *self.pattern.borrow() == Some((*pattern, playback_rate_hz)) {
+            return;
+        }
+
+        // Replace the sink rather than its source, as a sink can't drop a
+        // source that repeats forever. Dropping the old sink stops it.
+        let sink = Sink::try_new(&self.stream_handle)
+            .expect("Should be able to create Sink from output stream.");
+        if !self.is_tone_on() {
+            sink.pause();
+        }
+        sink.set_volume(self.volume());
+        sink.append(PatternSource::new(*pattern, playback_rate_hz).amplify(0.20));
+
+        *self.sink.borrow_mut() = sink;
+        *self.pattern.borrow_mut() = Some((*pattern, playback_rate_hz));
+    }
+}
+
+/// A tone that only keeps track of whether it's on, and the pattern and
+/// volume it was given, for something else to play, e.g. a [`Beeper`] on
+/// another thread.
+#[derive(Debug, Default)]
+pub struct ToneState {
+    is_on: Cell<bool>,
+    pattern: Cell<Option<([u8; 16], f32)>>,
+    volume: Cell<Option<f32>>,
+}
+
+impl ToneState {
+    /// The XO-CHIP audio pattern and its playback rate, if one was set.
+    pub fn pattern(&self) -> Option<([u8; 16], f32)> {
+        self.pattern.get()
+    }
+}
+
+impl Tone for ToneState {
+    fn start_tone(&self) {
+        self.is_on.set(true);
+    }
+
+    fn stop_tone(&self) {
+        self.is_on.set(false);
+    }
+
+    fn is_tone_on(&self) -> bool {
+        self.is_on.get()
+    }
+
+    fn set_volume(&self, volume: f32) {
+        self.volume.set(Some(volume));
+    }
+
+    fn volume(&self) -> f32 {
+        self.volume.get().unwrap_or(1.0)
+    }
+}
+
+impl SampledTone for ToneState {
+    fn set_pattern(&self, pattern: &[u8; 16], playback_rate_hz: f32) {
+        self.pattern.set(Some((*pattern, playback_rate_hz)));
+    }
+}
+
+/// The volume of the tone, as set by the volume keys: a level from silent to
+/// full, in steps of 10%, which muting silences without forgetting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    percent: u8,
+    muted: bool,
+}
+
+impl Volume {
+    /// Full volume, unmuted.
+    pub const FULL: Self = Self {
+        percent: 100,
+        muted: false,
+    };
+
+    /// A volume of `percent`, unmuted.
+    ///
+    /// # Panics
+    /// Panics if `percent` is more than 100.
+    pub fn new(percent: u8) -> Self {
+        assert!(percent <= 100, "The volume should be at most 100%");
+        Self {
+            percent,
+            muted: false,
+        }
+    }
+
+    /// Turn the volume up a step, up to full, unmuting it.
+    pub fn up(&mut self) {
+        self.percent = self.percent.saturating_add(VOLUME_STEP_PERCENT).min(100);
+        self.muted = false;
+    }
+
+    /// Turn the volume down a step, down to silent, unmuting it.
+    pub fn down(&mut self) {
+        self.percent = self.percent.saturating_sub(VOLUME_STEP_PERCENT);
+        self.muted = false;
+    }
+
+    /// Mute the tone, or unmute it back to the level it was at.
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    /// Whether the tone is muted.
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// The level, in percent, whether muted or not.
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// The volume to play the tone at, see [`Tone::set_volume`].
+    pub fn output(&self) -> f32 {
+        match self.muted {
+            true => 0.0,
+            false => self.percent as f32 / 100.0,
+        }
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+// An infinite source that plays a 1-bit XO-CHIP audio pattern on a loop.
+struct PatternSource {
+    pattern: [u8; 16],
+    position: f32, // in pattern samples
+    step: f32,     // pattern samples per output sample
+}
+
+impl PatternSource {
+    fn new(pattern: [u8; 16], playback_rate_hz: f32) -> Self {
+        Self {
+            pattern,
+            position: 0.0,
+            step: playback_rate_hz / PATTERN_OUTPUT_SAMPLE_RATE as f32,
+        }
+    }
+}
+
+impl Iterator for PatternSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bit = self.position as usize;
+        let is_high = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+        self.position = (self.position + self.step) % (self.pattern.len() * 8) as f32;
+        Some(if is_high { 1.0 } else { -1.0 })
+    }
+}
+
+impl Source for PatternSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        PATTERN_OUTPUT_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tone, ToneState, Volume};
+
+    #[test]
+    fn volume_steps_within_range() {
+        let mut volume = Volume::new(75);
+        volume.up();
+        assert_eq!(volume.percent(), 85);
+        volume.up();
+        volume.up();
+        assert_eq!(volume.percent(), 100);
+        assert_eq!(volume.output(), 1.0);
+
+        let mut volume = Volume::new(15);
+        volume.down();
+        assert_eq!(volume.percent(), 5);
+        volume.down();
+        assert_eq!(volume.percent(), 0);
+        assert_eq!(volume.output(), 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn volume_over_full() {
+        Volume::new(101);
+    }
+
+    #[test]
+    fn mute_keeps_level() {
+        let mut volume = Volume::new(50);
+        volume.toggle_mute();
+        assert!(volume.is_muted());
+        assert_eq!(volume.output(), 0.0);
+        assert_eq!(volume.percent(), 50);
+        volume.toggle_mute();
+        assert_eq!(volume.output(), 0.5);
+
+        // changing the level unmutes
+        volume.toggle_mute();
+        volume.down();
+        assert!(!volume.is_muted());
+        assert_eq!(volume.output(), 0.4);
+    }
+
+    #[test]
+    fn tone_state_full_volume_until_set() {
+        let tone = ToneState::default();
+        assert_eq!(tone.volume(), 1.0);
+        tone.set_volume(0.3);
+        assert_eq!(tone.volume(), 0.3);
     }
 }
@@ -0,0 +1,204 @@
+//! Interpreter behaviors that differ between CHIP-8 implementations.
+//!
+//! The RCA COSMAC VIP interpreter is the reference for this emulator, but
+//! later interpreters such as CHIP-48 and SCHIP on the HP48 changed the
+//! semantics of a handful of opcodes, and ROMs written for them depend on
+//! those changes. Each difference is a flag on [`Quirks`].
+//!
+//! A [`Chip8Variant`] bundles the quirks of a particular interpreter with the
+//! instruction set extensions and display it supports.
+
+use crate::memory::{
+    DisplaySize, ETI_660_DISPLAY_SIZE, ETI_660_PROGRAM_START_ADDRESS, LORES_DISPLAY_SIZE,
+    PROGRAM_START_ADDRESS, TWO_PAGE_HIRES_DISPLAY_SIZE,
+};
+
+/// Where two-page hires CHIP-8 programs start. The ROM starts with a `1260`
+/// jump into the interpreter patch it carries at 0x0260, which sets up the
+/// 64x64 display and then runs the CHIP-8 program at 0x02C0. The patch is
+/// COSMAC machine code, so execution starts straight at the CHIP-8 program.
+pub const TWO_PAGE_HIRES_ENTRY_ADDRESS: usize = 0x02C0;
+
+/// Switches for the behaviors that CHIP-8 interpreters disagree on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place instead of setting VX to VY shifted.
+    pub shift_uses_vx: bool,
+    /// How far FX55/FX65 move I after storing/loading registers.
+    pub load_store_increment: LoadStoreIncrement,
+    /// BNNN jumps to XNN + VX, where X is the high nibble of the address,
+    /// instead of NNN + V0.
+    pub jump_with_vx: bool,
+    /// DXYN wraps sprites that cross an edge of the display around to the
+    /// opposite edge instead of clipping them.
+    pub sprite_wrapping: bool,
+    /// 8XY1/8XY2/8XY3 set VF to zero.
+    pub vf_reset_on_logic: bool,
+    /// DXYN waits until a jiffy has passed since the last sprite was drawn,
+    /// like the COSMAC VIP waiting for the vertical blank.
+    pub display_wait: bool,
+    /// FX1E sets VF to 1 when I + VX goes past 0x0FFF, wrapping I around,
+    /// and to 0 otherwise, like the Amiga interpreter.
+    pub i_overflow_sets_vf: bool,
+    /// The tone sounds while a key is held down during FX0A, like the
+    /// COSMAC VIP giving feedback for key presses.
+    pub key_wait_tone: bool,
+    /// In hires mode, DXYN sets VF to the number of sprite rows that collided
+    /// or were clipped by the bottom of the display, instead of to 1 for any
+    /// collision, like SCHIP 1.1.
+    pub hires_vf_counts_rows: bool,
+}
+
+/// The amount FX55/FX65 add to I after storing/loading V0 : VX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoadStoreIncrement {
+    /// I = I + X + 1, leaving I just past the last register (COSMAC VIP).
+    XPlusOne,
+    /// I = I + X, leaving I on the last register (CHIP-48).
+    X,
+    /// I is left unchanged (SCHIP).
+    Unchanged,
+}
+
+impl Quirks {
+    /// The behavior of the original RCA COSMAC VIP interpreter.
+    pub const fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vx: false,
+            load_store_increment: LoadStoreIncrement::XPlusOne,
+            jump_with_vx: false,
+            sprite_wrapping: false,
+            vf_reset_on_logic: true,
+            display_wait: true,
+            i_overflow_sets_vf: false,
+            key_wait_tone: true,
+            hires_vf_counts_rows: false,
+        }
+    }
+
+    /// The behavior of CHIP-48 on the HP48. Shifts and jumps already behave as
+    /// they do on SCHIP, but FX55/FX65 leave I on the last register.
+    pub const fn chip48() -> Self {
+        Self {
+            shift_uses_vx: true,
+            load_store_increment: LoadStoreIncrement::X,
+            jump_with_vx: true,
+            sprite_wrapping: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            i_overflow_sets_vf: false,
+            key_wait_tone: false,
+            hires_vf_counts_rows: false,
+        }
+    }
+
+    /// The behavior of SCHIP 1.1 on the HP48.
+    pub const fn schip() -> Self {
+        Self {
+            shift_uses_vx: true,
+            load_store_increment: LoadStoreIncrement::Unchanged,
+            jump_with_vx: true,
+            sprite_wrapping: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            i_overflow_sets_vf: false,
+            key_wait_tone: false,
+            hires_vf_counts_rows: true,
+        }
+    }
+
+    /// The behavior of XO-CHIP, as implemented by Octo.
+    pub const fn xo_chip() -> Self {
+        Self {
+            shift_uses_vx: false,
+            load_store_increment: LoadStoreIncrement::XPlusOne,
+            jump_with_vx: false,
+            sprite_wrapping: true,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            i_overflow_sets_vf: false,
+            key_wait_tone: false,
+            hires_vf_counts_rows: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+/// The CHIP-8 interpreters that ROMs are commonly written for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Chip8Variant {
+    /// The original interpreter on the RCA COSMAC VIP.
+    #[default]
+    CosmacVip,
+    /// The COSMAC VIP interpreter with the two-page hires patch, giving a
+    /// 64x64 display.
+    HiresChip8,
+    /// The CHIP-8 interpreter of the ETI-660, which loads programs at 0x0600
+    /// and has a 64x48 display.
+    Eti660,
+    /// CHIP-48 on the HP48, which has none of the SCHIP opcodes.
+    Chip48,
+    /// SCHIP 1.1 on the HP48, adding a 128x64 hires display, scrolling, big
+    /// digits and RPL user flags.
+    SuperChip1_1,
+    /// XO-CHIP, adding a second display plane and sampled audio on top of
+    /// SCHIP.
+    XoChip,
+}
+
+impl Chip8Variant {
+    /// The quirks of the variant's interpreter.
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            Self::CosmacVip | Self::HiresChip8 | Self::Eti660 => Quirks::cosmac_vip(),
+            Self::Chip48 => Quirks::chip48(),
+            Self::SuperChip1_1 => Quirks::schip(),
+            Self::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    /// Whether the SCHIP opcodes, big digit font and hires display are
+    /// available.
+    pub const fn has_schip_opcodes(self) -> bool {
+        matches!(self, Self::SuperChip1_1 | Self::XoChip)
+    }
+
+    /// Whether the XO-CHIP opcodes are available.
+    pub const fn has_xo_chip_opcodes(self) -> bool {
+        matches!(self, Self::XoChip)
+    }
+
+    /// The display size after reset.
+    pub const fn initial_display_size(self) -> DisplaySize {
+        match self {
+            Self::HiresChip8 => TWO_PAGE_HIRES_DISPLAY_SIZE,
+            Self::Eti660 => ETI_660_DISPLAY_SIZE,
+            _ => LORES_DISPLAY_SIZE,
+        }
+    }
+
+    /// The address programs are loaded at. The program area runs from here
+    /// up to the CHIP-8 stack.
+    pub const fn program_start_address(self) -> usize {
+        match self {
+            Self::Eti660 => ETI_660_PROGRAM_START_ADDRESS,
+            _ => PROGRAM_START_ADDRESS,
+        }
+    }
+
+    /// The address execution starts from after reset.
+    pub const fn program_entry_address(self) -> usize {
+        match self {
+            Self::HiresChip8 => TWO_PAGE_HIRES_ENTRY_ADDRESS,
+            _ => self.program_start_address(),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Recording the inputs to a run of a CHIP-8 program, so it can be replayed
+//! exactly, e.g. to reproduce a bug.
+//!
+//! A recording is made by [`Chip8Interpreter::start_recording`] and
+//! [`Chip8Interpreter::stop_recording`], and replayed by
+//! [`Chip8Interpreter::start_replay`] with a [`ReplayRng`] from
+//! [`Recording::replay_rng`]. It can be written to a file with
+//! [`Recording::to_bytes`] and read back with [`Recording::from_bytes`].
+//!
+//! A recording only holds the inputs, so a replay must start from the same
+//! state as the recording did, e.g. straight after a reset with the same
+//! program loaded. The timers only count down deterministically in
+//! [`TimerMode::Ticked`].
+//!
+//! The byte format starts with the magic bytes `C8RC` and a big-endian `u16`
+//! format version.
+//!
+//! [`Chip8Interpreter::start_recording`]: crate::interpreter::Chip8Interpreter::start_recording
+//! [`Chip8Interpreter::stop_recording`]: crate::interpreter::Chip8Interpreter::stop_recording
+//! [`Chip8Interpreter::start_replay`]: crate::interpreter::Chip8Interpreter::start_replay
+//! [`TimerMode::Ticked`]: crate::interpreter::TimerMode::Ticked
+
+use std::cell::Cell;
+
+use crate::{save_state::Reader, Chip8Rng, Error, Result};
+
+const MAGIC: &[u8; 4] = b"C8RC";
+const VERSION: u16 = 1;
+
+const RANDOM_TAG: u8 = 0;
+const KEY_TAG: u8 = 1;
+const TICK_TAG: u8 = 2;
+const KEY_PRESSED_FLAG: u8 = 0x80;
+
+/// An input to the interpreter from outside of the CHIP-8 program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordedInput {
+    /// A random number used by CXKK.
+    Random(u8),
+    /// A hex key pressed or released.
+    Key { key: u8, pressed: bool },
+    /// A call to [`tick_60hz`](crate::interpreter::Chip8Interpreter::tick_60hz)
+    /// in ticked timer mode.
+    Tick,
+}
+
+/// The inputs to the interpreter over a number of steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Recording {
+    steps: u64,
+    // The index of the step each input was applied before, or during for
+    // random numbers, in order. Inputs given after the last step have an
+    // index of `steps`.
+    inputs: Vec<(u64, RecordedInput)>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of steps recorded.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The recorded inputs, each with the index of the step it was applied
+    /// before. Random numbers are given with the step that used them.
+    pub fn inputs(&self) -> &[(u64, RecordedInput)] {
+        &self.inputs
+    }
+
+    pub(crate) fn push(&mut self, input: RecordedInput) {
+        self.inputs.push((self.steps, input));
+    }
+
+    pub(crate) fn end_step(&mut self) {
+        self.steps += 1;
+    }
+
+    /// A random number generator that replays the recorded random numbers.
+    pub fn replay_rng(&self) -> ReplayRng {
+        ReplayRng {
+            values: self
+                .inputs
+                .iter()
+                .filter_map(|&(_, input)| match input {
+                    RecordedInput::Random(value) => Some(value),
+                    _ => None,
+                })
+                .collect(),
+            next: Cell::new(0),
+        }
+    }
+
+    /// Encode the recording in the versioned byte format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_be_bytes());
+        bytes.extend(self.steps.to_be_bytes());
+        bytes.extend((self.inputs.len() as u32).to_be_bytes());
+        for &(step, input) in &self.inputs {
+            bytes.extend(step.to_be_bytes());
+            bytes.extend(match input {
+                RecordedInput::Random(value) => [RANDOM_TAG, value],
+                RecordedInput::Key { key, pressed } => {
+                    [KEY_TAG, key | if pressed { KEY_PRESSED_FLAG } else { 0 }]
+                }
+                RecordedInput::Tick => [TICK_TAG, 0],
+            });
+        }
+        bytes
+    }
+
+    /// Decode a recording written by [`Recording::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedRecordingVersion`] if the recording was
+    /// written by a newer version of this library, or
+    /// [`Error::InvalidRecording`] if `bytes` are not a valid recording.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, || Error::InvalidRecording);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidRecording);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(Error::UnsupportedRecordingVersion(version));
+        }
+
+        let steps = reader.u64()?;
+        let num_inputs = reader.u32()?;
+        let mut inputs = Vec::new();
+        for _ in 0..num_inputs {
+            let step = reader.u64()?;
+            let input = match (reader.u8()?, reader.u8()?) {
+                (RANDOM_TAG, value) => RecordedInput::Random(value),
+                (KEY_TAG, key) if key & !KEY_PRESSED_FLAG <= 0xF => RecordedInput::Key {
+                    key: key & !KEY_PRESSED_FLAG,
+                    pressed: key & KEY_PRESSED_FLAG != 0,
+                },
+                (TICK_TAG, 0) => RecordedInput::Tick,
+                _ => return Err(Error::InvalidRecording),
+            };
+            if step > steps || inputs.last().is_some_and(|&(last, _)| step < last) {
+                return Err(Error::InvalidRecording);
+            }
+            inputs.push((step, input));
+        }
+        if !reader.bytes.is_empty() {
+            return Err(Error::InvalidRecording);
+        }
+
+        Ok(Self { steps, inputs })
+    }
+}
+
+/// A [`Chip8Rng`] that returns the random numbers from a [`Recording`], in
+/// the order they were recorded.
+pub struct ReplayRng {
+    values: Vec<u8>,
+    next: Cell<usize>,
+}
+
+impl ReplayRng {
+    /// Whether all of the recorded random numbers have been used.
+    pub fn is_finished(&self) -> bool {
+        self.next.get() == self.values.len()
+    }
+}
+
+impl Chip8Rng for ReplayRng {
+    /// # Panics
+    /// Panics if all of the recorded random numbers have been used, which
+    /// means the replay has diverged from the recording.
+    fn random_u8(&self) -> u8 {
+        let next = self.next.get();
+        let value = *self
+            .values
+            .get(next)
+            .expect("Replay should not use more random numbers than were recorded.");
+        self.next.set(next + 1);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordedInput, Recording};
+    use crate::{Chip8Rng, Error};
+
+    fn recording() -> Recording {
+        let mut recording = Recording::new();
+        recording.push(RecordedInput::Key {
+            key: 0xA,
+            pressed: true,
+        });
+        recording.end_step();
+        recording.push(RecordedInput::Random(0x42));
+        recording.push(RecordedInput::Tick);
+        recording.end_step();
+        recording.push(RecordedInput::Key {
+            key: 0xA,
+            pressed: false,
+        });
+        recording.push(RecordedInput::Random(0x99));
+        recording.end_step();
+        recording
+    }
+
+    #[test]
+    fn inputs_recorded_with_step_index() {
+        let recording = recording();
+        assert_eq!(recording.steps(), 3);
+        assert_eq!(
+            recording.inputs(),
+            [
+                (
+                    0,
+                    RecordedInput::Key {
+                        key: 0xA,
+                        pressed: true
+                    }
+                ),
+                (1, RecordedInput::Random(0x42)),
+                (1, RecordedInput::Tick),
+                (
+                    2,
+                    RecordedInput::Key {
+                        key: 0xA,
+                        pressed: false
+                    }
+                ),
+                (2, RecordedInput::Random(0x99)),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_rng_returns_recorded_numbers() {
+        let rng = recording().replay_rng();
+        assert_eq!(rng.random_u8(), 0x42);
+        assert!(!rng.is_finished());
+        assert_eq!(rng.random_u8(), 0x99);
+        assert!(rng.is_finished());
+    }
+
+    #[test]
+    #[should_panic]
+    fn replay_rng_runs_out() {
+        let rng = Recording::new().replay_rng();
+        rng.random_u8();
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let recording = recording();
+        let bytes = recording.to_bytes();
+        assert_eq!(&bytes[..6], b"C8RC\x00\x01");
+        assert_eq!(Recording::from_bytes(&bytes), Ok(recording));
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let bytes = recording().to_bytes();
+
+        assert_eq!(
+            Recording::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidRecording),
+            "Truncated recording"
+        );
+        assert_eq!(
+            Recording::from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(Error::InvalidRecording),
+            "Trailing bytes"
+        );
+
+        let mut newer_version = bytes.clone();
+        newer_version[5] = 2;
+        assert_eq!(
+            Recording::from_bytes(&newer_version),
+            Err(Error::UnsupportedRecordingVersion(2))
+        );
+
+        // the first input is at offset 6 + 8 + 4, with its tag after the step
+        let mut bad_tag = bytes.clone();
+        bad_tag[18 + 8] = 7;
+        assert_eq!(
+            Recording::from_bytes(&bad_tag),
+            Err(Error::InvalidRecording)
+        );
+
+        let mut step_out_of_range = bytes;
+        step_out_of_range[18 + 7] = 4;
+        assert_eq!(
+            Recording::from_bytes(&step_out_of_range),
+            Err(Error::InvalidRecording)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Rewinding through recent save states.
+//!
+//! The host pushes a [`SaveState`] into a [`RewindBuffer`] every so often,
+//! e.g. once a frame, and pops them back off to step backwards in time.
+//! Consecutive save states are mostly the same, so only the newest is kept
+//! whole. Each older one is kept as the XOR of it with the next newer state,
+//! run-length encoded to squash the runs of zeroes left by unchanged bytes.
+
+use std::collections::VecDeque;
+
+use crate::save_state::SaveState;
+
+/// A bounded stack of save states, which forgets the oldest state when full.
+pub struct RewindBuffer {
+    capacity: usize,
+    // The newest save state, in the `SaveState::to_bytes` format
+    newest: Option<Vec<u8>>,
+    // Deltas that each turn a save state into the one before it, oldest first
+    deltas: VecDeque<Delta>,
+}
+
+// The difference between two encoded save states.
+struct Delta {
+    // Length of the older state, which can differ from the newer state when
+    // the display size changed.
+    len: usize,
+    // The XOR of the two states, padded with zeroes to the longer of the two,
+    // run-length encoded by `encode_rle`.
+    rle_xor: Vec<u8>,
+}
+
+impl RewindBuffer {
+    /// Create an empty buffer holding up to `capacity` save states.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Rewind buffer capacity must be at least 1");
+        Self {
+            capacity,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// The maximum number of save states held.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of save states held.
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| 1 + self.deltas.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// The approximate number of bytes used to hold the save states.
+    pub fn size_in_bytes(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len)
+            + self
+                .deltas
+                .iter()
+                .map(|delta| delta.rle_xor.len())
+                .sum::<usize>()
+    }
+
+    /// Push the newest save state, forgetting the oldest if the buffer is
+    /// full.
+    pub fn push(&mut self, state: &SaveState) {
+        let bytes = state.to_bytes();
+        if let Some(previous) = self.newest.take() {
+            self.deltas.push_back(Delta {
+                len: previous.len(),
+                rle_xor: encode_rle(&xor(&previous, &bytes)),
+            });
+            if self.deltas.len() == self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.newest = Some(bytes);
+    }
+
+    /// Pop the newest save state, or `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<SaveState> {
+        let newest = self.newest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            let mut previous = xor(&newest, &decode_rle(&delta.rle_xor));
+            previous.truncate(delta.len);
+            self.newest = Some(previous);
+        }
+        Some(SaveState::from_bytes(&newest).expect("Rewind buffer should hold valid save states."))
+    }
+
+    /// Forget all of the save states.
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+}
+
+// XOR two byte strings, treating the shorter as if padded with zeroes.
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let (longer, shorter) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = longer.to_vec();
+    for (byte, other) in result.iter_mut().zip(shorter) {
+        *byte ^= other;
+    }
+    result
+}
+
+// Run-length encode the zero bytes in `bytes`. A run of up to 255 zeroes is
+// written as a 0 followed by the length of the run. Other bytes are written
+// as they are.
+fn encode_rle(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut zeroes: u8 = 0;
+    for &byte in bytes {
+        if byte == 0 {
+            if zeroes == u8::MAX {
+                encoded.extend([0, zeroes]);
+                zeroes = 0;
+            }
+            zeroes += 1;
+        } else {
+            if zeroes > 0 {
+                encoded.extend([0, zeroes]);
+                zeroes = 0;
+            }
+            encoded.push(byte);
+        }
+    }
+    if zeroes > 0 {
+        encoded.extend([0, zeroes]);
+    }
+    encoded
+}
+
+fn decode_rle(encoded: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoded = encoded.iter();
+    while let Some(&byte) = encoded.next() {
+        if byte == 0 {
+            let zeroes = *encoded.next().expect("Zero run should have a length.");
+            bytes.resize(bytes.len() + zeroes as usize, 0);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_rle, encode_rle, RewindBuffer};
+    use crate::{
+        interpreter::Chip8Interpreter,
+        memory::{CosmacRAM, HIRES_DISPLAY_SIZE, LORES_DISPLAY_SIZE},
+        quirks::Chip8Variant,
+        rng::MockChip8Rng,
+        save_state::SaveState,
+    };
+
+    // Save states taken after each step of a program that counts up in V0,
+    // switching to hires partway through.
+    fn save_states(n: usize) -> Vec<SaveState> {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0x7001
+            0x7001
+            0x7001
+            0x00FF
+            0x7001
+            0x1208
+        ))
+        .unwrap();
+        let mut chip8 =
+            Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::SuperChip1_1);
+        chip8.reset(&mut ram);
+
+        (0..n)
+            .map(|_| {
+                chip8.step(&mut ram).unwrap();
+                chip8.save_state(&ram)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pop_newest_first() {
+        let states = save_states(6);
+        let mut buffer = RewindBuffer::new(10);
+        for state in &states {
+            buffer.push(state);
+        }
+        assert_eq!(buffer.len(), 6);
+
+        for state in states.iter().rev() {
+            assert_eq!(buffer.pop().as_ref(), Some(state));
+        }
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn push_after_pop() {
+        let states = save_states(3);
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(&states[0]);
+        buffer.push(&states[1]);
+        assert_eq!(buffer.pop().as_ref(), Some(&states[1]));
+
+        buffer.push(&states[2]);
+        assert_eq!(buffer.pop().as_ref(), Some(&states[2]));
+        assert_eq!(buffer.pop().as_ref(), Some(&states[0]));
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn oldest_state_evicted_when_full() {
+        let states = save_states(6);
+        let mut buffer = RewindBuffer::new(4);
+        for state in &states {
+            buffer.push(state);
+        }
+        assert_eq!(buffer.len(), 4);
+
+        for state in states[2..].iter().rev() {
+            assert_eq!(buffer.pop().as_ref(), Some(state));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn states_with_different_display_sizes() {
+        let states = save_states(5);
+        assert_eq!(states[2].ram.display_size(), LORES_DISPLAY_SIZE);
+        assert_eq!(states[3].ram.display_size(), HIRES_DISPLAY_SIZE);
+
+        let mut buffer = RewindBuffer::new(5);
+        for state in &states {
+            buffer.push(state);
+        }
+        for state in states.iter().rev() {
+            assert_eq!(buffer.pop().as_ref(), Some(state));
+        }
+    }
+
+    #[test]
+    fn older_states_are_compressed() {
+        let states = save_states(100);
+        let mut buffer = RewindBuffer::new(100);
+        for state in &states {
+            buffer.push(state);
+        }
+
+        let full_size = states[99].to_bytes().len();
+        assert!(
+            buffer.size_in_bytes() < 2 * full_size,
+            "100 states should take up less space than 2 full states, took {} bytes",
+            buffer.size_in_bytes()
+        );
+    }
+
+    #[test]
+    fn run_length_encoding() {
+        let bytes = [[0; 300].as_slice(), &[1, 2, 0, 3], &[0; 5]].concat();
+        let encoded = encode_rle(&bytes);
+        assert_eq!(encoded, vec![0, 255, 0, 45, 1, 2, 0, 1, 3, 0, 5]);
+        assert_eq!(decode_rle(&encoded), bytes);
+    }
+}
@@ -1,10 +1,173 @@
+use std::cell::Cell;
+#[cfg(feature = "rand")]
+use std::cell::RefCell;
+
+/// A source of random bytes for CXKK.
+///
+/// Closures returning a `u8` can be used as random number generators, e.g. to
+/// feed CXKK a fixed sequence in a test. With the `rand` feature, any `rand` generator can be used
+/// through `RandRng`.
+///
+/// Unit tests in this crate use the `MockChip8Rng` generated by `mockall`,
+/// which is a struct rather than a closure, so it isn't affected by the
+/// implementation for closures.
 #[cfg_attr(test, mockall::automock)]
 pub trait Chip8Rng {
     fn random_u8(&self) -> u8;
 }
 
+impl<F: Fn() -> u8> Chip8Rng for F {
+    fn random_u8(&self) -> u8 {
+        self()
+    }
+}
+
 impl Chip8Rng for fastrand::Rng {
     fn random_u8(&self) -> u8 {
         self.u8(0..=255)
     }
 }
+
+/// A [`Chip8Rng`] backed by any generator from the `rand` crate.
+#[cfg(feature = "rand")]
+pub struct RandRng<R: rand::RngCore> {
+    rng: RefCell<R>,
+}
+
+#[cfg(feature = "rand")]
+impl<R: rand::RngCore> RandRng<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng: RefCell::new(rng),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.rng.into_inner()
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<R: rand::RngCore> Chip8Rng for RandRng<R> {
+    fn random_u8(&self) -> u8 {
+        self.rng.borrow_mut().next_u32() as u8
+    }
+}
+
+// Feedback taps of the maximal length 16-bit LFSR x^16 + x^14 + x^13 + x^11 + 1
+const LFSR_TAPS: u16 = 0xB400;
+// Used in place of a zero seed, which would lock the LFSR at zero
+const LFSR_DEFAULT_SEED: u16 = 0xACE1;
+
+/// The random number generators that the emulator can use for CXKK.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RngAlgorithm {
+    /// `fastrand`'s generator.
+    #[default]
+    Fastrand,
+    /// [`LfsrRng`], seeded with the low 16 bits of the seed.
+    Lfsr,
+}
+
+/// A deterministic random number generator built on a maximal length 16-bit
+/// Galois LFSR. Its bytes are cheap, repeat after 65535 bytes and are far
+/// from statistically random. The sequence only depends on the seed, so runs
+/// are reproducible.
+pub struct LfsrRng {
+    state: Cell<u16>,
+}
+
+impl LfsrRng {
+    /// Create a generator with the given seed. A seed of zero would never
+    /// produce anything but zeroes, so it's replaced with 0xACE1.
+    pub fn new(seed: u16) -> Self {
+        let seed = if seed == 0 { LFSR_DEFAULT_SEED } else { seed };
+        Self {
+            state: Cell::new(seed),
+        }
+    }
+}
+
+impl Chip8Rng for LfsrRng {
+    // Clock the LFSR 8 times, taking a bit of output each time.
+    fn random_u8(&self) -> u8 {
+        let mut state = self.state.get();
+        let mut byte = 0;
+        for _ in 0..8 {
+            let bit = state & 1;
+            state >>= 1;
+            if bit != 0 {
+                state ^= LFSR_TAPS;
+            }
+            byte = (byte << 1) | bit as u8;
+        }
+        self.state.set(state);
+        byte
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{Chip8Rng, LfsrRng};
+    use crate::{interpreter::Chip8Interpreter, memory::CosmacRAM};
+
+    #[test]
+    fn closure_rng() {
+        let counter = Cell::new(0u8);
+        let rng = || {
+            counter.set(counter.get() + 1);
+            counter.get()
+        };
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0xC0FF 0xC1FF 0xC20F))
+            .unwrap();
+        let mut chip8 = Chip8Interpreter::new(rng);
+        chip8.reset(&mut ram);
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        assert_eq!(&ram.get_v_registers()[..3], [1, 2, 3]);
+        assert_eq!(counter.get(), 3);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn rand_rng() {
+        use super::RandRng;
+        use rand::rngs::mock::StepRng;
+
+        let rng = RandRng::new(StepRng::new(0xFE, 1));
+        let bytes: Vec<u8> = (0..4).map(|_| rng.random_u8()).collect();
+        assert_eq!(bytes, [0xFE, 0xFF, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn lfsr_sequence() {
+        let rng = LfsrRng::new(0xACE1);
+        let bytes: Vec<u8> = (0..12).map(|_| rng.random_u8()).collect();
+        assert_eq!(
+            bytes,
+            [0x87, 0x23, 0x46, 0xDC, 0xB0, 0xDD, 0xEE, 0xF8, 0xFD, 0xC3, 0x5C, 0xBF]
+        );
+    }
+
+    #[test]
+    fn lfsr_zero_seed() {
+        let rng = LfsrRng::new(0);
+        assert_eq!(rng.random_u8(), 0x87);
+    }
+
+    #[test]
+    fn lfsr_period() {
+        let rng = LfsrRng::new(1);
+        let first: Vec<u8> = (0..4).map(|_| rng.random_u8()).collect();
+        // 8 * 65535 clocks brings the LFSR back to its seed
+        for _ in 4..65535 {
+            rng.random_u8();
+        }
+        let repeated: Vec<u8> = (0..4).map(|_| rng.random_u8()).collect();
+        assert_eq!(first, repeated);
+    }
+}
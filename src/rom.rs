@@ -0,0 +1,330 @@
+//! CHIP-8 programs, or roms, ready to be loaded into [`CosmacRAM`].
+//!
+//! A [`Rom`] is checked when it is made, so that a program that can never be
+//! run is rejected before any RAM is set up for it.
+//!
+//! Roms are usually binary `.ch8` files, but many old listings are kept as
+//! hex text, e.g. `A300 6080 F055`, which [`Rom::from_hex_text`] reads.
+//!
+//! Roms are identified by their SHA-1 hash, which a [`RomDatabase`] maps to
+//! the interpreter and quirks they were written for.
+//!
+//! [`RomDatabase`]: crate::rom_database::RomDatabase
+//! [`CosmacRAM`]: crate::memory::CosmacRAM
+
+use std::{cell::OnceCell, fs, path::Path};
+
+use crate::{
+    hash,
+    memory::{ROM_LAST_ADDRESS, ROM_START_ADDRESS},
+    Error, Result,
+};
+
+/// The largest rom that fits into the program area of 4K RAM.
+pub const ROM_MAX_SIZE: usize = ROM_LAST_ADDRESS - ROM_START_ADDRESS + 1;
+
+/// The bytes of a CHIP-8 program.
+#[derive(Debug, Clone)]
+pub struct Rom {
+    name: Option<String>,
+    bytes: Vec<u8>,
+    // Hashes of the bytes, worked out the first time they are asked for
+    sha1: OnceCell<[u8; 20]>,
+    crc32: OnceCell<u32>,
+}
+
+impl Rom {
+    /// Make a rom from the bytes of a CHIP-8 program.
+    ///
+    /// # Errors
+    /// Returns [`Error::EmptyRom`] if `bytes` is empty, or
+    /// [`Error::RomTooLarge`] if it is larger than [`ROM_MAX_SIZE`].
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        if bytes.is_empty() {
+            return Err(Error::EmptyRom);
+        } else if bytes.len() > ROM_MAX_SIZE {
+            return Err(Error::RomTooLarge(bytes.len()));
+        }
+        Ok(Self {
+            name: None,
+            bytes,
+            sha1: OnceCell::new(),
+            crc32: OnceCell::new(),
+        })
+    }
+
+    /// Read a rom from a file, named after the file without its extension.
+    /// Files that hold nothing but hex digits, `0x` prefixes and whitespace
+    /// are read as hex text, see [`Rom::from_hex_text`].
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be read, or the same errors as
+    /// [`Rom::new`] or [`Rom::from_hex_text`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let mut rom = if is_hex_text(&bytes) {
+            let text = String::from_utf8(bytes).expect("Hex text should be ASCII.");
+            Self::from_hex_text("", &text)?
+        } else {
+            Self::new(bytes)?
+        };
+        rom.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+        Ok(rom)
+    }
+
+    /// Read a rom from hex text, such as `"A300 6080 F055"`. Tokens are
+    /// separated by whitespace, can have a `0x` prefix, and can be in either
+    /// case. Each token is one or more whole bytes, so `A3 00` and `A300` are
+    /// the same.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidRomHexToken`] for the first token that isn't a
+    /// whole number of hex bytes, or the same errors as [`Rom::new`].
+    pub fn from_hex_text(name: &str, text: &str) -> Result<Self> {
+        let mut bytes = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            for token in line.split_whitespace() {
+                let digits = token
+                    .strip_prefix("0x")
+                    .or_else(|| token.strip_prefix("0X"))
+                    .unwrap_or(token);
+                if digits.is_empty()
+                    || digits.len() % 2 != 0
+                    || !digits.bytes().all(|digit| digit.is_ascii_hexdigit())
+                {
+                    return Err(Error::InvalidRomHexToken {
+                        line: line_index + 1,
+                        token: token.to_string(),
+                    });
+                }
+                bytes.extend((0..digits.len()).step_by(2).map(|i| {
+                    u8::from_str_radix(&digits[i..i + 2], 16).expect("Digits should be hex.")
+                }));
+            }
+        }
+        let mut rom = Self::new(bytes)?;
+        rom.name = Some(name.to_string());
+        Ok(rom)
+    }
+
+    /// The name of the rom, if it was read from a file or hex text.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The bytes of the program.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The size of the program in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Always false, as an empty rom can't be made.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The SHA-1 hash of the program, which identifies it in a
+    /// [`RomDatabase`](crate::rom_database::RomDatabase).
+    pub fn sha1(&self) -> [u8; 20] {
+        *self.sha1.get_or_init(|| hash::sha1(&self.bytes))
+    }
+
+    /// The SHA-1 hash of the program as lowercase hex, the way rom databases
+    /// list it.
+    pub fn sha1_hex(&self) -> String {
+        hash::to_hex(&self.sha1())
+    }
+
+    /// The CRC-32 of the program.
+    pub fn crc32(&self) -> u32 {
+        *self.crc32.get_or_init(|| hash::crc32(&self.bytes))
+    }
+}
+
+impl PartialEq for Rom {
+    /// Roms are equal when they have the same name and bytes, whether or not
+    /// their hashes have been worked out yet.
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.bytes == other.bytes
+    }
+}
+
+impl Eq for Rom {}
+
+// Whether the contents of a file look like hex text rather than a binary rom.
+fn is_hex_text(bytes: &[u8]) -> bool {
+    bytes.iter().any(u8::is_ascii_hexdigit)
+        && bytes.iter().all(|byte| {
+            byte.is_ascii_hexdigit() || byte.is_ascii_whitespace() || matches!(byte, b'x' | b'X')
+        })
+}
+
+impl TryFrom<&[u8]> for Rom {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Self::new(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{Rom, ROM_MAX_SIZE};
+    use crate::{
+        memory::{CosmacRAM, PROGRAM_MAX_SIZE, PROGRAM_START_ADDRESS},
+        test_utils::TempFile,
+        Error,
+    };
+
+    #[test]
+    fn rom_from_file() {
+        let file = TempFile::with_bytes("pong.ch8", &chip8_program_into_bytes!(0x1234 0x5678));
+        let rom = Rom::from_file(&file.path).unwrap();
+        assert_eq!(rom.bytes(), &[0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(
+            rom.name(),
+            Some(format!("{}-pong", std::process::id()).as_str())
+        );
+        assert_eq!(Rom::new(vec![0x12]).unwrap().name(), None);
+    }
+
+    #[test]
+    fn empty_rom_file() {
+        let file = TempFile::with_bytes("empty.ch8", &[]);
+        assert_eq!(Rom::from_file(&file.path), Err(Error::EmptyRom));
+    }
+
+    #[test]
+    fn rom_file_too_large() {
+        let file = TempFile::with_bytes("too-large.ch8", &vec![0x00; ROM_MAX_SIZE + 1]);
+        assert_eq!(
+            Rom::from_file(&file.path),
+            Err(Error::RomTooLarge(ROM_MAX_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn rom_from_hex_text() {
+        let rom = Rom::from_hex_text("draw", "A300 6080\n  0xf055 0X6000\r\n\ta3 00 D001\n120c\n")
+            .unwrap();
+        assert_eq!(rom.name(), Some("draw"));
+        assert_eq!(
+            rom.bytes(),
+            chip8_program_into_bytes!(0xA300 0x6080 0xF055 0x6000 0xA300 0xD001 0x120C)
+        );
+    }
+
+    #[test]
+    fn invalid_hex_text() {
+        assert_eq!(
+            Rom::from_hex_text("odd", "A300 6080\nF05 6000"),
+            Err(Error::InvalidRomHexToken {
+                line: 2,
+                token: "F05".to_string()
+            })
+        );
+        assert_eq!(
+            Rom::from_hex_text("not hex", "A300 G080"),
+            Err(Error::InvalidRomHexToken {
+                line: 1,
+                token: "G080".to_string()
+            })
+        );
+        assert_eq!(
+            Rom::from_hex_text("prefix only", "0x"),
+            Err(Error::InvalidRomHexToken {
+                line: 1,
+                token: "0x".to_string()
+            })
+        );
+        assert_eq!(Rom::from_hex_text("empty", " \n "), Err(Error::EmptyRom));
+    }
+
+    #[test]
+    fn hex_text_file_same_as_binary_file() {
+        let program = chip8_program_into_bytes!(0x00E0 0xA22A 0x600C 0xD01F 0x1208);
+        let binary = TempFile::with_bytes("binary.ch8", &program);
+        let text = TempFile::with_bytes("text.ch8", b"00e0 A22A\n600C D01F 1208\n");
+
+        let binary_rom = Rom::from_file(&binary.path).unwrap();
+        let text_rom = Rom::from_file(&text.path).unwrap();
+        assert_eq!(text_rom.bytes(), binary_rom.bytes());
+        assert_eq!(
+            text_rom.name(),
+            Some(format!("{}-text", std::process::id()).as_str())
+        );
+    }
+
+    #[test]
+    fn binary_rom_file_is_not_hex_text() {
+        // 0x12 isn't a printable character, so this can't be hex text
+        let file = TempFile::with_bytes("binary-looking.ch8", &[0x12, 0x34, 0x41, 0x42]);
+        assert_eq!(
+            Rom::from_file(&file.path).unwrap().bytes(),
+            &[0x12, 0x34, 0x41, 0x42]
+        );
+    }
+
+    #[test]
+    fn rom_hashes() {
+        let rom = Rom::new(b"abc".to_vec()).unwrap();
+        assert_eq!(rom.len(), 3);
+        assert_eq!(rom.sha1_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(rom.crc32(), 0x352441C2);
+
+        // hashing doesn't change equality
+        assert_eq!(rom, Rom::new(b"abc".to_vec()).unwrap());
+    }
+
+    #[test]
+    fn missing_rom_file() {
+        let path = std::env::temp_dir().join("no-such-dir/missing.ch8");
+        let err = Rom::from_file(path).unwrap_err();
+        assert_eq!(err, Error::Io(io::ErrorKind::NotFound.into()));
+        assert!(matches!(err, Error::Io(_)));
+    }
+
+    #[test]
+    fn empty_rom() {
+        assert_eq!(Rom::new(vec![]), Err(Error::EmptyRom));
+    }
+
+    #[test]
+    fn rom_too_large() {
+        assert_eq!(ROM_MAX_SIZE, PROGRAM_MAX_SIZE);
+        assert!(Rom::new(vec![0x00; ROM_MAX_SIZE]).is_ok());
+        assert_eq!(
+            Rom::new(vec![0x00; ROM_MAX_SIZE + 1]),
+            Err(Error::RomTooLarge(ROM_MAX_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn load_rom_into_ram() {
+        let rom = Rom::try_from(chip8_program_into_bytes!(0x1234 0x5678).as_slice()).unwrap();
+        let mut ram = CosmacRAM::new();
+        ram.load_rom(&rom).unwrap();
+        assert_eq!(
+            &ram.bytes()[PROGRAM_START_ADDRESS..][..4],
+            &[0x12, 0x34, 0x56, 0x78]
+        );
+    }
+
+    #[test]
+    fn rom_too_large_for_2k_ram() {
+        let rom = Rom::new(vec![0x00; 1185]).unwrap();
+        assert_eq!(
+            CosmacRAM::new_2k().load_rom(&rom),
+            Err(Error::Chip8ProgramTooLarge(1185))
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//! Settings for a rom kept in a file next to it, so that a game that needs a
+//! different interpreter, speed or palette runs that way without being told
+//! every time.
+//!
+//! For `game.ch8`, the settings are read from `game.ch8.toml`, or failing
+//! that `game.toml`. Each line sets one of the keys below, in TOML syntax,
+//! and any key can be left out:
+//!
+//! ```toml
+//! # runs best as SCHIP, a bit faster than usual
+//! variant = "schip"        # as --variant takes
+//! ips = 1000               # instructions run each second
+//! palette = "amber"        # as --palette takes
+//! keymap = "game.keys"     # a key map file, relative to this one
+//! tone_hz = 220
+//! sprite_wrapping = true   # any of the fields of Quirks
+//! load_store_increment = "x"
+//! ```
+//!
+//! Quirks set here replace those of the variant one by one. Flags given on
+//! the command line win over the settings here, see
+//! [`RomConfig::overridden_by`].
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{palette::Palette, Chip8Variant, Error, LoadStoreIncrement, Quirks, Result};
+
+// The keys a rom config can set, in the order they are listed in error
+// messages.
+const KEYS: [&str; 14] = [
+    "variant",
+    "ips",
+    "palette",
+    "keymap",
+    "tone_hz",
+    "shift_uses_vx",
+    "load_store_increment",
+    "jump_with_vx",
+    "sprite_wrapping",
+    "vf_reset_on_logic",
+    "display_wait",
+    "i_overflow_sets_vf",
+    "key_wait_tone",
+    "hires_vf_counts_rows",
+];
+
+/// The settings for a rom, each left as it is elsewhere if `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomConfig {
+    pub variant: Option<Chip8Variant>,
+    pub quirks: QuirkOverrides,
+    pub instructions_per_second: Option<u64>,
+    pub palette: Option<Palette>,
+    /// A [key map file](crate::keymap).
+    pub key_map_path: Option<PathBuf>,
+    pub tone_hz: Option<u32>,
+}
+
+/// Quirks to change from those of the variant, each left as it is if `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuirkOverrides {
+    pub shift_uses_vx: Option<bool>,
+    pub load_store_increment: Option<LoadStoreIncrement>,
+    pub jump_with_vx: Option<bool>,
+    pub sprite_wrapping: Option<bool>,
+    pub vf_reset_on_logic: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub i_overflow_sets_vf: Option<bool>,
+    pub key_wait_tone: Option<bool>,
+    pub hires_vf_counts_rows: Option<bool>,
+}
+
+impl QuirkOverrides {
+    /// Whether no quirks are changed.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `quirks` with those set here changed.
+    pub fn apply(&self, quirks: Quirks) -> Quirks {
+        Quirks {
+            shift_uses_vx: self.shift_uses_vx.unwrap_or(quirks.shift_uses_vx),
+            load_store_increment: self
+                .load_store_increment
+                .unwrap_or(quirks.load_store_increment),
+            jump_with_vx: self.jump_with_vx.unwrap_or(quirks.jump_with_vx),
+            sprite_wrapping: self.sprite_wrapping.unwrap_or(quirks.sprite_wrapping),
+            vf_reset_on_logic: self.vf_reset_on_logic.unwrap_or(quirks.vf_reset_on_logic),
+            display_wait: self.display_wait.unwrap_or(quirks.display_wait),
+            i_overflow_sets_vf: self.i_overflow_sets_vf.unwrap_or(quirks.i_overflow_sets_vf),
+            key_wait_tone: self.key_wait_tone.unwrap_or(quirks.key_wait_tone),
+            hires_vf_counts_rows: self
+                .hires_vf_counts_rows
+                .unwrap_or(quirks.hires_vf_counts_rows),
+        }
+    }
+
+    /// These overrides, with those set in `other` taking their place.
+    pub fn overridden_by(self, other: Self) -> Self {
+        Self {
+            shift_uses_vx: other.shift_uses_vx.or(self.shift_uses_vx),
+            load_store_increment: other.load_store_increment.or(self.load_store_increment),
+            jump_with_vx: other.jump_with_vx.or(self.jump_with_vx),
+            sprite_wrapping: other.sprite_wrapping.or(self.sprite_wrapping),
+            vf_reset_on_logic: other.vf_reset_on_logic.or(self.vf_reset_on_logic),
+            display_wait: other.display_wait.or(self.display_wait),
+            i_overflow_sets_vf: other.i_overflow_sets_vf.or(self.i_overflow_sets_vf),
+            key_wait_tone: other.key_wait_tone.or(self.key_wait_tone),
+            hires_vf_counts_rows: other.hires_vf_counts_rows.or(self.hires_vf_counts_rows),
+        }
+    }
+}
+
+impl RomConfig {
+    /// Read the settings from text in the format described in the
+    /// [module docs](self). The key map path is left as written.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidRomConfig`] for the first line that doesn't set
+    /// a known key to a value it can take, or that sets a key already set.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut config = Self::default();
+        let mut keys_set = Vec::new();
+        for (line_index, line) in text.lines().enumerate() {
+            let invalid = |reason: String| {
+                Error::InvalidRomConfig(format!("line {}: {reason}", line_index + 1))
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected `key = value`, found `{line}`")))?;
+            let key = key.trim();
+            if !KEYS.contains(&key) {
+                return Err(invalid(format!(
+                    "unknown key `{key}`, expected one of {}",
+                    KEYS.join(", ")
+                )));
+            }
+            if keys_set.contains(&key) {
+                return Err(invalid(format!("`{key}` is set twice")));
+            }
+            keys_set.push(key);
+            let value = parse_value(value).ok_or_else(|| {
+                invalid(format!("`{key}` has an invalid value `{}`", value.trim()))
+            })?;
+            let wrong_value = |expected: &str| invalid(format!("`{key}` should be {expected}"));
+
+            let quirks = &mut config.quirks;
+            match key {
+                "variant" => {
+                    config.variant =
+                        Some(value.string().and_then(variant_from_name).ok_or_else(|| {
+                            wrong_value(&one_of(VARIANT_NAMES.map(|(name, _)| name)))
+                        })?)
+                }
+                "ips" => {
+                    config.instructions_per_second = Some(
+                        value
+                            .integer()
+                            .filter(|&ips| ips > 0)
+                            .ok_or_else(|| wrong_value("a whole number above 0"))?,
+                    )
+                }
+                "palette" => {
+                    config.palette =
+                        Some(value.string().and_then(palette_from_name).ok_or_else(|| {
+                            wrong_value(&one_of(PALETTE_NAMES.map(|(name, _)| name)))
+                        })?)
+                }
+                "keymap" => {
+                    config.key_map_path =
+                        Some(PathBuf::from(value.string().ok_or_else(|| {
+                            wrong_value("the path of a key map file, in quotes")
+                        })?))
+                }
+                "tone_hz" => {
+                    config.tone_hz = Some(
+                        value
+                            .integer()
+                            .and_then(|hz| u32::try_from(hz).ok())
+                            .ok_or_else(|| wrong_value("a whole number of Hz"))?,
+                    )
+                }
+                "load_store_increment" => {
+                    quirks.load_store_increment = Some(
+                        value
+                            .string()
+                            .and_then(load_store_increment_from_name)
+                            .ok_or_else(|| {
+                                wrong_value(&one_of(
+                                    LOAD_STORE_INCREMENT_NAMES.map(|(name, _)| name),
+                                ))
+                            })?,
+                    )
+                }
+                quirk => {
+                    let flag = match quirk {
+                        "shift_uses_vx" => &mut quirks.shift_uses_vx,
+                        "jump_with_vx" => &mut quirks.jump_with_vx,
+                        "sprite_wrapping" => &mut quirks.sprite_wrapping,
+                        "vf_reset_on_logic" => &mut quirks.vf_reset_on_logic,
+                        "display_wait" => &mut quirks.display_wait,
+                        "i_overflow_sets_vf" => &mut quirks.i_overflow_sets_vf,
+                        "key_wait_tone" => &mut quirks.key_wait_tone,
+                        _ => &mut quirks.hires_vf_counts_rows,
+                    };
+                    *flag = Some(
+                        value
+                            .boolean()
+                            .ok_or_else(|| wrong_value("true or false"))?,
+                    );
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read the settings from a file, see [`RomConfig::parse`]. The key map
+    /// path is taken to be relative to the file.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be read, or the same errors as
+    /// [`RomConfig::parse`].
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config = Self::parse(&fs::read_to_string(path)?)?;
+        if let (Some(key_map_path), Some(dir)) = (&config.key_map_path, path.parent()) {
+            config.key_map_path = Some(dir.join(key_map_path));
+        }
+        Ok(config)
+    }
+
+    /// The file next to the rom at `rom_path` that holds its settings, if
+    /// there is one: `game.ch8.toml` for `game.ch8`, or failing that
+    /// `game.toml`.
+    pub fn path_for(rom_path: impl AsRef<Path>) -> Option<PathBuf> {
+        let rom_path = rom_path.as_ref();
+        let mut next_to_rom = rom_path.as_os_str().to_owned();
+        next_to_rom.push(".toml");
+        [PathBuf::from(next_to_rom), rom_path.with_extension("toml")]
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    /// These settings, with those set in `other` taking their place, e.g.
+    /// the settings in a rom's file overridden by those given on the
+    /// command line.
+    pub fn overridden_by(self, other: Self) -> Self {
+        Self {
+            variant: other.variant.or(self.variant),
+            quirks: self.quirks.overridden_by(other.quirks),
+            instructions_per_second: other
+                .instructions_per_second
+                .or(self.instructions_per_second),
+            palette: other.palette.or(self.palette),
+            key_map_path: other.key_map_path.or(self.key_map_path),
+            tone_hz: other.tone_hz.or(self.tone_hz),
+        }
+    }
+}
+
+// The names of the variants, as `--variant` takes them.
+const VARIANT_NAMES: [(&str, Chip8Variant); 6] = [
+    ("cosmac-vip", Chip8Variant::CosmacVip),
+    ("hires-chip8", Chip8Variant::HiresChip8),
+    ("eti660", Chip8Variant::Eti660),
+    ("chip48", Chip8Variant::Chip48),
+    ("schip", Chip8Variant::SuperChip1_1),
+    ("xo-chip", Chip8Variant::XoChip),
+];
+
+// The names of the palettes, as `--palette` takes them.
+const PALETTE_NAMES: [(&str, Palette); 3] = [
+    ("paper-white", Palette::PAPER_WHITE),
+    ("green", Palette::CLASSIC_GREEN),
+    ("amber", Palette::AMBER),
+];
+
+const LOAD_STORE_INCREMENT_NAMES: [(&str, LoadStoreIncrement); 3] = [
+    ("x-plus-one", LoadStoreIncrement::XPlusOne),
+    ("x", LoadStoreIncrement::X),
+    ("unchanged", LoadStoreIncrement::Unchanged),
+];
+
+fn variant_from_name(name: &str) -> Option<Chip8Variant> {
+    find_named(&VARIANT_NAMES, name)
+}
+
+fn palette_from_name(name: &str) -> Option<Palette> {
+    find_named(&PALETTE_NAMES, name)
+}
+
+fn load_store_increment_from_name(name: &str) -> Option<LoadStoreIncrement> {
+    find_named(&LOAD_STORE_INCREMENT_NAMES, name)
+}
+
+fn find_named<T: Copy>(named: &[(&str, T)], name: &str) -> Option<T> {
+    named
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, value)| value)
+}
+
+// `names`, quoted, as a list to pick from.
+fn one_of<const N: usize>(names: [&str; N]) -> String {
+    let names: Vec<String> = names.iter().map(|name| format!("\"{name}\"")).collect();
+    format!("one of {}", names.join(", "))
+}
+
+// A value in a rom config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value<'a> {
+    String(&'a str),
+    Integer(u64),
+    Boolean(bool),
+}
+
+impl<'a> Value<'a> {
+    fn string(self) -> Option<&'a str> {
+        match self {
+            Value::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn integer(self) -> Option<u64> {
+        match self {
+            Value::Integer(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    fn boolean(self) -> Option<bool> {
+        match self {
+            Value::Boolean(flag) => Some(flag),
+            _ => None,
+        }
+    }
+}
+
+// The value at the start of `text`, which may be followed by a comment: a
+// string in double quotes without escapes, a whole number, or a boolean.
+fn parse_value(text: &str) -> Option<Value<'_>> {
+    let text = text.trim();
+    let (value, rest) = match text.strip_prefix('"') {
+        Some(quoted) => {
+            let (string, rest) = quoted.split_once('"')?;
+            (Value::String(string), rest)
+        }
+        None => {
+            let (token, rest) = text.split_at(text.find('#').unwrap_or(text.len()));
+            let value = match token.trim() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                number => Value::Integer(number.replace('_', "").parse().ok()?),
+            };
+            (value, rest)
+        }
+    };
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{QuirkOverrides, RomConfig};
+    use crate::{
+        emulator::EmulatorConfig, palette::Palette, test_utils::TempFile, Chip8Variant, Error,
+        LoadStoreIncrement, Quirks,
+    };
+
+    #[test]
+    fn parse_rom_config() {
+        let text = "\
+            # runs best as SCHIP\n\
+            variant = \"schip\"\n\
+            ips = 1_000  # a bit faster\n\
+            palette = \"amber\"\n\
+            keymap = \"game.keys\"\n\
+            \n\
+            tone_hz = 220\n\
+            sprite_wrapping = true\n\
+            load_store_increment = \"x\"\n";
+        assert_eq!(
+            RomConfig::parse(text),
+            Ok(RomConfig {
+                variant: Some(Chip8Variant::SuperChip1_1),
+                quirks: QuirkOverrides {
+                    sprite_wrapping: Some(true),
+                    load_store_increment: Some(LoadStoreIncrement::X),
+                    ..QuirkOverrides::default()
+                },
+                instructions_per_second: Some(1000),
+                palette: Some(Palette::AMBER),
+                key_map_path: Some(PathBuf::from("game.keys")),
+                tone_hz: Some(220),
+            })
+        );
+        assert_eq!(RomConfig::parse(""), Ok(RomConfig::default()));
+    }
+
+    #[test]
+    fn parse_rom_config_errors() {
+        let error = |reason: &str| Err(Error::InvalidRomConfig(reason.to_string()));
+        assert_eq!(
+            RomConfig::parse("ips = 700\nspeed = 2"),
+            error(
+                "line 2: unknown key `speed`, expected one of variant, ips, palette, keymap, \
+                tone_hz, shift_uses_vx, load_store_increment, jump_with_vx, sprite_wrapping, \
+                vf_reset_on_logic, display_wait, i_overflow_sets_vf, key_wait_tone, \
+                hires_vf_counts_rows"
+            )
+        );
+        assert_eq!(
+            RomConfig::parse("[quirks]"),
+            error("line 1: expected `key = value`, found `[quirks]`")
+        );
+        assert_eq!(
+            RomConfig::parse("ips = 700\nips = 1000"),
+            error("line 2: `ips` is set twice")
+        );
+        assert_eq!(
+            RomConfig::parse("ips = 0"),
+            error("line 1: `ips` should be a whole number above 0")
+        );
+        assert_eq!(
+            RomConfig::parse("ips = \"fast\""),
+            error("line 1: `ips` should be a whole number above 0")
+        );
+        assert_eq!(
+            RomConfig::parse("variant = \"schip1.1\""),
+            error(
+                "line 1: `variant` should be one of \"cosmac-vip\", \"hires-chip8\", \
+                \"eti660\", \"chip48\", \"schip\", \"xo-chip\""
+            )
+        );
+        assert_eq!(
+            RomConfig::parse("display_wait = 1"),
+            error("line 1: `display_wait` should be true or false")
+        );
+        assert_eq!(
+            RomConfig::parse("palette = \"amber"),
+            error("line 1: `palette` has an invalid value `\"amber`")
+        );
+        assert_eq!(
+            RomConfig::parse("tone_hz = 220 hz"),
+            error("line 1: `tone_hz` has an invalid value `220 hz`")
+        );
+    }
+
+    #[test]
+    fn rom_config_next_to_rom() {
+        let rom = TempFile::with_bytes("rom_config_next_to_rom.ch8", &[0x12, 0x00]);
+        assert_eq!(RomConfig::path_for(&rom.path), None);
+
+        let short = TempFile::with_bytes("rom_config_next_to_rom.toml", b"ips = 500\n");
+        assert_eq!(RomConfig::path_for(&rom.path), Some(short.path.clone()));
+        let long = TempFile::with_bytes(
+            "rom_config_next_to_rom.ch8.toml",
+            b"ips = 1000\nkeymap = \"game.keys\"\n",
+        );
+        assert_eq!(RomConfig::path_for(&rom.path), Some(long.path.clone()));
+
+        let config = RomConfig::from_file(&long.path).unwrap();
+        assert_eq!(config.instructions_per_second, Some(1000));
+        assert_eq!(
+            config.key_map_path,
+            Some(long.path.parent().unwrap().join("game.keys"))
+        );
+    }
+
+    #[test]
+    fn command_line_over_rom_config_over_defaults() {
+        let rom_config = RomConfig::parse(
+            "variant = \"chip48\"\nips = 1000\npalette = \"amber\"\nsprite_wrapping = true\n",
+        )
+        .unwrap();
+        let command_line = RomConfig {
+            instructions_per_second: Some(1500),
+            tone_hz: Some(220),
+            quirks: QuirkOverrides {
+                sprite_wrapping: Some(false),
+                ..QuirkOverrides::default()
+            },
+            ..RomConfig::default()
+        };
+
+        assert_eq!(
+            EmulatorConfig::new().rom_config(&RomConfig::default()),
+            EmulatorConfig::new()
+        );
+        assert_eq!(
+            EmulatorConfig::new().rom_config(&rom_config),
+            EmulatorConfig::new()
+                .variant(Chip8Variant::Chip48)
+                .quirks(Quirks {
+                    sprite_wrapping: true,
+                    ..Quirks::chip48()
+                })
+                .instructions_per_second(1000)
+                .palette_preset(Palette::AMBER)
+        );
+        assert_eq!(
+            EmulatorConfig::new().rom_config(&rom_config.overridden_by(command_line)),
+            EmulatorConfig::new()
+                .variant(Chip8Variant::Chip48)
+                .quirks(Quirks::chip48())
+                .instructions_per_second(1500)
+                .palette_preset(Palette::AMBER)
+                .tone_hz(220)
+        );
+    }
+
+    #[test]
+    fn rom_config_variant_replaces_quirks_for_another_variant() {
+        let recommended = EmulatorConfig::new()
+            .variant(Chip8Variant::SuperChip1_1)
+            .quirks(Quirks {
+                display_wait: true,
+                ..Quirks::schip()
+            });
+        let same_variant = RomConfig::parse("variant = \"schip\"").unwrap();
+        assert_eq!(recommended.clone().rom_config(&same_variant), recommended);
+
+        let other_variant = RomConfig::parse("variant = \"xo-chip\"").unwrap();
+        assert_eq!(
+            recommended.rom_config(&other_variant),
+            EmulatorConfig::new().variant(Chip8Variant::XoChip)
+        );
+    }
+}
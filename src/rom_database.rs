@@ -0,0 +1,169 @@
+//! Looking up what is known about a rom, to pick the interpreter it was
+//! written for without being told.
+//!
+//! A [`RomDatabase`] maps the SHA-1 hash of a rom to a [`RomInfo`]. With the
+//! `serde` feature it can be read from JSON, an object keyed by the SHA-1
+//! hash in hex:
+//!
+//! ```json
+//! {
+//!     "0123456789abcdef0123456789abcdef01234567": {
+//!         "title": "Some Game",
+//!         "recommended_variant": "SuperChip1_1",
+//!         "recommended_quirks": null
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::{fs, path::Path};
+
+use crate::{hash, rom::Rom, Chip8Variant, Error, Quirks, Result};
+
+/// What is known about a rom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RomInfo {
+    pub title: String,
+    /// The interpreter the rom was written for.
+    pub recommended_variant: Option<Chip8Variant>,
+    /// Quirks to use instead of those of the recommended variant, for roms
+    /// that need a mix not matching any one interpreter.
+    pub recommended_quirks: Option<Quirks>,
+}
+
+/// Information about roms, keyed by their SHA-1 hash.
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    roms: HashMap<[u8; 20], RomInfo>,
+}
+
+impl RomDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a database from JSON in the format described in the
+    /// [module docs](self).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidRomDatabase`] if `json` isn't a valid database.
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let roms: HashMap<String, RomInfo> =
+            serde_json::from_str(json).map_err(|err| Error::InvalidRomDatabase(err.to_string()))?;
+        let mut database = Self::new();
+        for (sha1, info) in roms {
+            database.insert(&sha1, info)?;
+        }
+        Ok(database)
+    }
+
+    /// Read a database from a JSON file, see [`RomDatabase::from_json`].
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`] if the file can't be read, or the same errors as
+    /// [`RomDatabase::from_json`].
+    #[cfg(feature = "serde")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Add the information for the rom with the given SHA-1 hash, written as
+    /// hex, replacing any already there.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidRomDatabase`] if `sha1` isn't 40 hex digits.
+    pub fn insert(&mut self, sha1: &str, info: RomInfo) -> Result<()> {
+        let sha1 = hash::sha1_from_hex(sha1)
+            .ok_or_else(|| Error::InvalidRomDatabase(format!("\"{sha1}\" is not a SHA-1 hash")))?;
+        self.roms.insert(sha1, info);
+        Ok(())
+    }
+
+    /// The information for `rom`, or `None` if it isn't in the database.
+    pub fn lookup(&self, rom: &Rom) -> Option<&RomInfo> {
+        self.roms.get(&rom.sha1())
+    }
+
+    /// The number of roms in the database.
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RomDatabase, RomInfo};
+    use crate::{rom::Rom, Chip8Variant, Error};
+
+    fn schip_game() -> RomInfo {
+        RomInfo {
+            title: "SCHIP game".to_string(),
+            recommended_variant: Some(Chip8Variant::SuperChip1_1),
+            recommended_quirks: None,
+        }
+    }
+
+    #[test]
+    fn lookup_hit_and_miss() {
+        let rom = Rom::new(chip8_program_into_bytes!(0x00FF 0x1202)).unwrap();
+        let other_rom = Rom::new(chip8_program_into_bytes!(0x00E0 0x1202)).unwrap();
+
+        let mut database = RomDatabase::new();
+        database.insert(&rom.sha1_hex(), schip_game()).unwrap();
+        assert_eq!(database.len(), 1);
+
+        assert_eq!(database.lookup(&rom), Some(&schip_game()));
+        assert_eq!(database.lookup(&other_rom), None);
+    }
+
+    #[test]
+    fn insert_upper_case_hash() {
+        let rom = Rom::new(b"abc".to_vec()).unwrap();
+        let mut database = RomDatabase::new();
+        database
+            .insert("A9993E364706816ABA3E25717850C26C9CD0D89D", schip_game())
+            .unwrap();
+        assert_eq!(database.lookup(&rom), Some(&schip_game()));
+    }
+
+    #[test]
+    fn insert_invalid_hash() {
+        let mut database = RomDatabase::new();
+        assert_eq!(
+            database.insert("a9993e", schip_game()),
+            Err(Error::InvalidRomDatabase(
+                "\"a9993e\" is not a SHA-1 hash".to_string()
+            ))
+        );
+        assert!(database.is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn database_from_json() {
+        let json = r#"{
+            "a9993e364706816aba3e25717850c26c9cd0d89d": {
+                "title": "SCHIP game",
+                "recommended_variant": "SuperChip1_1",
+                "recommended_quirks": null
+            }
+        }"#;
+        let database = RomDatabase::from_json(json).unwrap();
+        assert_eq!(
+            database.lookup(&Rom::new(b"abc".to_vec()).unwrap()),
+            Some(&schip_game())
+        );
+
+        assert!(matches!(
+            RomDatabase::from_json(r#"{"abc": {"title": "x"}}"#),
+            Err(Error::InvalidRomDatabase(_))
+        ));
+    }
+}
@@ -0,0 +1,697 @@
+//! A fake window to run the emulator's run loop against in tests, as
+//! [`Emulator::run`](crate::emulator::Emulator::run) runs it on its thread,
+//! but without a display, an audio device or a real clock.
+//!
+//! The harness keeps the time by hand, jumping straight to whenever the run
+//! loop next wants to wake up, so a few seconds of emulation run in an
+//! instant and the same way every time. It hands the run loop a script of
+//! inputs at set times, and records what the window would have drawn and
+//! played, each at the time it was sent.
+
+use std::{
+    collections::VecDeque,
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+use crate::{
+    emulator::Emulator,
+    emulator_thread::{run_emulator, EmulatorInput, EmulatorOutput, InputSource, OutputSink},
+    keymap::DEFAULT_SCANCODES,
+    palette::Palette,
+    peripherals::{SampledTone, Tone},
+    Result,
+};
+
+/// A run of the emulator's run loop against the fake window, set up with the
+/// inputs to send and when.
+pub struct RunLoopHarness {
+    inputs: Vec<(Duration, EmulatorInput)>,
+}
+
+/// What the fake window was sent over a run, each at the time since the run
+/// started.
+#[derive(Debug, Default)]
+pub struct RunLog {
+    pub frames: Vec<(Duration, Frame)>,
+    pub titles: Vec<(Duration, String)>,
+    /// The tone starting and stopping.
+    pub tone: Vec<(Duration, bool)>,
+    /// The volume of the tone, whenever it changed.
+    pub volumes: Vec<(Duration, f32)>,
+    /// The XO-CHIP audio patterns the tone was given, with their playback
+    /// rates, each time it was given one.
+    pub patterns: Vec<(Duration, [u8; 16], f32)>,
+    /// When the run loop returned, whether closed or not.
+    pub finished_at: Duration,
+}
+
+/// A frame drawn by the fake window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// RGBA pixels, in rows of `width`.
+    pub pixels: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Frame {
+    /// Whether the pixel at `x`, `y` is drawn in the lit color of `palette`.
+    pub fn is_lit(&self, x: usize, y: usize, palette: &Palette) -> bool {
+        let offset = 4 * (y * self.width + x);
+        self.pixels[offset..offset + 4] == palette.on
+    }
+}
+
+impl RunLoopHarness {
+    /// A run with no inputs, other than closing the window at the end.
+    pub fn new() -> Self {
+        Self { inputs: Vec::new() }
+    }
+
+    /// Send `input` at `time` into the run.
+    pub fn at(mut self, time: Duration, input: EmulatorInput) -> Self {
+        self.inputs.push((time, input));
+        self
+    }
+
+    /// Press `key` on the host keyboard at `time`.
+    pub fn press(self, time: Duration, key: VirtualKeyCode) -> Self {
+        self.at(time, EmulatorInput::Key(key_input(key, true)))
+    }
+
+    /// Let go of `key` on the host keyboard at `time`.
+    pub fn release(self, time: Duration, key: VirtualKeyCode) -> Self {
+        self.at(time, EmulatorInput::Key(key_input(key, false)))
+    }
+
+    /// Press `key` on the host keyboard at `time`, and let it go straight
+    /// after.
+    pub fn tap(self, time: Duration, key: VirtualKeyCode) -> Self {
+        self.press(time, key).release(time, key)
+    }
+
+    /// Run `emulator` until the window is closed, `duration` into the run,
+    /// or the emulator stops by itself, returning how the run loop finished
+    /// and what it sent to the window.
+    pub fn run(self, emulator: &mut Emulator, duration: Duration) -> (Result<()>, RunLog) {
+        let clock = ManualClock::new();
+        let log = Arc::new(Mutex::new(RunLog::default()));
+        let mut inputs = self.inputs;
+        inputs.push((duration, EmulatorInput::Close));
+        // inputs sent at the same time arrive in the order they were added
+        inputs.sort_by_key(|&(time, _)| time);
+        let mut inputs = ScriptedInputs {
+            clock: clock.clone(),
+            script: inputs
+                .into_iter()
+                .map(|(time, input)| (clock.start + time, input))
+                .collect(),
+        };
+        let window = FakeWindow {
+            clock: clock.clone(),
+            log: Arc::clone(&log),
+            tone: RecordingTone {
+                clock,
+                log: Arc::clone(&log),
+                is_on: Mutex::new(false),
+                volume: Mutex::new(1.0),
+                pattern: Mutex::new(None),
+            },
+        };
+
+        let result = run_emulator(emulator, &mut inputs, &window);
+        let finished_at = window.clock.elapsed();
+        drop(window);
+        let mut log = Arc::try_unwrap(log)
+            .expect("The window should be gone after the run")
+            .into_inner()
+            .unwrap();
+        log.finished_at = finished_at;
+        (result, log)
+    }
+}
+
+// A clock that only moves when told to, shared by the fake window and the
+// scripted inputs.
+#[derive(Clone)]
+struct ManualClock {
+    start: Instant,
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    fn new() -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    // The time since the run started.
+    fn elapsed(&self) -> Duration {
+        self.now() - self.start
+    }
+
+    // Move the clock on to `time`, unless it's already past it.
+    fn advance_to(&self, time: Instant) {
+        let mut now = self.now.lock().unwrap();
+        *now = (*now).max(time);
+    }
+}
+
+// The inputs to send, at their times, and the clock, which jumps to the next
+// input or deadline whenever the run loop waits.
+struct ScriptedInputs {
+    clock: ManualClock,
+    script: VecDeque<(Instant, EmulatorInput)>,
+}
+
+impl InputSource for ScriptedInputs {
+    fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    fn next_input(
+        &mut self,
+        deadline: Option<Instant>,
+    ) -> std::result::Result<EmulatorInput, RecvTimeoutError> {
+        let next_at = self.script.front().map(|&(time, _)| time);
+        match (next_at, deadline) {
+            (Some(time), Some(deadline)) if time > deadline => {
+                self.clock.advance_to(deadline);
+                Err(RecvTimeoutError::Timeout)
+            }
+            (Some(time), _) => {
+                self.clock.advance_to(time);
+                Ok(self.script.pop_front().unwrap().1)
+            }
+            (None, Some(deadline)) => {
+                self.clock.advance_to(deadline);
+                Err(RecvTimeoutError::Timeout)
+            }
+            (None, None) => Err(RecvTimeoutError::Disconnected),
+        }
+    }
+}
+
+// Records the frames and titles sent to it, and plays the tone on a
+// recording tone, as the window does.
+struct FakeWindow {
+    clock: ManualClock,
+    log: Arc<Mutex<RunLog>>,
+    tone: RecordingTone,
+}
+
+impl OutputSink for FakeWindow {
+    fn send(&self, output: EmulatorOutput) {
+        let time = self.clock.elapsed();
+        let mut log = self.log.lock().unwrap();
+        match output {
+            EmulatorOutput::Frame {
+                pixels,
+                width,
+                height,
+            } => log.frames.push((
+                time,
+                Frame {
+                    pixels,
+                    width,
+                    height,
+                },
+            )),
+            EmulatorOutput::Title(title) => log.titles.push((time, title)),
+            // the tone records itself
+            output => {
+                drop(log);
+                match output {
+                    EmulatorOutput::Tone(true) => self.tone.start_tone(),
+                    EmulatorOutput::Tone(false) => self.tone.stop_tone(),
+                    EmulatorOutput::TonePattern(pattern, playback_rate_hz) => {
+                        self.tone.set_pattern(&pattern, playback_rate_hz)
+                    }
+                    EmulatorOutput::Volume(volume) => self.tone.set_volume(volume),
+                    // the thread sends the rest, after the run loop returns
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+/// A tone that records when it's started and stopped, its volume and the
+/// patterns it's given, in place of an audio device.
+pub struct RecordingTone {
+    clock: ManualClock,
+    log: Arc<Mutex<RunLog>>,
+    is_on: Mutex<bool>,
+    volume: Mutex<f32>,
+    pattern: Mutex<Option<([u8; 16], f32)>>,
+}
+
+impl Default for RecordingTone {
+    /// A tone recording into a log of its own, for tests outside a run.
+    fn default() -> Self {
+        Self {
+            clock: ManualClock::new(),
+            log: Arc::default(),
+            is_on: Mutex::new(false),
+            volume: Mutex::new(1.0),
+            pattern: Mutex::new(None),
+        }
+    }
+}
+
+impl RecordingTone {
+    /// The audio pattern it was last given, with its playback rate.
+    pub fn pattern(&self) -> Option<([u8; 16], f32)> {
+        *self.pattern.lock().unwrap()
+    }
+
+    fn set_on(&self, on: bool) {
+        let mut is_on = self.is_on.lock().unwrap();
+        if *is_on != on {
+            *is_on = on;
+            let time = self.clock.elapsed();
+            self.log.lock().unwrap().tone.push((time, on));
+        }
+    }
+}
+
+impl Tone for RecordingTone {
+    fn start_tone(&self) {
+        self.set_on(true);
+    }
+
+    fn stop_tone(&self) {
+        self.set_on(false);
+    }
+
+    fn is_tone_on(&self) -> bool {
+        *self.is_on.lock().unwrap()
+    }
+
+    fn set_volume(&self, volume: f32) {
+        *self.volume.lock().unwrap() = volume;
+        let time = self.clock.elapsed();
+        self.log.lock().unwrap().volumes.push((time, volume));
+    }
+
+    fn volume(&self) -> f32 {
+        *self.volume.lock().unwrap()
+    }
+}
+
+impl SampledTone for RecordingTone {
+    fn set_pattern(&self, pattern: &[u8; 16], playback_rate_hz: f32) {
+        *self.pattern.lock().unwrap() = Some((*pattern, playback_rate_hz));
+        let time = self.clock.elapsed();
+        let mut log = self.log.lock().unwrap();
+        log.patterns.push((time, *pattern, playback_rate_hz));
+    }
+}
+
+// The keys bound to hex keys 0 to F by default on a QWERTY keyboard, whose
+// scancodes are sent with them.
+const QWERTY_KEYPAD: [VirtualKeyCode; 16] = [
+    VirtualKeyCode::X,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::W,
+    VirtualKeyCode::E,
+    VirtualKeyCode::A,
+    VirtualKeyCode::S,
+    VirtualKeyCode::D,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::C,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::R,
+    VirtualKeyCode::F,
+    VirtualKeyCode::V,
+];
+
+/// `key` being pressed or released on the host keyboard, with the scancode it
+/// has on a QWERTY keyboard if it's one of the keypad keys.
+#[allow(deprecated)]
+pub fn key_input(key: VirtualKeyCode, pressed: bool) -> KeyboardInput {
+    let scancode = QWERTY_KEYPAD
+        .iter()
+        .position(|&keypad_key| keypad_key == key)
+        .map_or(0, |hex_key| DEFAULT_SCANCODES[hex_key]);
+    KeyboardInput {
+        scancode,
+        state: match pressed {
+            true => ElementState::Pressed,
+            false => ElementState::Released,
+        },
+        virtual_keycode: Some(key),
+        modifiers: ModifiersState::empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use winit::event::VirtualKeyCode;
+
+    use super::{RunLog, RunLoopHarness};
+    use crate::{
+        emulator::{Emulator, EmulatorConfig},
+        emulator_thread::EmulatorInput,
+        palette::Palette,
+        quirks::Chip8Variant,
+        rom::Rom,
+        test_utils::TempFile,
+    };
+
+    // A 60Hz frame, as the emulator times them.
+    const FRAME: Duration = Duration::from_micros(16_667);
+
+    fn emulator_with_program(program: &[u8], config: EmulatorConfig) -> Emulator {
+        let mut emulator = Emulator::new(config);
+        emulator
+            .load_rom(&Rom::new(program.to_vec()).unwrap())
+            .unwrap();
+        emulator
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    // The titles the window was given, without their times.
+    fn titles(log: &RunLog) -> Vec<&str> {
+        log.titles.iter().map(|(_, title)| title.as_str()).collect()
+    }
+
+    #[test]
+    fn frames_presented_only_when_the_display_changes() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x6005  // V0 = 5
+                0xF018  // tone timer = V0
+                0x6000  // V0 = 0
+                0xF029  // I = glyph for V0
+                0x603C  // V0 = 60
+                0xF015  // delay timer = V0
+                0xF107  // V1 = delay timer
+                0x3100  // skip if V1 == 0
+                0x120C  // jump back to wait
+                0xD235  // draw the glyph at (V2, V3)
+                0x1208  // start the next second
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(3500));
+        result.unwrap();
+
+        // the first frame shows the blank display, then the glyph is drawn
+        // and erased again each second, presented at the tick of the frame
+        // it was drawn in, with the frames in between skipped
+        let times: Vec<Duration> = log.frames.iter().map(|&(time, _)| time).collect();
+        assert_eq!(times, [0, 60, 120, 180].map(|frames| FRAME * frames));
+        let lit: Vec<bool> = log
+            .frames
+            .iter()
+            .map(|(_, frame)| frame.is_lit(0, 0, &Palette::default()))
+            .collect();
+        assert_eq!(lit, [false, true, false, true]);
+        assert!(log
+            .frames
+            .iter()
+            .all(|(_, frame)| (frame.width, frame.height) == (64, 32)));
+
+        // the tone sounds until the tone timer drops below 2, after the
+        // ticks of frames 0 to 3
+        assert_eq!(log.tone, [(Duration::ZERO, true), (FRAME * 3, false)]);
+        assert_eq!(log.volumes, [(Duration::ZERO, 1.0)]);
+        assert_eq!(titles(&log), ["CHIP-8 Emulator"]);
+
+        // a frame runs every 60th of a second, the first straight away, up
+        // to the window closing 3.5s in, each running 700 / 60 instructions,
+        // with the remainders carried over
+        assert_eq!(emulator.instructions_run(), 210 * 700 / 60);
+        assert_eq!(log.finished_at, millis(3500));
+    }
+
+    #[test]
+    fn tone_sounds_for_the_sound_timer() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x601E  // V0 = 30
+                0xF018  // tone timer = V0
+                0x1204  // loop forever
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(1000));
+        result.unwrap();
+
+        // the speaker stops once less than two of the 30 ticks are left
+        assert_eq!(log.tone, [(Duration::ZERO, true), (FRAME * 28, false)]);
+    }
+
+    #[test]
+    fn audio_pattern_sent_to_the_tone_once_loaded() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0xA20A  // I = the pattern below
+                0xF002  // load the audio pattern at I
+                0x6070  // V0 = 112
+                0xF03A  // pitch = V0
+                0x1208  // loop forever
+                0xF00F 0xAA55 0x0000 0x0000 0x0000 0x0000 0x0000 0x00FF
+            ),
+            EmulatorConfig::new().variant(Chip8Variant::XoChip),
+        );
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(500));
+        result.unwrap();
+
+        // the pattern and pitch are both set in the first frame, and sent
+        // once at its end, at 4000 * 2^((112 - 64) / 48) samples a second
+        let pattern = [
+            0xF0, 0x0F, 0xAA, 0x55, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF,
+        ];
+        assert_eq!(log.patterns, [(Duration::ZERO, pattern, 8000.0)]);
+    }
+
+    #[test]
+    fn instructions_paced_at_700_a_second() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7001  // V0 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(1000));
+        result.unwrap();
+
+        assert_eq!(emulator.instructions_run(), 700);
+        assert_eq!(log.frames.len(), 1);
+        assert_eq!(log.finished_at, millis(1000));
+    }
+
+    #[test]
+    fn halted_program_stops_stepping() {
+        let mut emulator =
+            emulator_with_program(&chip8_program_into_bytes!(0x1200), EmulatorConfig::new());
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(1000));
+        result.unwrap();
+
+        assert_eq!(titles(&log), ["CHIP-8 Emulator (HALTED)"]);
+        // the jump to itself, which is seen to halt the program
+        assert_eq!(emulator.instructions_run(), 1);
+    }
+
+    #[test]
+    fn pause_stops_the_clock_and_tone_and_reset_restarts() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x60FF  // V0 = 255
+                0xF018  // tone timer = V0
+                0x7101  // V1 += 1
+                0x1204  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new()
+            .tap(millis(500), VirtualKeyCode::P)
+            .tap(millis(1500), VirtualKeyCode::P)
+            .tap(millis(2000), VirtualKeyCode::Back)
+            .run(&mut emulator, millis(2500));
+        result.unwrap();
+
+        // the tone stops while paused. The reset stops it too, but the
+        // restarted rom sets the tone timer again in the same tick, so the
+        // window never hears a gap
+        let tone_times: Vec<(u128, bool)> = log
+            .tone
+            .iter()
+            .map(|&(time, on)| (time.as_millis(), on))
+            .collect();
+        assert_eq!(tone_times, [(0, true), (500, false), (1500, true)]);
+        assert_eq!(
+            titles(&log),
+            [
+                "CHIP-8 Emulator",
+                "CHIP-8 Emulator (paused)",
+                "CHIP-8 Emulator"
+            ]
+        );
+
+        // the second spent paused isn't caught up on. Nor is the time from
+        // the last frame to the pause, so frame 30 runs on resuming rather
+        // than before pausing, and frames 0 to 58 run before the reset
+        assert_eq!(emulator.instructions_run(), 59 * 700 / 60 + 350);
+        // the reset starts the rom afresh, running frames 0 to 29 before
+        // the close: 350 instructions, the 2 setting the tone then 174 loops
+        assert_eq!(emulator.state().v_registers[1], 174);
+    }
+
+    #[test]
+    fn focus_loss_pauses_until_focus_returns() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x7101  // V1 += 1
+                0x1200  // loop
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new()
+            .at(millis(1000), EmulatorInput::Focused(false))
+            .at(millis(3000), EmulatorInput::Focused(true))
+            .run(&mut emulator, millis(4000));
+        result.unwrap();
+
+        assert_eq!(
+            log.titles
+                .iter()
+                .map(|(time, title)| (time.as_millis(), title.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (0, "CHIP-8 Emulator"),
+                (1000, "CHIP-8 Emulator (paused)"),
+                (3000, "CHIP-8 Emulator")
+            ]
+        );
+        // 2s running, but the time from frame 59 to losing focus isn't
+        // caught up on, so frame 119 would only be due after the close
+        assert_eq!(emulator.instructions_run(), 119 * 700 / 60);
+    }
+
+    #[test]
+    fn held_key_seen_by_the_key_wait() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0xF00A  // V0 = next key pressed
+                0xF029  // I = glyph for V0
+                0xD115  // draw the glyph at (V1, V1)
+                0x1206  // loop forever
+            ),
+            EmulatorConfig::new(),
+        );
+
+        let (result, log) = RunLoopHarness::new()
+            .press(millis(100), VirtualKeyCode::W)
+            .release(millis(150), VirtualKeyCode::W)
+            .run(&mut emulator, millis(500));
+        result.unwrap();
+
+        assert_eq!(log.frames.len(), 2);
+        let (time, frame) = &log.frames[1];
+        let palette = Palette::default();
+        assert!(*time >= millis(150));
+        // W is hex key 5: the top bar of the 5, then its left side only
+        assert!(frame.is_lit(3, 0, &palette));
+        assert!(frame.is_lit(0, 1, &palette) && !frame.is_lit(3, 1, &palette));
+    }
+
+    #[test]
+    fn turbo_runs_more_frames_while_held() {
+        let program = chip8_program_into_bytes!(
+            0x7101  // V1 += 1
+            0x1200  // loop
+        );
+        let mut emulator = emulator_with_program(&program, EmulatorConfig::new());
+
+        let (result, log) = RunLoopHarness::new()
+            .press(millis(1000), VirtualKeyCode::Grave)
+            .release(millis(2000), VirtualKeyCode::Grave)
+            .run(&mut emulator, millis(3000));
+        result.unwrap();
+
+        // a second at 5x, as well as the two at the usual speed. The time
+        // from frame 59 to pressing turbo is caught up on at 5x too, running
+        // 4 more frames than the 420 of an exact switch
+        assert_eq!(emulator.instructions_run(), 424 * 700 / 60);
+        assert!(titles(&log).contains(&"CHIP-8 Emulator (turbo 5x)"));
+        assert_eq!(titles(&log).last(), Some(&"CHIP-8 Emulator"));
+    }
+
+    #[test]
+    fn run_loop_stops_when_the_rom_exits() {
+        let mut emulator = emulator_with_program(
+            &chip8_program_into_bytes!(
+                0x6005  // V0 = 5
+                0xF015  // delay timer = V0
+                0xF007  // V0 = delay timer
+                0x3000  // skip if V0 == 0
+                0x1204  // jump back to wait
+                0x00FD  // exit
+            ),
+            EmulatorConfig::new().variant(Chip8Variant::SuperChip1_1),
+        );
+
+        let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(1000));
+        result.unwrap();
+
+        // the delay timer runs out in the tick of frame 4, and the rom exits
+        // in frame 5, long before the window is closed
+        assert_eq!(log.finished_at, FRAME * 5);
+        assert_eq!(log.frames.len(), 1);
+    }
+
+    #[test]
+    fn run_loop_closes_the_emulator_however_it_stops() {
+        for (program, stops_by_itself) in [
+            // exits by itself
+            (chip8_program_into_bytes!(0x00FD), true),
+            // loops until the window is closed
+            (chip8_program_into_bytes!(0x1200), false),
+        ] {
+            let autosave = TempFile::new("run-loop.autosave.c8state");
+            let mut emulator = emulator_with_program(
+                &program,
+                EmulatorConfig::new()
+                    .variant(Chip8Variant::SuperChip1_1)
+                    .autosave_path(&autosave.path),
+            );
+
+            let (result, log) = RunLoopHarness::new().run(&mut emulator, millis(100));
+            result.unwrap();
+            assert_eq!(log.finished_at < millis(100), stops_by_itself);
+            assert!(
+                autosave.path.exists(),
+                "The emulator should be closed, stopping by itself: {stops_by_itself}"
+            );
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Save states, snapshots of the whole machine that can be restored later.
+//!
+//! A save state is made by [`Chip8Interpreter::save_state`] and restored by
+//! [`Chip8Interpreter::load_state`]. It can be written to a file with
+//! [`SaveState::to_bytes`] and read back with [`SaveState::from_bytes`].
+//!
+//! The byte format starts with the magic bytes `C8SS` and a big-endian `u16`
+//! format version. Later versions of this library will keep loading files
+//! written with older versions.
+//!
+//! [`Chip8Interpreter::save_state`]: crate::interpreter::Chip8Interpreter::save_state
+//! [`Chip8Interpreter::load_state`]: crate::interpreter::Chip8Interpreter::load_state
+
+use crate::{
+    interpreter::{AUDIO_PATTERN_SIZE, NUM_RPL_FLAGS},
+    memory::{
+        DisplaySize, RamSnapshot, DISPLAY_SIZES, MEMORY_SIZE, MEMORY_SIZES, NUM_DISPLAY_PLANES,
+    },
+    Error, Result,
+};
+
+const MAGIC: &[u8; 4] = b"C8SS";
+// Version 2 added the size of RAM, which was always 4K in version 1, and
+// version 3 the CRC-32 of the rom.
+const VERSION: u16 = 3;
+
+/// A snapshot of CHIP-8 RAM, the display and the interpreter state that
+/// lives outside of RAM.
+///
+/// The timers are stored as the number of jiffies left, so a save state
+/// doesn't depend on when it was made. The state of the random number
+/// generator is not saved. The rom the state was made with can be recorded
+/// with [`SaveState::with_rom_crc32`], to check it is loaded with the same
+/// rom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    pub(crate) ram: RamSnapshot,
+    pub(crate) timer: u16,
+    pub(crate) tone_timer: u16,
+    pub(crate) drawn_since_tick: bool,
+    pub(crate) rpl_flags: [u8; NUM_RPL_FLAGS],
+    pub(crate) audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pub(crate) audio_pitch: u8,
+    pub(crate) rom_crc32: Option<u32>,
+}
+
+impl SaveState {
+    /// Record that the state was made running the rom with this CRC-32, see
+    /// [`Rom::crc32`].
+    ///
+    /// [`Rom::crc32`]: crate::rom::Rom::crc32
+    pub fn with_rom_crc32(mut self, crc32: u32) -> Self {
+        self.rom_crc32 = Some(crc32);
+        self
+    }
+
+    /// The CRC-32 of the rom the state was made with, if it was recorded.
+    pub fn rom_crc32(&self) -> Option<u32> {
+        self.rom_crc32
+    }
+
+    /// Encode the save state in the versioned byte format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_be_bytes());
+        bytes.extend((self.ram.data.len() as u16).to_be_bytes());
+        bytes.extend(&self.ram.data);
+        bytes.extend((self.ram.display_size.width as u16).to_be_bytes());
+        bytes.extend((self.ram.display_size.height as u16).to_be_bytes());
+        for plane in &self.ram.display_planes {
+            bytes.extend(plane);
+        }
+        bytes.extend(self.timer.to_be_bytes());
+        bytes.extend(self.tone_timer.to_be_bytes());
+        bytes.push(self.drawn_since_tick as u8);
+        bytes.extend(self.rpl_flags);
+        match self.audio_pattern {
+            Some(pattern) => {
+                bytes.push(1);
+                bytes.extend(pattern);
+            }
+            None => {
+                bytes.push(0);
+                bytes.extend([0; AUDIO_PATTERN_SIZE]);
+            }
+        }
+        bytes.push(self.audio_pitch);
+        bytes.push(self.rom_crc32.is_some() as u8);
+        bytes.extend(self.rom_crc32.unwrap_or(0).to_be_bytes());
+        bytes
+    }
+
+    /// Decode a save state written by [`SaveState::to_bytes`].
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedSaveStateVersion`] if the save state was
+    /// written by a newer version of this library, or
+    /// [`Error::InvalidSaveState`] if `bytes` are not a valid save state.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes, || Error::InvalidSaveState);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Error::InvalidSaveState);
+        }
+        let version = reader.u16()?;
+        let memory_size = match version {
+            1 => MEMORY_SIZE,
+            2..=VERSION => reader.u16()? as usize,
+            _ => return Err(Error::UnsupportedSaveStateVersion(version)),
+        };
+        if !MEMORY_SIZES.contains(&memory_size) {
+            return Err(Error::InvalidSaveState);
+        }
+        let data = reader.take(memory_size)?.to_vec();
+        let display_size = DisplaySize {
+            width: reader.u16()? as usize,
+            height: reader.u16()? as usize,
+        };
+        if !DISPLAY_SIZES.contains(&display_size) {
+            return Err(Error::InvalidSaveState);
+        }
+        let mut display_planes: [Vec<u8>; NUM_DISPLAY_PLANES] = Default::default();
+        for plane in display_planes.iter_mut() {
+            *plane = reader.take(display_size.plane_size())?.to_vec();
+        }
+        let timer = reader.u16()?;
+        let tone_timer = reader.u16()?;
+        let drawn_since_tick = reader.u8()? != 0;
+        let rpl_flags = reader.take(NUM_RPL_FLAGS)?.try_into().unwrap();
+        let has_audio_pattern = reader.u8()? != 0;
+        let audio_pattern: [u8; AUDIO_PATTERN_SIZE] =
+            reader.take(AUDIO_PATTERN_SIZE)?.try_into().unwrap();
+        let audio_pitch = reader.u8()?;
+        let rom_crc32 = match version {
+            1 | 2 => None,
+            _ => {
+                let has_rom_crc32 = reader.u8()? != 0;
+                let crc32 = reader.u32()?;
+                has_rom_crc32.then_some(crc32)
+            }
+        };
+        if !reader.bytes.is_empty() {
+            return Err(Error::InvalidSaveState);
+        }
+
+        Ok(Self {
+            ram: RamSnapshot {
+                data,
+                display_size,
+                display_planes,
+            },
+            timer,
+            tone_timer,
+            drawn_since_tick,
+            rpl_flags,
+            audio_pattern: has_audio_pattern.then_some(audio_pattern),
+            audio_pitch,
+            rom_crc32,
+        })
+    }
+}
+
+// Reads big-endian fields in order, failing with the `invalid` error if it
+// runs out of bytes.
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+    invalid: fn() -> Error,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], invalid: fn() -> Error) -> Self {
+        Self { bytes, invalid }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err((self.invalid)());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SaveState;
+    use crate::{
+        interpreter::Chip8Interpreter,
+        memory::{CosmacRAM, HIRES_DISPLAY_SIZE},
+        quirks::Chip8Variant,
+        rng::MockChip8Rng,
+        Error,
+    };
+
+    fn save_state() -> SaveState {
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x00FF 0x6005 0xF015 NOOP))
+            .unwrap();
+        let mut chip8 =
+            Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::SuperChip1_1);
+        chip8.reset(&mut ram);
+        for _ in 0..3 {
+            chip8.step(&mut ram).unwrap();
+        }
+        chip8.save_state(&ram)
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let state = save_state();
+        assert_eq!(state.ram.display_size(), HIRES_DISPLAY_SIZE);
+        assert_eq!(state.timer, 5);
+
+        let bytes = state.to_bytes();
+        assert_eq!(&bytes[..8], b"C8SS\x00\x03\x10\x00");
+        assert_eq!(SaveState::from_bytes(&bytes), Ok(state));
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let bytes = save_state().to_bytes();
+
+        assert_eq!(
+            SaveState::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::InvalidSaveState),
+            "Truncated save state"
+        );
+        assert_eq!(
+            SaveState::from_bytes(&[&bytes[..], &[0]].concat()),
+            Err(Error::InvalidSaveState),
+            "Trailing bytes"
+        );
+        assert_eq!(
+            SaveState::from_bytes(b"NOPE\x00\x01"),
+            Err(Error::InvalidSaveState),
+            "Wrong magic bytes"
+        );
+
+        let mut newer_version = bytes.clone();
+        newer_version[5] = 4;
+        assert_eq!(
+            SaveState::from_bytes(&newer_version),
+            Err(Error::UnsupportedSaveStateVersion(4))
+        );
+
+        let mut bad_memory_size = bytes.clone();
+        bad_memory_size[6] = 0x20;
+        assert_eq!(
+            SaveState::from_bytes(&bad_memory_size),
+            Err(Error::InvalidSaveState)
+        );
+
+        let mut bad_display_size = bytes;
+        bad_display_size[8 + 4096 + 1] = 100;
+        assert_eq!(
+            SaveState::from_bytes(&bad_display_size),
+            Err(Error::InvalidSaveState)
+        );
+    }
+
+    #[test]
+    fn version_1_bytes() {
+        // version 1 is version 2 without the size of RAM, which was always 4K
+        let state = save_state();
+        let bytes = state.to_bytes();
+        let version_1_bytes = [b"C8SS\x00\x01", &bytes[8..bytes.len() - 5]].concat();
+        assert_eq!(SaveState::from_bytes(&version_1_bytes), Ok(state));
+    }
+
+    #[test]
+    fn version_2_bytes() {
+        // version 2 is version 3 without the CRC-32 of the rom
+        let state = save_state();
+        let bytes = state.to_bytes();
+        let version_2_bytes = [b"C8SS\x00\x02", &bytes[6..bytes.len() - 5]].concat();
+        assert_eq!(SaveState::from_bytes(&version_2_bytes), Ok(state));
+    }
+
+    #[test]
+    fn rom_crc32_round_trip() {
+        let state = save_state();
+        assert_eq!(state.rom_crc32(), None);
+        let state = state.with_rom_crc32(0xDEADBEEF);
+        assert_eq!(state.rom_crc32(), Some(0xDEADBEEF));
+        assert_eq!(SaveState::from_bytes(&state.to_bytes()), Ok(state));
+    }
+
+    #[test]
+    fn save_state_of_2k_ram() {
+        let mut ram = CosmacRAM::new_2k();
+        ram.load_chip8_program(&chip8_program_into_bytes!(0x6005 0xF015 NOOP))
+            .unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        chip8.reset(&mut ram);
+        chip8.step(&mut ram).unwrap();
+        chip8.step(&mut ram).unwrap();
+        let state = chip8.save_state(&ram);
+        assert_eq!(state.ram.bytes().len(), 2048);
+        assert_eq!(
+            SaveState::from_bytes(&state.to_bytes()).as_ref(),
+            Ok(&state)
+        );
+
+        let mut restored_ram = CosmacRAM::new_2k();
+        chip8.load_state(&mut restored_ram, &state).unwrap();
+        assert_eq!(restored_ram.bytes(), ram.bytes());
+        assert_eq!(
+            Chip8Interpreter::<MockChip8Rng>::state(&restored_ram).timer,
+            5
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Keeping the emulation in step with real time.
+//!
+//! The emulator runs whatever instructions and ticks fell due since it last
+//! ran, so time spent elsewhere, e.g. drawing, is made up for rather than
+//! lost. After a stall, e.g. under a debugger, there can be too much to make
+//! up for: running it all at once would stall the emulator again, and it
+//! would never catch up. The [`Scheduler`] bounds how far behind it lets the
+//! emulation fall, and drops the rest.
+
+use std::time::{Duration, Instant};
+
+/// Measures how far the emulation is behind real time, from the times it's
+/// given, so that it can be driven by a fake clock in tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheduler {
+    // when the emulation last caught up with real time, unless it's to
+    // start afresh
+    last_catch_up: Option<Instant>,
+    max_catch_up: Duration,
+    time_dropped: Duration,
+}
+
+impl Scheduler {
+    /// A scheduler that lets the emulation fall at most `max_catch_up`
+    /// behind real time.
+    pub fn new(max_catch_up: Duration) -> Self {
+        Self {
+            last_catch_up: None,
+            max_catch_up,
+            time_dropped: Duration::ZERO,
+        }
+    }
+
+    /// How much time to emulate to catch up with real time at `now`: the
+    /// time passed since the last catch up, but at most the most it lets the
+    /// emulation fall behind. Nothing is due at the first catch up, which
+    /// starts the clock, or if `now` is earlier than the last catch up.
+    pub fn catch_up(&mut self, now: Instant) -> Duration {
+        let Some(last_catch_up) = self.last_catch_up else {
+            self.last_catch_up = Some(now);
+            return Duration::ZERO;
+        };
+        let behind = now.saturating_duration_since(last_catch_up);
+        self.last_catch_up = Some(now.max(last_catch_up));
+        self.time_dropped += behind.saturating_sub(self.max_catch_up);
+        behind.min(self.max_catch_up)
+    }
+
+    /// Start the clock again at the next catch up, without catching up on
+    /// the time passed until then, e.g. while paused.
+    pub fn restart(&mut self) {
+        self.last_catch_up = None;
+    }
+
+    /// When the emulation falls behind again, with the next step due
+    /// `until_next_step` after the last catch up. Until then the emulation
+    /// is ahead, and can wait.
+    pub fn next_step_at(&self, until_next_step: Duration) -> Option<Instant> {
+        self.last_catch_up.map(|last| last + until_next_step)
+    }
+
+    /// The time dropped so far, rather than caught up on, as the emulation
+    /// fell too far behind.
+    pub fn time_dropped(&self) -> Duration {
+        self.time_dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Scheduler;
+
+    const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn catch_up_on_time_passed() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut scheduler = Scheduler::new(MAX_CATCH_UP);
+        assert_eq!(scheduler.next_step_at(Duration::from_millis(1)), None);
+
+        assert_eq!(scheduler.catch_up(at(0)), Duration::ZERO);
+        assert_eq!(scheduler.catch_up(at(5)), Duration::from_millis(5));
+        assert_eq!(scheduler.catch_up(at(5)), Duration::ZERO);
+        assert_eq!(scheduler.catch_up(at(12)), Duration::from_millis(7));
+        assert_eq!(
+            scheduler.next_step_at(Duration::from_millis(2)),
+            Some(at(14))
+        );
+        assert_eq!(scheduler.time_dropped(), Duration::ZERO);
+    }
+
+    #[test]
+    fn catch_up_bounded_after_stall() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut scheduler = Scheduler::new(MAX_CATCH_UP);
+        scheduler.catch_up(at(0));
+
+        assert_eq!(scheduler.catch_up(at(1000)), MAX_CATCH_UP);
+        assert_eq!(scheduler.time_dropped(), Duration::from_millis(900));
+        // carries on from the stall, rather than falling further behind
+        assert_eq!(scheduler.catch_up(at(1010)), Duration::from_millis(10));
+        assert_eq!(scheduler.time_dropped(), Duration::from_millis(900));
+    }
+
+    #[test]
+    fn restart_forgets_time_passed() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut scheduler = Scheduler::new(MAX_CATCH_UP);
+        scheduler.catch_up(at(0));
+
+        scheduler.restart();
+        assert_eq!(scheduler.next_step_at(Duration::ZERO), None);
+        assert_eq!(scheduler.catch_up(at(50)), Duration::ZERO);
+        assert_eq!(scheduler.catch_up(at(60)), Duration::from_millis(10));
+        assert_eq!(scheduler.time_dropped(), Duration::ZERO);
+    }
+
+    #[test]
+    fn clock_going_backwards_is_never_behind() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut scheduler = Scheduler::new(MAX_CATCH_UP);
+        scheduler.catch_up(at(10));
+
+        assert_eq!(scheduler.catch_up(at(5)), Duration::ZERO);
+        assert_eq!(scheduler.catch_up(at(12)), Duration::from_millis(2));
+    }
+}
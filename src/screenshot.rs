@@ -0,0 +1,185 @@
+//! Screenshots of the display, as PNG images.
+//!
+//! A screenshot is drawn from the CHIP-8 display buffer in a [`Palette`],
+//! not read back from the window, so the same display always gives the same
+//! image, whether or not the window is on the screen.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use time::OffsetDateTime;
+
+use crate::{
+    emulator::rgba_pixels_from_cosmac_display_buffer, interpreter::Chip8Interpreter,
+    memory::CosmacRAM, palette::Palette, rng::Chip8Rng,
+};
+
+/// A PNG image of the display that `chip8` renders, in `palette`, with each
+/// CHIP-8 pixel drawn as a `scale` by `scale` square.
+///
+/// # Panics
+/// Panics if `scale` is 0.
+pub fn screenshot_png<T: Chip8Rng>(
+    chip8: &Chip8Interpreter<T>,
+    ram: &CosmacRAM,
+    palette: &Palette,
+    scale: usize,
+) -> Vec<u8> {
+    assert!(
+        scale > 0,
+        "Screenshots should be at least a pixel per pixel"
+    );
+    let size = chip8.display_size_for_render(ram);
+    let rgba = rgba_pixels_from_cosmac_display_buffer(chip8, ram, palette, None);
+    let scaled = scale_rgba(&rgba, size.width, scale);
+    encode_png(size.width * scale, size.height * scale, &scaled)
+}
+
+/// The file name of a screenshot, or a clip, of the rom called `rom_name`,
+/// taken at `time`, e.g. `pong-20230415-093000.png` with the extension
+/// `png`. The time is in UTC.
+pub fn capture_file_name(rom_name: &str, time: SystemTime, extension: &str) -> String {
+    let time = OffsetDateTime::from(time.max(UNIX_EPOCH));
+    format!(
+        "{rom_name}-{:04}{:02}{:02}-{:02}{:02}{:02}.{extension}",
+        time.year(),
+        u8::from(time.month()),
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+    )
+}
+
+// Scale up RGBA pixels in rows of `width`, drawing each as a `scale` by
+// `scale` square.
+pub(crate) fn scale_rgba(rgba: &[u8], width: usize, scale: usize) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(rgba.len() * scale * scale);
+    for row in rgba.chunks(4 * width) {
+        let scaled_row: Vec<u8> = row
+            .chunks(4)
+            .flat_map(|pixel| pixel.repeat(scale))
+            .collect();
+        for _ in 0..scale {
+            scaled.extend(&scaled_row);
+        }
+    }
+    scaled
+}
+
+// Encode 8 bit RGBA pixels, in rows of `width`, as a PNG.
+fn encode_png(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .expect("Writing a PNG header to memory should not fail");
+    writer
+        .write_image_data(rgba)
+        .expect("There should be an RGBA pixel for each pixel of the image");
+    writer
+        .finish()
+        .expect("Writing a PNG to memory should not fail");
+    png
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{capture_file_name, encode_png, scale_rgba, screenshot_png};
+    use crate::{
+        interpreter::{Chip8Interpreter, TimerMode},
+        memory::CosmacRAM,
+        palette::Palette,
+        rng::MockChip8Rng,
+    };
+
+    // The width, height and RGBA pixels of a PNG.
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba).unwrap();
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(info.bit_depth, png::BitDepth::Eight);
+        rgba.truncate(info.buffer_size());
+        (info.width, info.height, rgba)
+    }
+
+    #[test]
+    fn screenshot_matches_golden_file() {
+        // draw the font sprite for 0 at (1, 2), and an 8 pixel line at (60, 31)
+        // that is clipped at the right edge
+        let mut ram = CosmacRAM::new();
+        ram.load_chip8_program(&chip8_program_into_bytes!(
+            0x6000  // V0 = 0
+            0xF029  // I = font sprite for 0
+            0x6101  // V1 = 1
+            0x6202  // V2 = 2
+            0xD125  // draw 0 at (1, 2)
+            0xA300  // I = 0x300
+            0x60FF  // V0 = 0xFF
+            0xF055  // M[0x300] = 0xFF
+            0xA300  // I = 0x300 again, after FX55 moved it on
+            0x633C  // V3 = 60
+            0x641F  // V4 = 31
+            0xD341  // draw a line at (60, 31)
+            0x1218  // loop forever
+        ))
+        .unwrap();
+        let mut chip8 = Chip8Interpreter::new(MockChip8Rng::new());
+        chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+        chip8.reset(&mut ram);
+        // the COSMAC VIP draws a sprite at most once a frame
+        for _ in 0..3 {
+            chip8.step_n(&mut ram, 10).unwrap();
+            chip8.tick_60hz(&mut ram);
+        }
+
+        let png = screenshot_png(&chip8, &ram, &Palette::CLASSIC_GREEN, 2);
+        assert_eq!(
+            decode_png(&png),
+            decode_png(include_bytes!(
+                "../tests/golden/screenshot_lores_green_x2.png"
+            ))
+        );
+    }
+
+    #[test]
+    fn scale_pixels_to_squares() {
+        let red = [0xFF, 0x00, 0x00, 0xFF];
+        let blue = [0x00, 0x00, 0xFF, 0xFF];
+        assert_eq!(
+            scale_rgba(&[red, blue].concat(), 2, 2),
+            [red, red, blue, blue, red, red, blue, blue].concat()
+        );
+        assert_eq!(scale_rgba(&red, 1, 1), red);
+    }
+
+    #[test]
+    fn encode_pixels_png() {
+        let pixels = [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0];
+        let png = encode_png(1, 2, &pixels);
+        assert_eq!(decode_png(&png), (1, 2, pixels.to_vec()));
+    }
+
+    #[test]
+    fn file_names_from_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(19_462 * 86_400 + 9 * 3600 + 30 * 60 + 5);
+        assert_eq!(
+            capture_file_name("pong", time, "png"),
+            "pong-20230415-093005.png"
+        );
+        assert_eq!(
+            capture_file_name("pong", time, "gif"),
+            "pong-20230415-093005.gif"
+        );
+        // leap day
+        let time = UNIX_EPOCH + Duration::from_secs(11_016 * 86_400);
+        assert_eq!(
+            capture_file_name("pong", time, "png"),
+            "pong-20000229-000000.png"
+        );
+    }
+}
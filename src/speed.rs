@@ -0,0 +1,179 @@
+//! Changing how fast the emulation runs while it runs.
+//!
+//! Holding the turbo key runs it [`TURBO_FACTOR`] times faster, and the
+//! instruction rate can be halved or doubled for the rest of the session.
+//! The 60Hz timers and display keep to real time as the instruction rate
+//! changes, as on a faster or slower CPU. Turbo can run them faster too, as
+//! if time passed faster, which keeps games whose pace is set by the delay
+//! timer in step with their instructions, see [`TurboMode`].
+
+/// How many times faster the emulation runs while the turbo key is held.
+pub const TURBO_FACTOR: u64 = 5;
+/// The slowest instruction rate that halving goes down to.
+pub const MIN_INSTRUCTIONS_PER_SECOND: u64 = 30;
+/// The fastest instruction rate that doubling goes up to.
+pub const MAX_INSTRUCTIONS_PER_SECOND: u64 = 100_000;
+
+/// What holding the turbo key runs faster.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TurboMode {
+    /// The instructions, and the 60Hz timers and display with them, as if
+    /// time passed faster. Games run faster just as they would have.
+    #[default]
+    Everything,
+    /// Only the instructions, with the timers and display kept to 60Hz.
+    /// Games paced by the delay timer don't speed up, but anything held up
+    /// by the CPU, e.g. loading a level, does.
+    InstructionsOnly,
+}
+
+/// The speed the emulation runs at, as changed by the speed hotkeys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Speed {
+    // the rate configured, to tell when it was changed
+    initial_instructions_per_second: u64,
+    instructions_per_second: u64,
+    turbo_mode: TurboMode,
+    turbo: bool,
+}
+
+impl Speed {
+    /// Run at `instructions_per_second`, with turbo running `turbo_mode`
+    /// faster.
+    ///
+    /// # Panics
+    /// Panics if `instructions_per_second` is 0.
+    pub fn new(instructions_per_second: u64, turbo_mode: TurboMode) -> Self {
+        assert!(
+            instructions_per_second > 0,
+            "At least one instruction should run each second"
+        );
+        Self {
+            initial_instructions_per_second: instructions_per_second,
+            instructions_per_second,
+            turbo_mode,
+            turbo: false,
+        }
+    }
+
+    /// Halve the instruction rate, down to [`MIN_INSTRUCTIONS_PER_SECOND`].
+    pub fn halve(&mut self) {
+        self.instructions_per_second = (self.instructions_per_second / 2)
+            .clamp(MIN_INSTRUCTIONS_PER_SECOND, MAX_INSTRUCTIONS_PER_SECOND);
+    }
+
+    /// Double the instruction rate, up to [`MAX_INSTRUCTIONS_PER_SECOND`].
+    pub fn double(&mut self) {
+        self.instructions_per_second = self
+            .instructions_per_second
+            .saturating_mul(2)
+            .clamp(MIN_INSTRUCTIONS_PER_SECOND, MAX_INSTRUCTIONS_PER_SECOND);
+    }
+
+    /// Turn turbo on, while the turbo key is held, or off.
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    /// Whether turbo is on.
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    /// The instructions to run each second of emulated time.
+    pub fn instructions_per_second(&self) -> u64 {
+        match (self.turbo, self.turbo_mode) {
+            (true, TurboMode::InstructionsOnly) => self.instructions_per_second * TURBO_FACTOR,
+            _ => self.instructions_per_second,
+        }
+    }
+
+    /// How many times faster than real time emulated time passes.
+    pub fn time_scale(&self) -> u32 {
+        match (self.turbo, self.turbo_mode) {
+            (true, TurboMode::Everything) => TURBO_FACTOR as u32,
+            _ => 1,
+        }
+    }
+
+    /// The speed, as shown in the window title, or `None` if it's the one
+    /// configured.
+    pub fn label(&self) -> Option<String> {
+        let rate = (self.instructions_per_second != self.initial_instructions_per_second)
+            .then(|| format!("{} IPS", self.instructions_per_second));
+        let turbo = self.turbo.then(|| format!("turbo {TURBO_FACTOR}x"));
+        match (turbo, rate) {
+            (Some(turbo), Some(rate)) => Some(format!("{turbo}, {rate}")),
+            (turbo, rate) => turbo.or(rate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Speed, TurboMode, MAX_INSTRUCTIONS_PER_SECOND, MIN_INSTRUCTIONS_PER_SECOND, TURBO_FACTOR,
+    };
+
+    #[test]
+    fn halve_and_double_within_range() {
+        let mut speed = Speed::new(700, TurboMode::Everything);
+        speed.double();
+        assert_eq!(speed.instructions_per_second(), 1400);
+        speed.halve();
+        speed.halve();
+        assert_eq!(speed.instructions_per_second(), 350);
+
+        for _ in 0..20 {
+            speed.halve();
+        }
+        assert_eq!(speed.instructions_per_second(), MIN_INSTRUCTIONS_PER_SECOND);
+        for _ in 0..20 {
+            speed.double();
+        }
+        assert_eq!(speed.instructions_per_second(), MAX_INSTRUCTIONS_PER_SECOND);
+
+        // a rate configured out of range is brought back into it
+        let mut speed = Speed::new(1_000_000, TurboMode::Everything);
+        speed.halve();
+        assert_eq!(speed.instructions_per_second(), MAX_INSTRUCTIONS_PER_SECOND);
+    }
+
+    #[test]
+    fn turbo_runs_everything_faster() {
+        let mut speed = Speed::new(700, TurboMode::Everything);
+        assert_eq!(speed.time_scale(), 1);
+        speed.set_turbo(true);
+        assert_eq!(speed.time_scale(), TURBO_FACTOR as u32);
+        assert_eq!(speed.instructions_per_second(), 700);
+        speed.set_turbo(false);
+        assert_eq!(speed.time_scale(), 1);
+    }
+
+    #[test]
+    fn turbo_runs_only_instructions_faster() {
+        let mut speed = Speed::new(700, TurboMode::InstructionsOnly);
+        speed.set_turbo(true);
+        assert_eq!(speed.time_scale(), 1);
+        assert_eq!(speed.instructions_per_second(), 700 * TURBO_FACTOR);
+        // the rate set while turbo is on is kept once it's off
+        speed.double();
+        assert_eq!(speed.instructions_per_second(), 1400 * TURBO_FACTOR);
+        speed.set_turbo(false);
+        assert_eq!(speed.instructions_per_second(), 1400);
+    }
+
+    #[test]
+    fn label_only_once_changed() {
+        let mut speed = Speed::new(700, TurboMode::Everything);
+        assert_eq!(speed.label(), None);
+        speed.set_turbo(true);
+        assert_eq!(speed.label().as_deref(), Some("turbo 5x"));
+        speed.double();
+        assert_eq!(speed.label().as_deref(), Some("turbo 5x, 1400 IPS"));
+        speed.set_turbo(false);
+        assert_eq!(speed.label().as_deref(), Some("1400 IPS"));
+        speed.halve();
+        assert_eq!(speed.label(), None);
+    }
+}
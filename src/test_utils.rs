@@ -5,6 +5,69 @@
 //     data.iter().copied().flat_map(u16::to_be_bytes).collect()
 // }
 
+use std::{fs, path::PathBuf};
+
+use crate::{
+    interpreter::Chip8Interpreter,
+    memory::CosmacRAM,
+    quirks::{Chip8Variant, Quirks},
+    rng::MockChip8Rng,
+};
+
+/// Get a new CHIP-8 interpreter and RAM, reset and loaded with the provided
+/// CHIP-8 program. It behaves like the COSMAC VIP, except that sprites are
+/// drawn without waiting for the next jiffy, so that tests can draw several
+/// in a row, and the SCHIP and XO-CHIP opcodes are available.
+pub fn new_chip8_with_program(program: &[u8]) -> (CosmacRAM, Chip8Interpreter<MockChip8Rng>) {
+    let quirks = Quirks {
+        display_wait: false,
+        ..Quirks::cosmac_vip()
+    };
+    new_chip8_with_program_and_quirks(program, quirks)
+}
+
+/// Same as [`new_chip8_with_program`], but with the interpreter behavior set
+/// by `quirks`.
+pub fn new_chip8_with_program_and_quirks(
+    program: &[u8],
+    quirks: Quirks,
+) -> (CosmacRAM, Chip8Interpreter<MockChip8Rng>) {
+    let mut ram = CosmacRAM::new();
+    let mut chip8 = Chip8Interpreter::with_variant(MockChip8Rng::new(), Chip8Variant::XoChip);
+    chip8.set_quirks(quirks);
+    ram.load_chip8_program(program)
+        .expect("Should be ok to load this test program.");
+    chip8.reset(&mut ram);
+    (ram, chip8)
+}
+
+/// A path in the temp directory, named after the process so that test runs
+/// don't clash, with the file there removed when dropped.
+pub struct TempFile {
+    pub path: PathBuf,
+}
+
+impl TempFile {
+    /// A path for a file that doesn't exist yet.
+    pub fn new(file_name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}-{file_name}", std::process::id()));
+        Self { path }
+    }
+
+    /// A file holding `bytes`.
+    pub fn with_bytes(file_name: &str, bytes: &[u8]) -> Self {
+        let file = Self::new(file_name);
+        fs::write(&file.path, bytes).unwrap();
+        file
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
 /// Convert u16 CHIP-8 instructions to a Vec<u8> of bytes in big endian order.
 macro_rules! chip8_program_into_bytes {
     ($($t:tt) *) => {{
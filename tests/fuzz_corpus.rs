@@ -0,0 +1,186 @@
+//! Replays every case in the fuzz regression corpus (see
+//! `tests/fuzz_corpus/README.md`) through the headless runner, and again
+//! through the interpreter with step hooks and tracing enabled. Each case has
+//! crashed the emulator at some point, and must now run to the error or stop
+//! pinned in its expectation file without panicking.
+
+use std::{
+    env, fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use chip8_emulator::{
+    emulator::{run_headless, HeadlessOptions, HeadlessStopReason, ScriptedKey},
+    interpreter::{Chip8Interpreter, StepOutcome, TimerMode},
+    memory::CosmacRAM,
+    Error,
+};
+
+// Enough for each case to get well past whatever used to crash it, while
+// keeping the run short.
+const MAX_INSTRUCTIONS: usize = 10_000;
+
+// A way of running a case to its outcome.
+type Run = fn(&[u8], Vec<ScriptedKey>) -> String;
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fuzz_corpus")
+}
+
+// Read the input script next to a case, one `<step> <hex key> <press|release>`
+// event per line.
+fn read_key_events(path: &Path) -> Vec<ScriptedKey> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [step, key, state] = fields[..] else {
+                panic!(
+                    "{}: expected `<step> <hex key> <press|release>`, found `{line}`",
+                    path.display()
+                );
+            };
+            ScriptedKey {
+                step: step.parse().expect("The step should be a whole number"),
+                key: u8::from_str_radix(key, 16).expect("The key should be a hex digit"),
+                pressed: match state {
+                    "press" => true,
+                    "release" => false,
+                    _ => panic!(
+                        "{}: expected press or release, found `{state}`",
+                        path.display()
+                    ),
+                },
+            }
+        })
+        .collect()
+}
+
+// Read the outcome expected of a case from its expectation file, e.g.
+// `error Chip8StackOverflow` or `stop Halted`.
+fn read_expected_outcome(path: &Path) -> String {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("{}: every case needs an expectation: {e}", path.display()));
+    let outcomes: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let [outcome] = outcomes[..] else {
+        panic!(
+            "{}: expected one outcome, found {outcomes:?}",
+            path.display()
+        );
+    };
+    assert!(
+        outcome.starts_with("error ")
+            || ["stop Halted", "stop Exited", "stop WaitingForKey"].contains(&outcome),
+        "{}: a case must end in an error, or halt, exit or wait for a key, not `{outcome}`",
+        path.display()
+    );
+    outcome.to_string()
+}
+
+// The name of the variant of `error`, without its fields.
+fn error_outcome(error: &Error) -> String {
+    let debug = format!("{error:?}");
+    let variant = debug.split(['(', ' ']).next().unwrap();
+    format!("error {variant}")
+}
+
+fn headless_outcome(program: &[u8], key_events: Vec<ScriptedKey>) -> String {
+    let options = HeadlessOptions {
+        max_instructions: MAX_INSTRUCTIONS,
+        key_events,
+        ..HeadlessOptions::default()
+    };
+    match run_headless(program, options) {
+        Ok(report) => format!("stop {:?}", report.stop_reason),
+        Err(e) => error_outcome(&e),
+    }
+}
+
+// Run `program` like the headless runner, with hooks looking at the state
+// around each step and the steps traced, which both read the instruction at
+// the program counter ahead of the interpreter.
+fn traced_outcome(program: &[u8], mut key_events: Vec<ScriptedKey>) -> String {
+    let mut ram = CosmacRAM::new();
+    ram.load_chip8_program(program).unwrap();
+    let mut chip8 = Chip8Interpreter::with_seed(0);
+    chip8.set_timer_mode(&mut ram, TimerMode::Ticked);
+    chip8.reset(&mut ram);
+    chip8.set_pre_step_hook(Box::new(|state| drop(format!("{state:?}"))));
+    chip8.set_post_step_hook(Box::new(|state| drop(format!("{state:?}"))));
+    chip8.enable_trace(io::sink());
+
+    key_events.sort_by_key(|event| event.step);
+    let mut key_events = key_events.into_iter().peekable();
+    for step in 0..MAX_INSTRUCTIONS {
+        while let Some(event) = key_events.next_if(|event| event.step <= step) {
+            chip8.handle_key(&mut ram, event.key, event.pressed);
+        }
+        let stop_reason = match chip8.step(&mut ram) {
+            Err(e) => return error_outcome(&e),
+            Ok(StepOutcome::Exited) => HeadlessStopReason::Exited,
+            Ok(StepOutcome::Halted) => HeadlessStopReason::Halted,
+            Ok(StepOutcome::WaitingForKey) if key_events.peek().is_none() => {
+                HeadlessStopReason::WaitingForKey
+            }
+            Ok(_) => continue,
+        };
+        return format!("stop {stop_reason:?}");
+    }
+    format!("stop {:?}", HeadlessStopReason::InstructionLimit)
+}
+
+#[test]
+fn corpus_runs_to_expected_outcomes() {
+    let mut cases: Vec<PathBuf> = fs::read_dir(corpus_dir())
+        .expect("The fuzz corpus should be readable")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty(), "The fuzz corpus should have cases in it");
+
+    let mut failures = Vec::new();
+    for path in &cases {
+        let program = fs::read(path).unwrap();
+        let key_events = read_key_events(&path.with_extension("keys"));
+        let expected = read_expected_outcome(&path.with_extension("expect"));
+        let runs: [(&str, Run); 2] = [("headless", headless_outcome), ("traced", traced_outcome)];
+        for (run, outcome) in runs {
+            let outcome =
+                panic::catch_unwind(AssertUnwindSafe(|| outcome(&program, key_events.clone())))
+                    .unwrap_or_else(|_| "panic".to_string());
+            if outcome != expected {
+                failures.push(format!(
+                    "{} ({run}): expected `{expected}`, got `{outcome}`",
+                    path.display()
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{failures:#?}");
+}
+
+// Print the outcome of the program at `$FUZZ_CORPUS_CASE`, with the input
+// script next to it, for `scripts/add-fuzz-corpus.sh` to minimize the program
+// against. A panic is printed as `panic`, as in the corpus runs.
+#[test]
+#[ignore = "run by scripts/add-fuzz-corpus.sh"]
+fn print_case_outcome() {
+    let path = PathBuf::from(
+        env::var_os("FUZZ_CORPUS_CASE").expect("FUZZ_CORPUS_CASE should name a program"),
+    );
+    let program = fs::read(&path).unwrap();
+    let key_events = read_key_events(&path.with_extension("keys"));
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| headless_outcome(&program, key_events)))
+        .unwrap_or_else(|_| "panic".to_string());
+    println!("outcome: {outcome}");
+}
@@ -0,0 +1,63 @@
+//! Runs a two-page hires CHIP-8 rom laid out the way they're distributed: a
+//! `1260` jump at 0x0200 into the interpreter patch it carries at 0x0260, and
+//! the CHIP-8 program after the patch at 0x02C0. The program tiles the whole
+//! 64x64 display with the hex digits, so the final display pins down both
+//! pages.
+
+use chip8_emulator::{
+    emulator::{run_headless, HeadlessOptions, HeadlessStopReason},
+    Chip8Variant,
+};
+
+// Bytes per row of the 64x64 display.
+const ROW_BYTES: usize = 64 / 8;
+
+fn hires_rom() -> Vec<u8> {
+    let mut rom = vec![0x12, 0x60];
+    // the rest of the first page, then the space the patch takes up, which
+    // is COSMAC machine code that the emulator never runs
+    rom.resize(0x02C0 - 0x0200, 0x00);
+    rom.extend(
+        [
+            0x6200u16, // V2 = 0, the digit to draw
+            0x6100,    // V1 = 0
+            0x6000,    // V0 = 0, the start of a row of digits
+            0xF229,    // I = the digit in the low nibble of V2
+            0xD015,    // draw it at (V0, V1)
+            0x7201,    // V2 += 1
+            0x7008,    // V0 += 8
+            0x3040,    // the row is done at x = 64
+            0x12C6,    // next digit
+            0x7106,    // V1 += 6
+            0x313C,    // all the rows are done at y = 60
+            0x12C4,    // next row
+            0x12D8,    // halt
+        ]
+        .into_iter()
+        .flat_map(u16::to_be_bytes),
+    );
+    rom
+}
+
+#[test]
+fn hires_rom_fills_both_pages() {
+    let options = HeadlessOptions {
+        variant: Chip8Variant::HiresChip8,
+        ..HeadlessOptions::default()
+    };
+    let report = run_headless(&hires_rom(), options).unwrap();
+
+    assert_eq!(report.stop_reason, HeadlessStopReason::Halted);
+    assert_eq!(report.state.program_counter, 0x02D8);
+    let display = &report.state.display_buffer[0];
+    assert_eq!(display.len(), 64 * ROW_BYTES, "The display should be 64x64");
+    for y in [0, 32, 58] {
+        assert!(
+            display[y * ROW_BYTES..][..ROW_BYTES]
+                .iter()
+                .all(|&byte| byte != 0),
+            "Each digit on row {y} should be drawn"
+        );
+    }
+    assert_eq!(report.display_hash, 0x26E045BF);
+}